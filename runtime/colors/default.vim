@@ -0,0 +1,2 @@
+" 默认配色：恢复所有高亮组的默认设置
+highlight clear
@@ -0,0 +1,20 @@
+" desert：深色背景的暖色调配色
+highlight clear
+highlight Normal ctermfg=7 ctermbg=0 guifg=#ffffff guibg=#333333
+highlight LineNr ctermfg=3 guifg=#ffff00
+highlight CursorLineNr cterm=bold ctermfg=11 gui=bold guifg=#ffff60
+highlight CursorLine cterm=NONE ctermbg=236 guibg=#444444
+highlight Visual cterm=reverse ctermfg=186 ctermbg=64 gui=NONE guifg=#f0e68c guibg=#6b8e23
+highlight Search ctermfg=0 ctermbg=11 guifg=#f5deb3 guibg=#cd853f
+highlight StatusLine cterm=bold,reverse gui=NONE guifg=#333333 guibg=#c2bfa5
+highlight NonText cterm=bold ctermfg=12 gui=bold guifg=#add8e6 guibg=#4d4d4d
+highlight Folded ctermfg=11 ctermbg=8 guifg=#ffd700 guibg=#4d4d4d
+highlight SpecialKey ctermfg=10 guifg=#9acd32
+highlight Comment ctermfg=14 guifg=#87ceeb
+highlight Constant ctermfg=13 guifg=#ffa0a0
+highlight Special ctermfg=11 guifg=#ffdead
+highlight Identifier ctermfg=10 guifg=#98fb98
+highlight Statement cterm=bold ctermfg=11 gui=bold guifg=#f0e68c
+highlight PreProc ctermfg=9 guifg=#cd5c5c
+highlight Type cterm=bold ctermfg=10 gui=bold guifg=#bdb76b
+highlight Todo ctermfg=9 ctermbg=11 guifg=#ff4500 guibg=#eeee00
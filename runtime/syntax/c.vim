@@ -0,0 +1,9 @@
+" C 语法
+syntax keyword Statement break case continue default do else for goto if return switch while sizeof
+syntax keyword Type char double float int long short signed unsigned void struct union enum typedef const static extern volatile register
+syntax match Number /\b(0x[0-9a-fA-F]+|[0-9]+(\.[0-9]+)?)[uUlLfF]*\b/
+syntax match PreProc /^\s*#\s*[a-z]+.*/
+syntax match Character /'([^'\\]|\\.)'/
+syntax region String start=/"/ skip=/\\./ end=/"/ oneline
+syntax match Comment /\/\/.*/
+syntax region Comment start=/\/\*/ end=/\*\// fold
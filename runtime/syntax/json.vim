@@ -0,0 +1,6 @@
+" JSON 语法
+syntax keyword Boolean true false
+syntax keyword Constant null
+syntax match Number /-?\b[0-9]+(\.[0-9]+)?([eE][+-]?[0-9]+)?\b/
+syntax region String start=/"/ skip=/\\./ end=/"/ oneline
+syntax match Identifier /"([^"\\]|\\.)*"\s*:/
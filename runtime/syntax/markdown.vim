@@ -0,0 +1,8 @@
+" Markdown 语法
+syntax match Title /^#{1,6}\s.*/
+syntax match Special /^\s*([-*+]|[0-9]+\.)\s/
+syntax match Comment /^>.*/
+syntax match Statement /\*\*[^*]+\*\*/
+syntax match Identifier /\[[^\]]*\]\([^)]*\)/
+syntax match String /`[^`]+`/
+syntax region String start=/^```/ end=/^```/ fold
@@ -0,0 +1,13 @@
+" Python 语法
+syntax keyword Statement and as assert async await break class continue def del elif else except finally for from global if import in is lambda nonlocal not or pass raise return try while with yield
+syntax keyword Boolean True False
+syntax keyword Constant None
+syntax keyword Function print len range open isinstance super
+syntax match Number /\b[0-9][0-9_]*(\.[0-9_]*)?([eE][+-]?[0-9]+)?j?\b/
+syntax match PreProc /@[A-Za-z_][A-Za-z0-9_.]*/
+syntax region String start=/'/ skip=/\\./ end=/'/ oneline
+syntax region String start=/"/ skip=/\\./ end=/"/ oneline
+" 三引号在同一位置匹配时优先，放在后面
+syntax region String start=/[rbfu]?'''/ end=/'''/ fold
+syntax region String start=/[rbfu]?"""/ end=/"""/ fold
+syntax match Comment /#.*/
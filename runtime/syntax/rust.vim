@@ -0,0 +1,14 @@
+" Rust 语法，模式使用 Rust 正则表达式
+syntax keyword Keyword as async await break const continue crate dyn else enum extern fn for if impl in let loop match mod move mut pub ref return self Self static struct super trait type unsafe use where while
+syntax keyword Type bool char str u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize f32 f64 String Vec Option Result Box
+syntax keyword Boolean true false
+syntax keyword Constant None Some Ok Err
+syntax match Number /\b[0-9][0-9_]*(\.[0-9_]+)?([eE][+-]?[0-9_]+)?(_?[iuf](8|16|32|64|128|size))?\b/
+syntax match Number /\b0x[0-9a-fA-F_]+\b/
+syntax match Macro /\b[a-z_][a-z0-9_]*!/
+syntax match PreProc /#!?\[[^\]]*\]/
+syntax match Character /'([^'\\]|\\.)'/
+syntax region String start=/"/ skip=/\\./ end=/"/
+syntax match Comment /\/\/.*/
+syntax match Todo /\b(TODO|FIXME|XXX)\b/
+syntax region Comment start=/\/\*/ end=/\*\// fold
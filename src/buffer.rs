@@ -1,14 +1,127 @@
 use std::io;
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::checktime::FileStamp;
+use crate::display::{self, Cell, Display, ScreenRow};
+use crate::fold::{self, Folds};
+use crate::gutter::Gutter;
+use crate::largefile::LargeFile;
+use crate::mark::{ChangeList, JumpList, LineEdit, Marks, Position};
+use crate::sign::Signs;
+use crate::syntax::{Highlighter, Span};
+use crate::treesitter::SyntaxTree;
+use crate::undo::UndoTree;
 
 #[derive(Debug)]
 pub struct Buffer {
     lines: Vec<String>,
+    // 编辑的文件，新建的 buffer 没有文件名
+    pub path: Option<PathBuf>,
+    // 写入文件之后是否修改过
+    pub modified: bool,
+    // 每次修改加一，用来判断交换文件是否需要更新
+    pub changedtick: usize,
+    pub marks: Marks,
+    pub changelist: ChangeList,
+    // 只有一个窗口，跳转列表放在 buffer 中以便跟随文本移动
+    pub jumplist: JumpList,
+    // 显示这个 buffer 使用的 tabstop 和 list 选项
+    pub display: Display,
+    // 语法高亮，没有加载语法时为 None
+    pub highlighter: Option<Highlighter>,
+    // 有内置解析器的语言使用语法树高亮
+    pub tree: Option<SyntaxTree>,
+    pub gutter: Gutter,
+    pub signs: Signs,
+    pub undo: UndoTree,
+    // 读入或写入时文件的状态，用来发现文件在编辑器外被修改
+    pub stamp: Option<FileStamp>,
+    // 按大文件打开时映射的文件和读入的行的范围
+    pub large: Option<LargeFile>,
+    // binary 模式读入时不合法的字节保存为私用字符，写入时还原为字节
+    pub escaped: bool,
+    // 读入时有按 fileencoding 不合法的字节，写入会把它们换成替换字符
+    pub illegal: bool,
+    // 通过 folds() 访问，以便在使用前重新计算
+    folds: Folds,
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Buffer {
     pub fn new() -> Self {
         Self {
-            lines: Vec::new()
+            lines: Vec::new(),
+            path: None,
+            modified: false,
+            changedtick: 0,
+            marks: Marks::new(),
+            changelist: ChangeList::new(),
+            jumplist: JumpList::new(),
+            display: Display::new(),
+            highlighter: None,
+            tree: None,
+            gutter: Gutter::new(),
+            signs: Signs::new(),
+            undo: UndoTree::default(),
+            stamp: None,
+            large: None,
+            escaped: false,
+            illegal: false,
+            folds: Folds::new(),
+        }
+    }
+
+    // 从文件读入的内容，没有修改
+    pub fn from_lines(lines: Vec<String>) -> Self {
+        let mut buffer = Self::new();
+        buffer.undo = UndoTree::new(lines.clone());
+        buffer.lines = lines;
+        buffer
+    }
+
+    // 记录修改的位置：'. '[ '] 标记和改变列表
+    fn changed(&mut self, start: Position, end: Position) {
+        self.modified = true;
+        self.changedtick += 1;
+        self.marks.set('.', start);
+        self.marks.set('[', start);
+        self.marks.set(']', end);
+        self.changelist.record(start);
+        if let Some(highlighter) = &self.highlighter {
+            (start.row..=end.row).for_each(|row| highlighter.invalidate(row));
+        }
+        self.folds.invalidate();
+    }
+
+    // 修改文本之前告诉语法树 start 到 old_end 之间的文本要换成 text
+    fn edit_tree(&self, start: Position, old_end: Position, text: &str) {
+        if let Some(tree) = &self.tree {
+            tree.edit(start, old_end, text);
+        }
+    }
+
+    // 在末尾补上一个空行，位置超出 buffer 时使用
+    fn push_empty_line(&mut self) {
+        let end = Position::new(self.lines.len(), 0);
+        self.edit_tree(end, end, "\n");
+        self.lines.push(String::new());
+    }
+
+    fn adjust_marks(&mut self, edit: LineEdit) {
+        self.marks.adjust(edit);
+        self.changelist.adjust(edit);
+        self.jumplist.adjust(edit);
+        self.signs.adjust(edit);
+        self.folds.adjust(edit);
+        if let Some(highlighter) = &self.highlighter {
+            highlighter.adjust(edit);
         }
     }
 
@@ -26,63 +139,309 @@ impl Buffer {
 
     pub fn insert_line(&mut self, index: usize, line: String) {
         while index > self.lines.len() {
-            self.lines.push(String::new());
+            self.push_empty_line();
         }
         
+        let end = Position::new(index, line.len());
+        let at = Position::new(index, 0);
+        self.edit_tree(at, at, &format!("{}\n", line));
         if index == self.lines.len() {
             self.lines.push(line);
         } else {
+            self.adjust_marks(LineEdit::Insert { at: index, count: 1 });
             self.lines.insert(index, line);
         }
+        self.changed(Position::new(index, 0), end);
     }
 
-    pub fn insert_char(&mut self, line: usize, col: usize, c: char) {
+    // 大文件继续读入的行，不算修改
+    pub fn append_loaded(&mut self, lines: Vec<String>) {
+        self.undo.loaded(&lines);
+        if !lines.is_empty() {
+            let end = Position::new(self.lines.len(), 0);
+            self.edit_tree(end, end, &format!("{}\n", lines.join("\n")));
+        }
+        self.lines.extend(lines);
+    }
+
+    // 替换一行的内容，行上的标记保留
+    pub fn set_line(&mut self, index: usize, line: String) {
+        if index < self.lines.len() {
+            let end = Position::new(index, line.len());
+            self.edit_tree(Position::new(index, 0), Position::new(index, self.lines[index].len()), &line);
+            self.lines[index] = line;
+            self.changed(Position::new(index, 0), end);
+        }
+    }
+
+    // 把从 row 开始的 count 行替换成 lines，撤销和重做使用
+    pub fn replace_lines(&mut self, row: usize, count: usize, lines: &[String]) {
+        let common = count.min(lines.len());
+        for (i, line) in lines[..common].iter().enumerate() {
+            self.set_line(row + i, line.clone());
+        }
+        for _ in common..count {
+            self.remove_line(row + common);
+        }
+        for (i, line) in lines[common..].iter().enumerate() {
+            self.insert_line(row + common + i, line.clone());
+        }
+    }
+
+    pub fn remove_line(&mut self, index: usize) -> Option<String> {
+        if index >= self.lines.len() {
+            return None;
+        }
+        self.edit_tree(Position::new(index, 0), Position::new(index + 1, 0), "");
+        let line = self.lines.remove(index);
+        self.adjust_marks(LineEdit::Remove { at: index, count: 1 });
+        let row = index.min(self.lines.len().saturating_sub(1));
+        self.changed(Position::new(row, 0), Position::new(row, 0));
+        Some(line)
+    }
+
+    // 在 col 处把一行拆成两行，后半部分成为新的下一行
+    pub fn split_line(&mut self, line: usize, col: usize) {
         while line >= self.lines.len() {
-            self.lines.push(String::new());
+            self.push_empty_line();
         }
 
-        if let Some(line_content) = self.get_line_mut(line) {
-            while line_content.len() < col {
-                line_content.push(' ');
+        let col = col.min(self.lines[line].len());
+        self.edit_tree(Position::new(line, col), Position::new(line, col), "\n");
+        let remainder = self.lines[line].split_off(col);
+        self.adjust_marks(LineEdit::Split { row: line, col });
+        self.lines.insert(line + 1, remainder);
+        self.changed(Position::new(line, col), Position::new(line + 1, 0));
+    }
+
+    // 把下一行接到 line 行的末尾，返回接合处的列号
+    pub fn join_lines(&mut self, line: usize) -> Option<usize> {
+        if line + 1 >= self.lines.len() {
+            return None;
+        }
+
+        let col = self.lines[line].len();
+        self.edit_tree(Position::new(line, col), Position::new(line + 1, 0), "");
+        let next = self.lines.remove(line + 1);
+        self.lines[line].push_str(&next);
+        self.adjust_marks(LineEdit::Join { row: line, col });
+        self.changed(Position::new(line, col), Position::new(line, col));
+        Some(col)
+    }
+
+    // 在指定位置插入可能包含换行的文本，返回插入文本之后的位置
+    pub fn insert_text(&mut self, position: Position, text: &str) -> Position {
+        while position.row >= self.lines.len() {
+            self.push_empty_line();
+        }
+
+        let mut row = position.row;
+        let mut col = position.col.min(self.lines[row].len());
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.split_line(row, col);
+                row += 1;
+                col = 0;
+            }
+            self.edit_tree(Position::new(row, col), Position::new(row, col), part);
+            self.lines[row].insert_str(col, part);
+            col += part.len();
+        }
+        self.changed(position, Position::new(row, col));
+        Position::new(row, col)
+    }
+
+    // [start, end) 之间的文本，跨行时用换行连接
+    pub fn text_range(&self, start: Position, end: Position) -> String {
+        let mut text = String::new();
+        for row in start.row..=end.row.min(self.lines.len().saturating_sub(1)) {
+            let line = &self.lines[row];
+            let from = if row == start.row { start.col.min(line.len()) } else { 0 };
+            let to = if row == end.row { end.col.min(line.len()) } else { line.len() };
+            if row > start.row {
+                text.push('\n');
             }
-            line_content.insert(col, c);
+            text.push_str(&line[from..to.max(from)]);
         }
+        text
+    }
+
+    // 删除 [start, end) 之间的文本（可以跨行），返回被删除的文本
+    pub fn delete_text(&mut self, start: Position, end: Position) -> String {
+        if start >= end || start.row >= self.lines.len() {
+            return String::new();
+        }
+        let end = if end.row >= self.lines.len() {
+            let last = self.lines.len() - 1;
+            Position::new(last, self.lines[last].len())
+        } else {
+            end
+        };
+
+        let start_col = start.col.min(self.lines[start.row].len());
+        let end_col = end.col.min(self.lines[end.row].len());
+        let deleted = if start.row == end.row {
+            let end_col = end_col.max(start_col);
+            self.edit_tree(Position::new(start.row, start_col), Position::new(start.row, end_col), "");
+            self.lines[start.row].drain(start_col..end_col).collect()
+        } else {
+            let line_end = Position::new(start.row, self.lines[start.row].len());
+            self.edit_tree(Position::new(start.row, start_col), line_end, "");
+            let mut deleted: String = self.lines[start.row].drain(start_col..).collect();
+            for _ in start.row + 1..end.row {
+                deleted.push('\n');
+                deleted.push_str(&self.remove_line(start.row + 1).unwrap_or_default());
+            }
+            deleted.push('\n');
+            self.edit_tree(Position::new(start.row + 1, 0), Position::new(start.row + 1, end_col), "");
+            deleted.extend(self.lines[start.row + 1].drain(..end_col));
+            self.join_lines(start.row);
+            deleted
+        };
+        self.changed(Position::new(start.row, start_col), Position::new(start.row, start_col));
+        deleted
+    }
+
+    pub fn insert_char(&mut self, line: usize, col: usize, c: char) {
+        while line >= self.lines.len() {
+            self.push_empty_line();
+        }
+
+        // 列超出行尾时先补上空格
+        let len = self.lines[line].len();
+        let at = Position::new(line, col.min(len));
+        self.edit_tree(at, at, &format!("{}{}", " ".repeat(col.saturating_sub(len)), c));
+        let line_content = &mut self.lines[line];
+        while line_content.len() < col {
+            line_content.push(' ');
+        }
+        line_content.insert(col, c);
+        self.changed(Position::new(line, col), Position::new(line, col));
     }
 
     pub fn remove_char(&mut self, line: usize, col: usize) -> bool {
+        let end = self.lines.get(line).and_then(|text| Some(col + text.get(col..)?.chars().next()?.len_utf8()));
+        if let Some(end) = end {
+            self.edit_tree(Position::new(line, col), Position::new(line, end), "");
+        }
         if let Some(line_content) = self.get_line_mut(line) {
             if col < line_content.len() {
                 line_content.remove(col);
+                self.changed(Position::new(line, col), Position::new(line, col));
                 return true;
             }
         }
         false
     }
 
+    // 一行展开后的屏幕列
+    pub fn line_cells(&self, line_index: usize) -> Vec<Cell> {
+        self.get_line(line_index)
+            .map_or_else(Vec::new, |line| display::cells(line, &self.display))
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    // 一行的语法高亮，语法树优先，没有语法时为空
+    pub fn line_spans(&self, line_index: usize) -> Vec<Span> {
+        if let Some(tree) = &self.tree {
+            return tree.line_spans(&self.lines, line_index);
+        }
+        self.highlighter
+            .as_ref()
+            .map_or_else(Vec::new, |highlighter| highlighter.line_spans(&self.lines, line_index))
+    }
+
+    // 字节位置在屏幕上的列
+    pub fn virtual_col(&self, position: Position) -> usize {
+        self.get_line(position.row)
+            .map_or(0, |line| display::virtual_col(line, position.col, &self.display))
+    }
+
+    // 文本左边的折叠列、标记列和行号列的总宽度
+    pub fn gutter_width(&self) -> usize {
+        self.gutter.width(self.line_count() + self.line_offset(), !self.signs.placed().is_empty())
+    }
+
+    // 大文件中读入的第一行的行号，其他文件是 0
+    pub fn line_offset(&self) -> usize {
+        self.large.as_ref().map_or(0, |large| large.first_line)
+    }
+
+    // 一行文本可用的屏幕宽度，最后一列不用以免终端自动换行
+    pub fn text_width(&self, term_width: u16) -> usize {
+        (term_width as usize).saturating_sub(self.gutter_width() + 1).max(1)
+    }
+
+    // 一行折行后的屏幕行
+    pub fn screen_rows(&self, line_index: usize, term_width: u16) -> Vec<ScreenRow> {
+        let line = self.get_line(line_index).map_or("", |line| line.as_str());
+        let cells = self.line_cells(line_index);
+        display::screen_rows(line, &cells, self.text_width(term_width), &self.display)
+    }
+
+    // 一行占用的屏幕行数，关闭的折叠只在开始行占一行
     pub fn line_screen_rows(&self, line_index: usize, term_width: u16) -> io::Result<u16> {
-        let line = match self.get_line(line_index) {
-            Some(line) => line,
-            None => return Ok(1),
-        };
-        
-        let content_width = line.len() + 5;
-        Ok((content_width as u16 + term_width - 1) / term_width)
+        match self.closed_fold(line_index) {
+            Some((start, _)) => Ok((start == line_index) as u16),
+            None => Ok(self.screen_rows(line_index, term_width).len() as u16),
+        }
     }
 
     pub fn get_line_part(&self, line_index: usize, row_index: u16, term_width: u16) -> String {
-        let line = match self.get_line(line_index) {
-            Some(line) => line,
-            None => return String::new(),
-        };
-        
-        let effective_width = term_width - 5;
-        let start = row_index as usize * effective_width as usize;
-        let end = start + effective_width as usize;
-        
-        if start >= line.len() {
-            String::new()
-        } else {
-            line[start..line.len().min(end)].to_string()
+        let cells = self.line_cells(line_index);
+        match self.screen_rows(line_index, term_width).get(row_index as usize) {
+            Some(row) => cells[row.start..row.end].iter().filter_map(|cell| cell.ch).collect(),
+            None => String::new(),
         }
     }
+
+    // 按 foldmethod 计算的折叠范围
+    fn fold_ranges(&self) -> Vec<(usize, usize)> {
+        let settings = self.folds.settings();
+        match settings.method.as_str() {
+            "indent" => fold::from_levels(&fold::indent_levels(&self.lines, settings.tabstop, settings.shiftwidth)),
+            "marker" => fold::from_levels(&fold::marker_levels(&self.lines, &settings.marker)),
+            "expr" => match Regex::new(&settings.expr) {
+                Ok(expr) if !settings.expr.is_empty() => fold::from_levels(&fold::expr_levels(&self.lines, &expr)),
+                _ => Vec::new(),
+            },
+            // 有语法树时折叠多行的节点，否则折叠带 fold 参数的 region
+            "syntax" => match (&self.tree, &self.highlighter) {
+                (Some(tree), _) => tree.fold_ranges(&self.lines),
+                (None, Some(highlighter)) => highlighter.fold_ranges(&self.lines),
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    // 文本修改后的第一次使用时重新计算折叠
+    pub fn folds(&self) -> &Folds {
+        if self.folds.needs_update() {
+            self.folds.update(self.fold_ranges());
+        }
+        &self.folds
+    }
+
+    pub fn folds_mut(&mut self) -> &mut Folds {
+        self.folds();
+        &mut self.folds
+    }
+
+    // 包含 row 的关闭的折叠
+    pub fn closed_fold(&self, row: usize) -> Option<(usize, usize)> {
+        self.folds().closed_at(row)
+    }
+
+    // row 所在的关闭的折叠的开始行和结束行，不在关闭的折叠中时就是 row
+    pub fn fold_start(&self, row: usize) -> usize {
+        self.closed_fold(row).map_or(row, |(start, _)| start)
+    }
+
+    pub fn fold_end(&self, row: usize) -> usize {
+        self.closed_fold(row).map_or(row, |(_, end)| end)
+    }
 }
@@ -0,0 +1,178 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::SystemTime;
+
+use termion::event::Key;
+
+use crate::editor::{Editor, Mode};
+use crate::encoding;
+use crate::file::FileOptions;
+
+// 读入或写入文件时记下的修改时间、inode 和大小，和现在的不同时文件在编辑器外被修改过
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileStamp {
+    modified: Option<SystemTime>,
+    inode: u64,
+    len: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            inode: metadata.ino(),
+            len: metadata.len(),
+        })
+    }
+}
+
+// 用 inotify 监视文件所在的目录，这样改名覆盖文件（git checkout、格式化工具）也能发现
+#[cfg(target_os = "linux")]
+pub struct Watcher {
+    inotify: inotify::Inotify,
+    name: std::ffi::OsString,
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    pub fn new(path: &Path) -> Option<Self> {
+        use inotify::{Inotify, WatchMask};
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let mut inotify = Inotify::init().ok()?;
+        let mask = WatchMask::MODIFY
+            | WatchMask::CLOSE_WRITE
+            | WatchMask::ATTRIB
+            | WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO;
+        inotify.add_watch(dir, mask).ok()?;
+        Some(Self { inotify, name: path.file_name()?.to_os_string() })
+    }
+
+    // 读出所有等待的事件，返回是否有关于这个文件的事件
+    pub fn changed(&mut self) -> bool {
+        let mut buffer = [0; 4096];
+        let mut changed = false;
+        while let Ok(events) = self.inotify.read_events(&mut buffer) {
+            let mut empty = true;
+            for event in events {
+                empty = false;
+                changed |= event.name == Some(self.name.as_os_str());
+            }
+            if empty {
+                break;
+            }
+        }
+        changed
+    }
+}
+
+// 其他系统只在获得焦点和 :checktime 时检查
+#[cfg(not(target_os = "linux"))]
+pub struct Watcher;
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    pub fn new(_path: &Path) -> Option<Self> {
+        None
+    }
+
+    pub fn changed(&mut self) -> bool {
+        false
+    }
+}
+
+impl Editor {
+    // 换成新的文件或者写入文件之后记下文件的状态
+    pub fn watch_file(&mut self, path: &Path) {
+        self.buffer.stamp = FileStamp::of(path);
+        if self.watcher.as_ref().is_none_or(|(watched, _)| watched != path) {
+            self.watcher = Watcher::new(path).map(|watcher| (path.to_path_buf(), watcher));
+        }
+    }
+
+    // :checktime 和获得焦点时：文件被修改过时询问是否重新读入，
+    // 设置了 autoread 并且 buffer 没有修改时直接重新读入
+    pub fn check_time(&mut self) -> io::Result<()> {
+        let (Some(path), Some(stamp)) = (self.buffer.path.clone(), self.buffer.stamp) else {
+            return Ok(());
+        };
+        let current = match FileStamp::of(&path) {
+            Some(current) if current == stamp => return Ok(()),
+            Some(current) => current,
+            None => {
+                // 只报告一次
+                self.buffer.stamp = None;
+                self.error(format!("E211: File \"{}\" no longer available", path.display()));
+                return Ok(());
+            }
+        };
+        if self.options.autoread && !self.buffer.modified {
+            return self.reload_file();
+        }
+        // 回答之前不再重复询问
+        self.buffer.stamp = Some(current);
+        let warning = if self.buffer.modified {
+            format!("W12: Warning: File \"{}\" has changed and the buffer was changed in the editor as well", path.display())
+        } else {
+            format!("W11: Warning: File \"{}\" has changed since editing started", path.display())
+        };
+        let message = vec![warning, String::from("[O]K, (L)oad File:")];
+        self.messages.extend(message.clone());
+        self.changed_prompt = Some(message);
+        Ok(())
+    }
+
+    // 重新读入当前文件，光标留在原来的位置
+    fn reload_file(&mut self) -> io::Result<()> {
+        let Some(path) = self.buffer.path.clone() else {
+            return Ok(());
+        };
+        let position = self.cursor.position();
+        // 按现在的编码、格式和 binary 设置读入，不重新检测
+        let options = FileOptions {
+            encoding: encoding::canonical(&self.options.fileencoding),
+            fileformat: ["unix", "dos", "mac"].into_iter().find(|&format| format == self.options.fileformat),
+            binary: Some(self.options.binary),
+        };
+        self.open_with(&path, options)?;
+        self.cursor.move_to(&self.buffer, position)
+    }
+
+    // 处理 W11、W12 提示的回答：O 保留 buffer 的内容，L 重新读入文件
+    pub fn answer_changed_prompt(&mut self, key: Key) -> io::Result<()> {
+        let Some(message) = self.changed_prompt.take() else {
+            return Ok(());
+        };
+        match key {
+            Key::Char('o' | 'O' | '\n') | Key::Esc => (),
+            Key::Char('l' | 'L') => self.reload_file()?,
+            _ => {
+                self.messages.extend(message.clone());
+                self.changed_prompt = Some(message);
+            }
+        }
+        Ok(())
+    }
+
+    // 等待按键时：inotify 报告了文件的变化或者终端获得了焦点，回到普通模式后再检查，返回是否需要重新显示
+    pub fn check_file_changes(&mut self) -> io::Result<bool> {
+        if let Some((_, watcher)) = self.watcher.as_mut() {
+            self.check_pending |= watcher.changed();
+        }
+        let prompt = self.changed_prompt.is_some() || self.swap_prompt.is_some() || self.sudo_prompt.is_some();
+        let idle = matches!(self.mode, Mode::Normal) && !prompt;
+        if !self.check_pending || !idle {
+            return Ok(false);
+        }
+        self.check_pending = false;
+        let stamp = self.buffer.stamp;
+        self.messages.clear();
+        self.check_time()?;
+        Ok(self.buffer.stamp != stamp || self.changed_prompt.is_some())
+    }
+}
@@ -0,0 +1,573 @@
+use std::io;
+use termion::event::Key;
+
+use crate::display;
+use crate::editor::Editor;
+use crate::highlight;
+use crate::keymap::{self, MapMode, Mapping};
+use crate::mark::{Marks, Position};
+use crate::syntax::{Highlighter, Syntax};
+use crate::treesitter::SyntaxTree;
+
+const ALL_MAP_MODES: [MapMode; 5] = [
+    MapMode::Normal,
+    MapMode::Visual,
+    MapMode::OperatorPending,
+    MapMode::Insert,
+    MapMode::CmdLine,
+];
+
+// 命令的行范围（从 0 开始，包含两端）
+pub type LineRange = (usize, usize);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum MapKind {
+    Map { noremap: bool },
+    Unmap,
+    Clear,
+}
+
+// 拆分命令名、! 和参数，如 "nnoremap <leader>w :w<CR>"
+fn split_command(line: &str) -> (&str, bool, &str) {
+    let line = line.trim_start_matches([' ', ':']);
+    let name_len = line
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(line.len());
+    let (name, rest) = line.split_at(name_len);
+    match rest.strip_prefix('!') {
+        Some(args) => (name, true, args.trim_start()),
+        None => (name, false, rest.trim_start()),
+    }
+}
+
+// :set 的参数按空白拆分，\ 是参数中的空格，如 set formatprg=fmt\ -w\ 60
+fn split_set_args(args: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = args.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&' ') => {
+                chars.next();
+                current.push(' ');
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+// 识别 map 命令族，返回作用的模式和命令类型
+fn map_command(name: &str, bang: bool) -> Option<(Vec<MapMode>, MapKind)> {
+    let (modes, rest) = match name.chars().next()? {
+        'n' if !name.starts_with("no") => (vec![MapMode::Normal], &name[1..]),
+        'v' | 'x' => (vec![MapMode::Visual], &name[1..]),
+        'o' => (vec![MapMode::OperatorPending], &name[1..]),
+        'i' => (vec![MapMode::Insert], &name[1..]),
+        'c' => (vec![MapMode::CmdLine], &name[1..]),
+        _ if bang => (vec![MapMode::Insert, MapMode::CmdLine], name),
+        _ => (
+            vec![MapMode::Normal, MapMode::Visual, MapMode::OperatorPending],
+            name,
+        ),
+    };
+
+    // 单字母缩写只在带模式前缀时有效（:nm、:nn、:iu），避免与 :m 等命令冲突，
+    // :cn 和 :on 是 :cnext 和 :only，c、o、i 模式的 noremap 至少要写成 :cno、:ono、:ino
+    let prefixed = rest.len() < name.len();
+    let short_noremap = prefixed && !name.starts_with(['c', 'o', 'i']);
+    let kind = match rest {
+        "map" => MapKind::Map { noremap: false },
+        "noremap" | "no" => MapKind::Map { noremap: true },
+        "unmap" | "unm" => MapKind::Unmap,
+        "mapclear" | "mapc" => MapKind::Clear,
+        "m" if prefixed => MapKind::Map { noremap: false },
+        "n" if short_noremap => MapKind::Map { noremap: true },
+        "u" | "un" if prefixed => MapKind::Unmap,
+        _ => return None,
+    };
+    Some((modes, kind))
+}
+
+impl Editor {
+    pub fn execute_command(&mut self, line: &str) -> io::Result<()> {
+        let (range, rest) = match self.parse_range(line.trim_start_matches([' ', ':'])) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                self.error(error);
+                return Ok(());
+            }
+        };
+        let (name, bang, args) = split_command(rest);
+
+        if let Some((modes, kind)) = map_command(name, bang) {
+            self.map_command(&modes, kind, args);
+            return Ok(());
+        }
+
+        match name {
+            // :{range}!cmd 过滤这些行
+            "" if bang => self.filter_command(range, args)?,
+            // 只有行号时跳转到该行
+            "" => {
+                if let Some((_, end)) = range {
+                    self.cursor.row = end;
+                    self.cursor.col = 0;
+                    self.cursor.update_screen_position(&self.buffer)?;
+                }
+            }
+            "norm" | "normal" => self.normal_command(range, bang, args)?,
+            "q" | "quit" if self.buffer.modified && !bang => {
+                self.error("E37: No write since last change (add ! to override)");
+            }
+            "q" | "quit" => self.quit = true,
+            "e" | "edit" => self.edit_command(bang, args)?,
+            "w" | "write" => self.write_command(range, bang, args)?,
+            "wq" | "x" | "xit" => {
+                // :x 只在修改过时写入
+                if name == "wq" || self.buffer.modified {
+                    self.write_command(range, bang, args)?;
+                }
+                if !self.aborted {
+                    self.quit = true;
+                }
+            }
+            "r" | "read" => {
+                // :0r 插入到第一行上面
+                let before_first = range == Some((0, 0)) && line.trim_start_matches([' ', ':']).starts_with('0');
+                self.read_command(range, bang, args, before_first)?;
+            }
+            "SudoWrite" => self.sudo_write_command(args)?,
+            "Hex" => self.hex_command()?,
+            "checkt" | "checktime" => self.check_time()?,
+            "u" | "un" | "undo" => self.undo(false, 1)?,
+            "red" | "redo" => self.undo(true, 1)?,
+            "rec" | "recover" => match (args.trim(), self.buffer.path.clone()) {
+                ("", Some(path)) => self.recover(&path)?,
+                ("", None) => self.error("E32: No file name"),
+                (name, _) => self.recover(std::path::Path::new(name))?,
+            },
+            "se" | "set" => {
+                let file_format = self.file_format();
+                for arg in split_set_args(args) {
+                    match self.options.set(&arg) {
+                        Ok(Some(message)) => self.messages.push(message),
+                        Ok(None) => (),
+                        Err(error) => {
+                            self.error(error);
+                            break;
+                        }
+                    }
+                }
+                self.buffer.display = self.options.display();
+                self.buffer.gutter = self.options.gutter();
+                self.load_syntax();
+                self.buffer.folds_mut().configure(self.options.folding());
+                self.update_swap_option();
+                // 修改编码或换行符后文件需要重新写入
+                if file_format != self.file_format() {
+                    self.buffer.modified = true;
+                }
+                self.cursor.update_screen_position(&self.buffer)?;
+            }
+            "sy" | "syn" | "syntax" => self.syntax_command(args),
+            "InspectTree" => self.inspect_tree(),
+            "sig" | "sign" => {
+                let line_count = self.buffer.line_count();
+                match self.buffer.signs.command(args, line_count) {
+                    Ok(lines) => self.messages.extend(lines),
+                    Err(error) => self.error(error),
+                }
+                self.cursor.update_screen_position(&self.buffer)?;
+            }
+            "hi" | "highlight" => match self.highlights.command(args) {
+                Ok(lines) => self.messages.extend(lines),
+                Err(error) => self.error(error),
+            },
+            "colo" | "colorscheme" => self.colorscheme(args.trim())?,
+            "let" => self.let_command(args),
+            "marks" => self.list_marks(args),
+            "ju" | "jumps" => self.list_jumps(),
+            "changes" => self.list_changes(),
+            "sor" | "sort" => self.sort_command(range, bang, args)?,
+            "ret" | "retab" => self.retab_command(range, bang, args)?,
+            _ => self.error(format!("E492: Not an editor command: {}", line.trim())),
+        }
+        Ok(())
+    }
+
+    // 按 syntax 选项加载语法文件和语法树，已经加载的相同语法不重新加载
+    pub fn load_syntax(&mut self) {
+        let name = self.options.syntax.clone();
+        // 大文件不加载语法
+        if !self.syntax_enabled || name.is_empty() || self.buffer.large.is_some() {
+            self.buffer.highlighter = None;
+            self.buffer.tree = None;
+            return;
+        }
+        if self.buffer.tree.as_ref().is_none_or(|tree| tree.language != name) {
+            self.buffer.tree = SyntaxTree::new(&name);
+        }
+        if self.buffer.highlighter.as_ref().is_some_and(|highlighter| highlighter.name == name) {
+            return;
+        }
+        self.buffer.highlighter = match Syntax::load(&name) {
+            Ok(syntax) => syntax.map(|syntax| Highlighter::new(&name, syntax)),
+            Err(error) => {
+                self.error(error);
+                None
+            }
+        };
+    }
+
+    // :syntax on/off/clear，以及 :syntax keyword/match/region 定义规则
+    fn syntax_command(&mut self, args: &str) {
+        match args.trim() {
+            "" | "on" | "enable" => {
+                self.syntax_enabled = true;
+                self.load_syntax();
+            }
+            "off" => {
+                self.syntax_enabled = false;
+                self.load_syntax();
+            }
+            // 清除后用 :syntax 定义的规则高亮，不再使用语法树
+            "clear" => {
+                self.buffer.tree = None;
+                let name = self.options.syntax.clone();
+                self.buffer.highlighter = Some(Highlighter::new(&name, Syntax::new()));
+            }
+            args => {
+                let name = self.options.syntax.clone();
+                let highlighter = self
+                    .buffer
+                    .highlighter
+                    .get_or_insert_with(|| Highlighter::new(&name, Syntax::new()));
+                match highlighter.syntax.define(args) {
+                    Ok(()) => highlighter.reset(),
+                    Err(error) => self.error(error),
+                }
+            }
+        }
+    }
+
+    // :colorscheme name 执行配色文件中的命令，没有参数时显示当前的配色方案
+    fn colorscheme(&mut self, name: &str) -> io::Result<()> {
+        if name.is_empty() {
+            self.messages.push(self.highlights.colors_name.clone());
+            return Ok(());
+        }
+        let text = match highlight::colorscheme_file(name) {
+            Some(text) => text,
+            None => {
+                self.error(format!("E185: Cannot find color scheme '{}'", name));
+                return Ok(());
+            }
+        };
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('"') {
+                continue;
+            }
+            self.execute_command(line)?;
+            if self.aborted {
+                break;
+            }
+        }
+        self.highlights.colors_name = name.to_string();
+        Ok(())
+    }
+
+    // :InspectTree 显示光标下的语法树节点，从根节点开始缩进
+    fn inspect_tree(&mut self) {
+        let nodes = match &self.buffer.tree {
+            Some(tree) => tree.inspect(self.buffer.lines(), self.cursor.position()),
+            None => {
+                self.error(format!("No parser for filetype: {}", self.options.filetype));
+                return;
+            }
+        };
+        self.messages.extend(nodes);
+    }
+
+    // :[range]retab[!] [new_tabstop]：按新的 tabstop 重新生成空白，默认作用于整个文件
+    fn retab_command(&mut self, range: Option<LineRange>, bang: bool, args: &str) -> io::Result<()> {
+        let tabstop = self.options.tabstop;
+        let new_tabstop = match args.trim() {
+            "" => tabstop,
+            arg => match arg.parse::<usize>() {
+                Ok(value) if value > 0 => value,
+                _ => {
+                    self.error(format!("E475: Invalid argument: {}", arg));
+                    return Ok(());
+                }
+            },
+        };
+
+        let last = self.buffer.line_count().saturating_sub(1);
+        let (first, last) = range.unwrap_or((0, last));
+        for row in first..=last {
+            let line = match self.buffer.get_line(row) {
+                Some(line) => line,
+                None => break,
+            };
+            let new_line = display::retab(line, tabstop, new_tabstop, self.options.expandtab, bang);
+            if new_line != *line {
+                self.buffer.set_line(row, new_line);
+            }
+        }
+
+        self.options.tabstop = new_tabstop;
+        self.buffer.display = self.options.display();
+        let position = self.cursor.position();
+        self.cursor.move_to(&self.buffer, position)
+    }
+
+    // 解析命令前的行范围，如 "%"、"3,$"、".,.+2"，返回从 0 开始的行号
+    pub fn parse_range<'a>(&self, line: &'a str) -> Result<(Option<LineRange>, &'a str), String> {
+        if let Some(rest) = line.strip_prefix('%') {
+            let last = self.buffer.line_count().saturating_sub(1);
+            return Ok((Some((0, last)), rest));
+        }
+
+        let (start, mut rest) = self.parse_address(line)?;
+        let start = match start {
+            Some(start) => start,
+            None => return Ok((None, line)),
+        };
+
+        let mut end = start;
+        if let Some(after) = rest.strip_prefix([',', ';']) {
+            let (address, after) = self.parse_address(after)?;
+            end = address.unwrap_or(self.cursor.row);
+            rest = after;
+        }
+
+        let line_count = self.buffer.line_count().max(1);
+        if start >= line_count || end >= line_count {
+            return Err(String::from("E16: Invalid range"));
+        }
+        if start > end {
+            return Ok((Some((end, start)), rest));
+        }
+        Ok((Some((start, end)), rest))
+    }
+
+    // 解析单个地址：行号、.、$ 以及后面的 +N/-N 偏移
+    fn parse_address<'a>(&self, text: &'a str) -> Result<(Option<usize>, &'a str), String> {
+        let text = text.trim_start();
+        let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+
+        let (mut line, mut rest) = if digits > 0 {
+            let number: usize = text[..digits].parse().map_err(|_| String::from("E16: Invalid range"))?;
+            (Some(number.saturating_sub(1) as isize), &text[digits..])
+        } else if let Some(rest) = text.strip_prefix('.') {
+            (Some(self.cursor.row as isize), rest)
+        } else if let Some(rest) = text.strip_prefix('$') {
+            (Some(self.buffer.line_count().saturating_sub(1) as isize), rest)
+        } else if let Some(rest) = text.strip_prefix('\'') {
+            let name = rest.chars().next().ok_or_else(|| String::from("E20: Mark not set"))?;
+            let position = self.buffer.marks.get(name).filter(|_| Marks::is_valid(name));
+            let position = position.ok_or_else(|| String::from("E20: Mark not set"))?;
+            (Some(position.row as isize), &rest[name.len_utf8()..])
+        } else {
+            (None, text)
+        };
+
+        while let Some(sign) = rest.chars().next().filter(|c| *c == '+' || *c == '-') {
+            let after = &rest[1..];
+            let digits = after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len());
+            let offset: isize = if digits == 0 { 1 } else { after[..digits].parse().unwrap_or(1) };
+            let base = line.unwrap_or(self.cursor.row as isize);
+            line = Some(if sign == '+' { base + offset } else { base - offset });
+            rest = &after[digits..];
+        }
+
+        match line {
+            Some(line) if line < 0 => Err(String::from("E16: Invalid range")),
+            Some(line) => Ok((Some(line as usize), rest)),
+            None => Ok((None, rest)),
+        }
+    }
+
+    // :[range]normal[!] {commands}，对范围内的每一行执行普通模式命令
+    fn normal_command(&mut self, range: Option<LineRange>, bang: bool, args: &str) -> io::Result<()> {
+        let keys: Vec<Key> = args.chars().map(Key::Char).collect();
+        if keys.is_empty() {
+            self.error("E471: Argument required");
+            return Ok(());
+        }
+
+        let (start, end) = match range {
+            Some(range) => range,
+            None => return self.execute_normal(&keys, !bang),
+        };
+        for row in start..=end {
+            if row >= self.buffer.line_count() {
+                break;
+            }
+            self.aborted = false;
+            self.cursor.row = row;
+            self.cursor.col = 0;
+            self.cursor.update_screen_position(&self.buffer)?;
+            self.execute_normal(&keys, !bang)?;
+            if self.quit {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn let_command(&mut self, args: &str) {
+        let (name, value) = match args.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => {
+                self.error(format!("E15: Invalid expression: {}", args));
+                return;
+            }
+        };
+
+        let unquoted = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')));
+        match (name, unquoted) {
+            ("mapleader" | "g:mapleader", Some(leader)) => self.keymaps.leader = leader.to_string(),
+            ("mapleader" | "g:mapleader", None) => {
+                self.error(format!("E15: Invalid expression: {}", value))
+            }
+            _ => self.error(format!("E121: Undefined variable: {}", name)),
+        }
+    }
+
+    fn map_command(&mut self, modes: &[MapMode], kind: MapKind, args: &str) {
+        let map_args = keymap::parse_map_args(args);
+        let lhs = keymap::parse_keys(&map_args.lhs, &self.keymaps.leader);
+
+        match kind {
+            MapKind::Clear => {
+                for mode in modes {
+                    self.keymaps.clear(*mode, map_args.buffer);
+                }
+            }
+            MapKind::Unmap => {
+                let mut removed = false;
+                for mode in modes {
+                    removed |= self.keymaps.remove(*mode, &lhs, map_args.buffer);
+                }
+                if !removed {
+                    self.error(String::from("E31: No such mapping"));
+                }
+            }
+            MapKind::Map { noremap } if !map_args.rhs.is_empty() => {
+                let mapping = Mapping {
+                    lhs,
+                    rhs: keymap::parse_keys(&map_args.rhs, &self.keymaps.leader),
+                    noremap,
+                    silent: map_args.silent,
+                };
+                for mode in modes {
+                    self.keymaps.add(*mode, mapping.clone(), map_args.buffer);
+                }
+            }
+            MapKind::Map { .. } => self.list_mappings(modes, &lhs),
+        }
+    }
+
+    // 不带 rhs 的 :map 列出映射，带 lhs 时只列出以它开头的映射
+    fn list_mappings(&mut self, modes: &[MapMode], prefix: &[Key]) {
+        let mut lines: Vec<String> = Vec::new();
+        for mode in ALL_MAP_MODES.iter().filter(|m| modes.contains(m)) {
+            for (mapping, buffer_local) in self.keymaps.list(*mode) {
+                if !mapping.lhs.starts_with(prefix) {
+                    continue;
+                }
+                let flags = format!(
+                    "{}{}",
+                    if mapping.noremap { "*" } else { " " },
+                    if buffer_local { "@" } else { " " },
+                );
+                lines.push(format!(
+                    "{}  {:<12} {}{}",
+                    mode.flag(),
+                    keymap::format_keys(&mapping.lhs),
+                    flags,
+                    keymap::format_keys(&mapping.rhs),
+                ));
+            }
+        }
+
+        if lines.is_empty() {
+            self.messages.push(String::from("No mapping found"));
+        } else {
+            self.messages.extend(lines);
+        }
+    }
+
+    // 列表中每个位置显示行号、列号和该行的文本
+    fn format_position(&self, label: &str, position: Position) -> String {
+        let text = self.buffer.get_line(position.row).map_or("", |line| line.trim());
+        format!("{:>4} {:>6} {:>4} {}", label, position.row + 1, position.col, text)
+    }
+
+    // :marks [names]
+    fn list_marks(&mut self, args: &str) {
+        let filter: Vec<char> = args.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut marks: Vec<(char, String)> = self
+            .buffer
+            .marks
+            .iter()
+            .map(|(name, position)| (name, self.format_position(&name.to_string(), position)))
+            .collect();
+        // 其他文件中的文件标记显示文件名
+        marks.extend(self.file_marks.iter().map(|(name, (path, position))| {
+            (*name, format!("{:>4} {:>6} {:>4} {}", name, position.row + 1, position.col, path.display()))
+        }));
+        marks.sort_by_key(|(name, _)| *name);
+        let mut lines = vec![String::from("mark line  col file/text")];
+        for (name, line) in marks {
+            if filter.is_empty() || filter.contains(&name) {
+                lines.push(line);
+            }
+        }
+
+        if lines.len() == 1 {
+            self.error(format!("E283: No marks matching \"{}\"", args));
+        } else {
+            self.messages.extend(lines);
+        }
+    }
+
+    // :jumps，数字表示与当前位置的距离，> 表示当前位置
+    fn list_jumps(&mut self) {
+        let jumplist = &self.buffer.jumplist;
+        let index = jumplist.index();
+        let mut lines = vec![String::from(" jump line  col file/text")];
+        for (i, position) in jumplist.entries().iter().enumerate() {
+            let distance = i.abs_diff(index);
+            let label = if i == index { format!(">{}", distance) } else { distance.to_string() };
+            lines.push(self.format_position(&label, *position));
+        }
+        if index == jumplist.entries().len() {
+            lines.push(String::from(">"));
+        }
+        self.messages.extend(lines);
+    }
+
+    fn list_changes(&mut self) {
+        let entries = self.buffer.changelist.entries().to_vec();
+        let mut lines = vec![String::from("change line  col text")];
+        for (i, position) in entries.iter().enumerate() {
+            lines.push(self.format_position(&(entries.len() - i).to_string(), *position));
+        }
+        lines.push(String::from(">"));
+        self.messages.extend(lines);
+    }
+}
@@ -2,6 +2,53 @@ use std::io;
 use termion::terminal_size;
 
 use crate::buffer::Buffer;
+use crate::display;
+use crate::mark::Position;
+
+// 单词移动使用的字符类别：空白、标点、单词字符
+pub fn char_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        2
+    } else {
+        1
+    }
+}
+
+// 位置上的字符类别，行尾视为空白
+fn class_at(buffer: &Buffer, position: Position) -> u8 {
+    buffer
+        .get_line(position.row)
+        .and_then(|line| line.get(position.col..))
+        .and_then(|rest| rest.chars().next())
+        .map_or(0, char_class)
+}
+
+fn line_len(buffer: &Buffer, row: usize) -> usize {
+    buffer.get_line(row).map_or(0, |line| line.len())
+}
+
+// 下一个位置，行尾之后是下一行的开始
+fn next_position(buffer: &Buffer, position: Position) -> Option<Position> {
+    if position.col < line_len(buffer, position.row) {
+        Some(Position::new(position.row, position.col + 1))
+    } else if position.row + 1 < buffer.line_count() {
+        Some(Position::new(position.row + 1, 0))
+    } else {
+        None
+    }
+}
+
+fn prev_position(buffer: &Buffer, position: Position) -> Option<Position> {
+    if position.col > 0 {
+        Some(Position::new(position.row, position.col - 1))
+    } else if position.row > 0 {
+        Some(Position::new(position.row - 1, line_len(buffer, position.row - 1)))
+    } else {
+        None
+    }
+}
 
 #[derive(Debug)]
 pub struct Cursor {
@@ -9,6 +56,10 @@ pub struct Cursor {
     pub col: usize,    // 列号（从0开始）
     pub screen_row: u16, // 屏幕上的行号（考虑到折行）
     pub screen_col: u16, // 屏幕上的列号（考虑到行号占用的空间）
+    // 上下移动时希望保持的屏幕列，$ 之后为 usize::MAX 表示保持在行尾
+    pub curswant: usize,
+    // nowrap 时屏幕最左边显示的虚拟列
+    pub leftcol: usize,
 }
 
 impl Cursor {
@@ -17,74 +68,204 @@ impl Cursor {
             row: 0,
             col: 0,
             screen_row: start_row,
-            screen_col: 5,  // 从行号后面开始
+            screen_col: 5,  // 从默认宽度的行号列后面开始
+            curswant: 0,
+            leftcol: 0,
         }
     }
 
+    // 光标左右移动后调用，记住新的屏幕列供上下移动使用
     pub fn update_screen_position(&mut self, buffer: &Buffer) -> io::Result<()> {
+        self.curswant = buffer.virtual_col(self.position());
+        self.update_screen(buffer)
+    }
+
+    fn update_screen(&mut self, buffer: &Buffer) -> io::Result<()> {
         let (term_width, _) = terminal_size()?;
         
+        // 关闭的折叠中的光标显示在折叠行的开头
         let mut screen_row = 4;
-        for i in 0..self.row {
+        for i in 0..buffer.fold_start(self.row) {
             screen_row += buffer.line_screen_rows(i, term_width)?;
         }
+        if buffer.closed_fold(self.row).is_some() {
+            self.screen_row = screen_row;
+            self.screen_col = buffer.gutter_width() as u16 + 1;
+            return Ok(());
+        }
         
-        let effective_width = term_width - 5;
-        let screen_row_offset = self.col / effective_width as usize;
-        let screen_col = self.col % effective_width as usize;
+        let effective_width = buffer.text_width(term_width);
+        let vcol = buffer.virtual_col(self.position());
+        let gutter = buffer.gutter_width();
+        if !buffer.display.wrap {
+            self.scroll_to(vcol, effective_width, buffer.display.sidescroll);
+            self.screen_row = screen_row;
+            self.screen_col = (gutter + vcol - self.leftcol) as u16 + 1;
+            return Ok(());
+        }
+
+        self.leftcol = 0;
+        let rows = buffer.screen_rows(self.row, term_width);
+        let index = rows.iter().rposition(|row| row.start <= vcol).unwrap_or(0);
+        let mut screen_row_offset = index;
+        let mut screen_col = rows[index].prefix + vcol - rows[index].start;
+        // 光标在占满一行的行尾之后时显示在下一行的开头
+        if screen_col >= effective_width {
+            screen_row_offset += 1;
+            screen_col = rows.get(1).map_or(0, |row| row.prefix);
+        }
         
         self.screen_row = screen_row + screen_row_offset as u16;
-        self.screen_col = screen_col as u16 + 5;
+        self.screen_col = (screen_col + gutter) as u16 + 1;
         
         Ok(())
     }
 
-    pub fn move_left(&mut self, buffer: &Buffer) -> io::Result<()> {
+    // nowrap 时让虚拟列 vcol 在屏幕内，sidescroll 为 0 时把它放到屏幕中间
+    fn scroll_to(&mut self, vcol: usize, width: usize, sidescroll: usize) {
+        if vcol >= self.leftcol && vcol < self.leftcol + width {
+            return;
+        }
+        self.leftcol = match (sidescroll, vcol < self.leftcol) {
+            (0, _) => vcol.saturating_sub(width / 2),
+            (_, true) => vcol.min(self.leftcol.saturating_sub(sidescroll)),
+            (_, false) => (vcol + 1 - width).max(self.leftcol + sidescroll),
+        };
+    }
+
+    // zh、zl、zs、ze：改变 nowrap 时屏幕左边的列，光标移到屏幕内
+    pub fn set_leftcol(&mut self, buffer: &Buffer, leftcol: usize) -> io::Result<()> {
+        if buffer.display.wrap {
+            return Ok(());
+        }
+        let (term_width, _) = terminal_size()?;
+        let width = buffer.text_width(term_width);
+        let line = buffer.get_line(self.row).map_or("", |line| line.as_str());
+        let last = display::cells(line, &buffer.display).len().saturating_sub(1);
+        self.leftcol = leftcol.min(last);
+
+        let vcol = buffer.virtual_col(self.position());
+        if vcol < self.leftcol {
+            self.col = display::col_at_virtual(line, self.leftcol, &buffer.display);
+            // 跨过左边界的制表符或宽字符不算在屏幕内
+            if buffer.virtual_col(self.position()) < self.leftcol {
+                self.col += line[self.col..].chars().next().map_or(0, char::len_utf8);
+            }
+        } else if vcol >= self.leftcol + width {
+            self.col = display::col_at_virtual(line, self.leftcol + width - 1, &buffer.display);
+        }
+        self.update_screen_position(buffer)
+    }
+
+    // gj、gk：按屏幕行上下移动，nowrap 时和 j、k 相同
+    pub fn move_screen_row(&mut self, buffer: &Buffer, down: bool) -> io::Result<bool> {
+        // 关闭的折叠只占一个屏幕行
+        let neighbor = if down { Some(buffer.fold_end(self.row) + 1) } else { buffer.fold_start(self.row).checked_sub(1) };
+        let folded = |row| buffer.closed_fold(row).is_some();
+        if !buffer.display.wrap || folded(self.row) || neighbor.is_some_and(folded) {
+            return if down { self.move_down(buffer) } else { self.move_up(buffer) };
+        }
+        let (term_width, _) = terminal_size()?;
+        let rows = buffer.screen_rows(self.row, term_width);
+        let vcol = buffer.virtual_col(self.position());
+        let index = rows.iter().rposition(|row| row.start <= vcol).unwrap_or(0);
+        let column = rows[index].prefix + vcol - rows[index].start;
+
+        let (row, target) = match (down, index) {
+            (true, _) if index + 1 < rows.len() => (self.row, rows[index + 1]),
+            (true, _) if self.row + 1 < buffer.line_count() => {
+                (self.row + 1, buffer.screen_rows(self.row + 1, term_width)[0])
+            }
+            (false, 1..) => (self.row, rows[index - 1]),
+            (false, 0) if self.row > 0 => {
+                let rows = buffer.screen_rows(self.row - 1, term_width);
+                (self.row - 1, rows[rows.len() - 1])
+            }
+            _ => return Ok(false),
+        };
+        let vcol = (target.start + column.saturating_sub(target.prefix)).min(target.end.saturating_sub(1).max(target.start));
+        self.row = row;
+        self.col = buffer
+            .get_line(row)
+            .map_or(0, |line| display::col_at_virtual(line, vcol, &buffer.display));
+        self.update_screen_position(buffer)?;
+        Ok(true)
+    }
+
+    // g0、g$：移动到当前屏幕行的第一个或最后一个字符
+    pub fn move_screen_row_edge(&mut self, buffer: &Buffer, end: bool) -> io::Result<()> {
+        let (term_width, _) = terminal_size()?;
+        let vcol = buffer.virtual_col(self.position());
+        let (start, stop) = if buffer.display.wrap {
+            let rows = buffer.screen_rows(self.row, term_width);
+            let row = rows[rows.iter().rposition(|row| row.start <= vcol).unwrap_or(0)];
+            (row.start, row.end)
+        } else {
+            let cells = buffer.line_cells(self.row).len();
+            (self.leftcol, (self.leftcol + buffer.text_width(term_width)).min(cells))
+        };
+        let target = if end { stop.saturating_sub(1).max(start) } else { start };
+        self.col = buffer
+            .get_line(self.row)
+            .map_or(0, |line| display::col_at_virtual(line, target, &buffer.display));
+        self.update_screen_position(buffer)
+    }
+
+    // 上下移动到 row 行，列由 curswant 决定
+    fn move_vertical(&mut self, buffer: &Buffer, row: usize) -> io::Result<()> {
+        self.row = row;
+        self.col = buffer
+            .get_line(row)
+            .map_or(0, |line| display::col_at_virtual(line, self.curswant, &buffer.display));
+        self.update_screen(buffer)
+    }
+
+    // 移动函数返回是否真的移动了，到达边界时返回 false
+    pub fn move_left(&mut self, buffer: &Buffer) -> io::Result<bool> {
         if self.col > 0 {
-            self.col -= 1;
+            let line = buffer.get_line(self.row).map_or("", |line| line.as_str());
+            let prev = line.get(..self.col).and_then(|before| before.chars().next_back());
+            self.col -= prev.map_or(1, char::len_utf8);
             self.update_screen_position(buffer)?;
+            return Ok(true);
         }
-        Ok(())
+        Ok(false)
     }
 
-    pub fn move_right(&mut self, buffer: &Buffer) -> io::Result<()> {
+    pub fn move_right(&mut self, buffer: &Buffer) -> io::Result<bool> {
         if let Some(line) = buffer.get_line(self.row) {
             if self.col < line.len() {
-                self.col += 1;
+                let next = line.get(self.col..).and_then(|rest| rest.chars().next());
+                self.col += next.map_or(1, char::len_utf8);
                 self.update_screen_position(buffer)?;
+                return Ok(true);
             }
         }
-        Ok(())
+        Ok(false)
     }
 
-    pub fn move_up(&mut self, buffer: &Buffer) -> io::Result<()> {
-        if self.row > 0 {
-            self.row -= 1;
-            if let Some(line) = buffer.get_line(self.row) {
-                self.col = self.col.min(line.len());
-            }
-            self.update_screen_position(buffer)?;
+    // 上下移动时跳过关闭的折叠，光标停在折叠的开始行
+    pub fn move_up(&mut self, buffer: &Buffer) -> io::Result<bool> {
+        let start = buffer.fold_start(self.row);
+        if start > 0 {
+            self.move_vertical(buffer, buffer.fold_start(start - 1))?;
+            return Ok(true);
         }
-        Ok(())
+        Ok(false)
     }
 
-    pub fn move_down(&mut self, buffer: &Buffer) -> io::Result<()> {
+    pub fn move_down(&mut self, buffer: &Buffer) -> io::Result<bool> {
         if buffer.line_count() == 0 {
-            return Ok(());
+            return Ok(false);
         }
 
-        if self.row >= buffer.line_count() - 1 {
-            return Ok(());
+        let end = buffer.fold_end(self.row);
+        if end >= buffer.line_count() - 1 {
+            return Ok(false);
         }
 
-        self.row += 1;
-        
-        if let Some(line) = buffer.get_line(self.row) {
-            self.col = self.col.min(line.len());
-        }
-        
-        self.update_screen_position(buffer)?;
-        Ok(())
+        self.move_vertical(buffer, buffer.fold_start(end + 1))?;
+        Ok(true)
     }
 
     pub fn move_to_start(&mut self, buffer: &Buffer) -> io::Result<()> {
@@ -99,4 +280,108 @@ impl Cursor {
         }
         Ok(())
     }
+
+    pub fn position(&self) -> Position {
+        Position::new(self.row, self.col)
+    }
+
+    // 移动到指定位置，超出文本范围时限制在最后一行/行尾
+    pub fn move_to(&mut self, buffer: &Buffer, position: Position) -> io::Result<()> {
+        self.row = position.row.min(buffer.line_count().saturating_sub(1));
+        let line_len = buffer.get_line(self.row).map_or(0, |line| line.len());
+        self.col = position.col.min(line_len);
+        self.update_screen_position(buffer)
+    }
+
+    pub fn move_to_first_non_blank(&mut self, buffer: &Buffer) -> io::Result<()> {
+        self.col = buffer
+            .get_line(self.row)
+            .and_then(|line| line.find(|c: char| !c.is_whitespace()))
+            .unwrap_or(0);
+        self.update_screen_position(buffer)
+    }
+
+    // w：下一个单词的开始，空行也算一个单词
+    pub fn move_word_forward(&mut self, buffer: &Buffer) -> io::Result<bool> {
+        let start = self.position();
+        let class = class_at(buffer, start);
+        let mut position = start;
+
+        while class != 0 && class_at(buffer, position) == class {
+            match next_position(buffer, position) {
+                Some(next) if next.row == position.row => position = next,
+                _ => break,
+            }
+        }
+        while class_at(buffer, position) == 0 {
+            match next_position(buffer, position) {
+                Some(next) if next.row != position.row && line_len(buffer, next.row) == 0 => {
+                    position = next;
+                    break;
+                }
+                Some(next) => position = next,
+                None => break,
+            }
+        }
+
+        if position == start {
+            return Ok(false);
+        }
+        self.move_to(buffer, position)?;
+        Ok(true)
+    }
+
+    // b：当前或上一个单词的开始
+    pub fn move_word_backward(&mut self, buffer: &Buffer) -> io::Result<bool> {
+        let start = self.position();
+        let mut position = match prev_position(buffer, start) {
+            Some(position) => position,
+            None => return Ok(false),
+        };
+
+        while class_at(buffer, position) == 0 && line_len(buffer, position.row) > 0 {
+            match prev_position(buffer, position) {
+                Some(prev) => position = prev,
+                None => break,
+            }
+        }
+        let class = class_at(buffer, position);
+        while class != 0 && position.col > 0 {
+            let prev = Position::new(position.row, position.col - 1);
+            if class_at(buffer, prev) != class {
+                break;
+            }
+            position = prev;
+        }
+
+        self.move_to(buffer, position)?;
+        Ok(true)
+    }
+
+    // e：当前或下一个单词的结尾
+    pub fn move_word_end(&mut self, buffer: &Buffer) -> io::Result<bool> {
+        let start = self.position();
+        let mut position = match next_position(buffer, start) {
+            Some(position) => position,
+            None => return Ok(false),
+        };
+
+        while class_at(buffer, position) == 0 {
+            match next_position(buffer, position) {
+                Some(next) => position = next,
+                None => return Ok(false),
+            }
+        }
+        let class = class_at(buffer, position);
+        loop {
+            let next = Position::new(position.row, position.col + 1);
+            if next.col >= line_len(buffer, position.row) || class_at(buffer, next) != class {
+                break;
+            }
+            position = next;
+        }
+
+        self.move_to(buffer, position)?;
+        Ok(true)
+    }
 }
@@ -0,0 +1,299 @@
+// 行在屏幕上的显示：制表符按 tabstop 展开，控制字符显示为 ^X，list 模式显示 listchars
+
+use crate::encoding;
+
+// listchars 选项中各种字符的显示方式，None 表示正常显示
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListChars {
+    // 制表符：第一个字符、填充字符和可选的最后一个字符，如 tab:>- 或 tab:<->
+    pub tab: Option<(char, char, Option<char>)>,
+    pub trail: Option<char>,
+    pub nbsp: Option<char>,
+    pub space: Option<char>,
+    pub eol: Option<char>,
+}
+
+impl Default for ListChars {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListChars {
+    // 和 vim 一样默认只显示行尾的 $
+    pub fn new() -> Self {
+        Self {
+            tab: None,
+            trail: None,
+            nbsp: None,
+            space: None,
+            eol: Some('$'),
+        }
+    }
+
+    // 解析 listchars 选项，如 "tab:>-,trail:-,nbsp:+"
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut listchars = Self {
+            eol: None,
+            ..Self::new()
+        };
+        let invalid = || format!("E474: Invalid argument: listchars={}", value);
+        for item in value.split(',').filter(|item| !item.is_empty()) {
+            let (name, chars) = item.split_once(':').ok_or_else(invalid)?;
+            let chars: Vec<char> = chars.chars().collect();
+            match (name, chars.as_slice()) {
+                ("tab", [first, fill]) => listchars.tab = Some((*first, *fill, None)),
+                ("tab", [first, fill, last]) => listchars.tab = Some((*first, *fill, Some(*last))),
+                ("trail", [c]) => listchars.trail = Some(*c),
+                ("nbsp", [c]) => listchars.nbsp = Some(*c),
+                ("space", [c]) => listchars.space = Some(*c),
+                ("eol", [c]) => listchars.eol = Some(*c),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(listchars)
+    }
+}
+
+// 显示一行需要的选项
+#[derive(Debug, Clone, PartialEq)]
+pub struct Display {
+    pub tabstop: usize,
+    pub list: bool,
+    pub listchars: ListChars,
+    // 长行折行显示，nowrap 时水平滚动
+    pub wrap: bool,
+    // 在 breakat 中的字符之后折行
+    pub linebreak: bool,
+    // 折行的后续部分和行首对齐
+    pub breakindent: bool,
+    // 显示在折行的后续部分前面
+    pub showbreak: String,
+    // nowrap 时水平滚动的最小列数
+    pub sidescroll: usize,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Self {
+        Self {
+            tabstop: 8,
+            list: false,
+            listchars: ListChars::new(),
+            wrap: true,
+            linebreak: false,
+            breakindent: false,
+            showbreak: String::new(),
+            sidescroll: 0,
+        }
+    }
+
+    // list 模式下没有设置 tab 时，制表符像控制字符一样显示为 ^I
+    fn expand_tab(&self) -> bool {
+        !self.list || self.listchars.tab.is_some()
+    }
+}
+
+// 屏幕上的一列
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cell {
+    // 显示的字符，宽字符的第二列为 None
+    pub ch: Option<char>,
+    // 这一列对应的字符在行中的字节位置
+    pub byte: usize,
+    // 是否是 listchars 或 ^X 这样的特殊显示
+    pub special: bool,
+}
+
+// 字符占的列数，中日韩文字和全角符号占两列
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+// 把一行展开成屏幕上的列
+pub fn cells(line: &str, display: &Display) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    let trail_start = line.trim_end_matches(' ').len();
+    for (byte, c) in line.char_indices() {
+        let plain = |ch| Cell { ch: Some(ch), byte, special: false };
+        let special = |ch| Cell { ch: Some(ch), byte, special: true };
+        match c {
+            '\t' if display.expand_tab() => {
+                let width = display.tabstop - cells.len() % display.tabstop;
+                match display.listchars.tab.filter(|_| display.list) {
+                    Some((first, fill, last)) => {
+                        for i in 0..width {
+                            let ch = match (i, last) {
+                                (i, Some(last)) if i + 1 == width => last,
+                                (0, _) => first,
+                                _ => fill,
+                            };
+                            cells.push(special(ch));
+                        }
+                    }
+                    None => cells.extend((0..width).map(|_| plain(' '))),
+                }
+            }
+            ' ' if display.list && byte >= trail_start && display.listchars.trail.is_some() => {
+                cells.extend(display.listchars.trail.map(special));
+            }
+            ' ' if display.list && display.listchars.space.is_some() => {
+                cells.extend(display.listchars.space.map(special));
+            }
+            '\u{a0}' if display.list && display.listchars.nbsp.is_some() => {
+                cells.extend(display.listchars.nbsp.map(special));
+            }
+            // binary 模式中不是 UTF-8 的字节显示为 <xx>
+            c if encoding::escaped_byte(c).is_some() => {
+                let byte = encoding::escaped_byte(c).unwrap_or(0);
+                cells.extend(format!("<{:02x}>", byte).chars().map(special));
+            }
+            c if (c as u32) < 0x20 || c == '\x7f' => {
+                let shown = if c == '\x7f' { '?' } else { ((c as u8) ^ 0x40) as char };
+                cells.push(special('^'));
+                cells.push(special(shown));
+            }
+            c => {
+                cells.push(plain(c));
+                if char_width(c) == 2 {
+                    cells.push(Cell { ch: None, byte, special: false });
+                }
+            }
+        }
+    }
+    if display.list {
+        if let Some(eol) = display.listchars.eol {
+            cells.push(Cell { ch: Some(eol), byte: line.len(), special: true });
+        }
+    }
+    cells
+}
+
+// linebreak 时可以在这些字符之后折行，和 vim 的默认 breakat 相同
+const BREAKAT: &str = " \t!@*-+;:,./?";
+
+// 折行后的一个屏幕行：显示 cells[start..end]，前面有 prefix 列的 showbreak 和缩进
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScreenRow {
+    pub start: usize,
+    pub end: usize,
+    pub prefix: usize,
+}
+
+// 把一行的屏幕列按 width 折成多个屏幕行，nowrap 时只有一行
+pub fn screen_rows(line: &str, cells: &[Cell], width: usize, display: &Display) -> Vec<ScreenRow> {
+    if !display.wrap || cells.is_empty() {
+        return vec![ScreenRow { start: 0, end: cells.len(), prefix: 0 }];
+    }
+    let mut prefix = display.showbreak.chars().map(char_width).sum::<usize>();
+    if display.breakindent {
+        prefix += crate::indent::indent_width(line, display.tabstop);
+    }
+    // 前缀太宽时不显示，保证每行至少能显示一半的宽度
+    if prefix > width / 2 {
+        prefix = 0;
+    }
+
+    let mut rows = Vec::new();
+    let mut start = 0;
+    while start < cells.len() {
+        let row_prefix = if rows.is_empty() { 0 } else { prefix };
+        let mut end = (start + width - row_prefix).min(cells.len());
+        if end < cells.len() && display.linebreak {
+            let breaks = (start + 1..=end).rev().find(|&i| {
+                cells[i - 1].ch.is_some_and(|c| BREAKAT.contains(c)) && cells[i].byte != cells[i - 1].byte
+            });
+            end = breaks.unwrap_or(end);
+        }
+        // 宽字符不能分在两行
+        if end < cells.len() && end > start + 1 && cells[end].ch.is_none() {
+            end -= 1;
+        }
+        rows.push(ScreenRow { start, end, prefix: row_prefix });
+        start = end;
+    }
+    rows
+}
+
+// 字节位置 col 在屏幕上的列，即虚拟列
+pub fn virtual_col(line: &str, col: usize, display: &Display) -> usize {
+    let col = col.min(line.len());
+    let cells = cells(line, display);
+    cells.iter().position(|cell| cell.byte >= col).unwrap_or(cells.len())
+}
+
+// 虚拟列 vcol 所在字符的字节位置，超出行尾时返回行的长度
+pub fn col_at_virtual(line: &str, vcol: usize, display: &Display) -> usize {
+    cells(line, display)
+        .get(vcol)
+        .map_or(line.len(), |cell| cell.byte.min(line.len()))
+}
+
+// :retab 把含制表符的空白按新的 tabstop 重新生成，all 时（:retab!）也处理只有空格的空白
+pub fn retab(line: &str, tabstop: usize, new_tabstop: usize, expandtab: bool, all: bool) -> String {
+    let mut result = String::new();
+    let mut vcol = 0;
+    let mut run_start = None;
+    let mut run = String::new();
+
+    let flush = |result: &mut String, run: &mut String, start: Option<usize>, end: usize| {
+        if let Some(start) = start {
+            if run.contains('\t') || (all && run.len() > 1) {
+                result.push_str(&whitespace(start, end, new_tabstop, expandtab));
+            } else {
+                result.push_str(run);
+            }
+        }
+        run.clear();
+    };
+
+    for c in line.chars() {
+        if c == ' ' || c == '\t' {
+            run_start.get_or_insert(vcol);
+            run.push(c);
+            vcol += if c == '\t' { tabstop - vcol % tabstop } else { 1 };
+        } else {
+            flush(&mut result, &mut run, run_start.take(), vcol);
+            result.push(c);
+            vcol += char_width(c);
+        }
+    }
+    flush(&mut result, &mut run, run_start, vcol);
+    result
+}
+
+// 从虚拟列 start 到 end 的空白，noexpandtab 时尽量用制表符
+fn whitespace(start: usize, end: usize, tabstop: usize, expandtab: bool) -> String {
+    if expandtab {
+        return " ".repeat(end - start);
+    }
+    let mut text = String::new();
+    let mut vcol = start;
+    while vcol + (tabstop - vcol % tabstop) <= end {
+        text.push('\t');
+        vcol += tabstop - vcol % tabstop;
+    }
+    text.push_str(&" ".repeat(end - vcol));
+    text
+}
@@ -1,25 +1,64 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
 use std::io::{self, stdout, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use regex::Regex;
 use termion::raw::IntoRawMode;
-use termion::event::Key;
+use termion::event::{Event, Key};
 use termion::input::TermRead;
 use termion::cursor;
 use termion::clear;
 use termion::terminal_size;
 
 use crate::buffer::Buffer;
+use crate::checktime::Watcher;
 use crate::cursor::Cursor;
+use crate::display::{self, Cell};
+use crate::gutter::SIGN_WIDTH;
+use crate::keymap::{Keymaps, MapMatch, MapMode, Mapping};
+use crate::mark::{Marks, Position};
+use crate::insert::InsertPrefix;
+use crate::operator::{ChangeCounts, MotionKind, PendingOperator};
+use crate::options::Options;
+use crate::register::{Register, Registers};
+use crate::search;
+use crate::highlight::Highlights;
+use crate::shell::SudoPrompt;
+use crate::swap::SwapPrompt;
+use crate::syntax::Span;
+
+// 递归映射的最大展开深度，对应 vim 的 maxmapdepth
+const MAX_MAP_DEPTH: usize = 1000;
 
 #[derive(Debug, Copy, Clone)]
 pub enum Mode {
     Normal,
     Insert,
+    Command,
+    Visual,
+    VisualLine,
 }
 
 impl Mode {
-    pub fn display_name(&self) -> &str {
+    pub fn display_name(&self) -> &'static str {
         match self {
             Mode::Normal => "-- NORMAL --",
             Mode::Insert => "-- INSERT --",
+            Mode::Command => "-- COMMAND --",
+            Mode::Visual => "-- VISUAL --",
+            Mode::VisualLine => "-- VISUAL LINE --",
+        }
+    }
+
+    pub fn map_mode(&self) -> MapMode {
+        match self {
+            Mode::Normal => MapMode::Normal,
+            Mode::Insert => MapMode::Insert,
+            Mode::Command => MapMode::CmdLine,
+            Mode::Visual | Mode::VisualLine => MapMode::Visual,
         }
     }
 }
@@ -28,6 +67,77 @@ pub struct Editor {
     pub buffer: Buffer,
     pub cursor: Cursor,
     pub mode: Mode,
+    pub options: Options,
+    pub keymaps: Keymaps,
+    pub command_line: String,
+    pub messages: Vec<String>,
+    pub pending_keys: Vec<Key>,
+    pending_since: Option<Instant>,
+    // 正在执行映射时是否是 <silent> 映射，不是 <silent> 的映射执行命令后在命令行显示这个命令
+    mapping_silent: Option<bool>,
+    pub quit: bool,
+    pub registers: Registers,
+    // 普通模式下输入的计数
+    pub count: Option<usize>,
+    // 等待参数的普通模式命令，如 q、@
+    pub normal_prefix: Option<char>,
+    // 正在录制的宏寄存器和已录制的按键
+    pub recording: Option<char>,
+    pub recorded_keys: Vec<Key>,
+    pub last_macro: Option<char>,
+    // 宏回放插入的按键，和输入的按键走相同的处理流程
+    pub typeahead: VecDeque<Key>,
+    // 命令执行失败（如移动到边界），宏回放遇到它会停止
+    pub aborted: bool,
+    // 可视模式选择的起点
+    pub visual_start: Position,
+    // 等待移动命令的操作符和 "x 选择的寄存器
+    pub operator: Option<PendingOperator>,
+    pub selected_register: Option<char>,
+    // 当前插入的开始位置、进入插入的命令、计数和已输入的文本
+    pub insert_start: Position,
+    pub insert_kind: char,
+    pub insert_count: usize,
+    pub inserted_text: String,
+    // 当前命令的按键和上一次修改的按键，供 . 重复，计数另外记下
+    pub change_keys: Vec<Key>,
+    pub change_counts: ChangeCounts,
+    pub last_change: Vec<Key>,
+    pub last_change_counts: ChangeCounts,
+    // 插入模式下 Ctrl-r、Ctrl-v 等待的参数
+    pub insert_prefix: Option<InsertPrefix>,
+    // Ctrl-o 执行的普通模式命令结束后回到插入模式
+    pub insert_oneshot: bool,
+    // Ctrl-t/Ctrl-d 产生的缩进的结束位置，backspace 不含 indent 时不能删除
+    pub insert_indent: Option<Position>,
+    // :syntax on/off，关闭时不加载语法文件
+    pub syntax_enabled: bool,
+    // 高亮组和配色方案
+    pub highlights: Highlights,
+    // 当前文件的交换文件，以及上一次写入时的 changedtick 和之后的按键数
+    pub swap: Option<PathBuf>,
+    pub swap_tick: usize,
+    pub keys_since_swap: usize,
+    pub last_input: Instant,
+    // 打开文件时发现交换文件，等待回答
+    pub swap_prompt: Option<SwapPrompt>,
+    // :Hex 把 buffer 显示为十六进制
+    pub hex: bool,
+    // 命令行的类型：: 是 Ex 命令，/ 和 ? 是搜索，以及上一次搜索的方向
+    pub command_type: char,
+    pub search_forward: bool,
+    // hlsearch 使用的上一次搜索模式和编译好的正则表达式，模式改变时才重新编译
+    pub search_regex: RefCell<Option<(String, Option<Regex>)>>,
+    // 监视当前文件的 inotify，等待回答的 W11/W12 提示，以及获得焦点或文件变化之后需要检查
+    pub watcher: Option<(PathBuf, Watcher)>,
+    pub changed_prompt: Option<Vec<String>>,
+    pub check_pending: bool,
+    // 终端没有焦点时状态行使用 StatusLineNC
+    pub focused: bool,
+    // :SudoWrite 等待输入密码
+    pub sudo_prompt: Option<SudoPrompt>,
+    // 其他文件中的 A-Z 文件标记和所在的文件，当前文件的 A-Z 标记在 buffer 中以便跟随修改移动
+    pub file_marks: BTreeMap<char, (PathBuf, Position)>,
 }
 
 impl Editor {
@@ -36,16 +146,63 @@ impl Editor {
             buffer: Buffer::new(),
             cursor: Cursor::new(4),  // 从第4行开始（前面有3行提示信息）
             mode: Mode::Normal,
+            options: Options::new(),
+            keymaps: Keymaps::new(),
+            command_line: String::new(),
+            messages: Vec::new(),
+            pending_keys: Vec::new(),
+            mapping_silent: None,
+            pending_since: None,
+            quit: false,
+            registers: Registers::new(),
+            count: None,
+            normal_prefix: None,
+            recording: None,
+            recorded_keys: Vec::new(),
+            last_macro: None,
+            typeahead: VecDeque::new(),
+            aborted: false,
+            visual_start: Position::default(),
+            operator: None,
+            selected_register: None,
+            insert_start: Position::default(),
+            insert_kind: 'i',
+            insert_count: 1,
+            inserted_text: String::new(),
+            change_keys: Vec::new(),
+            change_counts: ChangeCounts::default(),
+            last_change: Vec::new(),
+            last_change_counts: ChangeCounts::default(),
+            insert_prefix: None,
+            insert_oneshot: false,
+            insert_indent: None,
+            syntax_enabled: true,
+            highlights: Highlights::new(),
+            swap: None,
+            swap_tick: 0,
+            keys_since_swap: 0,
+            last_input: Instant::now(),
+            swap_prompt: None,
+            hex: false,
+            command_type: ':',
+            search_forward: true,
+            search_regex: RefCell::new(None),
+            watcher: None,
+            changed_prompt: None,
+            check_pending: false,
+            focused: true,
+            sudo_prompt: None,
+            file_marks: BTreeMap::new(),
         })
     }
 
-    fn set_cursor_style(&self) -> io::Result<()> {
+    pub fn set_cursor_style(&self) -> io::Result<()> {
         match self.mode {
-            Mode::Normal => {
+            Mode::Normal | Mode::Visual | Mode::VisualLine => {
                 // Set block cursor
                 print!("\x1b[2 q");
             }
-            Mode::Insert => {
+            Mode::Insert | Mode::Command => {
                 // Set vertical line cursor
                 print!("\x1b[6 q");
             }
@@ -60,19 +217,44 @@ impl Editor {
         self.set_cursor_style()?;
         self.draw()?;
         
-        let stdin = io::stdin();
-        for key in stdin.keys() {
-            if !self.handle_key(key?)? {
-                break;
+        // 非阻塞读取，以便在等待按键时处理 timeoutlen
+        let mut events = termion::async_stdin().events();
+        loop {
+            match events.next() {
+                Some(event) => match event? {
+                    Event::Key(key) if !self.handle_key(key)? => break,
+                    // 终端获得焦点时检查文件是否在编辑器外被修改
+                    Event::Unsupported(bytes) if bytes == b"\x1b[I" => {
+                        self.check_pending = true;
+                        self.focused = true;
+                        self.draw()?;
+                    }
+                    Event::Unsupported(bytes) if bytes == b"\x1b[O" => {
+                        self.focused = false;
+                        self.draw()?;
+                    }
+                    _ => (),
+                },
+                None => {
+                    self.check_timeout()?;
+                    self.check_swap_idle();
+                    if self.check_file_changes()? {
+                        self.draw()?;
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
             }
         }
-        
+
+        self.close_swap();
+        print!("\x1b[m");
         self.clear_screen()?;
         Ok(())
     }
 
     fn init_screen(&self) -> io::Result<()> {
-        print!("{}{}", clear::All, cursor::Goto(1, 1));
+        // 打开焦点报告，终端获得焦点时发送 ESC [ I
+        print!("{}{}\x1b[?1004h", clear::All, cursor::Goto(1, 1));
         println!("欢迎使用 RustVim! 按 Ctrl-c 退出\r");
         println!("按 i 进入插入模式，按 ESC 返回普通模式\r");
         println!("普通模式命令: x(删除字符) o/O(插入新行) 0/$(行首/尾)\r");
@@ -80,128 +262,1004 @@ impl Editor {
     }
 
     fn clear_screen(&self) -> io::Result<()> {
-        print!("{}", clear::All);
+        print!("{}\x1b[?1004l", clear::All);
         stdout().flush()
     }
 
     fn show_mode(&self) -> io::Result<()> {
         let (width, _) = terminal_size()?;
-        print!("{}{}{}",
-            cursor::Goto(width - 12, 1),
-            clear::CurrentLine,
-            self.mode.display_name()
+        let name = match self.mode {
+            Mode::Normal if self.insert_oneshot => "-- (insert) --",
+            mode => mode.display_name(),
+        };
+        // 第一行是状态行，模式显示在右边
+        let status = self.highlights.start(if self.focused { "StatusLine" } else { "StatusLineNC" });
+        print!("{}{}{}{}{:<15}{}",
+            cursor::Goto(1, 1),
+            status,
+            " ".repeat(width.saturating_sub(15) as usize),
+            self.highlights.start("ModeMsg"),
+            name,
+            self.highlights.end()
         );
         stdout().flush()
     }
 
-    fn draw_line(&self, line_num: usize, screen_row: u16) -> io::Result<()> {
+    fn draw_line(&self, line_num: usize, screen_row: u16, search: Option<&Regex>) -> io::Result<()> {
         let (term_width, _) = terminal_size()?;
-        let screen_lines = self.buffer.line_screen_rows(line_num, term_width)?;
-        
-        // 绘制第一行（包含行号）
-        print!("{}", cursor::Goto(1, screen_row));
-        print!("{}", clear::CurrentLine);
-        print!("{:3} {}", line_num + 1, self.buffer.get_line_part(line_num, 0, term_width));
-        
-        // 绘制后续折行（不包含行号，用空格对齐）
-        for i in 1..screen_lines {
-            print!("{}", cursor::Goto(1, screen_row + i));
+        let effective_width = self.buffer.text_width(term_width);
+        let cells = self.buffer.line_cells(line_num);
+        let spans = self.buffer.line_spans(line_num);
+        let selection = self.visual_selection(line_num);
+        let line = self.buffer.get_line(line_num).map_or("", |line| line.as_str());
+        let matches = search.map_or_else(Vec::new, |regex| search::line_matches(regex, line));
+
+        // nowrap 时只显示从 leftcol 开始的一屏
+        if !self.buffer.display.wrap {
+            let start = self.cursor.leftcol.min(cells.len());
+            let end = (start + effective_width).min(cells.len());
+            print!("{}{}{}", cursor::Goto(1, screen_row), clear::CurrentLine, self.gutter(Some(line_num)));
+            self.draw_line_part(line_num, &cells[start..end], &spans, selection, &matches);
+            return stdout().flush();
+        }
+
+        // 第一行显示行号，后续折行用空白对齐，前面显示 breakindent 的缩进和 showbreak
+        let rows = display::screen_rows(line, &cells, effective_width, &self.buffer.display);
+        let showbreak = &self.buffer.display.showbreak;
+        for (i, row) in rows.iter().enumerate() {
+            print!("{}", cursor::Goto(1, screen_row + i as u16));
             print!("{}", clear::CurrentLine);
-            print!("    {}", self.buffer.get_line_part(line_num, i, term_width));
+            print!("{}", self.gutter(if i == 0 { Some(line_num) } else { None }));
+            if row.prefix > 0 {
+                let indent = row.prefix - showbreak.chars().count().min(row.prefix);
+                print!("{}", " ".repeat(indent));
+                if !showbreak.is_empty() {
+                    print!("{}{}{}", self.highlights.start("NonText"), showbreak, self.highlights.end());
+                }
+            }
+            self.draw_line_part(line_num, &cells[row.start..row.end], &spans, selection, &matches);
         }
         
         stdout().flush()
     }
 
+    // 关闭的折叠：用 Folded 显示折叠的行数和第一行的内容，用 - 填满一行
+    fn draw_fold(&self, start: usize, end: usize, screen_row: u16) -> io::Result<()> {
+        let (term_width, _) = terminal_size()?;
+        let width = self.buffer.text_width(term_width);
+        let line = self.buffer.get_line(start).map_or("", |line| line.as_str());
+        let mut text = String::new();
+        let mut used = 0;
+        for ch in self.buffer.folds().text(start, end, line).chars() {
+            let ch = if ch == '\t' { ' ' } else { ch };
+            let ch_width = display::char_width(ch);
+            if used + ch_width > width {
+                break;
+            }
+            text.push(ch);
+            used += ch_width;
+        }
+        text.push_str(&"-".repeat(width - used));
+        print!("{}{}{}", cursor::Goto(1, screen_row), clear::CurrentLine, self.gutter(Some(start)));
+        print!("{}{}{}", self.highlights.start("Folded"), text, self.highlights.end());
+        stdout().flush()
+    }
+
+    // 行左边的折叠列、标记列和行号列，折行的后续部分只显示空白
+    fn gutter(&self, line_num: Option<usize>) -> String {
+        let gutter = &self.buffer.gutter;
+        // 大文件只读入了一部分行，行号从读入的第一行在文件中的行号开始
+        let offset = self.buffer.line_offset();
+        let line_count = self.buffer.line_count() + offset;
+        let highlight = |group: &str, text: &str| {
+            let start = self.highlights.start(group);
+            if start.is_empty() {
+                text.to_string()
+            } else {
+                format!("{}{}{}", start, text, self.highlights.end())
+            }
+        };
+
+        let mut output = String::new();
+        if gutter.foldcolumn > 0 {
+            let text = match line_num {
+                Some(row) => self.buffer.folds().column(row, gutter.foldcolumn),
+                None => " ".repeat(gutter.foldcolumn),
+            };
+            output.push_str(&highlight("FoldColumn", &text));
+        }
+        if gutter.sign_width(!self.buffer.signs.placed().is_empty()) > 0 {
+            match line_num.and_then(|row| self.buffer.signs.at(row)) {
+                Some(sign) => {
+                    let width: usize = sign.text.chars().map(display::char_width).sum();
+                    let text = format!("{}{}", sign.text, " ".repeat(SIGN_WIDTH.saturating_sub(width)));
+                    output.push_str(&highlight(sign.texthl.as_deref().unwrap_or("SignColumn"), &text));
+                }
+                None => output.push_str(&highlight("SignColumn", "  ")),
+            }
+        }
+        let width = gutter.number_width(line_count);
+        match line_num {
+            Some(row) if width > 0 => {
+                let text = gutter.number_text(row + offset, self.cursor.row + offset, line_count);
+                let current = row == self.cursor.row && (self.options.cursorline || gutter.relativenumber);
+                output.push_str(&highlight(if current { "CursorLineNr" } else { "LineNr" }, &text));
+            }
+            _ => output.push_str(&" ".repeat(width)),
+        }
+        output
+    }
+
+    // 绘制折行的一部分：可视模式选中的部分用 Visual，hlsearch 的匹配用 Search，listchars 等特殊字符
+    // 用 NonText 和 SpecialKey，其余字符按语法高亮组着色，cursorline 时整行使用 CursorLine
+    fn draw_line_part(
+        &self,
+        line_num: usize,
+        cells: &[Cell],
+        spans: &[Span],
+        selection: Option<(usize, usize)>,
+        matches: &[(usize, usize)],
+    ) {
+        let line_len = self.buffer.get_line(line_num).map_or(0, |line| line.len());
+        let base = if self.options.cursorline && line_num == self.cursor.row {
+            self.highlights.start("CursorLine")
+        } else {
+            String::new()
+        };
+        let end = self.highlights.end();
+        let mut output = base.clone();
+        for cell in cells {
+            let ch = match cell.ch {
+                Some(ch) => ch,
+                None => continue,
+            };
+            let selected = selection.is_some_and(|(start, end)| cell.byte >= start && cell.byte < end);
+            let group = if selected {
+                Some("Visual")
+            } else if matches.iter().any(|&(start, end)| cell.byte >= start && cell.byte < end) {
+                Some("Search")
+            } else if cell.special {
+                Some(if cell.byte >= line_len { "NonText" } else { "SpecialKey" })
+            } else {
+                spans
+                    .iter()
+                    .rev()
+                    .find(|span| cell.byte >= span.start && cell.byte < span.end)
+                    .map(|span| span.group.as_str())
+            };
+            let start = group.map_or_else(String::new, |group| self.highlights.start(group));
+            if start.is_empty() {
+                output.push(ch);
+                continue;
+            }
+            // 结束高亮后恢复 CursorLine
+            output.push_str(&start);
+            output.push(ch);
+            output.push_str(&end);
+            output.push_str(&base);
+        }
+        if !base.is_empty() {
+            output.push_str(&end);
+        }
+        print!("{}", output);
+    }
+
+    // 返回该行被选中的列范围 [start, end)
+    fn visual_selection(&self, line_num: usize) -> Option<(usize, usize)> {
+        let (start, end) = match self.mode {
+            Mode::Visual | Mode::VisualLine => {
+                let cursor = self.cursor.position();
+                (self.visual_start.min(cursor), self.visual_start.max(cursor))
+            }
+            _ => return None,
+        };
+        if line_num < start.row || line_num > end.row {
+            return None;
+        }
+
+        let line_len = self.buffer.get_line(line_num).map_or(0, |line| line.len());
+        if let Mode::VisualLine = self.mode {
+            return Some((0, line_len.max(1)));
+        }
+        let from = if line_num == start.row { start.col } else { 0 };
+        let to = if line_num == end.row { end.col + 1 } else { line_len.max(1) };
+        Some((from, to))
+    }
+
     fn draw(&self) -> io::Result<()> {
+        // 没有其他高亮的地方使用 Normal 的颜色，清除的行也用它的背景色
+        print!("{}", self.highlights.end());
         self.show_mode()?;
+        let (term_width, term_height) = terminal_size()?;
+        let search = self.search_highlight();
         
         // 绘制所有行
         let mut screen_row = 4;  // 从第4行开始
-        for line_num in 0..self.buffer.line_count() {
-            self.draw_line(line_num, screen_row)?;
-            screen_row += self.buffer.line_screen_rows(line_num, terminal_size()?.0)?;
+        let mut line_num = 0;
+        while line_num < self.buffer.line_count() {
+            // 关闭的折叠显示为一行
+            if let Some((start, end)) = self.buffer.closed_fold(line_num) {
+                self.draw_fold(start, end, screen_row)?;
+                screen_row += 1;
+                line_num = end + 1;
+                continue;
+            }
+            self.draw_line(line_num, screen_row, search.as_ref())?;
+            screen_row += self.buffer.line_screen_rows(line_num, term_width)?;
+            line_num += 1;
         }
+
+        // 清除文本下方残留的内容
+        while screen_row < term_height {
+            print!("{}{}", cursor::Goto(1, screen_row), clear::CurrentLine);
+            screen_row += 1;
+        }
+
+        self.draw_command_line(term_height)?;
         
         // 更新光标位置
-        print!("{}", cursor::Goto(self.cursor.screen_col, self.cursor.screen_row));
+        match self.mode {
+            Mode::Command => {
+                let col = self.command_line.chars().count() as u16 + 2;
+                print!("{}", cursor::Goto(col, term_height));
+            }
+            _ => print!("{}", cursor::Goto(self.cursor.screen_col, self.cursor.screen_row)),
+        }
         stdout().flush()
     }
 
+    // 最底部一行显示命令行，消息从底部向上显示
+    fn draw_command_line(&self, term_height: u16) -> io::Result<()> {
+        if let Mode::Command = self.mode {
+            print!("{}{}{}{}", cursor::Goto(1, term_height), clear::CurrentLine, self.command_type, self.command_line);
+            return Ok(());
+        }
+
+        let count = self.messages.len().min(term_height as usize - 4);
+        let first_row = term_height - count.max(1) as u16 + 1;
+        print!("{}{}", cursor::Goto(1, term_height), clear::CurrentLine);
+        if let (Some(register), true) = (self.recording, self.messages.is_empty()) {
+            print!("recording @{}", register);
+        }
+        for (i, message) in self.messages[self.messages.len() - count..].iter().enumerate() {
+            print!("{}{}{}", cursor::Goto(1, first_row + i as u16), clear::CurrentLine, message);
+        }
+        Ok(())
+    }
+
     pub fn handle_key(&mut self, key: Key) -> io::Result<bool> {
-        match key {
-            Key::Ctrl('c') => return Ok(false),
-            Key::Left => self.cursor.move_left(&self.buffer)?,
-            Key::Right => self.cursor.move_right(&self.buffer)?,
-            Key::Up => self.cursor.move_up(&self.buffer)?,
-            Key::Down => self.cursor.move_down(&self.buffer)?,
-            key => {
-                match self.mode {
-                    Mode::Normal => self.handle_normal_mode(key)?,
-                    Mode::Insert => self.handle_insert_mode(key)?,
-                }
+        if key == Key::Ctrl('c') {
+            return Ok(false);
+        }
+
+        self.messages.clear();
+        self.aborted = false;
+        if self.sudo_prompt.is_some() {
+            self.answer_sudo_prompt(key)?;
+            self.draw()?;
+            return Ok(!self.quit);
+        }
+        if self.changed_prompt.is_some() {
+            self.answer_changed_prompt(key)?;
+            self.draw()?;
+            return Ok(!self.quit);
+        }
+        if self.swap_prompt.is_some() {
+            self.answer_swap_prompt(key)?;
+            self.draw()?;
+            return Ok(!self.quit);
+        }
+        if self.recording.is_some() {
+            self.recorded_keys.push(key);
+        }
+
+        self.feed_key(key)?;
+        self.run_typeahead()?;
+        // 回到普通模式时一个命令（包括整个插入）结束，作为一个撤销节点
+        if let Mode::Normal = self.mode {
+            self.commit_undo();
+            if self.hex {
+                self.snap_hex_cursor()?;
             }
+            self.update_large_window()?;
         }
+        self.count_swap_key();
+
         self.draw()?;
-        Ok(true)
+        Ok(!self.quit)
+    }
+
+    pub fn feed_key(&mut self, key: Key) -> io::Result<()> {
+        self.pending_keys.push(key);
+        self.pending_since = Some(Instant::now());
+        self.process_pending(false)
+    }
+
+    // 依次执行宏插入的按键，出错时丢弃剩余的按键
+    pub fn run_typeahead(&mut self) -> io::Result<()> {
+        while let Some(key) = self.typeahead.pop_front() {
+            self.feed_key(key)?;
+            if self.aborted || self.quit {
+                self.typeahead.clear();
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // 显示错误消息并中止正在执行的宏
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.messages.push(message.into());
+        self.aborted = true;
+    }
+
+    // 等待的按键超过 timeoutlen 时，按已输入的部分解析映射
+    pub fn check_timeout(&mut self) -> io::Result<()> {
+        let expired = match self.pending_since {
+            Some(since) => {
+                self.options.timeout
+                    && since.elapsed() >= Duration::from_millis(self.options.timeoutlen as u64)
+            }
+            None => false,
+        };
+        if expired && !self.pending_keys.is_empty() {
+            self.process_pending(true)?;
+            self.draw()?;
+        }
+        Ok(())
+    }
+
+    pub fn process_pending(&mut self, force: bool) -> io::Result<()> {
+        let mut keys = std::mem::take(&mut self.pending_keys);
+        self.resolve_keys(&mut keys, force, 0)?;
+        keys.append(&mut self.pending_keys);
+        self.pending_keys = keys;
+        if self.pending_keys.is_empty() {
+            self.pending_since = None;
+        }
+        Ok(())
+    }
+
+    // 在按键序列中查找映射并执行，force 为 false 时遇到映射前缀会停下等待更多按键
+    fn resolve_keys(&mut self, keys: &mut Vec<Key>, force: bool, depth: usize) -> io::Result<()> {
+        while !keys.is_empty() && !self.quit {
+            let map_mode = self.map_mode();
+            match self.keymaps.lookup(map_mode, keys) {
+                MapMatch::Full(mapping) => {
+                    keys.clear();
+                    self.apply_mapping(mapping, depth)?;
+                }
+                MapMatch::Prefix | MapMatch::Ambiguous(_) if !force => return Ok(()),
+                _ => {
+                    // 使用最长的完整匹配前缀，没有则原样执行第一个按键
+                    let matched = (1..=keys.len()).rev().find_map(|len| {
+                        match self.keymaps.lookup(map_mode, &keys[..len]) {
+                            MapMatch::Full(mapping) | MapMatch::Ambiguous(mapping) => Some((len, mapping)),
+                            _ => None,
+                        }
+                    });
+                    match matched {
+                        Some((len, mapping)) => {
+                            keys.drain(..len);
+                            self.apply_mapping(mapping, depth)?;
+                        }
+                        None => {
+                            let key = keys.remove(0);
+                            self.dispatch_key(key)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_mapping(&mut self, mapping: Mapping, depth: usize) -> io::Result<()> {
+        if depth >= MAX_MAP_DEPTH {
+            self.error("E223: recursive mapping");
+            return Ok(());
+        }
+
+        // <silent> 映射中再使用的映射也不显示
+        let silent = self.mapping_silent;
+        self.mapping_silent = Some(mapping.silent || silent == Some(true));
+        let result = self.run_mapping(mapping, depth);
+        self.mapping_silent = silent;
+        result
+    }
+
+    fn run_mapping(&mut self, mapping: Mapping, depth: usize) -> io::Result<()> {
+        if mapping.noremap {
+            for key in mapping.rhs {
+                self.dispatch_key(key)?;
+            }
+            return Ok(());
+        }
+
+        // 像 vim 一样，rhs 以 lhs 开头时（如 nmap j jzz）开头部分不再映射
+        let mut rhs = mapping.rhs;
+        if rhs.starts_with(&mapping.lhs) {
+            for key in rhs.drain(..mapping.lhs.len()) {
+                self.dispatch_key(key)?;
+            }
+        }
+        self.resolve_keys(&mut rhs, true, depth + 1)
+    }
+
+    // 有等待的操作符时使用 omap 的映射
+    pub fn map_mode(&self) -> MapMode {
+        match (self.mode, self.operator) {
+            (Mode::Normal, Some(_)) => MapMode::OperatorPending,
+            (mode, _) => mode.map_mode(),
+        }
+    }
+
+    // 普通模式下没有未完成的计数、前缀和操作符时，按键开始一个新的命令
+    pub fn starts_command(&self) -> bool {
+        matches!(self.mode, Mode::Normal)
+            && self.count.is_none()
+            && self.normal_prefix.is_none()
+            && self.operator.is_none()
+            && self.selected_register.is_none()
+    }
+
+    // 执行一个已经过映射处理的按键
+    pub fn dispatch_key(&mut self, key: Key) -> io::Result<()> {
+        if self.starts_command() {
+            self.change_keys.clear();
+            self.change_counts = ChangeCounts::default();
+        }
+        if !matches!(self.mode, Mode::Command) {
+            self.change_keys.push(key);
+        }
+
+        let from_insert = matches!(self.mode, Mode::Insert);
+        match (self.mode, key) {
+            (Mode::Command, key) => self.handle_command_mode(key)?,
+            (_, Key::Left) => {
+                self.cursor.move_left(&self.buffer)?;
+            }
+            (_, Key::Right) => {
+                self.cursor.move_right(&self.buffer)?;
+            }
+            (_, Key::Up) => {
+                self.cursor.move_up(&self.buffer)?;
+            }
+            (_, Key::Down) => {
+                self.cursor.move_down(&self.buffer)?;
+            }
+            (Mode::Normal, key) => self.handle_normal_mode(key)?,
+            (Mode::Insert, key) => self.handle_insert_mode(key)?,
+            (Mode::Visual | Mode::VisualLine, key) => self.handle_visual_mode(key)?,
+        }
+        if self.insert_oneshot && !from_insert {
+            self.resume_insert()?;
+        }
+        Ok(())
     }
 
     pub fn handle_normal_mode(&mut self, key: Key) -> io::Result<()> {
+        if let Some(prefix) = self.normal_prefix.take() {
+            return self.handle_normal_prefix(prefix, key);
+        }
+
+        match key {
+            Key::Char(c @ '1'..='9') => {
+                self.push_count(c);
+                return Ok(());
+            }
+            // 0 在计数中是数字，否则是移动到行首
+            Key::Char('0') if self.count.is_some() => {
+                self.push_count('0');
+                return Ok(());
+            }
+            Key::Esc => {
+                self.count = None;
+                self.operator = None;
+                self.selected_register = None;
+                return Ok(());
+            }
+            // / 和 ? 也可以作为操作符的移动命令，计数留给搜索使用
+            Key::Char(c @ ('/' | '?')) => return self.start_search(c),
+            // gqq 格式化当前行，和 dd 一样处理
+            Key::Char('q') if self.operator.is_some_and(|pending| pending.op == 'q') => (),
+            Key::Char('q') if self.recording.is_some() && self.operator.is_none() => {
+                self.count = None;
+                self.stop_recording();
+                return Ok(());
+            }
+            // :Hex 显示时 h、l 按字节移动
+            Key::Char(c @ ('h' | 'l')) if self.hex && self.operator.is_none() => {
+                let n = self.count.take().unwrap_or(1);
+                return self.hex_move(c == 'l', n);
+            }
+            Key::Char(c @ ('q' | '@' | 'm' | '\'' | '`' | 'g' | '"' | 'z' | 'r')) => {
+                self.normal_prefix = Some(c);
+                return Ok(());
+            }
+            // 操作符后面的 i、a 开始一个文本对象
+            Key::Char(c @ ('i' | 'a')) if self.operator.is_some() => {
+                self.normal_prefix = Some(c);
+                return Ok(());
+            }
+            _ => (),
+        }
+
+        let count = self.count.take();
+        let start = self.cursor.position();
+        let pending_op = self.operator.map(|pending| pending.op);
+        // 像 vim 一样，光标在单词上时 cw 相当于 ce
+        let key = match (pending_op, key) {
+            (Some('c'), Key::Char('w')) if !self.char_under_cursor().is_whitespace() => Key::Char('e'),
+            _ => key,
+        };
+        if let Some((kind, moved)) = self.do_motion(key, self.motion_count(count))? {
+            // 操作符的 w 不跨过行尾：最后一个单词在行尾时到行尾为止
+            if pending_op.is_some() && key == Key::Char('w') && self.cursor.row > start.row {
+                let row = self.cursor.row - 1;
+                let len = self.buffer.get_line(row).map_or(0, |line| line.len());
+                self.cursor.move_to(&self.buffer, Position::new(row, len))?;
+            }
+            return self.finish_motion(start, kind, moved);
+        }
+
+        if let Key::Char(op @ ('d' | 'c' | 'y' | '=' | '<' | '>' | '!' | 'q')) = key {
+            match self.operator.take() {
+                // dd、cc、yy、==、<<、>>、!!、gqq
+                Some(pending) if pending.op == op => {
+                    let n = pending.count.unwrap_or(1).saturating_mul(count.unwrap_or(1));
+                    self.operate_lines(op, n)?;
+                    if !matches!(op, 'y' | 'c' | '!') {
+                        self.finish_change();
+                    }
+                }
+                Some(_) => self.aborted = true,
+                None => self.operator = Some(PendingOperator { op, count }),
+            }
+            return Ok(());
+        }
+
+        // 操作符后面不是移动命令时取消操作符
+        if self.operator.take().is_some() {
+            self.selected_register = None;
+            self.aborted = true;
+            return Ok(());
+        }
+
+        let n = count.unwrap_or(1);
         match key {
-            Key::Char('i') => {
-                self.mode = Mode::Insert;
+            Key::Char('i') => self.begin_insert('i', n)?,
+            Key::Char('a') => {
+                self.cursor.move_right(&self.buffer)?;
+                self.begin_insert('a', n)?;
+            }
+            Key::Char(kind @ ('o' | 'O')) => {
+                // 在关闭的折叠之后或之前开始新行
+                let row = if kind == 'o' {
+                    self.buffer.fold_end(self.cursor.row) + 1
+                } else {
+                    self.buffer.fold_start(self.cursor.row)
+                };
+                self.buffer.insert_line(row, String::new());
+                self.cursor.move_to(&self.buffer, Position::new(row, 0))?;
+                self.begin_insert(kind, n)?;
+                let from = if kind == 'o' { row - 1 } else { row + 1 };
+                self.indent_new_line(from)?;
+            }
+            // x 相当于 dl，X 相当于 dh
+            Key::Char(c @ ('x' | 'X')) => {
+                let motion = if c == 'x' { 'l' } else { 'h' };
+                // $ 之后光标在最后一个字符后面，x 从最后一个字符开始删除
+                let line = self.buffer.get_line(start.row).map_or("", |line| line.as_str());
+                let start = match line.char_indices().next_back() {
+                    Some((last, _)) if c == 'x' && start.col > last => Position::new(start.row, last),
+                    _ => start,
+                };
+                self.cursor.move_to(&self.buffer, start)?;
+                self.operator = Some(PendingOperator { op: 'd', count: None });
+                if let Some((kind, moved)) = self.do_motion(Key::Char(motion), Some(n))? {
+                    self.finish_motion(start, kind, moved)?;
+                }
+                self.operator = None;
+                self.finish_change();
+            }
+            Key::Char(c @ ('p' | 'P')) => {
+                self.put(c == 'P', n)?;
+                self.finish_change();
+            }
+            Key::Char('.') => self.repeat_change(count)?,
+            Key::Char('u') => self.undo(false, n)?,
+            Key::Ctrl('r') => self.undo(true, n)?,
+            Key::Char(':') => {
+                self.mode = Mode::Command;
+                self.command_type = ':';
+                self.command_line.clear();
+                self.set_cursor_style()?;
+            }
+            Key::Char('v') => self.start_visual(Mode::Visual)?,
+            Key::Char('V') => self.start_visual(Mode::VisualLine)?,
+            Key::Ctrl('o') => {
+                let position = self.buffer.jumplist.back(self.cursor.position());
+                self.jump_in_list(position)?;
+            }
+            // Ctrl-i 和 Tab 是同一个按键
+            Key::Char('\t') => {
+                let position = self.buffer.jumplist.forward();
+                self.jump_in_list(position)?;
+            }
+            _ => self.selected_register = None,
+        }
+        Ok(())
+    }
+
+    fn char_under_cursor(&self) -> char {
+        self.buffer
+            .get_line(self.cursor.row)
+            .and_then(|line| line.get(self.cursor.col..))
+            .and_then(|rest| rest.chars().next())
+            .unwrap_or(' ')
+    }
+
+    fn push_count(&mut self, digit: char) {
+        // 计数不放在修改的按键中，有等待的操作符时是移动命令的计数
+        if self.change_keys.last() == Some(&Key::Char(digit)) {
+            self.change_keys.pop();
+        }
+        let digit = digit.to_digit(10).unwrap_or(0) as usize;
+        let count = self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+        self.count = Some(count);
+        match self.operator {
+            Some(_) => self.change_counts.motion = Some((self.change_keys.len(), count)),
+            None => self.change_counts.count = Some(count),
+        }
+    }
+
+    // 处理 q{register}、@{register}、m{mark}、g 开头等需要一个参数的命令
+    fn handle_normal_prefix(&mut self, prefix: char, key: Key) -> io::Result<()> {
+        let name = match key {
+            Key::Char(c) => c,
+            _ => {
+                self.count = None;
+                self.operator = None;
+                return Ok(());
+            }
+        };
+
+        // 可以作为操作符范围的移动命令
+        let start = self.cursor.position();
+        match (prefix, name) {
+            ('g', 'g') if self.buffer.large.is_some() => {
+                let count = self.count.take();
+                let count = self.motion_count(count).unwrap_or(1);
+                let moved = self.large_goto(Some(count))?;
+                return self.finish_motion(start, MotionKind::Linewise, moved);
+            }
+            ('g', 'g') => {
+                let count = self.count.take();
+                let row = self.motion_count(count).map_or(0, |n| n.saturating_sub(1));
+                self.jump_to_line(row)?;
+                return self.finish_motion(start, MotionKind::Linewise, true);
+            }
+            // gj、gk、g0、g$ 按屏幕行移动
+            ('g', 'j' | 'k') => {
+                let count = self.count.take();
+                let n = self.motion_count(count).unwrap_or(1);
+                let mut moved = false;
+                for _ in 0..n {
+                    if !self.cursor.move_screen_row(&self.buffer, name == 'j')? {
+                        break;
+                    }
+                    moved = true;
+                }
+                return self.finish_motion(start, MotionKind::Exclusive, moved);
+            }
+            ('g', '0' | '$') => {
+                self.count = None;
+                self.cursor.move_screen_row_edge(&self.buffer, name == '$')?;
+                let kind = if name == '$' { MotionKind::Inclusive } else { MotionKind::Exclusive };
+                return self.finish_motion(start, kind, true);
+            }
+            ('\'' | '`', _) => {
+                self.count = None;
+                let moved = self.jump_to_mark(name, prefix == '`')?;
+                let kind = if prefix == '`' { MotionKind::Exclusive } else { MotionKind::Linewise };
+                return self.finish_motion(start, kind, moved);
+            }
+            ('"', _) if Registers::is_valid(name) => {
+                self.selected_register = Some(name);
+                return Ok(());
+            }
+            ('i' | 'a', _) => {
+                self.count = None;
+                return self.select_text_object(name, prefix == 'a');
+            }
+            // gq{motion} 格式化文本，gqgq 格式化当前行，可视模式下 gq 格式化选中的行
+            ('g', 'q') => {
+                if let Mode::Visual | Mode::VisualLine = self.mode {
+                    self.count = None;
+                    self.end_visual()?;
+                    let start = self.buffer.marks.get('<').unwrap_or_default();
+                    let end = self.buffer.marks.get('>').unwrap_or_default();
+                    self.format_lines(start.row, end.row)?;
+                    self.finish_change();
+                    return Ok(());
+                }
+                let count = self.count.take();
+                match self.operator.take() {
+                    Some(pending) if pending.op == 'q' => {
+                        let n = pending.count.unwrap_or(1).saturating_mul(count.unwrap_or(1));
+                        self.operate_lines('q', n)?;
+                        self.finish_change();
+                    }
+                    Some(_) => self.aborted = true,
+                    None => self.operator = Some(PendingOperator { op: 'q', count }),
+                }
+                return Ok(());
+            }
+            // zf{motion} 创建折叠，可视模式下 zf 折叠选中的行
+            ('z', 'f') if self.operator.is_none() => {
+                if let Mode::Visual | Mode::VisualLine = self.mode {
+                    self.count = None;
+                    self.end_visual()?;
+                    let start = self.buffer.marks.get('<').unwrap_or_default();
+                    let end = self.buffer.marks.get('>').unwrap_or_default();
+                    return self.create_fold(start.row, end.row);
+                }
+                let count = self.count.take();
+                self.operator = Some(PendingOperator { op: 'z', count });
+                return Ok(());
+            }
+            ('z', 'j' | 'k') => {
+                let count = self.count.take();
+                let moved = self.move_to_fold(name == 'j', self.motion_count(count).unwrap_or(1))?;
+                return self.finish_motion(start, MotionKind::Linewise, moved);
+            }
+            _ => (),
+        }
+
+        let count = self.count.take().unwrap_or(1);
+        if self.operator.take().is_some() {
+            self.aborted = true;
+            return Ok(());
+        }
+        match (prefix, name) {
+            ('q', _) => self.start_recording(name),
+            ('@', _) => self.execute_register(name, count)?,
+            ('m', _) => self.set_mark(name),
+            ('r', _) => self.replace_chars(name, count)?,
+            ('g', ';') => {
+                let position = self.buffer.changelist.older();
+                self.jump_in_changelist(position, "E662: At start of changelist")?;
+            }
+            ('z', 'h' | 'l' | 'H' | 'L' | 's' | 'e') => self.scroll_horizontal(name, count)?,
+            ('z', 'o' | 'c' | 'a' | 'R' | 'M') => self.fold_command(name)?,
+            ('g', ',') => {
+                let position = self.buffer.changelist.newer();
+                self.jump_in_changelist(position, "E663: At end of changelist")?;
+            }
+            _ => self.aborted = true,
+        }
+        Ok(())
+    }
+
+    pub fn set_mark(&mut self, name: char) {
+        if !Marks::is_valid(name) || matches!(name, '.' | '^') {
+            self.aborted = true;
+            return;
+        }
+        // 文件标记只有一个，设置在当前文件中时去掉其他文件中的
+        if name.is_ascii_uppercase() {
+            self.file_marks.remove(&name);
+        }
+        self.buffer.marks.set(name, self.cursor.position());
+    }
+
+    // 'a 跳到标记所在行的第一个非空字符，`a 跳到标记的准确位置，'A 可以跳到其他文件
+    pub fn jump_to_mark(&mut self, name: char, exact: bool) -> io::Result<bool> {
+        let position = match self.buffer.marks.get(name) {
+            Some(position) if Marks::is_valid(name) => position,
+            // 操作符不能作用到其他文件
+            None if self.file_marks.contains_key(&name) && self.operator.is_none() => {
+                match self.open_mark_file(name)?.and_then(|_| self.buffer.marks.get(name)) {
+                    Some(position) => position,
+                    None => return Ok(false),
+                }
+            }
+            _ => {
+                self.error("E20: Mark not set");
+                return Ok(false);
+            }
+        };
+        self.push_jump();
+        self.cursor.move_to(&self.buffer, position)?;
+        if !exact {
+            self.cursor.move_to_first_non_blank(&self.buffer)?;
+        }
+        Ok(true)
+    }
+
+    // 打开文件标记所在的文件，当前的 buffer 修改过时不能离开
+    fn open_mark_file(&mut self, name: char) -> io::Result<Option<()>> {
+        let Some((path, _)) = self.file_marks.get(&name).cloned() else {
+            return Ok(None);
+        };
+        if self.buffer.modified {
+            self.error("E37: No write since last change (add ! to override)");
+            return Ok(None);
+        }
+        self.open(&path)?;
+        // 发现交换文件时还在等待回答，没有打开
+        Ok((self.buffer.path.as_deref() == Some(path.as_path())).then_some(()))
+    }
+
+    // 换成 path 的 buffer 时记下当前文件的文件标记，把 path 的文件标记放到新的 buffer 中
+    pub fn switch_file_marks(&mut self, buffer: &mut Buffer, path: &Path) {
+        if let Some(current) = &self.buffer.path {
+            for (name, position) in self.buffer.marks.iter().filter(|(name, _)| name.is_ascii_uppercase()) {
+                self.file_marks.insert(name, (current.clone(), position));
+            }
+        }
+        let same_file = |file: &Path| {
+            file == path || matches!((fs::canonicalize(file), fs::canonicalize(path)), (Ok(a), Ok(b)) if a == b)
+        };
+        let names: Vec<char> = self
+            .file_marks
+            .iter()
+            .filter(|(_, (file, _))| same_file(file))
+            .map(|(name, _)| *name)
+            .collect();
+        for name in names {
+            if let Some((_, position)) = self.file_marks.remove(&name) {
+                buffer.marks.set(name, position);
+            }
+        }
+    }
+
+    // 记录跳转前的位置到跳转列表和 '' 标记
+    pub fn push_jump(&mut self) {
+        let position = self.cursor.position();
+        self.buffer.jumplist.push(position);
+        self.buffer.marks.set('\'', position);
+    }
+
+    pub fn jump_to_line(&mut self, row: usize) -> io::Result<()> {
+        self.push_jump();
+        self.cursor.move_to(&self.buffer, Position::new(row, 0))?;
+        self.cursor.move_to_first_non_blank(&self.buffer)
+    }
+
+    fn jump_in_list(&mut self, position: Option<Position>) -> io::Result<()> {
+        match position {
+            Some(position) => self.cursor.move_to(&self.buffer, position),
+            None => {
+                self.aborted = true;
+                Ok(())
+            }
+        }
+    }
+
+    fn jump_in_changelist(&mut self, position: Option<Position>, error: &str) -> io::Result<()> {
+        match position {
+            Some(position) => self.cursor.move_to(&self.buffer, position),
+            None if self.buffer.changelist.entries().is_empty() => {
+                self.error("E664: changelist is empty");
+                Ok(())
+            }
+            None => {
+                self.error(error);
+                Ok(())
+            }
+        }
+    }
+
+    fn start_visual(&mut self, mode: Mode) -> io::Result<()> {
+        self.mode = mode;
+        self.visual_start = self.cursor.position();
+        self.set_cursor_style()
+    }
+
+    // 离开可视模式时设置 '< 和 '> 标记
+    fn end_visual(&mut self) -> io::Result<()> {
+        let cursor = self.cursor.position();
+        let mut start = self.visual_start.min(cursor);
+        let mut end = self.visual_start.max(cursor);
+        if let Mode::VisualLine = self.mode {
+            start.col = 0;
+            end.col = self.buffer.get_line(end.row).map_or(0, |line| line.len());
+        }
+        self.buffer.marks.set('<', start);
+        self.buffer.marks.set('>', end);
+        self.mode = Mode::Normal;
+        self.set_cursor_style()
+    }
+
+    pub fn handle_visual_mode(&mut self, key: Key) -> io::Result<()> {
+        if self.normal_prefix.is_some() {
+            return self.handle_normal_mode(key);
+        }
+
+        match (self.mode, key) {
+            (_, Key::Esc) | (Mode::Visual, Key::Char('v')) | (Mode::VisualLine, Key::Char('V')) => {
+                self.end_visual()?;
+            }
+            (_, Key::Char('v')) => self.mode = Mode::Visual,
+            (_, Key::Char(c @ ('i' | 'a' | 'z'))) => self.normal_prefix = Some(c),
+            (_, Key::Char('V')) => self.mode = Mode::VisualLine,
+            // o 跳到选择的另一端
+            (_, Key::Char('o')) => {
+                let other = self.visual_start;
+                self.visual_start = self.cursor.position();
+                self.cursor.move_to(&self.buffer, other)?;
+            }
+            (_, Key::Char(':')) => {
+                self.end_visual()?;
+                self.mode = Mode::Command;
+                self.command_line = String::from("'<,'>");
                 self.set_cursor_style()?;
             }
-            Key::Char('h') => self.cursor.move_left(&self.buffer)?,
-            Key::Char('l') => self.cursor.move_right(&self.buffer)?,
-            Key::Char('k') => self.cursor.move_up(&self.buffer)?,
-            Key::Char('j') => self.cursor.move_down(&self.buffer)?,
-            Key::Char('0') => self.cursor.move_to_start(&self.buffer)?,
-            Key::Char('$') => self.cursor.move_to_end(&self.buffer)?,
+            // ! 在命令行中输入过滤选中行的命令
+            (_, Key::Char('!')) => {
+                self.end_visual()?;
+                self.mode = Mode::Command;
+                self.command_line = String::from("'<,'>!");
+                self.set_cursor_style()?;
+            }
+            (_, Key::Char(c @ ('d' | 'x' | 'y' | 'c' | '=' | '<' | '>'))) => {
+                let op = if c == 'x' { 'd' } else { c };
+                let linewise = matches!(self.mode, Mode::VisualLine);
+                self.end_visual()?;
+                let start = self.buffer.marks.get('<').unwrap_or_default();
+                let end = self.buffer.marks.get('>').unwrap_or_default();
+                let kind = if linewise { MotionKind::Linewise } else { MotionKind::Inclusive };
+                self.apply_operator(op, start, end, kind)?;
+            }
+            // 移动命令与普通模式相同
+            (_, Key::Char('h' | 'j' | 'k' | 'l' | 'w' | 'b' | 'e' | '0' | '$' | 'G' | 'g' | '\'' | '`' | '"'))
+            | (_, Key::Char('1'..='9')) => self.handle_normal_mode(key)?,
             _ => (),
         }
         Ok(())
     }
 
-    pub fn handle_insert_mode(&mut self, key: Key) -> io::Result<()> {
+    pub fn handle_command_mode(&mut self, key: Key) -> io::Result<()> {
         match key {
             Key::Esc => {
                 self.mode = Mode::Normal;
+                self.command_type = ':';
+                self.command_line.clear();
+                // d/ 之后取消搜索时也取消操作符
+                self.operator = None;
+                self.count = None;
                 self.set_cursor_style()?;
             }
-            Key::Char(c) => {
-                if c == '\n' {
-                    // 获取当前行的剩余内容
-                    let current_line = self.buffer.get_line(self.cursor.row)
-                        .unwrap_or(&String::new())
-                        .to_string();
-                    let (_, remainder) = current_line.split_at(self.cursor.col);
-                    
-                    // 在当前行后插入新行
-                    self.buffer.insert_line(self.cursor.row + 1, remainder.to_string());
-                    
-                    // 更新当前行，移除已经移动到新行的内容
-                    if let Some(line) = self.buffer.get_line_mut(self.cursor.row) {
-                        line.truncate(self.cursor.col);
-                    }
-                    
-                    // 移动光标到新行的开始
-                    self.cursor.row += 1;
-                    self.cursor.col = 0;
-                    self.cursor.update_screen_position(&self.buffer)?;
+            Key::Char('\n') => {
+                self.mode = Mode::Normal;
+                self.set_cursor_style()?;
+                let command = std::mem::take(&mut self.command_line);
+                let command_type = std::mem::replace(&mut self.command_type, ':');
+                if command_type != ':' {
+                    self.search_command(&command, command_type == '/')?;
                 } else {
-                    self.buffer.insert_char(self.cursor.row, self.cursor.col, c);
-                    self.cursor.col += 1;
-                    self.cursor.update_screen_position(&self.buffer)?;
+                    if !command.trim().is_empty() {
+                        self.registers.set(':', Register {
+                            text: command.clone(),
+                            linewise: false,
+                        });
+                    }
+                    self.execute_command(&command)?;
                 }
-            }
-            Key::Backspace => {
-                if self.cursor.col > 0 {
-                    self.cursor.col -= 1;
-                    self.buffer.remove_char(self.cursor.row, self.cursor.col);
-                    self.cursor.update_screen_position(&self.buffer)?;
+                // 映射执行的命令没有输出时像 vim 一样留在命令行上，<silent> 映射不显示
+                if self.mapping_silent == Some(false) && self.messages.is_empty() && !self.quit {
+                    self.messages.push(format!("{}{}", command_type, command));
                 }
             }
+            Key::Backspace if self.command_line.pop().is_none() => {
+                self.mode = Mode::Normal;
+                self.command_type = ':';
+                self.operator = None;
+                self.set_cursor_style()?;
+            }
+            Key::Char(c) => self.command_line.push(c),
             _ => (),
         }
         Ok(())
@@ -0,0 +1,263 @@
+use encoding_rs::{GB18030, GBK, SHIFT_JIS};
+
+// 编码的标准名字，不认识的编码返回 None，空字符串表示 utf-8
+pub fn canonical(name: &str) -> Option<&'static str> {
+    let name = name.to_ascii_lowercase();
+    let canonical = match name.as_str() {
+        "" | "utf-8" | "utf8" => "utf-8",
+        "utf-16" | "utf-16be" | "ucs-2" | "ucs-2be" => "utf-16",
+        "utf-16le" | "ucs-2le" => "utf-16le",
+        "latin1" | "latin-1" | "iso-8859-1" => "latin1",
+        "cp936" | "gbk" => "cp936",
+        "gb18030" => "gb18030",
+        "sjis" | "shift-jis" | "shift_jis" | "cp932" => "sjis",
+        _ => return None,
+    };
+    Some(canonical)
+}
+
+// fileencodings 中的每一项是编码或 ucs-bom
+pub fn check_list(value: &str) -> bool {
+    value.split(',').all(|name| name == "ucs-bom" || (!name.is_empty() && canonical(name).is_some()))
+}
+
+// binary 模式中不是 UTF-8 的字节保存为 U+10FF00 开始的私用字符，
+// 文件中本来就有的这些字符也按字节保存，写入时就能得到原来的字节
+const ESCAPE_BASE: u32 = 0x10ff00;
+
+// 保存字节的字符对应的字节
+pub fn escaped_byte(c: char) -> Option<u8> {
+    (c as u32).checked_sub(ESCAPE_BASE).map(|byte| byte as u8)
+}
+
+fn escape(byte: u8) -> char {
+    char::from_u32(ESCAPE_BASE + byte as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+fn push_valid(text: &mut String, valid: &str) {
+    for c in valid.chars() {
+        if escaped_byte(c).is_some() {
+            text.extend(c.to_string().bytes().map(escape));
+        } else {
+            text.push(c);
+        }
+    }
+}
+
+// binary 模式读入：合法的 UTF-8 按原样保存，其他字节保存为私用字符
+pub fn decode_binary(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                push_valid(&mut text, valid);
+                break;
+            }
+            Err(error) => {
+                let (valid, after) = rest.split_at(error.valid_up_to());
+                push_valid(&mut text, std::str::from_utf8(valid).unwrap_or_default());
+                let invalid = error.error_len().unwrap_or(after.len());
+                text.extend(after[..invalid].iter().map(|&byte| escape(byte)));
+                rest = &after[invalid..];
+            }
+        }
+    }
+    text
+}
+
+// 写入用 decode_binary 读入的 buffer 时使用：保存字节的字符还原为原来的字节
+pub fn encode_binary(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match escaped_byte(c) {
+            Some(byte) => bytes.push(byte),
+            None => bytes.extend_from_slice(c.to_string().as_bytes()),
+        }
+    }
+    bytes
+}
+
+// 中文和日文的双字节编码，文件可能同时是两种编码的合法内容
+fn double_byte(name: &str) -> bool {
+    matches!(name, "cp936" | "gb18030" | "sjis")
+}
+
+// 文件开头的 BOM 和对应的编码
+fn bom(bytes: &[u8]) -> Option<(&'static str, usize)> {
+    if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+        Some(("utf-8", 3))
+    } else if bytes.starts_with(&[0xff, 0xfe]) {
+        Some(("utf-16le", 2))
+    } else if bytes.starts_with(&[0xfe, 0xff]) {
+        Some(("utf-16", 2))
+    } else {
+        None
+    }
+}
+
+// 按编码转换为 UTF-8，返回转换的结果和是否有不合法的字节
+pub fn decode(bytes: &[u8], name: &str) -> (String, bool) {
+    match name {
+        "utf-16" | "utf-16le" => {
+            let units = bytes.chunks(2).map(|pair| match (pair, name) {
+                (&[high, low], "utf-16") | (&[low, high], _) => u16::from_be_bytes([high, low]),
+                _ => 0xfffd,
+            });
+            let mut errors = bytes.len() % 2 == 1;
+            let text = char::decode_utf16(units)
+                .map(|c| {
+                    c.unwrap_or_else(|_| {
+                        errors = true;
+                        char::REPLACEMENT_CHARACTER
+                    })
+                })
+                .collect();
+            (text, errors)
+        }
+        "latin1" => (bytes.iter().map(|&byte| byte as char).collect(), false),
+        "cp936" | "gb18030" | "sjis" => {
+            let encoding = match name {
+                "cp936" => GBK,
+                "gb18030" => GB18030,
+                _ => SHIFT_JIS,
+            };
+            let (text, errors) = encoding.decode_without_bom_handling(bytes);
+            (text.into_owned(), errors)
+        }
+        _ => match std::str::from_utf8(bytes) {
+            Ok(text) => (text.to_string(), false),
+            Err(_) => (String::from_utf8_lossy(bytes).into_owned(), true),
+        },
+    }
+}
+
+// 双字节编码的可能性：GB2312 的常用汉字，或者 Shift-JIS 的假名和第一水准汉字
+fn score(bytes: &[u8], name: &str) -> usize {
+    let mut score = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let lead = bytes[i];
+        let pair = match name {
+            "sjis" => matches!(lead, 0x81..=0x9f | 0xe0..=0xfc),
+            _ => lead >= 0x81,
+        };
+        if !pair {
+            i += 1;
+            continue;
+        }
+        let trail = bytes.get(i + 1).copied().unwrap_or(0);
+        let common = match name {
+            "sjis" => matches!(lead, 0x82 | 0x83 | 0x88..=0x9f),
+            _ => matches!((lead, trail), (0xa1..=0xf7, 0xa1..=0xfe)),
+        };
+        score += common as usize;
+        i += 2;
+    }
+    score
+}
+
+// 读入的文件：内容、编码和是否有 BOM
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: &'static str,
+    pub bom: bool,
+    // 指定的编码有不合法的字节
+    pub errors: bool,
+}
+
+// 按 fileencodings 的顺序使用第一个没有错误的编码，
+// 遇到双字节编码时比较列表中所有没有错误的双字节编码，选择可能性最大的一个
+pub fn detect(bytes: &[u8], fileencodings: &str) -> Decoded {
+    let names: Vec<&str> = fileencodings.split(',').collect();
+    let mut tried_double_byte = false;
+    for name in &names {
+        if *name == "ucs-bom" {
+            if let Some((encoding, len)) = bom(bytes) {
+                let (text, errors) = decode(&bytes[len..], encoding);
+                return Decoded { text, encoding, bom: true, errors };
+            }
+            continue;
+        }
+        let encoding = match canonical(name) {
+            Some(encoding) => encoding,
+            None => continue,
+        };
+        if !double_byte(encoding) {
+            let (text, errors) = decode(bytes, encoding);
+            if !errors {
+                return Decoded { text, encoding, bom: false, errors };
+            }
+            continue;
+        }
+        if tried_double_byte {
+            continue;
+        }
+        tried_double_byte = true;
+        let best = names
+            .iter()
+            .filter_map(|name| canonical(name).filter(|encoding| double_byte(encoding)))
+            .filter_map(|encoding| match decode(bytes, encoding) {
+                (text, false) => Some((score(bytes, encoding), encoding, text)),
+                _ => None,
+            })
+            // 可能性相同时使用列表中靠前的编码
+            .rev()
+            .max_by_key(|(score, _, _)| *score);
+        if let Some((_, encoding, text)) = best {
+            return Decoded { text, encoding, bom: false, errors: false };
+        }
+    }
+    // 所有编码都有错误时按 UTF-8 读入
+    let (text, errors) = decode(bytes, "utf-8");
+    Decoded { text, encoding: "utf-8", bom: false, errors }
+}
+
+// 使用 ++enc 指定的编码读入，文件开头是这个编码的 BOM 时去掉 BOM
+pub fn decode_with(bytes: &[u8], encoding: &'static str) -> Decoded {
+    let (bom, len) = match bom(bytes) {
+        Some((found, len)) if found == encoding => (true, len),
+        _ => (false, 0),
+    };
+    let (text, errors) = decode(&bytes[len..], encoding);
+    Decoded { text, encoding, bom, errors }
+}
+
+// 转换为文件的编码，有不能转换的字符时返回 None
+pub fn encode(text: &str, name: &str, bom: bool) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match name {
+        "utf-16" | "utf-16le" => {
+            let little = name == "utf-16le";
+            let units = bom.then_some(0xfeff).into_iter().chain(text.encode_utf16());
+            for unit in units {
+                bytes.extend(if little { unit.to_le_bytes() } else { unit.to_be_bytes() });
+            }
+        }
+        "latin1" => {
+            for c in text.chars() {
+                bytes.push(u8::try_from(c as u32).ok()?);
+            }
+        }
+        "cp936" | "gb18030" | "sjis" => {
+            let encoding = match name {
+                "cp936" => GBK,
+                "gb18030" => GB18030,
+                _ => SHIFT_JIS,
+            };
+            let (encoded, _, errors) = encoding.encode(text);
+            if errors {
+                return None;
+            }
+            bytes.extend_from_slice(&encoded);
+        }
+        _ => {
+            if bom {
+                bytes.extend([0xef, 0xbb, 0xbf]);
+            }
+            bytes.extend_from_slice(text.as_bytes());
+        }
+    }
+    Some(bytes)
+}
@@ -0,0 +1,519 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::buffer::Buffer;
+use crate::command::LineRange;
+use crate::cursor::Cursor;
+use crate::editor::Editor;
+use crate::encoding::{self, Decoded};
+use crate::hex;
+use crate::save::{self, SaveOptions};
+
+// 按换行拆分文件内容，最后一个换行之后不算一行
+pub fn split_lines(text: &str) -> Vec<String> {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    if text.is_empty() {
+        return Vec::new();
+    }
+    text.split('\n').map(str::to_string).collect()
+}
+
+// 每行后面加上换行
+pub fn join_lines(lines: &[String]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(line);
+        text.push('\n');
+    }
+    text
+}
+
+// fileformat 对应的换行符
+pub fn line_ending(fileformat: &str) -> &'static str {
+    match fileformat {
+        "dos" => "\r\n",
+        "mac" => "\r",
+        _ => "\n",
+    }
+}
+
+// 检测换行符：使用 fileformats 中允许的、出现最多的一种，同时返回是否混合了几种换行符
+// 没有换行符时使用 fileformats 的第一项
+pub fn detect_format(text: &str, fileformats: &str) -> (&'static str, bool) {
+    let (mut dos, mut unix, mut mac) = (0, 0, 0);
+    let mut bytes = text.bytes().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\r' if bytes.peek() == Some(&b'\n') => {
+                bytes.next();
+                dos += 1;
+            }
+            b'\r' => mac += 1,
+            b'\n' => unix += 1,
+            _ => (),
+        }
+    }
+    let mixed = [dos, unix, mac].iter().filter(|&&count| count > 0).count() > 1;
+    let allowed: Vec<&str> = fileformats.split(',').collect();
+    let best = allowed
+        .iter()
+        .filter_map(|format| match *format {
+            "dos" => Some(("dos", dos)),
+            "unix" => Some(("unix", unix)),
+            "mac" => Some(("mac", mac)),
+            _ => None,
+        })
+        .filter(|(_, count)| *count > 0)
+        // 数量相同时使用列表中靠前的一种
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(format, _)| format);
+    let first = match allowed.first() {
+        Some(&"dos") => "dos",
+        Some(&"mac") => "mac",
+        _ => "unix",
+    };
+    (best.unwrap_or(first), mixed)
+}
+
+// 按文件名检测文件类型，决定语法高亮、语法树和缩进规则，不认识时为空
+pub fn detect_filetype(path: &Path) -> &'static str {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    if name == "Makefile" || name == "makefile" {
+        return "make";
+    }
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref().unwrap_or("") {
+        "rs" => "rust",
+        "py" | "pyw" | "pyi" => "python",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "cs" => "cs",
+        "java" => "java",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" | "mts" | "cts" => "typescript",
+        "go" => "go",
+        "json" => "json",
+        "css" => "css",
+        "yaml" | "yml" => "yaml",
+        "md" | "markdown" => "markdown",
+        _ => "",
+    }
+}
+
+// 按 fileformat 拆分：dos 也接受单独的 LF，mac 接受所有换行符，所以行中不会有 LF
+pub fn split_lines_as(text: &str, fileformat: &str) -> Vec<String> {
+    match fileformat {
+        "dos" => split_lines(text)
+            .into_iter()
+            .map(|mut line| {
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+                line
+            })
+            .collect(),
+        "mac" => split_lines(&text.replace("\r\n", "\n").replace('\r', "\n")),
+        _ => split_lines(text),
+    }
+}
+
+// 每行后面加上 fileformat 的换行符
+pub fn join_lines_as(lines: &[String], fileformat: &str) -> String {
+    let ending = line_ending(fileformat);
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(line);
+        text.push_str(ending);
+    }
+    text
+}
+
+// 读入文件并转换为 UTF-8，没有指定编码时按 fileencodings 检测，同时返回文件的字节数
+// binary 时不检测编码，不是 UTF-8 的字节原样保存
+pub fn read_file(path: &Path, fileencodings: &str, encoding: Option<&'static str>, binary: bool) -> io::Result<(Decoded, usize)> {
+    let bytes = fs::read(path)?;
+    let decoded = match encoding {
+        _ if binary => Decoded {
+            text: encoding::decode_binary(&bytes),
+            encoding: "utf-8",
+            bom: false,
+            errors: false,
+        },
+        Some(encoding) => encoding::decode_with(&bytes, encoding),
+        None => encoding::detect(&bytes, fileencodings),
+    };
+    Ok((decoded, bytes.len()))
+}
+
+// 写入文件使用的编码和换行符
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteFormat<'a> {
+    pub encoding: &'a str,
+    pub bom: bool,
+    pub fileformat: &'a str,
+    // binary 时按原样写入字节，使用 LF，endofline 为 false 时最后一行后面没有换行
+    pub binary: bool,
+    pub endofline: bool,
+    // buffer 是用 decode_binary 读入的（binary 模式和大文件），私用字符写回原来的字节
+    pub escaped: bool,
+    // 读入时有不合法的字节，没有用 ! 或 ++enc 时不写入，以免丢失这些字节
+    pub illegal: bool,
+}
+
+// 按文件的格式转换为字节，有不能转换的字符时返回 InvalidData 错误
+pub fn encode_lines(lines: &[String], format: &WriteFormat) -> io::Result<Vec<u8>> {
+    let bytes = if format.binary {
+        let mut text = join_lines(lines);
+        if !format.endofline {
+            text.pop();
+        }
+        if format.escaped {
+            encoding::encode_binary(&text)
+        } else {
+            text.into_bytes()
+        }
+    } else if format.escaped && format.encoding == "utf-8" {
+        let text = join_lines_as(lines, format.fileformat);
+        let bom: &[u8] = if format.bom { &[0xef, 0xbb, 0xbf] } else { &[] };
+        [bom, &encoding::encode_binary(&text)].concat()
+    } else {
+        encoding::encode(&join_lines_as(lines, format.fileformat), format.encoding, format.bom).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "E513: Write error, conversion failed (make 'fenc' empty to override)",
+            )
+        })?
+    };
+    Ok(bytes)
+}
+
+// :e 和 :w 文件名前面的 ++enc=编码、++ff=换行符、++bin 和 ++nobin
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileOptions {
+    pub encoding: Option<&'static str>,
+    pub fileformat: Option<&'static str>,
+    pub binary: Option<bool>,
+}
+
+// 分开 ++ 参数和文件名
+pub fn parse_file_args(args: &str) -> Result<(FileOptions, &str), String> {
+    let mut options = FileOptions::default();
+    let mut rest = args.trim_start();
+    while let Some(arg) = rest.strip_prefix("++") {
+        let end = arg.find(' ').unwrap_or(arg.len());
+        let invalid = || format!("E474: Invalid argument: ++{}", &arg[..end]);
+        if let "bin" | "binary" | "nobin" | "nobinary" = &arg[..end] {
+            options.binary = Some(!arg.starts_with("no"));
+            rest = arg[end..].trim_start();
+            continue;
+        }
+        match arg[..end].split_once('=') {
+            Some(("enc" | "encoding", value)) => {
+                let encoding = encoding::canonical(value).filter(|_| !value.is_empty());
+                options.encoding = Some(encoding.ok_or_else(invalid)?);
+            }
+            Some(("ff" | "fileformat", value)) => {
+                let fileformat = ["unix", "dos", "mac"].into_iter().find(|&format| format == value);
+                options.fileformat = Some(fileformat.ok_or_else(invalid)?);
+            }
+            _ => return Err(invalid()),
+        }
+        rest = arg[end..].trim_start();
+    }
+    Ok((options, rest))
+}
+
+impl Editor {
+    // 打开文件，有交换文件时先询问怎样处理
+    pub fn open(&mut self, path: &Path) -> io::Result<()> {
+        self.open_with(path, FileOptions::default())
+    }
+
+    pub fn open_with(&mut self, path: &Path, options: FileOptions) -> io::Result<()> {
+        // 重新打开当前文件时自己的交换文件不算
+        if self.buffer.path.as_deref() == Some(path) {
+            self.close_swap();
+        }
+        if self.options.swapfile && self.check_swap(path, &options) {
+            return Ok(());
+        }
+        self.load_file(path, &options)?;
+        self.create_swap();
+        Ok(())
+    }
+
+    // 读入文件替换当前的 buffer，文件不存在时是一个新的空 buffer
+    pub fn load_file(&mut self, path: &Path, options: &FileOptions) -> io::Result<()> {
+        if self.is_large_file(path, options) {
+            self.options.binary = false;
+            return self.load_large_file(path, options);
+        }
+        let binary = options.binary.unwrap_or(self.options.binary);
+        self.options.binary = binary;
+        let (decoded, bytes) = match read_file(path, &self.options.fileencodings, options.encoding, binary) {
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                self.messages.push(format!("\"{}\" [New]", path.display()));
+                self.options.fileencoding = String::from(options.encoding.unwrap_or("utf-8"));
+                self.options.bomb = false;
+                self.options.endofline = true;
+                self.options.fileformat = String::from(options.fileformat.unwrap_or_else(|| detect_format("", &self.options.fileformats).0));
+                let mut buffer = Buffer::new();
+                buffer.escaped = binary;
+                self.set_buffer(buffer, path)?;
+                return Ok(());
+            }
+            Err(_) => {
+                self.error(format!("E484: Can't open file {}", path.display()));
+                return Ok(());
+            }
+        };
+        let (fileformat, mixed) = match options.fileformat {
+            _ if binary => ("unix", false),
+            Some(fileformat) => (fileformat, false),
+            None => detect_format(&decoded.text, &self.options.fileformats),
+        };
+        let endofline = decoded.text.is_empty() || decoded.text.ends_with(['\n', '\r']);
+        let lines = split_lines_as(&decoded.text, fileformat);
+        let mut flags = String::new();
+        if decoded.encoding != "utf-8" {
+            flags.push_str("[converted] ");
+        }
+        if fileformat != "unix" {
+            flags.push_str(&format!("[{}] ", fileformat));
+        }
+        if mixed {
+            flags.push_str("[mixed] ");
+        }
+        if !endofline {
+            flags.push_str("[noeol] ");
+        }
+        if decoded.errors {
+            flags.push_str("[ILLEGAL BYTE] ");
+        }
+        self.messages.push(format!("\"{}\" {}{}L, {}B", path.display(), flags, lines.len(), bytes));
+        self.options.fileencoding = decoded.encoding.to_string();
+        self.options.bomb = decoded.bom;
+        self.options.fileformat = fileformat.to_string();
+        self.options.endofline = endofline;
+        let mut buffer = Buffer::from_lines(lines);
+        buffer.escaped = binary;
+        buffer.illegal = decoded.errors && !binary;
+        self.set_buffer(buffer, path)?;
+        self.read_undo_file();
+        Ok(())
+    }
+
+    // 写入文件使用的格式，++ 参数优先于选项
+    pub fn write_format<'a>(&'a self, options: &FileOptions) -> WriteFormat<'a> {
+        let binary = options.binary.unwrap_or(self.options.binary);
+        WriteFormat {
+            encoding: options.encoding.unwrap_or(&self.options.fileencoding),
+            bom: self.options.bomb,
+            fileformat: options.fileformat.unwrap_or(&self.options.fileformat),
+            binary,
+            // 只有 binary 模式保留最后一行没有换行
+            endofline: self.options.endofline || !binary,
+            escaped: self.buffer.escaped || self.buffer.large.is_some(),
+            illegal: self.buffer.illegal && options.encoding.is_none(),
+        }
+    }
+
+    // 写入时的备份选项，bang 是 :w! 的 !
+    pub fn save_options(&self, bang: bool) -> SaveOptions<'_> {
+        SaveOptions {
+            backup: self.options.backup,
+            writebackup: self.options.writebackup,
+            backupdir: &self.options.backupdir,
+            force: bang,
+        }
+    }
+
+    // 按当前的格式把文件的字节转换为行
+    pub fn decode_lines(&self, bytes: &[u8]) -> Vec<String> {
+        if self.options.binary {
+            return split_lines(&encoding::decode_binary(bytes));
+        }
+        let encoding = encoding::canonical(&self.options.fileencoding).unwrap_or("utf-8");
+        split_lines_as(&encoding::decode_with(bytes, encoding).text, &self.options.fileformat)
+    }
+
+    // 影响写入的文件内容的选项，修改后 buffer 需要重新写入
+    pub fn file_format(&self) -> (String, bool, String, bool, bool) {
+        let options = &self.options;
+        (options.fileencoding.clone(), options.bomb, options.fileformat.clone(), options.binary, options.endofline)
+    }
+
+    // 换成新的 buffer，使用当前的显示和折叠选项
+    pub fn set_buffer(&mut self, mut buffer: Buffer, path: &Path) -> io::Result<()> {
+        self.close_swap();
+        buffer.path = Some(path.to_path_buf());
+        buffer.display = self.options.display();
+        buffer.gutter = self.options.gutter();
+        buffer.folds_mut().configure(self.options.folding());
+        self.switch_file_marks(&mut buffer, path);
+        // <buffer> 映射属于各自的文件，重新读入同一个文件时保留，回到原来的文件时恢复
+        if self.buffer.path.as_deref() != Some(path) {
+            let key = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            self.keymaps.switch_buffer(self.buffer.path.as_deref().map(key), &key(path));
+        }
+        self.buffer = buffer;
+        self.watch_file(path);
+        self.hex = false;
+        // 文件类型属于每个 buffer，按新的文件重新检测，语法和语法树跟着重建
+        let filetype = detect_filetype(path);
+        self.options.filetype = filetype.to_string();
+        self.options.syntax = filetype.to_string();
+        self.load_syntax();
+        self.cursor = Cursor::new(4);
+        self.cursor.update_screen_position(&self.buffer)
+    }
+
+    // :e[dit][!] [++opt] [file]：没有参数时重新读入当前文件，! 放弃修改
+    pub fn edit_command(&mut self, bang: bool, args: &str) -> io::Result<()> {
+        let (options, args) = match parse_file_args(args) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                self.error(error);
+                return Ok(());
+            }
+        };
+        let path = match (args.trim(), &self.buffer.path) {
+            ("", Some(path)) => path.clone(),
+            ("", None) => {
+                self.error("E32: No file name");
+                return Ok(());
+            }
+            (name, _) => PathBuf::from(name),
+        };
+        if self.buffer.modified && !bang {
+            self.error("E37: No write since last change (add ! to override)");
+            return Ok(());
+        }
+        self.open_with(&path, options)
+    }
+
+    // :Hex 显示时是十六进制列中的字节，range 只转换这些行
+    pub fn encode_buffer(&self, range: Option<LineRange>, format: &WriteFormat) -> io::Result<Vec<u8>> {
+        let lines = match range {
+            Some((start, end)) => self.buffer.lines().get(start..=end).unwrap_or_default(),
+            None => self.buffer.lines(),
+        };
+        if self.hex {
+            return hex::parse(lines, range.map_or(0, |(start, _)| start)).map_err(io::Error::other);
+        }
+        if format.illegal && !format.binary {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "E513: Write error, conversion failed: illegal bytes were read (add ! or ++enc to override)",
+            ));
+        }
+        encode_lines(lines, format)
+    }
+
+    // :[range]w[rite][!] [++opt] [file]、:w >> [file] 追加和 :w !cmd，没有文件名的 buffer 使用参数作为文件名
+    pub fn write_command(&mut self, range: Option<LineRange>, bang: bool, args: &str) -> io::Result<()> {
+        let (options, args) = match parse_file_args(args) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                self.error(error);
+                return Ok(());
+            }
+        };
+        if let Some(command) = args.strip_prefix('!') {
+            return self.write_to_command(range, &options, command);
+        }
+        let (append, args) = match args.strip_prefix(">>") {
+            Some(rest) => (true, rest.trim()),
+            None => (false, args.trim()),
+        };
+        let path = match (args, &self.buffer.path) {
+            ("", Some(path)) => path.clone(),
+            ("", None) => {
+                self.error("E32: No file name");
+                return Ok(());
+            }
+            (name, _) => PathBuf::from(name),
+        };
+        // 范围是整个 buffer 时和没有范围相同
+        let range = range.filter(|&(start, end)| start > 0 || end + 1 < self.buffer.line_count());
+        if range.is_some() && !append && !bang && self.buffer.path.as_ref() == Some(&path) {
+            self.error("E140: Use ! to write partial buffer");
+            return Ok(());
+        }
+        let mut format = self.write_format(&options);
+        format.illegal &= !bang;
+        let (encoding, fileformat, binary) = (format.encoding, format.fileformat, format.binary || self.hex);
+        let contents = match self.encode_buffer(range, &format) {
+            Ok(contents) => contents,
+            Err(error) => {
+                self.error(error.to_string());
+                return Ok(());
+            }
+        };
+        // 大文件写入整个 buffer 时没有读入的部分从映射中复制
+        let large = self.buffer.large.as_ref().filter(|_| range.is_none());
+        let write = |writer: &mut dyn Write| match large {
+            Some(large) => large.write_around(writer, &contents),
+            None => writer.write_all(&contents),
+        };
+        let written = if append {
+            OpenOptions::new()
+                .append(true)
+                .create(bang)
+                .open(&path)
+                .map_err(|_| io::Error::other(format!("E212: Can't open file for writing: {}", path.display())))
+                .and_then(|mut file| write(&mut file))
+        } else {
+            save::save(&path, &self.save_options(bang), write)
+        };
+        if let Err(error) = written {
+            self.error(error.to_string());
+            return Ok(());
+        }
+        let bytes = large.map_or(contents.len(), |large| large.written_len(contents.len()));
+        let mut flags = String::new();
+        if encoding != "utf-8" && !binary {
+            flags.push_str("[converted] ");
+        }
+        if fileformat != "unix" && !binary {
+            flags.push_str(&format!("[{}] ", fileformat));
+        }
+        if !format.endofline && !self.hex {
+            flags.push_str("[noeol] ");
+        }
+        let action = if append { "appended" } else { "written" };
+        // 大文件不知道总行数
+        if large.is_some() {
+            self.messages.push(format!("\"{}\" [large] {}{}B {}", path.display(), flags, bytes, action));
+        } else {
+            let lines = range.map_or(self.buffer.line_count(), |(start, end)| end + 1 - start);
+            self.messages.push(format!("\"{}\" {}{}L, {}B {}", path.display(), flags, lines, bytes, action));
+        }
+        if range.is_none() && !append {
+            self.finish_write(&path)?;
+        }
+        Ok(())
+    }
+
+    // 写入整个 buffer 之后：没有文件名的 buffer 使用写入的文件名，写入当前文件时 buffer 不再是修改过的
+    pub fn finish_write(&mut self, path: &Path) -> io::Result<()> {
+        if self.buffer.path.is_none() {
+            self.buffer.path = Some(path.to_path_buf());
+            self.create_swap();
+        }
+        if self.buffer.path.as_deref() == Some(path) {
+            self.buffer.modified = false;
+            self.buffer.illegal = false;
+            self.commit_undo();
+            self.buffer.undo.save_seq = self.buffer.undo.current;
+            self.reopen_large_file()?;
+            self.watch_file(path);
+            self.write_undo_file();
+            self.sync_swap();
+        }
+        Ok(())
+    }
+}
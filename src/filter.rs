@@ -0,0 +1,142 @@
+use std::io;
+
+use crate::command::LineRange;
+use crate::editor::Editor;
+use crate::indent;
+use crate::mark::Position;
+use crate::shell::{self, run, shell_command};
+
+// gq 没有 formatprg 也没有 textwidth 时格式化的宽度
+const DEFAULT_TEXT_WIDTH: usize = 79;
+
+// 按 width 重新排列文本：空行分隔段落，段落中的单词尽量填满一行，使用段落第一行的缩进
+pub fn format_text(lines: &[String], width: usize, tabstop: usize) -> Vec<String> {
+    let mut formatted = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut indent = "";
+    for line in lines.iter().map(|line| line.as_str()).chain([""]) {
+        if !line.trim().is_empty() {
+            if paragraph.is_empty() {
+                indent = &line[..indent::indent_len(line)];
+            }
+            paragraph.extend(line.split_whitespace());
+            continue;
+        }
+        let mut current = String::from(indent);
+        let mut empty = true;
+        for word in paragraph.drain(..) {
+            // 比宽度还长的单词单独占一行
+            if !empty && indent::display_width(&current, tabstop) + 1 + word.chars().count() > width {
+                formatted.push(std::mem::replace(&mut current, String::from(indent)));
+                empty = true;
+            }
+            if !empty {
+                current.push(' ');
+            }
+            current.push_str(word);
+            empty = false;
+        }
+        if !empty {
+            formatted.push(current);
+        }
+        formatted.push(String::new());
+    }
+    // 去掉末尾补上的空行
+    formatted.pop();
+    formatted
+}
+
+impl Editor {
+    // :{range}!cmd 用命令的输出替换这些行，没有范围时只执行命令并显示输出
+    pub fn filter_command(&mut self, range: Option<LineRange>, command: &str) -> io::Result<()> {
+        match range {
+            Some((first, last)) => {
+                if self.filter_lines(first, last, command)? {
+                    self.cursor.move_to(&self.buffer, Position::new(first, 0))?;
+                    self.cursor.move_to_first_non_blank(&self.buffer)?;
+                }
+                Ok(())
+            }
+            None => {
+                let Some(command) = self.expand_command(command) else {
+                    return Ok(());
+                };
+                match run(&mut shell_command(&self.options.shell, &command), |_| Ok(())) {
+                    Ok(output) => {
+                        self.messages.extend(shell::output_lines(&output.stdout));
+                        self.messages.extend(shell::output_lines(&output.stderr));
+                        self.messages.extend(shell::exit_message(&output));
+                    }
+                    Err(_) => self.error(format!("Cannot execute shell {}", self.options.shell)),
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // 把 first 到 last 行作为命令的输入，命令成功时用输出替换这些行，作为一次修改撤销。
+    // 命令失败时显示退出状态和标准错误，不修改 buffer。返回是否替换了
+    pub fn filter_lines(&mut self, first: usize, last: usize, command: &str) -> io::Result<bool> {
+        let Some(command) = self.expand_command(command) else {
+            return Ok(false);
+        };
+        let last = last.min(self.buffer.line_count().saturating_sub(1));
+        let lines = &self.buffer.lines()[first..=last];
+        let output = run(&mut shell_command(&self.options.shell, &command), |writer| {
+            lines.iter().try_for_each(|line| writeln!(writer, "{}", line))
+        });
+        let output = match output {
+            Ok(output) => output,
+            Err(_) => {
+                self.error(format!("Cannot execute shell {}", self.options.shell));
+                return Ok(false);
+            }
+        };
+        self.messages.extend(shell::output_lines(&output.stderr));
+        if let Some(message) = shell::exit_message(&output) {
+            self.error(message);
+            return Ok(false);
+        }
+
+        let mut filtered = shell::output_lines(&output.stdout);
+        let count = last + 1 - first;
+        // 输出为空时删除这些行，buffer 至少保留一个空行
+        if filtered.is_empty() && count == self.buffer.line_count() {
+            filtered.push(String::new());
+        }
+        self.buffer.replace_lines(first, count, &filtered);
+        let end = first + filtered.len().max(1) - 1;
+        self.buffer.marks.set('[', Position::new(first, 0));
+        self.buffer.marks.set(']', Position::new(end, 0));
+        if count > 2 {
+            self.messages.push(format!("{} lines filtered", count));
+        }
+        Ok(true)
+    }
+
+    // gq：用 formatprg 格式化这些行，没有设置时按 textwidth 排列文本，光标移到格式化后的最后一行
+    pub fn format_lines(&mut self, first: usize, last: usize) -> io::Result<()> {
+        let last = last.min(self.buffer.line_count().saturating_sub(1));
+        let end = if self.options.formatprg.is_empty() {
+            let width = match self.options.textwidth {
+                0 => DEFAULT_TEXT_WIDTH,
+                width => width,
+            };
+            let formatted = format_text(&self.buffer.lines()[first..=last], width, self.options.tabstop);
+            let formatted = if formatted.is_empty() { vec![String::new()] } else { formatted };
+            let end = first + formatted.len() - 1;
+            if formatted[..] != self.buffer.lines()[first..=last] {
+                self.buffer.replace_lines(first, last + 1 - first, &formatted);
+            }
+            end
+        } else {
+            let formatprg = self.options.formatprg.clone();
+            if !self.filter_lines(first, last, &formatprg)? {
+                return Ok(());
+            }
+            self.buffer.marks.get(']').map_or(first, |end| end.row)
+        };
+        self.cursor.move_to(&self.buffer, Position::new(end, 0))?;
+        self.cursor.move_to_first_non_blank(&self.buffer)
+    }
+}
@@ -0,0 +1,439 @@
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+
+use regex::Regex;
+
+use crate::indent;
+use crate::mark::LineEdit;
+
+// 折叠使用的选项：foldmethod、foldlevel、foldmarker、foldexpr，indent 方法还需要缩进的宽度
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoldSettings {
+    // manual、indent、marker、syntax 或 expr
+    pub method: String,
+    // 层数大于 foldlevel 的折叠默认关闭
+    pub level: usize,
+    // 开始和结束标记，用逗号分隔
+    pub marker: String,
+    // 没有 vim 脚本，foldexpr 是一个正则表达式，匹配的行开始一个新的折叠
+    pub expr: String,
+    pub shiftwidth: usize,
+    pub tabstop: usize,
+}
+
+impl Default for FoldSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FoldSettings {
+    pub fn new() -> Self {
+        Self {
+            method: String::from("manual"),
+            level: 0,
+            marker: String::from("{{{,}}}"),
+            expr: String::new(),
+            shiftwidth: 8,
+            tabstop: 8,
+        }
+    }
+}
+
+// 从 start 到 end 行（包含两端）的折叠
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+    pub closed: bool,
+}
+
+// 一行的折叠级别，对应 foldexpr 的返回值 "1"、">1"、"<1"
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Level {
+    // 在第 n 层折叠中
+    Depth(usize),
+    // 开始一个第 n 层的折叠
+    Start(usize),
+    // 第 n 层的折叠在这一行结束
+    End(usize),
+}
+
+// 把每行的级别转换成折叠的范围，只有一行的折叠不能关闭，不计算在内
+pub fn from_levels(levels: &[Level]) -> Vec<(usize, usize)> {
+    let mut stack: Vec<usize> = Vec::new();
+    let mut ranges = Vec::new();
+    let close = |stack: &mut Vec<usize>, ranges: &mut Vec<(usize, usize)>, depth: usize, end: usize| {
+        while stack.len() > depth {
+            if let Some(start) = stack.pop() {
+                ranges.push((start, end));
+            }
+        }
+    };
+
+    for (row, &level) in levels.iter().enumerate() {
+        let depth = match level {
+            Level::Depth(n) | Level::End(n) => {
+                close(&mut stack, &mut ranges, n, row.saturating_sub(1));
+                n
+            }
+            Level::Start(n) => {
+                close(&mut stack, &mut ranges, n.saturating_sub(1), row.saturating_sub(1));
+                n
+            }
+        };
+        while stack.len() < depth {
+            stack.push(row);
+        }
+        if let Level::End(n) = level {
+            close(&mut stack, &mut ranges, n.saturating_sub(1), row);
+        }
+    }
+    close(&mut stack, &mut ranges, 0, levels.len().saturating_sub(1));
+    ranges.retain(|&(start, end)| end > start);
+    ranges
+}
+
+// indent：缩进除以 shiftwidth 是级别，空行取上下非空行中较小的级别
+pub fn indent_levels(lines: &[String], tabstop: usize, shiftwidth: usize) -> Vec<Level> {
+    let depths: Vec<Option<usize>> = lines
+        .iter()
+        .map(|line| {
+            (!line.trim().is_empty()).then(|| indent::indent_width(line, tabstop) / shiftwidth.max(1))
+        })
+        .collect();
+    (0..lines.len())
+        .map(|row| {
+            let depth = depths[row].unwrap_or_else(|| {
+                let above = depths[..row].iter().rev().find_map(|depth| *depth);
+                let below = depths[row + 1..].iter().find_map(|depth| *depth);
+                above.unwrap_or(0).min(below.unwrap_or(0))
+            });
+            Level::Depth(depth)
+        })
+        .collect()
+}
+
+// marker：开始标记开始一层新的折叠，结束标记结束当前的折叠
+pub fn marker_levels(lines: &[String], marker: &str) -> Vec<Level> {
+    let (open, close) = marker.split_once(',').unwrap_or(("{{{", "}}}"));
+    let mut depth = 0;
+    lines
+        .iter()
+        .map(|line| {
+            let opens = line.matches(open).count();
+            let closes = line.matches(close).count();
+            if opens > closes {
+                depth += opens - closes;
+                Level::Start(depth)
+            } else if closes > opens {
+                let level = Level::End(depth);
+                depth = depth.saturating_sub(closes - opens);
+                level
+            } else {
+                Level::Depth(depth)
+            }
+        })
+        .collect()
+}
+
+// expr：匹配 foldexpr 的行开始一个第一层的折叠，相当于返回 ">1"，其余的行返回 "="
+pub fn expr_levels(lines: &[String], expr: &Regex) -> Vec<Level> {
+    let mut depth = 0;
+    lines
+        .iter()
+        .map(|line| {
+            if expr.is_match(line) {
+                depth = 1;
+                Level::Start(1)
+            } else {
+                Level::Depth(depth)
+            }
+        })
+        .collect()
+}
+
+// 每个折叠的层数，最外层是 1；folds 按开始行排序，外层在前
+fn depths(folds: &[Fold]) -> Vec<usize> {
+    let mut ends: Vec<usize> = Vec::new();
+    folds
+        .iter()
+        .map(|fold| {
+            while ends.last().is_some_and(|&end| end < fold.start) {
+                ends.pop();
+            }
+            ends.push(fold.end);
+            ends.len()
+        })
+        .collect()
+}
+
+// 按 foldlevel 打开或关闭所有折叠
+fn apply_level(folds: &mut [Fold], level: usize) {
+    let depths = depths(folds);
+    for (fold, depth) in folds.iter_mut().zip(depths) {
+        fold.closed = depth > level;
+    }
+}
+
+// buffer 的折叠，manual 以外的方法在文本修改后用到时重新计算
+#[derive(Debug)]
+pub struct Folds {
+    settings: FoldSettings,
+    // 按开始行排序，开始行相同时外层在前
+    folds: RefCell<Vec<Fold>>,
+    dirty: Cell<bool>,
+    // 设置改变后重新计算时按 foldlevel 决定折叠是否关闭，否则保留原来的状态
+    reset: Cell<bool>,
+}
+
+impl Default for Folds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Folds {
+    pub fn new() -> Self {
+        Self {
+            settings: FoldSettings::new(),
+            folds: RefCell::new(Vec::new()),
+            dirty: Cell::new(false),
+            reset: Cell::new(false),
+        }
+    }
+
+    pub fn settings(&self) -> &FoldSettings {
+        &self.settings
+    }
+
+    // :set 之后更新设置：计算方法改变时重新计算，只有 foldlevel 改变时按新的级别打开或关闭
+    pub fn configure(&mut self, settings: FoldSettings) {
+        // 语法可能改变了
+        self.dirty.set(true);
+        if settings == self.settings {
+            return;
+        }
+        let method_changed = FoldSettings { level: self.settings.level, ..settings.clone() } != self.settings;
+        self.settings = settings;
+        // 改成 manual 时保留已有的折叠
+        if method_changed && self.settings.method != "manual" {
+            self.reset.set(true);
+        } else {
+            apply_level(self.folds.get_mut(), self.settings.level);
+        }
+    }
+
+    // 文本改变了
+    pub fn invalidate(&self) {
+        self.dirty.set(true);
+    }
+
+    pub fn needs_update(&self) -> bool {
+        self.dirty.get() && self.settings.method != "manual"
+    }
+
+    // 用重新计算的范围替换折叠，开始行相同的折叠保持原来的打开或关闭状态
+    pub fn update(&self, mut ranges: Vec<(usize, usize)>) {
+        ranges.sort_by_key(|&(start, end)| (start, Reverse(end)));
+        ranges.dedup();
+        let reset = self.reset.replace(false);
+        let mut folds = self.folds.borrow_mut();
+        let mut updated: Vec<Fold> = ranges
+            .into_iter()
+            .map(|(start, end)| Fold {
+                start,
+                end,
+                closed: !reset && folds.iter().any(|fold| fold.start == start && fold.closed),
+            })
+            .collect();
+        if reset {
+            apply_level(&mut updated, self.settings.level);
+        }
+        *folds = updated;
+        self.dirty.set(false);
+    }
+
+    pub fn folds(&self) -> Vec<Fold> {
+        self.folds.borrow().clone()
+    }
+
+    // 插入和删除行时折叠跟随文本移动，全部被删除的折叠也被删除
+    pub fn adjust(&mut self, edit: LineEdit) {
+        let folds = self.folds.get_mut();
+        for fold in folds.iter_mut() {
+            match edit {
+                LineEdit::Insert { at, count } => {
+                    if fold.start >= at {
+                        fold.start += count;
+                    }
+                    if fold.end >= at {
+                        fold.end += count;
+                    }
+                }
+                LineEdit::Remove { at, count } => {
+                    if fold.start >= at + count {
+                        fold.start -= count;
+                    } else if fold.start >= at {
+                        fold.start = at;
+                    }
+                    if fold.end >= at + count {
+                        fold.end -= count;
+                    } else if fold.end >= at {
+                        // 结束行被删除，at 为 0 时整个折叠都被删除了
+                        fold.end = at.wrapping_sub(1);
+                    }
+                }
+                LineEdit::Split { row, .. } => {
+                    if fold.start > row {
+                        fold.start += 1;
+                    }
+                    if fold.end >= row {
+                        fold.end += 1;
+                    }
+                }
+                LineEdit::Join { row, .. } => {
+                    if fold.start > row {
+                        fold.start -= 1;
+                    }
+                    if fold.end > row {
+                        fold.end -= 1;
+                    }
+                }
+            }
+        }
+        folds.retain(|fold| fold.end != usize::MAX && fold.end >= fold.start);
+        folds.sort_by_key(|fold| (fold.start, Reverse(fold.end)));
+    }
+
+    // 包含 row 的最外层的关闭的折叠
+    pub fn closed_at(&self, row: usize) -> Option<(usize, usize)> {
+        self.folds
+            .borrow()
+            .iter()
+            .find(|fold| fold.closed && fold.end > fold.start && fold.start <= row && row <= fold.end)
+            .map(|fold| (fold.start, fold.end))
+    }
+
+    // zf：创建一个关闭的折叠
+    pub fn create(&mut self, start: usize, end: usize) -> Result<(), String> {
+        if !matches!(self.settings.method.as_str(), "manual") {
+            return Err(String::from("E350: Cannot create fold with current 'foldmethod'"));
+        }
+        let folds = self.folds.get_mut();
+        folds.push(Fold { start, end, closed: true });
+        folds.sort_by_key(|fold| (fold.start, Reverse(fold.end)));
+        Ok(())
+    }
+
+    // zo：打开光标处关闭的折叠，没有折叠时返回 false
+    pub fn open(&mut self, row: usize) -> bool {
+        let folds = self.folds.get_mut();
+        if !folds.iter().any(|fold| fold.start <= row && row <= fold.end) {
+            return false;
+        }
+        if let Some(fold) = folds.iter_mut().find(|fold| fold.closed && fold.start <= row && row <= fold.end) {
+            fold.closed = false;
+        }
+        true
+    }
+
+    // zc：关闭包含光标的最内层的打开的折叠，重复时关闭外面一层
+    pub fn close(&mut self, row: usize) -> bool {
+        let folds = self.folds.get_mut();
+        let containing: Vec<usize> = (0..folds.len())
+            .filter(|&i| folds[i].start <= row && row <= folds[i].end)
+            .collect();
+        if containing.is_empty() {
+            return false;
+        }
+        if let Some(&i) = containing.iter().rev().find(|&&i| !folds[i].closed) {
+            folds[i].closed = true;
+        }
+        true
+    }
+
+    // za：光标在关闭的折叠上时打开它，否则关闭
+    pub fn toggle(&mut self, row: usize) -> bool {
+        if self.closed_at(row).is_some() {
+            self.open(row)
+        } else {
+            self.close(row)
+        }
+    }
+
+    // zR、zM：打开或关闭所有折叠，返回新的 foldlevel
+    pub fn set_all(&mut self, closed: bool) -> usize {
+        let folds = self.folds.get_mut();
+        for fold in folds.iter_mut() {
+            fold.closed = closed;
+        }
+        self.settings.level = if closed { 0 } else { depths(folds).into_iter().max().unwrap_or(0) };
+        self.settings.level
+    }
+
+    // 折叠的开始行可以看到（不在其他关闭的折叠中）
+    fn visible(&self, fold: &Fold) -> bool {
+        self.closed_at(fold.start).is_none_or(|(start, _)| start == fold.start)
+    }
+
+    // zj：下一个折叠的开始行，关闭的折叠算作一行
+    pub fn next_start(&self, row: usize) -> Option<usize> {
+        let row = self.closed_at(row).map_or(row, |(_, end)| end);
+        self.folds
+            .borrow()
+            .iter()
+            .filter(|fold| fold.start > row && self.visible(fold))
+            .map(|fold| fold.start)
+            .min()
+    }
+
+    // zk：上一个折叠的结束行
+    pub fn prev_end(&self, row: usize) -> Option<usize> {
+        let row = self.closed_at(row).map_or(row, |(start, _)| start);
+        self.folds
+            .borrow()
+            .iter()
+            .filter(|fold| fold.end < row && self.visible(fold))
+            .map(|fold| self.closed_at(fold.end).map_or(fold.end, |(_, end)| end))
+            .filter(|&end| end < row)
+            .max()
+    }
+
+    // 折叠列的内容：打开的折叠开始处显示 -，关闭的显示 +，折叠中的其他行显示 |
+    pub fn column(&self, row: usize, width: usize) -> String {
+        let folds = self.folds.borrow();
+        let mut text = String::new();
+        for fold in folds.iter().filter(|fold| fold.start <= row && row <= fold.end && fold.end > fold.start) {
+            if fold.closed {
+                text.push('+');
+                break;
+            }
+            text.push(if fold.start == row { '-' } else { '|' });
+        }
+        let mut text: String = text.chars().take(width).collect();
+        text.push_str(&" ".repeat(width - text.chars().count()));
+        text
+    }
+
+    // 关闭的折叠显示的文字，如 "+--  5 lines: fn main() {"，去掉开头的空白和折叠标记
+    pub fn text(&self, start: usize, end: usize, line: &str) -> String {
+        let depth = {
+            let folds = self.folds.borrow();
+            let depths = depths(&folds);
+            folds
+                .iter()
+                .position(|fold| fold.start == start && fold.end == end)
+                .map_or(1, |i| depths[i])
+        };
+        let mut line = line.trim().to_string();
+        if self.settings.method == "marker" {
+            let open = self.settings.marker.split(',').next().unwrap_or("{{{");
+            if let Some(at) = line.find(open) {
+                let rest = line[at + open.len()..].trim_start_matches(|c: char| c.is_ascii_digit());
+                line = format!("{}{}", line[..at].trim_end(), rest);
+            }
+        }
+        format!("+-{}{:>3} lines: {}", "-".repeat(depth), end - start + 1, line.trim())
+    }
+}
@@ -0,0 +1,72 @@
+// 文本左边的折叠列、标记列和行号列
+
+// 标记列的宽度，标记文本最多两列
+pub const SIGN_WIDTH: usize = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gutter {
+    pub number: bool,
+    pub relativenumber: bool,
+    // 行号列的最小宽度，包括后面的空格
+    pub numberwidth: usize,
+    // auto 时只在有标记时显示标记列
+    pub signcolumn: String,
+    pub foldcolumn: usize,
+}
+
+impl Default for Gutter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn digits(n: usize) -> usize {
+    n.max(1).to_string().len()
+}
+
+impl Gutter {
+    pub fn new() -> Self {
+        Self {
+            number: true,
+            relativenumber: false,
+            numberwidth: 4,
+            signcolumn: String::from("auto"),
+            foldcolumn: 0,
+        }
+    }
+
+    // 行号列随行数增加变宽，如 1000 行以上需要 5 列
+    pub fn number_width(&self, line_count: usize) -> usize {
+        if self.number || self.relativenumber {
+            self.numberwidth.max(digits(line_count) + 1)
+        } else {
+            0
+        }
+    }
+
+    pub fn sign_width(&self, has_signs: bool) -> usize {
+        match self.signcolumn.as_str() {
+            "yes" => SIGN_WIDTH,
+            "auto" if has_signs => SIGN_WIDTH,
+            _ => 0,
+        }
+    }
+
+    pub fn width(&self, line_count: usize, has_signs: bool) -> usize {
+        self.foldcolumn + self.sign_width(has_signs) + self.number_width(line_count)
+    }
+
+    // 行号列的内容：relativenumber 显示和光标行的距离，同时设置 number 时光标行显示左对齐的行号
+    pub fn number_text(&self, row: usize, cursor_row: usize, line_count: usize) -> String {
+        let width = self.number_width(line_count);
+        if width == 0 {
+            return String::new();
+        }
+        let number_width = width - 1;
+        match (self.number, self.relativenumber) {
+            (true, true) if row == cursor_row => format!("{:<number_width$} ", row + 1),
+            (_, true) => format!("{:>number_width$} ", row.abs_diff(cursor_row)),
+            _ => format!("{:>number_width$} ", row + 1),
+        }
+    }
+}
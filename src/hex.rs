@@ -0,0 +1,227 @@
+use std::io;
+
+use crate::editor::Editor;
+use crate::file::FileOptions;
+use crate::mark::Position;
+
+// 和 xxd 相同的格式：8 位偏移、每行 16 个字节按两个字节分组、ASCII 列
+pub const BYTES_PER_LINE: usize = 16;
+const HEX_START: usize = 10;
+const HEX_END: usize = HEX_START + 39;
+const ASCII_START: usize = HEX_END + 2;
+
+// 第 i 个字节在十六进制列和 ASCII 列中的位置
+pub fn hex_col(i: usize) -> usize {
+    HEX_START + i * 2 + i / 2
+}
+
+pub fn ascii_col(i: usize) -> usize {
+    ASCII_START + i
+}
+
+// 光标所在的列对应的字节：字节在行中的序号、是否在 ASCII 列和十六进制列中的第几位
+pub fn byte_at(col: usize) -> Option<(usize, bool, usize)> {
+    if (HEX_START..HEX_END).contains(&col) {
+        let rel = col - HEX_START;
+        // 分组之间的空格属于前一个字节
+        let within = (rel % 5).min(3);
+        return Some(((rel / 5) * 2 + within / 2, false, within % 2));
+    }
+    let i = col.checked_sub(ASCII_START)?;
+    (i < BYTES_PER_LINE).then_some((i, true, 0))
+}
+
+pub fn format_line(offset: usize, bytes: &[u8]) -> String {
+    let mut line = format!("{:08x}: ", offset);
+    for i in 0..BYTES_PER_LINE {
+        match bytes.get(i) {
+            Some(byte) => line.push_str(&format!("{:02x}", byte)),
+            None => line.push_str("  "),
+        }
+        if i % 2 == 1 {
+            line.push(' ');
+        }
+    }
+    line.push(' ');
+    line.extend(bytes.iter().map(|&byte| if (0x20..0x7f).contains(&byte) { byte as char } else { '.' }));
+    line
+}
+
+pub fn dump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(BYTES_PER_LINE)
+        .enumerate()
+        .map(|(i, chunk)| format_line(i * BYTES_PER_LINE, chunk))
+        .collect()
+}
+
+// 十六进制列中第 i 个字节：Ok(None) 表示这个位置是空的，Err 表示不是两位十六进制数字
+fn byte_in_line(line: &str, i: usize) -> Result<Option<u8>, ()> {
+    let col = hex_col(i);
+    if col >= line.len() {
+        return Ok(None);
+    }
+    match line.get(col..(col + 2).min(line.len())) {
+        Some(" " | "  ") => Ok(None),
+        Some(pair) if pair.len() == 2 && pair.bytes().all(|c| c.is_ascii_hexdigit()) => {
+            u8::from_str_radix(pair, 16).map(Some).map_err(|_| ())
+        }
+        _ => Err(()),
+    }
+}
+
+// 一行十六进制列中的字节，遇到不是十六进制数字的位置时结束
+pub fn line_bytes(line: &str) -> Vec<u8> {
+    (0..BYTES_PER_LINE)
+        .map_while(|i| byte_in_line(line, i).ok().flatten())
+        .collect()
+}
+
+// 像 xxd -r 一样只使用十六进制列。每行必须保持 dump 的格式：偏移正确、字节连续、
+// 分组之间是空格、只有最后一行可以少于 16 个字节，否则返回错误，不能转换回字节
+pub fn parse(lines: &[String], first_row: usize) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    // 空文件的 dump 是一个空行
+    if lines.len() == 1 && lines[0].is_empty() && first_row == 0 {
+        return Ok(bytes);
+    }
+    for (i, line) in lines.iter().enumerate() {
+        let row = first_row + i;
+        let invalid = || format!("Invalid hex dump in line {}: {}", row + 1, line);
+        if line.get(..HEX_START) != Some(&format!("{:08x}: ", row * BYTES_PER_LINE)) {
+            return Err(invalid());
+        }
+        let mut count = 0;
+        for index in 0..BYTES_PER_LINE {
+            match byte_in_line(line, index).map_err(|_| invalid())? {
+                Some(_) if count < index => return Err(invalid()),
+                Some(byte) => {
+                    bytes.push(byte);
+                    count += 1;
+                }
+                None => (),
+            }
+            let separator = hex_col(index) + 2;
+            if index % 2 == 1 && line.as_bytes().get(separator).is_some_and(|&c| c != b' ') {
+                return Err(invalid());
+            }
+        }
+        if count == 0 || (count < BYTES_PER_LINE && i + 1 < lines.len()) {
+            return Err(invalid());
+        }
+    }
+    Ok(bytes)
+}
+
+impl Editor {
+    // :Hex：把文件的字节显示为十六进制，再次执行时转换回文本
+    pub fn hex_command(&mut self) -> io::Result<()> {
+        let modified = self.buffer.modified;
+        let lines = if self.hex {
+            match parse(self.buffer.lines(), 0) {
+                Ok(bytes) => self.decode_lines(&bytes),
+                Err(error) => {
+                    self.error(error);
+                    return Ok(());
+                }
+            }
+        } else {
+            let format = self.write_format(&FileOptions::default());
+            match crate::file::encode_lines(self.buffer.lines(), &format) {
+                Ok(bytes) => dump(&bytes),
+                Err(error) => {
+                    self.error(error.to_string());
+                    return Ok(());
+                }
+            }
+        };
+        let count = self.buffer.line_count();
+        self.buffer.replace_lines(0, count, &lines);
+        // 转换回文本时按当前的 binary 选项读入字节
+        if self.hex {
+            self.buffer.escaped = self.options.binary;
+        }
+        // 只是显示方式不同，文件的内容没有变化
+        self.buffer.modified = modified;
+        self.hex = !self.hex;
+        self.cursor.move_to(&self.buffer, Position::new(0, if self.hex { hex_col(0) } else { 0 }))
+    }
+
+    fn hex_byte_count(&self) -> usize {
+        let last = self.buffer.line_count().saturating_sub(1);
+        last * BYTES_PER_LINE + self.buffer.get_line(last).map_or(0, |line| line_bytes(line).len())
+    }
+
+    // :Hex 中的 h、l：按字节移动，可以移到上一行或下一行，留在原来的列中
+    pub fn hex_move(&mut self, forward: bool, count: usize) -> io::Result<()> {
+        let (i, ascii, _) = byte_at(self.cursor.col).unwrap_or((0, false, 0));
+        let index = self.cursor.row * BYTES_PER_LINE + i;
+        let last = self.hex_byte_count().saturating_sub(1);
+        let target = if forward { index.saturating_add(count).min(last) } else { index.saturating_sub(count) };
+        if target == index {
+            self.aborted = true;
+            return Ok(());
+        }
+        let i = target % BYTES_PER_LINE;
+        let col = if ascii { ascii_col(i) } else { hex_col(i) };
+        self.cursor.move_to(&self.buffer, Position::new(target / BYTES_PER_LINE, col))
+    }
+
+    // 其他移动之后把光标放到字节的位置上
+    pub fn snap_hex_cursor(&mut self) -> io::Result<()> {
+        let count = self.buffer.get_line(self.cursor.row).map_or(0, |line| line_bytes(line).len());
+        if count == 0 {
+            return Ok(());
+        }
+        let (i, ascii, nibble) = match byte_at(self.cursor.col) {
+            Some(found) => found,
+            None if self.cursor.col >= HEX_END => (BYTES_PER_LINE - 1, true, 0),
+            None => (0, false, 0),
+        };
+        let i = i.min(count - 1);
+        let col = if ascii { ascii_col(i) } else { hex_col(i) + nibble };
+        if col != self.cursor.col {
+            self.cursor.move_to(&self.buffer, Position::new(self.cursor.row, col))?;
+        }
+        Ok(())
+    }
+
+    // :Hex 中的 r：在十六进制列中替换一位十六进制数字，在 ASCII 列中替换整个字节，两列同时更新
+    pub fn hex_replace(&mut self, c: char, count: usize) -> io::Result<()> {
+        let row = self.cursor.row;
+        let line = self.buffer.get_line(row).cloned().unwrap_or_default();
+        let mut bytes = line_bytes(&line);
+        let (i, ascii, nibble) = match byte_at(self.cursor.col) {
+            Some((i, ascii, nibble)) if i < bytes.len() => (i, ascii, nibble),
+            _ => {
+                self.aborted = true;
+                return Ok(());
+            }
+        };
+        let value = if ascii { c.is_ascii().then_some(c as u8) } else { c.to_digit(16).map(|digit| digit as u8) };
+        let value = match value {
+            Some(value) if count == 1 || ascii => value,
+            _ => {
+                self.aborted = true;
+                return Ok(());
+            }
+        };
+        // ASCII 列中的计数替换多个字节，不超过行尾
+        if ascii && i + count > bytes.len() {
+            self.aborted = true;
+            return Ok(());
+        }
+        if ascii {
+            bytes[i..i + count].fill(value);
+        } else if nibble == 0 {
+            bytes[i] = value << 4 | (bytes[i] & 0x0f);
+        } else {
+            bytes[i] = (bytes[i] & 0xf0) | value;
+        }
+        self.buffer.set_line(row, format_line(row * BYTES_PER_LINE, &bytes));
+        let col = if ascii { ascii_col(i + count - 1) } else { self.cursor.col };
+        self.cursor.move_to(&self.buffer, Position::new(row, col))?;
+        self.finish_change();
+        Ok(())
+    }
+}
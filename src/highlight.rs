@@ -0,0 +1,493 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// 内置的配色方案，用户目录下的同名文件优先
+const BUILTIN_COLORS: [(&str, &str); 2] = [
+    ("default", include_str!("../runtime/colors/default.vim")),
+    ("desert", include_str!("../runtime/colors/desert.vim")),
+];
+
+// 按名字找配色文件：先找 ~/.config/rustvim/colors/{name}.vim，再找内置的
+pub fn colorscheme_file(name: &str) -> Option<String> {
+    let user = env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".config/rustvim/colors").join(format!("{}.vim", name)));
+    if let Some(text) = user.and_then(|path| fs::read_to_string(path).ok()) {
+        return Some(text);
+    }
+    BUILTIN_COLORS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, text)| text.to_string())
+}
+
+// 终端支持的颜色数，颜色按它降级输出
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    Color256,
+    Color16,
+}
+
+impl ColorDepth {
+    // 按 COLORTERM 和 TERM 环境变量判断终端能力
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Color256
+        } else {
+            ColorDepth::Color16
+        }
+    }
+}
+
+// cterm 颜色是 0-255 的编号，gui 颜色是 RGB
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Color {
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+// vim 的 16 色名字和 cterm 编号
+const COLOR_NAMES: [(&str, u8); 23] = [
+    ("black", 0),
+    ("darkred", 1),
+    ("darkgreen", 2),
+    ("brown", 3),
+    ("darkyellow", 3),
+    ("darkblue", 4),
+    ("darkmagenta", 5),
+    ("darkcyan", 6),
+    ("lightgray", 7),
+    ("gray", 7),
+    ("grey", 7),
+    ("darkgray", 8),
+    ("red", 9),
+    ("lightred", 9),
+    ("green", 10),
+    ("lightgreen", 10),
+    ("yellow", 11),
+    ("blue", 12),
+    ("lightblue", 12),
+    ("magenta", 13),
+    ("cyan", 14),
+    ("lightcyan", 14),
+    ("white", 15),
+];
+
+// xterm 默认的 16 色
+const PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// 256 色编号对应的 RGB
+fn ansi_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => PALETTE[n as usize],
+        16..=231 => {
+            let n = n - 16;
+            (CUBE[(n / 36) as usize], CUBE[(n / 6 % 6) as usize], CUBE[(n % 6) as usize])
+        }
+        _ => {
+            let level = 8 + (n - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+// 在 256 色的色块和灰度中找最接近的颜色
+fn rgb_to_256(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255).min_by_key(|&n| distance(ansi_rgb(n), rgb)).unwrap_or(0)
+}
+
+fn rgb_to_16(rgb: (u8, u8, u8)) -> u8 {
+    (0..16).min_by_key(|&n| distance(PALETTE[n as usize], rgb)).unwrap_or(0)
+}
+
+impl Color {
+    // 解析 ctermfg 的值：编号或颜色名
+    fn parse_cterm(value: &str) -> Result<Option<Self>, String> {
+        if value.eq_ignore_ascii_case("none") {
+            return Ok(None);
+        }
+        if let Ok(n) = value.parse::<u8>() {
+            return Ok(Some(Color::Ansi(n)));
+        }
+        let name = value.to_ascii_lowercase();
+        COLOR_NAMES
+            .iter()
+            .find(|(color, _)| *color == name)
+            .map(|&(_, n)| Some(Color::Ansi(n)))
+            .ok_or_else(|| format!("E421: Color name or number not recognized: {}", value))
+    }
+
+    // 解析 guifg 的值：#rrggbb 或颜色名
+    fn parse_gui(value: &str) -> Result<Option<Self>, String> {
+        if value.eq_ignore_ascii_case("none") {
+            return Ok(None);
+        }
+        if let Some(hex) = value.strip_prefix('#') {
+            let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Some(Color::Rgb(r, g, b))),
+                _ => Err(format!("E254: Cannot allocate color {}", value)),
+            };
+        }
+        match Self::parse_cterm(value) {
+            Ok(Some(Color::Ansi(n))) if n < 16 => {
+                let (r, g, b) = PALETTE[n as usize];
+                Ok(Some(Color::Rgb(r, g, b)))
+            }
+            _ => Err(format!("E254: Cannot allocate color {}", value)),
+        }
+    }
+
+    // 按终端能力生成 SGR 参数，background 时生成背景色
+    fn sgr(self, depth: ColorDepth, background: bool) -> String {
+        let base = if background { 40 } else { 30 };
+        let sixteen = |n: u8| match n {
+            0..=7 => format!("{}", base + n as u32),
+            _ => format!("{}", base + 60 + (n - 8) as u32),
+        };
+        match (self, depth) {
+            (Color::Rgb(r, g, b), ColorDepth::TrueColor) => format!("{};2;{};{};{}", base + 8, r, g, b),
+            (Color::Rgb(r, g, b), ColorDepth::Color256) => format!("{};5;{}", base + 8, rgb_to_256((r, g, b))),
+            (Color::Rgb(r, g, b), ColorDepth::Color16) => sixteen(rgb_to_16((r, g, b))),
+            (Color::Ansi(n), ColorDepth::Color16) if n >= 16 => sixteen(rgb_to_16(ansi_rgb(n))),
+            (Color::Ansi(n), _) if n < 16 => sixteen(n),
+            (Color::Ansi(n), _) => format!("{};5;{}", base + 8, n),
+        }
+    }
+
+    fn show(self) -> String {
+        match self {
+            Color::Ansi(n) => n.to_string(),
+            Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+}
+
+// cterm= 和 gui= 的属性
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Attributes {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+    pub strikethrough: bool,
+}
+
+impl Attributes {
+    fn parse(value: &str) -> Result<Self, String> {
+        let mut attributes = Self::default();
+        for item in value.split(',') {
+            match item.to_ascii_lowercase().as_str() {
+                "none" | "" => attributes = Self::default(),
+                "bold" => attributes.bold = true,
+                "italic" => attributes.italic = true,
+                "underline" | "undercurl" | "underdouble" | "underdotted" | "underdashed" => {
+                    attributes.underline = true
+                }
+                "reverse" | "inverse" | "standout" => attributes.reverse = true,
+                "strikethrough" => attributes.strikethrough = true,
+                _ => return Err(format!("E418: Illegal value: {}", item)),
+            }
+        }
+        Ok(attributes)
+    }
+
+    fn sgr(self) -> Vec<String> {
+        [
+            (self.bold, "1"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.reverse, "7"),
+            (self.strikethrough, "9"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, code)| code.to_string())
+        .collect()
+    }
+
+    fn show(self) -> String {
+        let names: Vec<&str> = [
+            (self.bold, "bold"),
+            (self.italic, "italic"),
+            (self.underline, "underline"),
+            (self.reverse, "reverse"),
+            (self.strikethrough, "strikethrough"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| *name)
+        .collect();
+        names.join(",")
+    }
+
+    fn is_empty(self) -> bool {
+        self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HighlightGroup {
+    pub ctermfg: Option<Color>,
+    pub ctermbg: Option<Color>,
+    pub guifg: Option<Color>,
+    pub guibg: Option<Color>,
+    pub cterm: Attributes,
+    pub gui: Attributes,
+    // :highlight link 链接到的组，使用那个组的颜色
+    pub link: Option<String>,
+}
+
+impl HighlightGroup {
+    // :highlight 列出时的格式，如 "Comment        xxx ctermfg=4 guifg=#80a0ff"
+    fn show(&self, name: &str) -> String {
+        let mut items = Vec::new();
+        if !self.cterm.is_empty() {
+            items.push(format!("cterm={}", self.cterm.show()));
+        }
+        items.extend(self.ctermfg.map(|color| format!("ctermfg={}", color.show())));
+        items.extend(self.ctermbg.map(|color| format!("ctermbg={}", color.show())));
+        if !self.gui.is_empty() {
+            items.push(format!("gui={}", self.gui.show()));
+        }
+        items.extend(self.guifg.map(|color| format!("guifg={}", color.show())));
+        items.extend(self.guibg.map(|color| format!("guibg={}", color.show())));
+        match (&self.link, items.is_empty()) {
+            (Some(link), true) => format!("{:<14} xxx links to {}", name, link),
+            (_, true) => format!("{:<14} xxx cleared", name),
+            _ => format!("{:<14} xxx {}", name, items.join(" ")),
+        }
+    }
+}
+
+// 默认的高亮组，和 vim 的浅色背景默认配色相近
+const DEFAULT_GROUPS: [&str; 25] = [
+    "Normal",
+    "LineNr ctermfg=3 guifg=#af5f00",
+    "CursorLineNr cterm=bold ctermfg=3 gui=bold guifg=#af5f00",
+    "CursorLine cterm=underline guibg=#e5e5e5",
+    "SignColumn ctermfg=4 ctermbg=7 guifg=#00008b guibg=#bebebe",
+    "FoldColumn ctermfg=4 ctermbg=7 guifg=#00008b guibg=#bebebe",
+    "Folded ctermfg=4 ctermbg=7 guifg=#00008b guibg=#d3d3d3",
+    "Visual cterm=reverse gui=reverse",
+    "Search ctermfg=0 ctermbg=11 guibg=#ffff00",
+    "StatusLine cterm=bold,reverse gui=bold,reverse",
+    "StatusLineNC cterm=reverse gui=reverse",
+    "NonText ctermfg=12 gui=bold guifg=#0000ff",
+    "SpecialKey ctermfg=4 guifg=#0000ff",
+    "ModeMsg cterm=bold gui=bold",
+    "ErrorMsg ctermfg=15 ctermbg=1 guifg=#ffffff guibg=#ff0000",
+    "Comment ctermfg=4 guifg=#0000ff",
+    "Constant ctermfg=1 guifg=#ff00ff",
+    "Special ctermfg=5 guifg=#6a5acd",
+    "Identifier ctermfg=6 guifg=#008b8b",
+    "Statement ctermfg=3 gui=bold guifg=#a52a2a",
+    "PreProc ctermfg=5 guifg=#6a0dad",
+    "Type ctermfg=2 gui=bold guifg=#2e8b57",
+    "Underlined cterm=underline ctermfg=5 gui=underline guifg=#6a5acd",
+    "Error ctermfg=15 ctermbg=9 guifg=#ffffff guibg=#ff0000",
+    "Todo ctermfg=0 ctermbg=11 guifg=#0000ff guibg=#ffff00",
+];
+
+// 细分的语法组默认链接到上面的基本组
+const DEFAULT_LINKS: [(&str, &str); 27] = [
+    ("String", "Constant"),
+    ("Character", "Constant"),
+    ("Number", "Constant"),
+    ("Boolean", "Constant"),
+    ("Float", "Number"),
+    ("Function", "Identifier"),
+    ("Conditional", "Statement"),
+    ("Repeat", "Statement"),
+    ("Label", "Statement"),
+    ("Operator", "Statement"),
+    ("Keyword", "Statement"),
+    ("Exception", "Statement"),
+    ("Include", "PreProc"),
+    ("Define", "PreProc"),
+    ("Macro", "PreProc"),
+    ("PreCondit", "PreProc"),
+    ("StorageClass", "Type"),
+    ("Structure", "Type"),
+    ("Typedef", "Type"),
+    ("SpecialChar", "Special"),
+    ("Tag", "Special"),
+    ("Delimiter", "Special"),
+    ("SpecialComment", "Special"),
+    ("Debug", "Special"),
+    ("Title", "PreProc"),
+    ("CursorColumn", "CursorLine"),
+    ("Whitespace", "NonText"),
+];
+
+// 所有高亮组和当前的配色方案
+#[derive(Debug, Clone)]
+pub struct Highlights {
+    groups: BTreeMap<String, HighlightGroup>,
+    pub depth: ColorDepth,
+    // :colorscheme 加载的配色方案名
+    pub colors_name: String,
+}
+
+impl Default for Highlights {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Highlights {
+    pub fn new() -> Self {
+        let mut highlights = Self {
+            groups: BTreeMap::new(),
+            depth: ColorDepth::detect(),
+            colors_name: String::from("default"),
+        };
+        highlights.reset();
+        highlights
+    }
+
+    // :highlight clear 恢复默认的高亮组
+    pub fn reset(&mut self) {
+        self.groups.clear();
+        for definition in DEFAULT_GROUPS {
+            let _ = self.command(definition);
+        }
+        for (from, to) in DEFAULT_LINKS {
+            self.group_mut(from).link = Some(to.to_string());
+        }
+    }
+
+    fn group_mut(&mut self, name: &str) -> &mut HighlightGroup {
+        self.groups.entry(name.to_string()).or_default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HighlightGroup> {
+        self.groups.get(name)
+    }
+
+    // 跟随链接找到实际设置了颜色的组
+    fn resolve(&self, name: &str) -> Option<&HighlightGroup> {
+        let mut group = self.groups.get(name)?;
+        // 限制次数以防链接成环
+        for _ in 0..100 {
+            match &group.link {
+                Some(link) => group = self.groups.get(link)?,
+                None => break,
+            }
+        }
+        Some(group)
+    }
+
+    // 开始绘制高亮组的转义序列，组没有设置时为空
+    pub fn start(&self, name: &str) -> String {
+        let group = match self.resolve(name) {
+            Some(group) => group,
+            None => return String::new(),
+        };
+        let truecolor = self.depth == ColorDepth::TrueColor;
+        // 真彩色终端优先使用 gui 设置，否则使用 cterm 设置，没有时把 gui 颜色降级
+        let pick = |cterm: Option<Color>, gui: Option<Color>| if truecolor { gui.or(cterm) } else { cterm.or(gui) };
+        let attributes = match (truecolor, group.gui.is_empty()) {
+            (true, false) => group.gui,
+            _ => group.cterm,
+        };
+        let mut codes = attributes.sgr();
+        codes.extend(pick(group.ctermfg, group.guifg).map(|color| color.sgr(self.depth, false)));
+        codes.extend(pick(group.ctermbg, group.guibg).map(|color| color.sgr(self.depth, true)));
+        if codes.is_empty() {
+            return String::new();
+        }
+        format!("\x1b[{}m", codes.join(";"))
+    }
+
+    // 结束高亮，回到 Normal 的颜色
+    pub fn end(&self) -> String {
+        format!("\x1b[m{}", self.start("Normal"))
+    }
+
+    // 处理 :highlight 的参数，返回要显示的行
+    pub fn command(&mut self, args: &str) -> Result<Vec<String>, String> {
+        let mut words: Vec<&str> = args.split_whitespace().collect();
+        if words.first() == Some(&"default") {
+            words.remove(0);
+        }
+        match words.as_slice() {
+            [] => Ok(self.groups.iter().map(|(name, group)| group.show(name)).collect()),
+            ["clear"] => {
+                self.reset();
+                Ok(Vec::new())
+            }
+            ["clear", name] => {
+                self.groups.insert(name.to_string(), HighlightGroup::default());
+                Ok(Vec::new())
+            }
+            // 链接会替换组原来的设置
+            ["link", from, to] => {
+                self.groups.insert(from.to_string(), HighlightGroup {
+                    link: Some(to.to_string()),
+                    ..HighlightGroup::default()
+                });
+                Ok(Vec::new())
+            }
+            ["link", ..] => Err(format!("E412: Not enough arguments: \":highlight link {}\"", args)),
+            [name] => match self.groups.get(*name) {
+                Some(group) => Ok(vec![group.show(name)]),
+                None => Err(format!("E411: Highlight group not found: {}", name)),
+            },
+            [name, items @ ..] => {
+                // 设置颜色后不再使用链接
+                let mut group = HighlightGroup {
+                    link: None,
+                    ..self.groups.get(*name).cloned().unwrap_or_default()
+                };
+                for item in items {
+                    let (key, value) = item
+                        .split_once('=')
+                        .ok_or_else(|| format!("E416: Missing equal sign: {}", item))?;
+                    match key {
+                        "ctermfg" => group.ctermfg = Color::parse_cterm(value)?,
+                        "ctermbg" => group.ctermbg = Color::parse_cterm(value)?,
+                        "guifg" => group.guifg = Color::parse_gui(value)?,
+                        "guibg" => group.guibg = Color::parse_gui(value)?,
+                        "cterm" => group.cterm = Attributes::parse(value)?,
+                        "gui" => group.gui = Attributes::parse(value)?,
+                        "term" | "start" | "stop" | "guisp" | "font" => (),
+                        _ => return Err(format!("E423: Illegal argument: {}", item)),
+                    }
+                }
+                self.groups.insert(name.to_string(), group);
+                Ok(Vec::new())
+            }
+        }
+    }
+}
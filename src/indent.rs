@@ -0,0 +1,179 @@
+// 缩进相关的计算，列宽按 tabstop 展开制表符
+
+// 文本在屏幕上占的列数，从第 0 列开始计算
+pub fn display_width(text: &str, tabstop: usize) -> usize {
+    text.chars().fold(0, |width, c| {
+        if c == '\t' {
+            width + tabstop - width % tabstop
+        } else {
+            width + 1
+        }
+    })
+}
+
+// 行首空白的字节长度
+pub fn indent_len(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+
+// 行首空白的显示宽度
+pub fn indent_width(line: &str, tabstop: usize) -> usize {
+    display_width(&line[..indent_len(line)], tabstop)
+}
+
+// 生成指定宽度的缩进，expandtab 时只用空格
+pub fn indent_string(width: usize, tabstop: usize, expandtab: bool) -> String {
+    if expandtab {
+        " ".repeat(width)
+    } else {
+        "\t".repeat(width / tabstop) + &" ".repeat(width % tabstop)
+    }
+}
+
+// 把行的缩进换成指定宽度，返回新行
+pub fn set_indent(line: &str, width: usize, tabstop: usize, expandtab: bool) -> String {
+    indent_string(width, tabstop, expandtab) + &line[indent_len(line)..]
+}
+
+// 向右或向左移动一个 shiftwidth，round 时对齐到 shiftwidth 的倍数
+pub fn shift_width(width: usize, shiftwidth: usize, right: bool, round: bool) -> usize {
+    match (right, round) {
+        (true, true) => (width / shiftwidth + 1) * shiftwidth,
+        (true, false) => width + shiftwidth,
+        (false, true) if !width.is_multiple_of(shiftwidth) => width / shiftwidth * shiftwidth,
+        (false, _) => width.saturating_sub(shiftwidth),
+    }
+}
+
+// 计算一行应有缩进的规则，按文件类型选择
+pub trait IndentRule {
+    // prev 是上面最近的非空行，line 是要缩进的行，返回缩进宽度
+    fn indent(&self, prev: Option<&str>, line: &str, tabstop: usize, shiftwidth: usize) -> usize;
+
+    // 在行首输入这些字符时重新缩进当前行
+    fn triggers(&self) -> &str {
+        ""
+    }
+}
+
+fn first_word(line: &str) -> &str {
+    let line = line.trim_start();
+    let end = line.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(line.len());
+    &line[..end]
+}
+
+// 上一行的缩进加上或减去 shiftwidth
+fn relative(prev: Option<&str>, tabstop: usize, more: bool, less: bool, shiftwidth: usize) -> usize {
+    let width = prev.map_or(0, |prev| indent_width(prev, tabstop));
+    match (more, less) {
+        (true, false) => width + shiftwidth,
+        (false, true) => width.saturating_sub(shiftwidth),
+        _ => width,
+    }
+}
+
+// smartindent：大括号、cinwords 关键字后缩进，# 开头的行不缩进
+pub struct SmartIndent;
+
+impl IndentRule for SmartIndent {
+    fn indent(&self, prev: Option<&str>, line: &str, tabstop: usize, shiftwidth: usize) -> usize {
+        if line.trim_start().starts_with('#') {
+            return 0;
+        }
+        let more = prev.is_some_and(|prev| {
+            let prev = prev.trim_end();
+            prev.ends_with('{')
+                || (matches!(first_word(prev), "if" | "else" | "while" | "do" | "for" | "switch")
+                    && !prev.ends_with([';', '}']))
+        });
+        let less = line.trim_start().starts_with('}');
+        relative(prev, tabstop, more, less, shiftwidth)
+    }
+
+    fn triggers(&self) -> &str {
+        "}#"
+    }
+}
+
+// C、Rust 等用括号分块的语言
+pub struct BraceIndent;
+
+impl IndentRule for BraceIndent {
+    fn indent(&self, prev: Option<&str>, line: &str, tabstop: usize, shiftwidth: usize) -> usize {
+        let more = prev.is_some_and(|prev| prev.trim_end().ends_with(['{', '(', '[']));
+        let less = line.trim_start().starts_with(['}', ')', ']']);
+        relative(prev, tabstop, more, less, shiftwidth)
+    }
+
+    fn triggers(&self) -> &str {
+        "})]"
+    }
+}
+
+pub struct PythonIndent;
+
+impl IndentRule for PythonIndent {
+    fn indent(&self, prev: Option<&str>, line: &str, tabstop: usize, shiftwidth: usize) -> usize {
+        let prev = match prev {
+            Some(prev) => prev,
+            None => return indent_width(line, tabstop),
+        };
+        let width = indent_width(prev, tabstop);
+        let current = indent_width(line, tabstop);
+        let blank = line.trim().is_empty();
+        if prev.trim_end().ends_with(':') {
+            return width + shiftwidth;
+        }
+        // return 等语句结束一个块，已经比块少缩进的行不再减少
+        let less = width.saturating_sub(shiftwidth);
+        if matches!(first_word(prev), "return" | "pass" | "break" | "continue" | "raise") {
+            return if blank || current > less { less } else { current };
+        }
+        // 还和块的内容对齐的 else 等和块的开头对齐
+        if matches!(first_word(line), "else" | "elif" | "except" | "finally") && current >= width {
+            return less;
+        }
+        // 新行复制上一行的缩进，其他行的缩进由代码本身决定，保持不变
+        if blank {
+            width
+        } else {
+            current
+        }
+    }
+
+    fn triggers(&self) -> &str {
+        ":"
+    }
+}
+
+pub struct YamlIndent;
+
+impl IndentRule for YamlIndent {
+    fn indent(&self, prev: Option<&str>, _line: &str, tabstop: usize, shiftwidth: usize) -> usize {
+        let prev = match prev {
+            Some(prev) => prev,
+            None => return 0,
+        };
+        // 列表项的内容和 "- " 后面的文本对齐
+        let mut width = indent_width(prev, tabstop);
+        if prev.trim_start().starts_with("- ") {
+            width += 2;
+        }
+        if prev.trim_end().ends_with(':') {
+            width += shiftwidth;
+        }
+        width
+    }
+}
+
+// 文件类型对应的缩进规则
+pub fn filetype_rule(filetype: &str) -> Option<Box<dyn IndentRule>> {
+    match filetype {
+        "c" | "cpp" | "cs" | "java" | "javascript" | "typescript" | "go" | "rust" | "json" | "css" => {
+            Some(Box::new(BraceIndent))
+        }
+        "python" => Some(Box::new(PythonIndent)),
+        "yaml" => Some(Box::new(YamlIndent)),
+        _ => None,
+    }
+}
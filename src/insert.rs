@@ -0,0 +1,357 @@
+use std::io;
+use termion::event::Key;
+
+use crate::cursor::char_class;
+use crate::editor::{Editor, Mode};
+use crate::indent;
+use crate::mark::Position;
+use crate::operator::ChangeCounts;
+
+// 插入模式下等待参数的按键
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InsertPrefix {
+    // Ctrl-r 等待寄存器名
+    Register,
+    // Ctrl-v 等待要原样插入的按键
+    Literal,
+    // Ctrl-v 后输入的字符编码，如 065、x41、u20ac
+    Code { radix: u32, max: usize, digits: usize, value: u32 },
+}
+
+// Ctrl-v 后的按键对应的字符
+fn literal_char(key: Key) -> Option<char> {
+    match key {
+        // 回车原样插入为 ^M
+        Key::Char('\n') => Some('\r'),
+        Key::Char(c) => Some(c),
+        Key::Ctrl(c) => Some(((c as u8) & 0x1f) as char),
+        Key::Esc => Some('\x1b'),
+        Key::Backspace => Some('\x7f'),
+        _ => None,
+    }
+}
+
+impl Editor {
+    pub fn handle_insert_mode(&mut self, key: Key) -> io::Result<()> {
+        if let Some(prefix) = self.insert_prefix.take() {
+            return self.handle_insert_prefix(prefix, key);
+        }
+
+        match key {
+            Key::Esc => self.finish_insert()?,
+            Key::Char('\t') if self.options.expandtab => {
+                let line = self.buffer.get_line(self.cursor.row).map_or("", |line| line.as_str());
+                let tabstop = self.options.tabstop;
+                let width = indent::display_width(&line[..self.cursor.col.min(line.len())], tabstop);
+                self.insert_str(&" ".repeat(tabstop - width % tabstop))?;
+            }
+            Key::Char('\n') => self.insert_newline()?,
+            Key::Char(c) => {
+                self.insert_str(&c.to_string())?;
+                self.reindent_on_trigger(c)?;
+            }
+            Key::Backspace | Key::Ctrl('h') => {
+                let floor = self.backspace_floor(false);
+                if self.cursor.col > floor {
+                    // 光标的列是字节位置，退回前一个字符的长度
+                    let line = self.buffer.get_line(self.cursor.row).map_or("", |line| line.as_str());
+                    let before = &line[..self.cursor.col.min(line.len())];
+                    let len = before.chars().next_back().map_or(1, char::len_utf8);
+                    self.delete_before(self.cursor.col - len)?;
+                } else if self.cursor.col == 0 {
+                    self.join_previous_line()?;
+                }
+            }
+            // Ctrl-w 删除光标前的单词
+            Key::Ctrl('w') => {
+                let floor = self.backspace_floor(true);
+                if self.cursor.col > floor {
+                    let line = self.buffer.get_line(self.cursor.row).cloned().unwrap_or_default();
+                    let before = &line[floor..self.cursor.col.min(line.len())];
+                    let trimmed = before.trim_end();
+                    let class = trimmed.chars().next_back().map_or(0, char_class);
+                    let word = trimmed.trim_end_matches(|c| char_class(c) == class);
+                    self.delete_before(floor + word.len())?;
+                } else if self.cursor.col == 0 {
+                    self.join_previous_line()?;
+                }
+            }
+            // Ctrl-u 删除光标前输入的文本，再按一次删除到缩进处
+            Key::Ctrl('u') => {
+                let floor = self.backspace_floor(true);
+                if self.cursor.col > floor {
+                    let line = self.buffer.get_line(self.cursor.row).map_or("", |line| line.as_str());
+                    let indent = indent::indent_len(line);
+                    let target = if floor == 0 && self.cursor.col > indent { indent } else { floor };
+                    self.delete_before(target)?;
+                } else if self.cursor.col == 0 {
+                    self.join_previous_line()?;
+                }
+            }
+            Key::Delete => {
+                let len = self.buffer.get_line(self.cursor.row).map_or(0, |line| line.len());
+                if self.cursor.col < len {
+                    self.buffer.remove_char(self.cursor.row, self.cursor.col);
+                } else if self.options.backspace_allows("eol") {
+                    self.buffer.join_lines(self.cursor.row);
+                }
+                self.cursor.update_screen_position(&self.buffer)?;
+            }
+            Key::Home => {
+                self.cursor.move_to_start(&self.buffer)?;
+            }
+            Key::End => {
+                let len = self.buffer.get_line(self.cursor.row).map_or(0, |line| line.len());
+                self.cursor.move_to(&self.buffer, Position::new(self.cursor.row, len))?;
+            }
+            Key::Ctrl('t') => self.shift_current_line(true)?,
+            Key::Ctrl('d') => self.shift_current_line(false)?,
+            Key::Ctrl('r') => self.insert_prefix = Some(InsertPrefix::Register),
+            Key::Ctrl('v') | Key::Ctrl('q') => self.insert_prefix = Some(InsertPrefix::Literal),
+            // Ctrl-o 执行一个普通模式命令后回到插入模式
+            Key::Ctrl('o') => {
+                self.change_keys.pop();
+                self.change_keys.push(Key::Esc);
+                self.finish_change();
+                self.mode = Mode::Normal;
+                self.insert_oneshot = true;
+                self.set_cursor_style()?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn handle_insert_prefix(&mut self, prefix: InsertPrefix, key: Key) -> io::Result<()> {
+        match prefix {
+            InsertPrefix::Register => {
+                if let Key::Char(name) = key {
+                    match self.registers.get(name).cloned() {
+                        Some(register) if register.linewise => self.insert_str(&format!("{}\n", register.text))?,
+                        Some(register) => self.insert_str(&register.text)?,
+                        None => self.aborted = true,
+                    }
+                }
+                Ok(())
+            }
+            InsertPrefix::Literal => {
+                // 数字直接开始十进制编码，o、x、u、U 开始八进制和十六进制编码
+                let (radix, max, first) = match key {
+                    Key::Char(c @ '0'..='9') => (10, 3, Some(c)),
+                    Key::Char('o' | 'O') => (8, 3, None),
+                    Key::Char('x' | 'X') => (16, 2, None),
+                    Key::Char('u') => (16, 4, None),
+                    Key::Char('U') => (16, 8, None),
+                    key => {
+                        return match literal_char(key) {
+                            Some(c) => self.insert_str(&c.to_string()),
+                            None => Ok(()),
+                        };
+                    }
+                };
+                let code = InsertPrefix::Code { radix, max, digits: 0, value: 0 };
+                match first {
+                    Some(digit) => self.handle_insert_prefix(code, Key::Char(digit)),
+                    None => {
+                        self.insert_prefix = Some(code);
+                        Ok(())
+                    }
+                }
+            }
+            InsertPrefix::Code { radix, max, digits, value } => {
+                let digit = match key {
+                    Key::Char(c) => c.to_digit(radix),
+                    _ => None,
+                };
+                match digit {
+                    Some(digit) if digits + 1 < max => {
+                        let value = value * radix + digit;
+                        self.insert_prefix = Some(InsertPrefix::Code { radix, max, digits: digits + 1, value });
+                        Ok(())
+                    }
+                    Some(digit) => self.insert_code(value * radix + digit),
+                    // 编码没有输入完时，遇到其它按键先插入已有的编码
+                    None => {
+                        if digits > 0 {
+                            self.insert_code(value)?;
+                        }
+                        self.handle_insert_mode(key)
+                    }
+                }
+            }
+        }
+    }
+
+    fn insert_code(&mut self, value: u32) -> io::Result<()> {
+        match char::from_u32(value) {
+            Some(c) => self.insert_str(&c.to_string()),
+            None => {
+                self.aborted = true;
+                Ok(())
+            }
+        }
+    }
+
+    // 在光标处插入文本并记录，供计数和 . 重复
+    pub fn insert_str(&mut self, text: &str) -> io::Result<()> {
+        self.inserted_text.push_str(text);
+        let end = self.buffer.insert_text(self.cursor.position(), text);
+        self.cursor.move_to(&self.buffer, end)
+    }
+
+    // 退出插入模式
+    fn finish_insert(&mut self) -> io::Result<()> {
+        let indent = self.insert_indent.take().filter(|indent| indent.row == self.cursor.row);
+        self.clear_autoindent(indent)?;
+        self.repeat_insert()?;
+        self.mode = Mode::Normal;
+        let position = self.cursor.position();
+        self.buffer.marks.set('^', position);
+        if self.buffer.marks.get('.').is_some_and(|change| change >= self.insert_start) {
+            self.buffer.marks.set('[', self.insert_start);
+        }
+        self.finish_change();
+        self.set_cursor_style()
+    }
+
+    // 当前行退格最多能删除到的列，stop 为 true 时 Ctrl-w 和 Ctrl-u 先停在插入开始处
+    fn backspace_floor(&self, stop: bool) -> usize {
+        let mut floor = 0;
+        if self.cursor.row == self.insert_start.row {
+            let start = self.insert_start.col;
+            if !self.options.backspace_allows("start") || (stop && self.cursor.col > start) {
+                floor = start.min(self.cursor.col);
+            }
+        }
+        if let Some(indent) = self.insert_indent {
+            if indent.row == self.cursor.row && !self.options.backspace_allows("indent") {
+                floor = floor.max(indent.col.min(self.cursor.col));
+            }
+        }
+        floor
+    }
+
+    // 删除当前行从 col 到光标的文本
+    fn delete_before(&mut self, col: usize) -> io::Result<()> {
+        let end = self.cursor.position();
+        let start = Position::new(end.row, col);
+        let deleted = self.buffer.delete_text(start, end);
+        for _ in deleted.chars() {
+            self.inserted_text.pop();
+        }
+        self.cursor.move_to(&self.buffer, start)?;
+        self.insert_start = self.insert_start.min(start);
+        Ok(())
+    }
+
+    // 在行首退格时和上一行合并，需要 backspace 包含 eol
+    fn join_previous_line(&mut self) -> io::Result<()> {
+        let row = self.cursor.row;
+        let allowed = row > 0
+            && self.options.backspace_allows("eol")
+            && (row > self.insert_start.row || self.options.backspace_allows("start"));
+        if !allowed {
+            return Ok(());
+        }
+        if self.inserted_text.ends_with('\n') {
+            self.inserted_text.pop();
+        }
+        if let Some(col) = self.buffer.join_lines(row - 1) {
+            let position = Position::new(row - 1, col);
+            self.cursor.move_to(&self.buffer, position)?;
+            self.insert_start = self.insert_start.min(position);
+        }
+        Ok(())
+    }
+
+    // Ctrl-t/Ctrl-d：当前行增加或减少一个 shiftwidth 的缩进，光标跟随文本
+    fn shift_current_line(&mut self, right: bool) -> io::Result<()> {
+        let row = self.cursor.row;
+        let line = self.buffer.get_line(row).map_or("", |line| line.as_str());
+        let width = indent::indent_width(line, self.options.tabstop);
+        let width = indent::shift_width(width, self.options.shift_width(), right, true);
+        let len = self.set_line_indent(row, width)?;
+        self.insert_indent = Some(Position::new(row, len));
+        Ok(())
+    }
+
+    // 回车：拆分当前行，新行按缩进规则或 autoindent 缩进
+    fn insert_newline(&mut self) -> io::Result<()> {
+        // 先按原来的缩进缩进新行，再清除上一行只有自动缩进的空白
+        let indent = self.insert_indent.take().filter(|indent| indent.row == self.cursor.row);
+        self.insert_str("\n")?;
+        self.indent_new_line(self.cursor.row - 1)?;
+        self.clear_autoindent(indent)
+    }
+
+    // 给光标所在的新行加上缩进，没有缩进规则时 autoindent 复制 from 行的缩进
+    pub fn indent_new_line(&mut self, from: usize) -> io::Result<()> {
+        let row = self.cursor.row;
+        let width = match self.indent_rule() {
+            Some(rule) => self.rule_indent(rule.as_ref(), row),
+            None if self.options.autoindent => {
+                let line = self.buffer.get_line(from).map_or("", |line| line.as_str());
+                indent::indent_width(line, self.options.tabstop)
+            }
+            None => return Ok(()),
+        };
+        let len = self.set_line_indent(row, width)?;
+        if len > 0 {
+            self.insert_indent = Some(Position::new(row, len));
+        }
+        self.cursor.move_to(&self.buffer, Position::new(row, len))
+    }
+
+    // 只有自动缩进的行在换行或退出插入模式时删除缩进
+    fn clear_autoindent(&mut self, indent: Option<Position>) -> io::Result<()> {
+        let indent = match indent {
+            Some(indent) => indent,
+            None => return Ok(()),
+        };
+        let line = self.buffer.get_line(indent.row).map_or("", |line| line.as_str());
+        if line.len() == indent.col && line.trim().is_empty() {
+            self.buffer.delete_text(Position::new(indent.row, 0), indent);
+            if self.cursor.row == indent.row {
+                self.cursor.move_to(&self.buffer, Position::new(indent.row, 0))?;
+            }
+        }
+        Ok(())
+    }
+
+    // 在行首输入规则的触发字符时重新缩进当前行，如 } 和 Python 的 else:
+    fn reindent_on_trigger(&mut self, c: char) -> io::Result<()> {
+        let rule = match self.indent_rule() {
+            Some(rule) if rule.triggers().contains(c) => rule,
+            _ => return Ok(()),
+        };
+        let row = self.cursor.row;
+        let line = self.buffer.get_line(row).map_or("", |line| line.as_str());
+        let typed = line[..self.cursor.col.min(line.len())].trim_start();
+        // 只在触发字符是行的第一个字符，或 Python 的关键字以 : 结束时生效
+        let first = typed.chars().count() == 1;
+        let keyword = c == ':' && typed.len() == line.trim().len();
+        if first || keyword {
+            let width = self.rule_indent(rule.as_ref(), row);
+            self.set_line_indent(row, width)?;
+        }
+        Ok(())
+    }
+
+    // Ctrl-o 执行的命令结束后回到插入模式
+    pub fn resume_insert(&mut self) -> io::Result<()> {
+        match self.mode {
+            // 回到插入模式后输入的文本是一个新的修改，. 重复时像 i 开始的插入
+            Mode::Normal if self.starts_command() => {
+                self.insert_oneshot = false;
+                self.change_keys = vec![Key::Char('i')];
+                self.change_counts = ChangeCounts::default();
+                self.begin_insert('i', 1)
+            }
+            Mode::Insert => {
+                self.insert_oneshot = false;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
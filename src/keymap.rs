@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use termion::event::Key;
+
+// 映射所属的模式，对应 :nmap/:vmap/:omap/:imap/:cmap
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MapMode {
+    Normal,
+    Visual,
+    OperatorPending,
+    Insert,
+    CmdLine,
+}
+
+impl MapMode {
+    pub fn flag(&self) -> char {
+        match self {
+            MapMode::Normal => 'n',
+            MapMode::Visual => 'v',
+            MapMode::OperatorPending => 'o',
+            MapMode::Insert => 'i',
+            MapMode::CmdLine => 'c',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub lhs: Vec<Key>,
+    pub rhs: Vec<Key>,
+    pub noremap: bool,
+    pub silent: bool,
+}
+
+// 按键序列匹配的结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapMatch {
+    // 没有任何映射以该序列开头
+    None,
+    // 是某些映射的前缀，需要等待更多按键
+    Prefix,
+    // 完整匹配，但还有更长的映射以它开头（需要 timeoutlen 决定）
+    Ambiguous(Mapping),
+    // 唯一的完整匹配
+    Full(Mapping),
+}
+
+// map 类命令解析后的参数
+#[derive(Debug, Default, PartialEq)]
+pub struct MapArgs {
+    pub lhs: String,
+    pub rhs: String,
+    pub silent: bool,
+    pub buffer: bool,
+}
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    mapping: Option<Mapping>,
+    children: HashMap<Key, TrieNode>,
+}
+
+impl TrieNode {
+    fn find(&self, keys: &[Key]) -> Option<&TrieNode> {
+        let mut node = self;
+        for key in keys {
+            node = node.children.get(key)?;
+        }
+        Some(node)
+    }
+
+    fn remove(&mut self, keys: &[Key]) -> bool {
+        match keys.split_first() {
+            None => self.mapping.take().is_some(),
+            Some((first, rest)) => {
+                let removed = match self.children.get_mut(first) {
+                    Some(child) => child.remove(rest),
+                    None => return false,
+                };
+                // 删除后清理空节点
+                if let Some(child) = self.children.get(first) {
+                    if child.mapping.is_none() && child.children.is_empty() {
+                        self.children.remove(first);
+                    }
+                }
+                removed
+            }
+        }
+    }
+
+    fn collect(&self, out: &mut Vec<Mapping>) {
+        if let Some(mapping) = &self.mapping {
+            out.push(mapping.clone());
+        }
+        for child in self.children.values() {
+            child.collect(out);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Keymaps {
+    pub leader: String,
+    global: HashMap<MapMode, TrieNode>,
+    buffer: HashMap<MapMode, TrieNode>,
+    // 其他文件的 <buffer> 映射，回到这些文件时恢复
+    saved: HashMap<PathBuf, HashMap<MapMode, TrieNode>>,
+}
+
+impl Default for Keymaps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Keymaps {
+    pub fn new() -> Self {
+        Self {
+            leader: String::from("\\"),
+            global: HashMap::new(),
+            buffer: HashMap::new(),
+            saved: HashMap::new(),
+        }
+    }
+
+    fn table(&mut self, mode: MapMode, buffer_local: bool) -> &mut TrieNode {
+        let tables = if buffer_local { &mut self.buffer } else { &mut self.global };
+        tables.entry(mode).or_default()
+    }
+
+    pub fn add(&mut self, mode: MapMode, mapping: Mapping, buffer_local: bool) {
+        let mut node = self.table(mode, buffer_local);
+        for key in &mapping.lhs {
+            node = node.children.entry(*key).or_default();
+        }
+        node.mapping = Some(mapping);
+    }
+
+    pub fn remove(&mut self, mode: MapMode, lhs: &[Key], buffer_local: bool) -> bool {
+        self.table(mode, buffer_local).remove(lhs)
+    }
+
+    pub fn clear(&mut self, mode: MapMode, buffer_local: bool) {
+        let tables = if buffer_local { &mut self.buffer } else { &mut self.global };
+        tables.remove(&mode);
+    }
+
+    // 换成其他文件时保存原来文件的 <buffer> 映射，换上新文件的映射
+    pub fn switch_buffer(&mut self, from: Option<PathBuf>, to: &Path) {
+        let current = std::mem::take(&mut self.buffer);
+        if let Some(from) = from.filter(|_| !current.is_empty()) {
+            self.saved.insert(from, current);
+        }
+        self.buffer = self.saved.remove(to).unwrap_or_default();
+    }
+
+    // buffer 局部映射优先于全局映射
+    pub fn lookup(&self, mode: MapMode, keys: &[Key]) -> MapMatch {
+        if keys.is_empty() {
+            return MapMatch::None;
+        }
+
+        let mut exact = None;
+        let mut longer = false;
+        for tables in [&self.buffer, &self.global] {
+            if let Some(node) = tables.get(&mode).and_then(|root| root.find(keys)) {
+                if exact.is_none() {
+                    exact = node.mapping.clone();
+                }
+                longer |= !node.children.is_empty();
+            }
+        }
+
+        match (exact, longer) {
+            (Some(mapping), true) => MapMatch::Ambiguous(mapping),
+            (Some(mapping), false) => MapMatch::Full(mapping),
+            (None, true) => MapMatch::Prefix,
+            (None, false) => MapMatch::None,
+        }
+    }
+
+    pub fn list(&self, mode: MapMode) -> Vec<(Mapping, bool)> {
+        let mut result = Vec::new();
+        for (tables, buffer_local) in [(&self.buffer, true), (&self.global, false)] {
+            let mut mappings = Vec::new();
+            if let Some(root) = tables.get(&mode) {
+                root.collect(&mut mappings);
+            }
+            result.extend(mappings.into_iter().map(|m| (m, buffer_local)));
+        }
+        result.sort_by_key(|(m, _)| format_keys(&m.lhs));
+        result
+    }
+}
+
+// 解析 map 命令的参数: [<silent>] [<buffer>] {lhs} {rhs}
+pub fn parse_map_args(args: &str) -> MapArgs {
+    let mut result = MapArgs::default();
+    let mut rest = args.trim_start();
+
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        if lower.starts_with("<silent>") {
+            result.silent = true;
+        } else if lower.starts_with("<buffer>") {
+            result.buffer = true;
+        } else {
+            break;
+        }
+        rest = rest[8..].trim_start();
+    }
+
+    match rest.find(' ') {
+        Some(pos) => {
+            result.lhs = rest[..pos].to_string();
+            result.rhs = rest[pos..].trim_start().to_string();
+        }
+        None => result.lhs = rest.to_string(),
+    }
+    result
+}
+
+// 把 "<C-w>j<leader>x" 这样的按键记法解析成按键序列
+pub fn parse_keys(text: &str, leader: &str) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                if let Some(special) = parse_special(&rest[1..end], leader) {
+                    keys.extend(special);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+        keys.push(Key::Char(c));
+        rest = &rest[c.len_utf8()..];
+    }
+    keys
+}
+
+fn parse_special(name: &str, leader: &str) -> Option<Vec<Key>> {
+    let lower = name.to_ascii_lowercase();
+    let key = match lower.as_str() {
+        "leader" => return Some(parse_keys(leader, "")),
+        "nop" => return Some(Vec::new()),
+        "cr" | "enter" | "return" | "nl" => Key::Char('\n'),
+        "esc" => Key::Esc,
+        "space" => Key::Char(' '),
+        "tab" => Key::Char('\t'),
+        "bs" => Key::Backspace,
+        "del" => Key::Delete,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "insert" => Key::Insert,
+        "lt" => Key::Char('<'),
+        "bar" => Key::Char('|'),
+        "bslash" => Key::Char('\\'),
+        _ => {
+            if let Some(num) = lower.strip_prefix('f') {
+                let n: u8 = num.parse().ok()?;
+                if (1..=12).contains(&n) {
+                    return Some(vec![Key::F(n)]);
+                }
+                return None;
+            }
+
+            let (modifier, rest) = lower.split_once('-')?;
+            let mut chars = name[modifier.len() + 1..].chars();
+            let c = match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return parse_modified_special(modifier, rest),
+            };
+            match modifier {
+                "c" => Key::Ctrl(c.to_ascii_lowercase()),
+                "m" | "a" => Key::Alt(c),
+                _ => return None,
+            }
+        }
+    };
+    Some(vec![key])
+}
+
+// <C-Space> 之类带修饰的特殊键
+fn parse_modified_special(modifier: &str, name: &str) -> Option<Vec<Key>> {
+    match (modifier, name) {
+        ("c", "space") | ("c", "@") => Some(vec![Key::Null]),
+        ("m", "space") | ("a", "space") => Some(vec![Key::Alt(' ')]),
+        _ => None,
+    }
+}
+
+// 把按键序列格式化成 :map 列表中的记法
+pub fn format_keys(keys: &[Key]) -> String {
+    keys.iter().map(|key| format_key(*key)).collect()
+}
+
+pub fn format_key(key: Key) -> String {
+    match key {
+        Key::Char('\n') => "<CR>".to_string(),
+        Key::Char(' ') => "<Space>".to_string(),
+        Key::Char('\t') => "<Tab>".to_string(),
+        Key::Char('<') => "<lt>".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("<C-{}>", c.to_ascii_uppercase()),
+        Key::Alt(c) => format!("<M-{}>", c),
+        Key::Esc => "<Esc>".to_string(),
+        Key::Backspace => "<BS>".to_string(),
+        Key::Delete => "<Del>".to_string(),
+        Key::Up => "<Up>".to_string(),
+        Key::Down => "<Down>".to_string(),
+        Key::Left => "<Left>".to_string(),
+        Key::Right => "<Right>".to_string(),
+        Key::Home => "<Home>".to_string(),
+        Key::End => "<End>".to_string(),
+        Key::PageUp => "<PageUp>".to_string(),
+        Key::PageDown => "<PageDown>".to_string(),
+        Key::Insert => "<Insert>".to_string(),
+        Key::F(n) => format!("<F{}>", n),
+        Key::Null => "<C-@>".to_string(),
+        _ => String::new(),
+    }
+}
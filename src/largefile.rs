@@ -0,0 +1,333 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use memmap2::Mmap;
+use regex::bytes;
+use regex::Regex;
+
+use crate::buffer::Buffer;
+use crate::editor::Editor;
+use crate::encoding;
+use crate::file::{self, FileOptions};
+use crate::mark::Position;
+use crate::search;
+
+// 大文件每次读入的行数，光标接近读入部分的两端时读入更多的行或者换一个位置
+pub const WINDOW_LINES: usize = 1000;
+const MARGIN: usize = WINDOW_LINES / 4;
+// 后台建立索引时每次扫描的字节数，扫描完一块就可以使用
+const CHUNK: usize = 1 << 20;
+
+#[derive(Debug, Default)]
+struct IndexState {
+    // 每一行开始的字节位置
+    starts: Vec<usize>,
+    // 已经扫描的字节数，以及是否扫描完整个文件
+    scanned: usize,
+    done: bool,
+}
+
+// 在后台线程中建立的行索引，查询还没有扫描到的部分时等待
+#[derive(Debug, Default)]
+pub struct LineIndex {
+    state: Mutex<IndexState>,
+    ready: Condvar,
+}
+
+impl LineIndex {
+    fn build(&self, map: &[u8]) {
+        let mut offset = 0;
+        while offset < map.len() {
+            let end = (offset + CHUNK).min(map.len());
+            let mut starts = Vec::new();
+            if offset == 0 {
+                starts.push(0);
+            }
+            let next_lines = map[offset..end].iter().enumerate().filter(|(_, &byte)| byte == b'\n');
+            starts.extend(next_lines.map(|(i, _)| offset + i + 1).filter(|&start| start < map.len()));
+            let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+            state.starts.extend(starts);
+            state.scanned = end;
+            self.ready.notify_all();
+            drop(state);
+            offset = end;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+        state.done = true;
+        self.ready.notify_all();
+    }
+
+    // 等待直到索引满足条件或者扫描完整个文件
+    fn wait<T>(&self, ready: impl Fn(&IndexState) -> bool, result: impl Fn(&IndexState) -> T) -> T {
+        let mut state = self.state.lock().unwrap_or_else(|error| error.into_inner());
+        while !state.done && !ready(&state) {
+            state = self.ready.wait(state).unwrap_or_else(|error| error.into_inner());
+        }
+        result(&state)
+    }
+
+    // 第 line 行开始的字节位置，超过文件的行数时返回 None
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.wait(|state| state.starts.len() > line, |state| state.starts.get(line).copied())
+    }
+
+    // 字节位置所在的行
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.wait(
+            |state| state.scanned > offset,
+            |state| state.starts.partition_point(|&start| start <= offset).saturating_sub(1),
+        )
+    }
+
+    // 文件的总行数，需要等待扫描完整个文件
+    pub fn line_count(&self) -> usize {
+        self.wait(|_| false, |state| state.starts.len())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state.lock().unwrap_or_else(|error| error.into_inner()).done
+    }
+}
+
+// 映射到内存的大文件和读入 buffer 的行：从 first_line 行开始，在文件中的字节范围是 start..end
+#[derive(Debug, Clone)]
+pub struct LargeFile {
+    map: Arc<Mmap>,
+    pub index: Arc<LineIndex>,
+    pub first_line: usize,
+    pub start: usize,
+    pub end: usize,
+    // dos 格式时去掉行尾的 CR
+    pub dos: bool,
+}
+
+impl LargeFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: 映射是只读的。文件在编辑器外被截断时访问映射会出错，这和 vim 读大文件时的限制相同；
+        // 写入时先写到临时文件再改名，不会修改映射的文件
+        let map = Arc::new(unsafe { Mmap::map(&file)? });
+        let index = Arc::new(LineIndex::default());
+        let (scan_map, scan_index) = (Arc::clone(&map), Arc::clone(&index));
+        thread::spawn(move || scan_index.build(&scan_map));
+        Ok(Self { map, index, first_line: 0, start: 0, end: 0, dos: false })
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    // 从读入部分的末尾继续读入最多 max 行，不是 UTF-8 的字节和 binary 模式一样保存
+    pub fn read_lines(&mut self, max: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        while self.end < self.map.len() && lines.len() < max {
+            let rest = &self.map[self.end..];
+            let len = rest.iter().position(|&byte| byte == b'\n').unwrap_or(rest.len());
+            let mut line = &rest[..len];
+            if self.dos {
+                line = line.strip_suffix(b"\r").unwrap_or(line);
+            }
+            lines.push(encoding::decode_binary(line));
+            self.end = (self.end + len + 1).min(self.map.len());
+        }
+        lines
+    }
+
+    // 换成从第 line 行开始读入，行号超过文件的行数时返回 None
+    pub fn read_at(&mut self, line: usize) -> Option<Vec<String>> {
+        let start = self.index.line_start(line)?;
+        self.first_line = line;
+        self.start = start;
+        self.end = start;
+        Some(self.read_lines(WINDOW_LINES))
+    }
+
+    // 写入整个文件：读入的部分换成 loaded，没有读入的部分原样复制
+    pub fn write_around(&self, writer: &mut dyn Write, loaded: &[u8]) -> io::Result<()> {
+        writer.write_all(&self.map[..self.start])?;
+        writer.write_all(loaded)?;
+        writer.write_all(&self.map[self.end..])
+    }
+
+    pub fn written_len(&self, loaded: usize) -> usize {
+        self.start + loaded + (self.map.len() - self.end)
+    }
+
+    // 在 from..to 中查找，向前找第一个匹配，向后找最后一个匹配
+    pub fn find(&self, regex: &bytes::Regex, from: usize, to: usize, forward: bool) -> Option<usize> {
+        let text = self.map.get(from..to)?;
+        let found = if forward { regex.find(text) } else { regex.find_iter(text).last() };
+        found.map(|found| from + found.start())
+    }
+}
+
+impl Editor {
+    // 超过 largefile 的文件映射到内存，先读入开头的一部分行，不加载语法、撤销文件和交换文件
+    pub fn is_large_file(&self, path: &Path, options: &FileOptions) -> bool {
+        let limit = (self.options.largefile as u64).saturating_mul(1 << 20);
+        let binary = options.binary.unwrap_or(self.options.binary);
+        let utf8 = options.encoding.is_none_or(|encoding| encoding == "utf-8");
+        limit > 0 && !binary && utf8 && fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.len() >= limit)
+    }
+
+    pub fn load_large_file(&mut self, path: &Path, options: &FileOptions) -> io::Result<()> {
+        let mut large = match LargeFile::open(path) {
+            Ok(large) => large,
+            Err(_) => {
+                self.error(format!("E484: Can't open file {}", path.display()));
+                return Ok(());
+            }
+        };
+        // 只按开头的内容检测换行符，只支持 unix 和 dos
+        let head = String::from_utf8_lossy(&large.bytes()[..large.len().min(CHUNK)]).into_owned();
+        let fileformat = match options.fileformat {
+            Some(fileformat) => fileformat,
+            None => file::detect_format(&head, &self.options.fileformats).0,
+        };
+        large.dos = fileformat == "dos";
+        let lines = large.read_lines(WINDOW_LINES);
+        let dos = if large.dos { "[dos] " } else { "" };
+        self.messages.push(format!("\"{}\" [large] {}{}B", path.display(), dos, large.len()));
+        self.options.fileencoding = String::from("utf-8");
+        self.options.bomb = false;
+        self.options.fileformat = String::from(if large.dos { "dos" } else { "unix" });
+        self.options.endofline = large.bytes().last().is_none_or(|&byte| byte == b'\n');
+        let mut buffer = Buffer::from_lines(lines);
+        buffer.large = Some(large);
+        self.set_buffer(buffer, path)
+    }
+
+    // 换成从 first_line 开始的一部分行，修改过的行还没有写入时不能换
+    fn show_large_window(&mut self, first_line: usize) -> io::Result<bool> {
+        let (Some(mut large), Some(path)) = (self.buffer.large.clone(), self.buffer.path.clone()) else {
+            return Ok(false);
+        };
+        if self.buffer.modified {
+            self.error("E37: No write since last change (add ! to override)");
+            return Ok(false);
+        }
+        let Some(lines) = large.read_at(first_line) else {
+            return Ok(false);
+        };
+        let mut buffer = Buffer::from_lines(lines);
+        buffer.large = Some(large);
+        self.set_buffer(buffer, &path)?;
+        Ok(true)
+    }
+
+    // 换成包含第 line 行的部分，光标移到这一行的 col 列
+    fn show_large_line(&mut self, line: usize, col: usize) -> io::Result<bool> {
+        let first_line = line.saturating_sub(WINDOW_LINES / 2);
+        if !self.show_large_window(first_line)? {
+            return Ok(false);
+        }
+        self.cursor.move_to(&self.buffer, Position::new(line - first_line, col))?;
+        Ok(true)
+    }
+
+    // 大文件中的 G 和 {count}G：跳到没有读入的行时等待索引，再读入那一部分
+    pub fn large_goto(&mut self, count: Option<usize>) -> io::Result<bool> {
+        let Some(large) = self.buffer.large.clone() else {
+            return Ok(false);
+        };
+        let total = large.index.line_count();
+        let target = count.map_or(total, |n| n.min(total)).saturating_sub(1);
+        let loaded = large.first_line..large.first_line + self.buffer.line_count();
+        if loaded.contains(&target) {
+            self.jump_to_line(target - large.first_line)?;
+            return Ok(true);
+        }
+        // 操作符不能作用于没有读入的行
+        if self.operator.is_some() {
+            return Ok(false);
+        }
+        let first_line = if count.is_none() { total.saturating_sub(WINDOW_LINES) } else { target.saturating_sub(WINDOW_LINES / 2) };
+        if !self.show_large_window(first_line)? {
+            return Ok(false);
+        }
+        self.cursor.move_to(&self.buffer, Position::new(target - first_line, 0))?;
+        self.cursor.move_to_first_non_blank(&self.buffer)?;
+        Ok(true)
+    }
+
+    // 先在读入的行中查找，再查找文件中没有读入的部分，找到时读入那一部分
+    pub fn find_in_large_file(&mut self, regex: &Regex, from: Position, forward: bool) -> io::Result<Option<(Position, bool)>> {
+        let found = search::find(&self.buffer, regex, from, forward);
+        let Some(large) = self.buffer.large.clone() else {
+            return Ok(found);
+        };
+        if let Some((_, false)) = found {
+            return Ok(found);
+        }
+        let Ok(bytes_regex) = bytes::RegexBuilder::new(regex.as_str()).multi_line(true).build() else {
+            return Ok(found);
+        };
+        // 向前是读入部分之后、再绕回文件开头，向后相反
+        let (after, before) = ((large.end, large.len()), (0, large.start));
+        let (first, second) = if forward { (after, before) } else { (before, after) };
+        let outside = large
+            .find(&bytes_regex, first.0, first.1, forward)
+            .map(|offset| (offset, false))
+            .or_else(|| large.find(&bytes_regex, second.0, second.1, forward).map(|offset| (offset, true)));
+        let Some((offset, wrapped)) = outside else {
+            return Ok(found);
+        };
+        let line = large.index.line_of(offset);
+        let col = offset - large.index.line_start(line).unwrap_or(offset);
+        if !self.show_large_line(line, col)? {
+            return Ok(None);
+        }
+        Ok(Some((self.cursor.position(), wrapped)))
+    }
+
+    // 每个命令之后：光标接近读入部分的末尾时继续读入，没有修改时换成光标附近的部分以免占用太多内存
+    pub fn update_large_window(&mut self) -> io::Result<()> {
+        let count = self.buffer.line_count();
+        let Some(large) = self.buffer.large.as_mut() else {
+            return Ok(());
+        };
+        let line = large.first_line + self.cursor.row;
+        let near_end = self.cursor.row + MARGIN >= count && large.end < large.len();
+        let near_start = self.cursor.row < MARGIN && large.first_line > 0;
+        if self.buffer.modified {
+            if near_end {
+                let lines = large.read_lines(WINDOW_LINES);
+                self.buffer.append_loaded(lines);
+            }
+            return Ok(());
+        }
+        if near_start || (near_end && count >= 3 * WINDOW_LINES) {
+            let col = self.cursor.col;
+            self.show_large_line(line, col)?;
+        } else if near_end {
+            let lines = large.read_lines(WINDOW_LINES);
+            self.buffer.append_loaded(lines);
+        }
+        Ok(())
+    }
+
+    // 写入当前文件之后重新映射，读入的行在新文件中的开始位置和之后的内容不变
+    pub fn reopen_large_file(&mut self) -> io::Result<()> {
+        let (Some(large), Some(path)) = (&self.buffer.large, &self.buffer.path) else {
+            return Ok(());
+        };
+        let mut reopened = LargeFile::open(path)?;
+        reopened.first_line = large.first_line;
+        reopened.start = large.start;
+        reopened.end = reopened.len() - (large.len() - large.end);
+        reopened.dos = large.dos;
+        self.buffer.large = Some(reopened);
+        Ok(())
+    }
+}
@@ -1,3 +1,31 @@
 pub mod buffer;
+pub mod checktime;
+pub mod command;
 pub mod cursor;
+pub mod display;
 pub mod editor;
+pub mod encoding;
+pub mod file;
+pub mod filter;
+pub mod fold;
+pub mod gutter;
+pub mod hex;
+pub mod highlight;
+pub mod indent;
+pub mod insert;
+pub mod keymap;
+pub mod largefile;
+pub mod macros;
+pub mod mark;
+pub mod operator;
+pub mod options;
+pub mod register;
+pub mod save;
+pub mod search;
+pub mod shell;
+pub mod sign;
+pub mod sort;
+pub mod swap;
+pub mod syntax;
+pub mod treesitter;
+pub mod undo;
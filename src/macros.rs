@@ -0,0 +1,105 @@
+use std::io;
+use termion::event::Key;
+
+use crate::editor::{Editor, Mode};
+use crate::keymap;
+use crate::register::{Register, Registers};
+
+impl Editor {
+    pub fn start_recording(&mut self, register: char) {
+        if !register.is_ascii_alphanumeric() && register != '"' {
+            self.aborted = true;
+            return;
+        }
+        self.recording = Some(register);
+        self.recorded_keys.clear();
+    }
+
+    // 结束录制，最后一个按键是结束录制的 q，不保存
+    pub fn stop_recording(&mut self) {
+        let register = match self.recording.take() {
+            Some(register) => register,
+            None => return,
+        };
+        let mut keys = std::mem::take(&mut self.recorded_keys);
+        keys.pop();
+        self.registers.set(register, Register {
+            text: keymap::format_keys(&keys),
+            linewise: false,
+        });
+    }
+
+    // @{register}：把寄存器的内容作为按键插入到 typeahead 的最前面
+    pub fn execute_register(&mut self, name: char, count: usize) -> io::Result<()> {
+        let name = match name {
+            '@' => match self.last_macro {
+                Some(name) => name,
+                None => {
+                    self.error("E748: No previously used register");
+                    return Ok(());
+                }
+            },
+            name => name,
+        };
+        if !Registers::is_valid(name) {
+            self.error(format!("E354: Invalid register name: '{}'", name));
+            return Ok(());
+        }
+        self.last_macro = Some(name);
+
+        let register = match self.registers.get(name) {
+            Some(register) => register.clone(),
+            None => {
+                self.aborted = true;
+                return Ok(());
+            }
+        };
+
+        // @: 重复上一次的命令行
+        if name == ':' {
+            for _ in 0..count {
+                self.execute_command(&register.text)?;
+                if self.aborted {
+                    break;
+                }
+            }
+            return Ok(());
+        }
+
+        let mut keys = keymap::parse_keys(&register.text, &self.keymaps.leader);
+        if register.linewise {
+            keys.push(Key::Char('\n'));
+        }
+        for _ in 0..count {
+            for key in keys.iter().rev() {
+                self.typeahead.push_front(*key);
+            }
+        }
+        Ok(())
+    }
+
+    // :normal 同步执行按键，结束时像 vim 一样取消未完成的命令
+    pub fn execute_normal(&mut self, keys: &[Key], remap: bool) -> io::Result<()> {
+        let saved = std::mem::take(&mut self.typeahead);
+        for key in keys {
+            if remap {
+                self.feed_key(*key)?;
+            } else {
+                self.dispatch_key(*key)?;
+            }
+            self.run_typeahead()?;
+            if self.aborted || self.quit {
+                break;
+            }
+        }
+        self.process_pending(true)?;
+
+        self.normal_prefix = None;
+        self.count = None;
+        if !matches!(self.mode, Mode::Normal) {
+            self.dispatch_key(Key::Esc)?;
+        }
+        self.typeahead = saved;
+        Ok(())
+    }
+}
@@ -1,11 +1,28 @@
-mod buffer;
-mod cursor;
-mod editor;
-
+use std::env;
 use std::io;
-use editor::Editor;
+use std::path::Path;
+use rustvim::editor::Editor;
+use rustvim::swap;
 
 fn main() -> io::Result<()> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
     let mut editor = Editor::new()?;
+    // rustvim -b file 以 binary 模式编辑文件
+    if let Some(i) = args.iter().position(|arg| arg == "-b") {
+        args.remove(i);
+        editor.execute_command("set binary")?;
+    }
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => (),
+        // rustvim -r 列出当前目录中的交换文件
+        ["-r"] => {
+            for line in swap::list(Path::new(".")) {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        ["-r", file] => editor.recover(Path::new(file))?,
+        [file, ..] => editor.open(Path::new(file))?,
+    }
     editor.run()
 }
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+
+// 跳转列表和改变列表保存的最大条目数
+const LIST_SIZE: usize = 100;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Position {
+    pub fn new(row: usize, col: usize) -> Self {
+        Self { row, col }
+    }
+
+    // 根据行的变化调整位置，位置所在的行被删除时返回 false
+    pub fn adjust(&mut self, edit: LineEdit) -> bool {
+        match edit {
+            LineEdit::Insert { at, count } => {
+                if self.row >= at {
+                    self.row += count;
+                }
+            }
+            LineEdit::Remove { at, count } => {
+                if self.row >= at + count {
+                    self.row -= count;
+                } else if self.row >= at {
+                    self.row = at;
+                    self.col = 0;
+                    return false;
+                }
+            }
+            LineEdit::Split { row, col } => {
+                if self.row > row {
+                    self.row += 1;
+                } else if self.row == row && self.col >= col {
+                    self.row += 1;
+                    self.col -= col;
+                }
+            }
+            LineEdit::Join { row, col } => {
+                if self.row == row + 1 {
+                    self.row = row;
+                    self.col += col;
+                } else if self.row > row + 1 {
+                    self.row -= 1;
+                }
+            }
+        }
+        true
+    }
+}
+
+// Buffer 中行的变化，标记和列表据此跟随文本移动
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineEdit {
+    // 在 at 处插入 count 行
+    Insert { at: usize, count: usize },
+    // 删除从 at 开始的 count 行
+    Remove { at: usize, count: usize },
+    // 在 (row, col) 处把一行拆成两行
+    Split { row: usize, col: usize },
+    // 把 row + 1 行接到 row 行的 col 处
+    Join { row: usize, col: usize },
+}
+
+// 按字母区分的标记：a-z 属于 buffer，A-Z 是全局文件标记，其余是特殊标记
+#[derive(Debug, Default)]
+pub struct Marks {
+    marks: BTreeMap<char, Position>,
+}
+
+impl Marks {
+    pub fn new() -> Self {
+        Self {
+            marks: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_valid(name: char) -> bool {
+        name.is_ascii_alphabetic() || matches!(name, '\'' | '`' | '.' | '^' | '[' | ']' | '<' | '>')
+    }
+
+    pub fn get(&self, name: char) -> Option<Position> {
+        let name = if name == '`' { '\'' } else { name };
+        self.marks.get(&name).copied()
+    }
+
+    pub fn set(&mut self, name: char, position: Position) {
+        let name = if name == '`' { '\'' } else { name };
+        self.marks.insert(name, position);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (char, Position)> + '_ {
+        self.marks.iter().map(|(name, position)| (*name, *position))
+    }
+
+    // 所在行被删除的字母标记会被删除，特殊标记移动到删除位置
+    pub fn adjust(&mut self, edit: LineEdit) {
+        self.marks.retain(|name, position| position.adjust(edit) || !name.is_ascii_alphabetic());
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct JumpList {
+    entries: Vec<Position>,
+    index: usize,
+}
+
+impl JumpList {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            index: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[Position] {
+        &self.entries
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    // 记录跳转前的位置，同一行只保留最新的一条
+    pub fn push(&mut self, position: Position) {
+        self.entries.retain(|entry| entry.row != position.row);
+        self.entries.push(position);
+        if self.entries.len() > LIST_SIZE {
+            self.entries.remove(0);
+        }
+        self.index = self.entries.len();
+    }
+
+    // Ctrl-o：在列表末尾时先记录当前位置，以便 Ctrl-i 能回来
+    pub fn back(&mut self, current: Position) -> Option<Position> {
+        if self.index == 0 {
+            return None;
+        }
+        if self.index == self.entries.len() {
+            self.push(current);
+            self.index = self.entries.len() - 1;
+        }
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        Some(self.entries[self.index])
+    }
+
+    // Ctrl-i
+    pub fn forward(&mut self) -> Option<Position> {
+        if self.index + 1 >= self.entries.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.entries[self.index])
+    }
+
+    pub fn adjust(&mut self, edit: LineEdit) {
+        for entry in &mut self.entries {
+            entry.adjust(edit);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ChangeList {
+    entries: Vec<Position>,
+    index: usize,
+}
+
+impl ChangeList {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            index: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[Position] {
+        &self.entries
+    }
+
+    // 同一行上连续的修改只占一条
+    pub fn record(&mut self, position: Position) {
+        match self.entries.last_mut() {
+            Some(last) if last.row == position.row => *last = position,
+            _ => {
+                self.entries.push(position);
+                if self.entries.len() > LIST_SIZE {
+                    self.entries.remove(0);
+                }
+            }
+        }
+        self.index = self.entries.len();
+    }
+
+    // g;
+    pub fn older(&mut self) -> Option<Position> {
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        Some(self.entries[self.index])
+    }
+
+    // g,
+    pub fn newer(&mut self) -> Option<Position> {
+        if self.index + 1 >= self.entries.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.entries[self.index])
+    }
+
+    pub fn adjust(&mut self, edit: LineEdit) {
+        for entry in &mut self.entries {
+            entry.adjust(edit);
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use std::io;
+use termion::event::Key;
+
+use crate::editor::{Editor, Mode};
+use crate::indent::{self, BraceIndent, IndentRule, SmartIndent};
+use crate::mark::Position;
+use crate::register::Register;
+
+// 移动命令的类型，决定操作符作用的范围
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MotionKind {
+    // 不包含目标位置的字符，如 w、h
+    Exclusive,
+    // 包含目标位置的字符，如 e、$
+    Inclusive,
+    // 作用于整行，如 j、G
+    Linewise,
+}
+
+// 等待移动命令的操作符，如 d、c、y，以及它前面的计数
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PendingOperator {
+    pub op: char,
+    pub count: Option<usize>,
+}
+
+// 修改命令的计数，不放在按键中以便 . 替换：count 是命令或操作符前的计数，
+// motion 是操作符后移动命令的计数和它在按键中的位置
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ChangeCounts {
+    pub count: Option<usize>,
+    pub motion: Option<(usize, usize)>,
+}
+
+impl Editor {
+    // 操作符的计数和移动命令的计数相乘，如 2d3w 删除 6 个单词
+    pub fn motion_count(&self, count: Option<usize>) -> Option<usize> {
+        match (self.operator.and_then(|pending| pending.count), count) {
+            (Some(a), Some(b)) => Some(a.saturating_mul(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // 执行移动命令，不是移动命令时返回 None，否则返回移动类型和是否移动成功
+    pub fn do_motion(&mut self, key: Key, count: Option<usize>) -> io::Result<Option<(MotionKind, bool)>> {
+        let n = count.unwrap_or(1);
+        let result = match key {
+            Key::Char('h') => (MotionKind::Exclusive, self.repeat_move(n, |c, b| c.move_left(b))?),
+            Key::Char('l') => (MotionKind::Exclusive, self.repeat_move(n, |c, b| c.move_right(b))?),
+            Key::Char('j') => (MotionKind::Linewise, self.repeat_move(n, |c, b| c.move_down(b))?),
+            Key::Char('k') => (MotionKind::Linewise, self.repeat_move(n, |c, b| c.move_up(b))?),
+            Key::Char('w') => (MotionKind::Exclusive, self.repeat_move(n, |c, b| c.move_word_forward(b))?),
+            Key::Char('b') => (MotionKind::Exclusive, self.repeat_move(n, |c, b| c.move_word_backward(b))?),
+            Key::Char('e') => (MotionKind::Inclusive, self.repeat_move(n, |c, b| c.move_word_end(b))?),
+            Key::Char('0') => {
+                self.cursor.move_to_start(&self.buffer)?;
+                (MotionKind::Exclusive, true)
+            }
+            // 3$ 移动到下面第 2 行的行尾
+            Key::Char('$') => {
+                let moved = n == 1 || self.repeat_move(n - 1, |c, b| c.move_down(b))?;
+                self.cursor.move_to_end(&self.buffer)?;
+                // 之后上下移动时保持在行尾
+                self.cursor.curswant = usize::MAX;
+                (MotionKind::Inclusive, moved)
+            }
+            // 5G 跳到第 5 行，没有计数时跳到最后一行
+            Key::Char('G') if self.buffer.large.is_some() => (MotionKind::Linewise, self.large_goto(count)?),
+            Key::Char('G') => {
+                let last = self.buffer.line_count().saturating_sub(1);
+                let row = count.map_or(last, |n| n.saturating_sub(1).min(last));
+                self.jump_to_line(row)?;
+                (MotionKind::Linewise, true)
+            }
+            Key::Char(c @ ('n' | 'N')) => (MotionKind::Exclusive, self.search_next(c == 'N', n)?),
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+
+    // 重复移动 n 次，至少移动一次就算成功
+    fn repeat_move<F>(&mut self, n: usize, mut step: F) -> io::Result<bool>
+    where
+        F: FnMut(&mut crate::cursor::Cursor, &crate::buffer::Buffer) -> io::Result<bool>,
+    {
+        let mut moved = false;
+        for _ in 0..n {
+            if !step(&mut self.cursor, &self.buffer)? {
+                break;
+            }
+            moved = true;
+        }
+        Ok(moved)
+    }
+
+    // 移动完成后，如果有等待的操作符就作用于移动经过的范围
+    pub fn finish_motion(&mut self, start: Position, kind: MotionKind, moved: bool) -> io::Result<()> {
+        let pending = self.operator.take();
+        if !moved {
+            self.aborted = true;
+            self.selected_register = None;
+            return Ok(());
+        }
+
+        if let Some(pending) = pending {
+            let end = self.cursor.position();
+            self.cursor.move_to(&self.buffer, start)?;
+            self.apply_operator(pending.op, start, end, kind)?;
+            // c 在退出插入模式时才结束，y 和 zf 不修改文本，! 在命令行中执行
+            if !matches!(pending.op, 'y' | 'c' | 'z' | '!') {
+                self.finish_change();
+            }
+        }
+        Ok(())
+    }
+
+    // 语法树文本对象：if/af 函数、ic/ac 类、ia/aa 参数，用于操作符之后或可视模式
+    pub fn select_text_object(&mut self, object: char, around: bool) -> io::Result<()> {
+        let range = self.buffer.tree.as_ref().and_then(|tree| {
+            tree.text_object(self.buffer.lines(), self.cursor.position(), object, around)
+        });
+        let (mut start, mut end) = match range {
+            Some(range) => range,
+            None => {
+                self.operator = None;
+                self.selected_register = None;
+                self.aborted = true;
+                return Ok(());
+            }
+        };
+
+        // 大括号在行尾和行首时，只取中间的整行
+        let line_len = |row| self.buffer.get_line(row).map_or(0, |line| line.len());
+        let first_non_blank = |row| self.buffer.get_line(row).map_or(0, |line| indent::indent_len(line));
+        if start.row < end.row && start.col >= line_len(start.row) {
+            start = Position::new(start.row + 1, 0);
+        }
+        if start.row < end.row && end.col <= first_non_blank(end.row) {
+            end = Position::new(end.row - 1, line_len(end.row - 1));
+        }
+        let linewise = start.col <= first_non_blank(start.row) && end.col >= line_len(end.row);
+
+        if let Mode::Visual | Mode::VisualLine = self.mode {
+            self.visual_start = start;
+            if linewise {
+                self.mode = Mode::VisualLine;
+                return self.cursor.move_to(&self.buffer, end);
+            }
+            let line = self.buffer.get_line(end.row).map_or("", |line| line.as_str());
+            let last = line[..end.col].chars().next_back().map_or(0, char::len_utf8);
+            return self.cursor.move_to(&self.buffer, Position::new(end.row, end.col - last));
+        }
+        self.cursor.move_to(&self.buffer, end)?;
+        let kind = if linewise { MotionKind::Linewise } else { MotionKind::Exclusive };
+        self.finish_motion(start, kind, true)
+    }
+
+    // zh、zl 水平滚动 count 列，zH、zL 滚动半屏，zs、ze 让光标在屏幕的最左边、最右边
+    pub fn scroll_horizontal(&mut self, key: char, count: usize) -> io::Result<()> {
+        let (term_width, _) = termion::terminal_size()?;
+        let width = self.buffer.text_width(term_width);
+        let leftcol = self.cursor.leftcol;
+        let vcol = self.buffer.virtual_col(self.cursor.position());
+        let leftcol = match key {
+            'h' => leftcol.saturating_sub(count),
+            'l' => leftcol + count,
+            'H' => leftcol.saturating_sub(width / 2),
+            'L' => leftcol + width / 2,
+            's' => vcol,
+            _ => (vcol + 1).saturating_sub(width),
+        };
+        self.cursor.set_leftcol(&self.buffer, leftcol)
+    }
+
+    // zo、zc、za 打开或关闭光标处的折叠，zR、zM 打开或关闭所有折叠
+    pub fn fold_command(&mut self, key: char) -> io::Result<()> {
+        let row = self.cursor.row;
+        let folds = self.buffer.folds_mut();
+        let found = match key {
+            'o' => folds.open(row),
+            'c' => folds.close(row),
+            'a' => folds.toggle(row),
+            _ => {
+                self.options.foldlevel = folds.set_all(key == 'M');
+                true
+            }
+        };
+        if !found {
+            self.error("E490: No fold found");
+        }
+        // 光标移到关闭的折叠的开始行
+        let row = self.buffer.fold_start(row);
+        self.cursor.move_to(&self.buffer, Position::new(row, self.cursor.col))
+    }
+
+    // zf 和可视模式的 zf：创建关闭的折叠
+    pub fn create_fold(&mut self, start: usize, end: usize) -> io::Result<()> {
+        if let Err(error) = self.buffer.folds_mut().create(start, end) {
+            self.error(error);
+            return Ok(());
+        }
+        let row = self.buffer.fold_start(start);
+        self.cursor.move_to(&self.buffer, Position::new(row, self.cursor.col))
+    }
+
+    // zj 移动到下一个折叠的开始，zk 移动到上一个折叠的结束
+    pub fn move_to_fold(&mut self, down: bool, count: usize) -> io::Result<bool> {
+        let mut moved = false;
+        for _ in 0..count {
+            let folds = self.buffer.folds();
+            let target = if down { folds.next_start(self.cursor.row) } else { folds.prev_end(self.cursor.row) };
+            match target {
+                Some(row) => self.cursor.move_to(&self.buffer, Position::new(row, self.cursor.col))?,
+                None => break,
+            }
+            moved = true;
+        }
+        Ok(moved)
+    }
+
+    // dd、cc、yy：作用于从当前行开始的 count 行，关闭的折叠算作一行
+    pub fn operate_lines(&mut self, op: char, count: usize) -> io::Result<()> {
+        if self.buffer.line_count() == 0 && op != 'c' {
+            self.aborted = true;
+            return Ok(());
+        }
+        let start = self.cursor.position();
+        let last = self.buffer.line_count().saturating_sub(1);
+        let mut row = start.row;
+        for _ in 1..count {
+            if self.buffer.fold_end(row) >= last {
+                break;
+            }
+            row = self.buffer.fold_end(row) + 1;
+        }
+        self.apply_operator(op, start, Position::new(row, 0), MotionKind::Linewise)
+    }
+
+    // 对 start 到 end 之间的文本执行操作符
+    pub fn apply_operator(&mut self, op: char, start: Position, end: Position, kind: MotionKind) -> io::Result<()> {
+        let (mut start, mut end) = (start.min(end), start.max(end));
+        // 按行的操作符作用于整个关闭的折叠
+        if kind == MotionKind::Linewise || matches!(op, '=' | '<' | '>' | 'z' | '!' | 'q') {
+            start.row = self.buffer.fold_start(start.row);
+            end.row = self.buffer.fold_end(end.row);
+        }
+        if op == 'z' {
+            self.selected_register = None;
+            return self.create_fold(start.row, end.row);
+        }
+        // !{motion} 在命令行中输入过滤这些行的命令，gq{motion} 格式化这些行
+        if op == '!' {
+            self.selected_register = None;
+            self.cursor.move_to(&self.buffer, Position::new(start.row, self.cursor.col))?;
+            self.mode = Mode::Command;
+            self.command_line = match end.row - start.row {
+                0 => String::from(".!"),
+                n => format!(".,.+{}!", n),
+            };
+            return self.set_cursor_style();
+        }
+        if op == 'q' {
+            self.selected_register = None;
+            return self.format_lines(start.row, end.row);
+        }
+        // = < > 总是作用于整行
+        if matches!(op, '=' | '<' | '>') {
+            self.selected_register = None;
+            return self.indent_lines(op, start.row, end.row);
+        }
+        let register = self.selected_register.take();
+
+        if kind == MotionKind::Linewise {
+            let text: Vec<String> = (start.row..=end.row)
+                .filter_map(|row| self.buffer.get_line(row).cloned())
+                .collect();
+            let register_content = Register {
+                text: text.join("\n"),
+                linewise: true,
+            };
+
+            if op == 'y' {
+                self.registers.yank(register, register_content);
+                return self.cursor.move_to(&self.buffer, Position::new(start.row, self.cursor.col));
+            }
+
+            self.registers.delete(register, register_content);
+            for _ in start.row..=end.row {
+                self.buffer.remove_line(start.row);
+            }
+            if op == 'c' {
+                self.buffer.insert_line(start.row, String::new());
+                self.cursor.move_to(&self.buffer, Position::new(start.row, 0))?;
+                return self.begin_insert('c', 1);
+            }
+            if self.buffer.line_count() == 0 {
+                self.buffer.insert_line(0, String::new());
+            }
+            self.cursor.move_to(&self.buffer, Position::new(start.row, 0))?;
+            return self.cursor.move_to_first_non_blank(&self.buffer);
+        }
+
+        if kind == MotionKind::Inclusive {
+            let len = self.buffer.get_line(end.row).map_or(0, |line| line.len());
+            end.col = (end.col + 1).min(len);
+        }
+        let register_content = Register {
+            text: self.buffer.text_range(start, end),
+            linewise: false,
+        };
+
+        if op == 'y' {
+            self.registers.yank(register, register_content);
+            return self.cursor.move_to(&self.buffer, start);
+        }
+
+        self.registers.delete(register, register_content);
+        self.buffer.delete_text(start, end);
+        self.cursor.move_to(&self.buffer, start)?;
+        if op == 'c' {
+            return self.begin_insert('c', 1);
+        }
+        Ok(())
+    }
+
+    // p/P：把寄存器的内容放到光标之后/之前，count 表示重复次数
+    pub fn put(&mut self, before: bool, count: usize) -> io::Result<()> {
+        let name = self.selected_register.take().unwrap_or('"');
+        let register = match self.registers.get(name) {
+            Some(register) => register.clone(),
+            None => {
+                self.error(format!("E353: Nothing in register {}", name));
+                return Ok(());
+            }
+        };
+
+        if register.linewise {
+            let row = if before || self.buffer.line_count() == 0 {
+                self.buffer.fold_start(self.cursor.row)
+            } else {
+                self.buffer.fold_end(self.cursor.row) + 1
+            };
+            let lines: Vec<&str> = register.text.split('\n').collect();
+            let mut at = row;
+            for _ in 0..count {
+                for line in &lines {
+                    self.buffer.insert_line(at, line.to_string());
+                    at += 1;
+                }
+            }
+            self.buffer.marks.set('[', Position::new(row, 0));
+            self.buffer.marks.set(']', Position::new(at - 1, 0));
+            self.cursor.move_to(&self.buffer, Position::new(row, 0))?;
+            return self.cursor.move_to_first_non_blank(&self.buffer);
+        }
+
+        let mut position = self.cursor.position();
+        let line_len = self.buffer.get_line(position.row).map_or(0, |line| line.len());
+        if !before && position.col < line_len {
+            position.col += 1;
+        }
+        let text = register.text.repeat(count);
+        let end = self.buffer.insert_text(position, &text);
+        self.buffer.marks.set('[', position);
+        self.buffer.marks.set(']', end);
+
+        // 单行文本光标停在插入文本的最后一个字符上
+        if text.contains('\n') {
+            self.cursor.move_to(&self.buffer, position)
+        } else {
+            self.cursor.move_to(&self.buffer, Position::new(end.row, end.col.saturating_sub(1)))
+        }
+    }
+
+    // 进入插入模式，count 大于 1 时退出插入模式会重复输入的文本
+    pub fn begin_insert(&mut self, kind: char, count: usize) -> io::Result<()> {
+        self.mode = Mode::Insert;
+        self.insert_start = self.cursor.position();
+        self.insert_kind = kind;
+        self.insert_count = count;
+        self.inserted_text.clear();
+        self.set_cursor_style()
+    }
+
+    // 3ix<Esc> 插入 xxx，3ox<Esc> 插入三行 x
+    pub fn repeat_insert(&mut self) -> io::Result<()> {
+        let text = std::mem::take(&mut self.inserted_text);
+        for _ in 1..self.insert_count {
+            let position = match self.insert_kind {
+                'o' | 'O' => {
+                    let row = self.cursor.row + 1;
+                    self.buffer.insert_line(row, String::new());
+                    self.cursor.move_to(&self.buffer, Position::new(row, 0))?;
+                    if !text.is_empty() {
+                        self.indent_new_line(row - 1)?;
+                    }
+                    self.buffer.insert_text(self.cursor.position(), &text)
+                }
+                _ => self.buffer.insert_text(self.cursor.position(), &text),
+            };
+            self.cursor.move_to(&self.buffer, position)?;
+        }
+        self.insert_count = 1;
+        Ok(())
+    }
+
+    // r{char}：把光标处开始的 count 个字符替换为 char，字符不够时不替换
+    pub fn replace_chars(&mut self, c: char, count: usize) -> io::Result<()> {
+        if self.hex {
+            return self.hex_replace(c, count);
+        }
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let line = self.buffer.get_line(row).cloned().unwrap_or_default();
+        let rest = &line[col.min(line.len())..];
+        if rest.chars().count() < count || c == '\n' {
+            self.aborted = true;
+            return Ok(());
+        }
+        let end = col + rest.char_indices().nth(count).map_or(rest.len(), |(i, _)| i);
+        let replaced = format!("{}{}{}", &line[..col], c.to_string().repeat(count), &line[end..]);
+        self.buffer.set_line(row, replaced);
+        // 光标停在最后一个替换的字符上
+        self.cursor.move_to(&self.buffer, Position::new(row, col + c.len_utf8() * (count - 1)))?;
+        self.finish_change();
+        Ok(())
+    }
+
+    // 一个修改命令结束时记下它的按键，供 . 重复
+    pub fn finish_change(&mut self) {
+        self.last_change = self.change_keys.clone();
+        self.last_change_counts = self.change_counts;
+    }
+
+    // .：重复上一次修改，新的计数替换操作符和移动命令的计数，如 d3w 之后 2. 是 d2w
+    pub fn repeat_change(&mut self, count: Option<usize>) -> io::Result<()> {
+        if self.last_change.is_empty() {
+            self.aborted = true;
+            return Ok(());
+        }
+
+        let counts = match count {
+            Some(count) => ChangeCounts { count: Some(count), motion: None },
+            None => self.last_change_counts,
+        };
+        let digits = |count: usize| count.to_string().chars().map(Key::Char).collect::<Vec<_>>();
+        let mut keys = self.last_change.clone();
+        if let Some((at, count)) = counts.motion {
+            keys.splice(at..at, digits(count));
+        }
+        if let Some(count) = counts.count {
+            keys.splice(..0, digits(count));
+        }
+        self.execute_normal(&keys, false)
+    }
+
+    // 当前生效的缩进规则：文件类型的规则优先，其次是 smartindent
+    pub fn indent_rule(&self) -> Option<Box<dyn IndentRule>> {
+        indent::filetype_rule(&self.options.filetype)
+            .or_else(|| self.options.smartindent.then(|| Box::new(SmartIndent) as Box<dyn IndentRule>))
+    }
+
+    // row 上面最近的非空行
+    pub fn prev_nonblank(&self, row: usize) -> Option<&str> {
+        (0..row)
+            .rev()
+            .filter_map(|i| self.buffer.get_line(i))
+            .find(|line| !line.trim().is_empty())
+            .map(|line| line.as_str())
+    }
+
+    // 用规则计算第 row 行应有的缩进
+    pub fn rule_indent(&self, rule: &dyn IndentRule, row: usize) -> usize {
+        let line = self.buffer.get_line(row).map_or("", |line| line.as_str());
+        rule.indent(self.prev_nonblank(row), line, self.options.tabstop, self.options.shift_width())
+    }
+
+    // 把第 row 行的缩进设为 width，光标和插入开始位置跟随文本移动，返回新缩进的长度
+    pub fn set_line_indent(&mut self, row: usize, width: usize) -> io::Result<usize> {
+        let line = match self.buffer.get_line(row) {
+            Some(line) => line.clone(),
+            None => return Ok(0),
+        };
+        let new_line = indent::set_indent(&line, width, self.options.tabstop, self.options.expandtab);
+        let old_len = indent::indent_len(&line);
+        let new_len = indent::indent_len(&new_line);
+        if new_line == line {
+            return Ok(new_len);
+        }
+
+        let shift = |col: usize| if col >= old_len { col - old_len + new_len } else { col.min(new_len) };
+        let col = shift(self.cursor.col);
+        if self.insert_start.row == row {
+            self.insert_start.col = shift(self.insert_start.col);
+        }
+        self.buffer.delete_text(Position::new(row, 0), Position::new(row, old_len));
+        self.buffer.insert_text(Position::new(row, 0), &new_line[..new_len]);
+        if self.cursor.row == row {
+            self.cursor.move_to(&self.buffer, Position::new(row, col))?;
+        }
+        Ok(new_len)
+    }
+
+    // =、<、>：重新缩进或移动 first 到 last 行，空行不移动
+    pub fn indent_lines(&mut self, op: char, first: usize, last: usize) -> io::Result<()> {
+        let last = last.min(self.buffer.line_count().saturating_sub(1));
+        // 没有缩进规则时 = 按 C 风格的括号缩进
+        let rule = self.indent_rule().unwrap_or_else(|| Box::new(BraceIndent));
+        for row in first..=last {
+            let line = self.buffer.get_line(row).map_or("", |line| line.as_str());
+            let width = if line.trim().is_empty() {
+                if op != '=' {
+                    continue;
+                }
+                0
+            } else if op == '=' {
+                self.rule_indent(rule.as_ref(), row)
+            } else {
+                let width = indent::indent_width(line, self.options.tabstop);
+                indent::shift_width(width, self.options.shift_width(), op == '>', self.options.shiftround)
+            };
+            self.set_line_indent(row, width)?;
+        }
+
+        let count = last + 1 - first;
+        if count > 2 {
+            let message = match op {
+                '=' => format!("{} lines indented ", count),
+                _ => format!("{} lines {}ed 1 time", count, op),
+            };
+            self.messages.push(message);
+        }
+        self.cursor.move_to(&self.buffer, Position::new(first, 0))?;
+        self.cursor.move_to_first_non_blank(&self.buffer)
+    }
+}
@@ -0,0 +1,386 @@
+use regex::Regex;
+
+use crate::display::{self, Display, ListChars};
+use crate::encoding;
+use crate::fold::FoldSettings;
+use crate::gutter::Gutter;
+
+// :set 可以修改的编辑器选项
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub timeout: bool,
+    pub timeoutlen: usize,
+    // 插入模式下退格可以删除的内容：indent、eol、start
+    pub backspace: String,
+    pub shiftwidth: usize,
+    pub tabstop: usize,
+    pub expandtab: bool,
+    pub shiftround: bool,
+    pub autoindent: bool,
+    pub smartindent: bool,
+    // 按文件类型选择缩进规则，如 rust、python、yaml
+    pub filetype: String,
+    // 语法高亮使用的语法文件，设置 filetype 时跟着改变
+    pub syntax: String,
+    pub list: bool,
+    pub listchars: String,
+    // 用 CursorLine 高亮光标所在行
+    pub cursorline: bool,
+    // 用 Search 高亮上一次搜索模式的所有匹配
+    pub hlsearch: bool,
+    pub number: bool,
+    pub relativenumber: bool,
+    pub numberwidth: usize,
+    // auto、yes 或 no
+    pub signcolumn: String,
+    pub foldcolumn: usize,
+    pub wrap: bool,
+    pub linebreak: bool,
+    pub breakindent: bool,
+    pub showbreak: String,
+    // nowrap 时水平滚动的最小列数，0 表示把光标放到屏幕中间
+    pub sidescroll: usize,
+    // manual、indent、marker、syntax 或 expr
+    pub foldmethod: String,
+    pub foldlevel: usize,
+    pub foldmarker: String,
+    pub foldexpr: String,
+    pub swapfile: bool,
+    // 输入多少个按键或空闲多少毫秒后写交换文件
+    pub updatecount: usize,
+    pub updatetime: usize,
+    // 写入文件时把撤销树保存到 undodir 中
+    pub undofile: bool,
+    pub undodir: String,
+    // 当前文件的编码和写入时是否加上 BOM
+    pub fileencoding: String,
+    pub bomb: bool,
+    // 读入文件时依次尝试的编码
+    pub fileencodings: String,
+    // 当前文件的换行符：unix、dos 或 mac，和读入文件时允许的换行符
+    pub fileformat: String,
+    pub fileformats: String,
+    // binary 模式按字节编辑文件，endofline 表示最后一行后面是否有换行
+    pub binary: bool,
+    pub endofline: bool,
+    // 超过这么多 MB 的文件按大文件打开：映射到内存、只读入一部分行，0 表示不使用
+    pub largefile: usize,
+    // 文件在编辑器外被修改而 buffer 没有修改时自动重新读入
+    pub autoread: bool,
+    // 写入时保留原来的文件作为备份，writebackup 只在写入期间保留，备份放在 backupdir 的第一个可用目录中
+    pub backup: bool,
+    pub writebackup: bool,
+    pub backupdir: String,
+    // :w !cmd、:r !cmd 用来执行命令的 shell
+    pub shell: String,
+    // gq 使用的外部格式化程序，为空时按 textwidth 排列文本，textwidth 为 0 时宽度是 79
+    pub formatprg: String,
+    pub textwidth: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self {
+            timeout: true,
+            timeoutlen: 1000,
+            backspace: String::from("indent,eol,start"),
+            shiftwidth: 8,
+            tabstop: 8,
+            expandtab: false,
+            shiftround: false,
+            autoindent: false,
+            smartindent: false,
+            filetype: String::new(),
+            syntax: String::new(),
+            list: false,
+            listchars: String::from("eol:$"),
+            cursorline: false,
+            hlsearch: false,
+            number: true,
+            relativenumber: false,
+            numberwidth: 4,
+            signcolumn: String::from("auto"),
+            foldcolumn: 0,
+            wrap: true,
+            linebreak: false,
+            breakindent: false,
+            showbreak: String::new(),
+            sidescroll: 0,
+            foldmethod: String::from("manual"),
+            foldlevel: 0,
+            foldmarker: String::from("{{{,}}}"),
+            foldexpr: String::new(),
+            swapfile: true,
+            updatecount: 200,
+            updatetime: 4000,
+            undofile: false,
+            undodir: String::from("~/.local/state/rustvim/undo"),
+            fileencoding: String::from("utf-8"),
+            bomb: false,
+            fileencodings: String::from("ucs-bom,utf-8,gb18030,sjis,latin1"),
+            fileformat: String::from("unix"),
+            fileformats: String::from("unix,dos,mac"),
+            binary: false,
+            endofline: true,
+            largefile: 100,
+            autoread: false,
+            backup: false,
+            writebackup: true,
+            backupdir: String::from(".,~/.local/state/rustvim/backup"),
+            shell: std::env::var("SHELL").unwrap_or_else(|_| String::from("sh")),
+            formatprg: String::new(),
+            textwidth: 0,
+        }
+    }
+
+    // buffer 显示使用的选项
+    pub fn display(&self) -> Display {
+        Display {
+            tabstop: self.tabstop,
+            list: self.list,
+            listchars: ListChars::parse(&self.listchars).unwrap_or_default(),
+            wrap: self.wrap,
+            linebreak: self.linebreak,
+            breakindent: self.breakindent,
+            showbreak: self.showbreak.clone(),
+            sidescroll: self.sidescroll,
+        }
+    }
+
+    // 文本左边各列使用的选项
+    pub fn gutter(&self) -> Gutter {
+        Gutter {
+            number: self.number,
+            relativenumber: self.relativenumber,
+            numberwidth: self.numberwidth,
+            signcolumn: self.signcolumn.clone(),
+            foldcolumn: self.foldcolumn,
+        }
+    }
+
+    // 折叠使用的选项
+    pub fn folding(&self) -> FoldSettings {
+        FoldSettings {
+            method: self.foldmethod.clone(),
+            level: self.foldlevel,
+            marker: self.foldmarker.clone(),
+            expr: self.foldexpr.clone(),
+            shiftwidth: self.shift_width(),
+            tabstop: self.tabstop,
+        }
+    }
+
+    // backspace 是否包含某一项，兼容 vim 旧的数字写法
+    pub fn backspace_allows(&self, item: &str) -> bool {
+        let items = match self.backspace.as_str() {
+            "0" => "",
+            "1" => "indent,eol",
+            "2" | "3" => "indent,eol,start",
+            items => items,
+        };
+        items.split(',').any(|name| name == item)
+    }
+
+    // shiftwidth 为 0 时使用 tabstop 的值
+    pub fn shift_width(&self) -> usize {
+        if self.shiftwidth == 0 {
+            self.tabstop
+        } else {
+            self.shiftwidth
+        }
+    }
+
+    fn bool_option(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "timeout" | "to" => Some(&mut self.timeout),
+            "expandtab" | "et" => Some(&mut self.expandtab),
+            "shiftround" | "sr" => Some(&mut self.shiftround),
+            "autoindent" | "ai" => Some(&mut self.autoindent),
+            "smartindent" | "si" => Some(&mut self.smartindent),
+            "list" => Some(&mut self.list),
+            "cursorline" | "cul" => Some(&mut self.cursorline),
+            "hlsearch" | "hls" => Some(&mut self.hlsearch),
+            "number" | "nu" => Some(&mut self.number),
+            "relativenumber" | "rnu" => Some(&mut self.relativenumber),
+            "wrap" => Some(&mut self.wrap),
+            "linebreak" | "lbr" => Some(&mut self.linebreak),
+            "breakindent" | "bri" => Some(&mut self.breakindent),
+            "swapfile" | "swf" => Some(&mut self.swapfile),
+            "undofile" | "udf" => Some(&mut self.undofile),
+            "bomb" => Some(&mut self.bomb),
+            "binary" | "bin" => Some(&mut self.binary),
+            "endofline" | "eol" => Some(&mut self.endofline),
+            "autoread" | "ar" => Some(&mut self.autoread),
+            "backup" | "bk" => Some(&mut self.backup),
+            "writebackup" | "wb" => Some(&mut self.writebackup),
+            _ => None,
+        }
+    }
+
+    fn number_option(&mut self, name: &str) -> Option<&mut usize> {
+        match name {
+            "timeoutlen" | "tm" => Some(&mut self.timeoutlen),
+            "shiftwidth" | "sw" => Some(&mut self.shiftwidth),
+            "tabstop" | "ts" => Some(&mut self.tabstop),
+            "numberwidth" | "nuw" => Some(&mut self.numberwidth),
+            "foldcolumn" | "fdc" => Some(&mut self.foldcolumn),
+            "sidescroll" | "ss" => Some(&mut self.sidescroll),
+            "foldlevel" | "fdl" => Some(&mut self.foldlevel),
+            "updatecount" | "uc" => Some(&mut self.updatecount),
+            "updatetime" | "ut" => Some(&mut self.updatetime),
+            "largefile" | "lf" => Some(&mut self.largefile),
+            "textwidth" | "tw" => Some(&mut self.textwidth),
+            _ => None,
+        }
+    }
+
+    fn string_option(&mut self, name: &str) -> Option<&mut String> {
+        match name {
+            "backspace" | "bs" => Some(&mut self.backspace),
+            "filetype" | "ft" => Some(&mut self.filetype),
+            "listchars" | "lcs" => Some(&mut self.listchars),
+            "syntax" | "syn" => Some(&mut self.syntax),
+            "signcolumn" | "scl" => Some(&mut self.signcolumn),
+            "showbreak" | "sbr" => Some(&mut self.showbreak),
+            "foldmethod" | "fdm" => Some(&mut self.foldmethod),
+            "foldmarker" | "fmr" => Some(&mut self.foldmarker),
+            "foldexpr" | "fde" => Some(&mut self.foldexpr),
+            "undodir" | "udir" => Some(&mut self.undodir),
+            "backupdir" | "bdir" => Some(&mut self.backupdir),
+            "shell" | "sh" => Some(&mut self.shell),
+            "formatprg" | "fp" => Some(&mut self.formatprg),
+            "fileencoding" | "fenc" => Some(&mut self.fileencoding),
+            "fileencodings" | "fencs" => Some(&mut self.fileencodings),
+            "fileformat" | "ff" => Some(&mut self.fileformat),
+            "fileformats" | "ffs" => Some(&mut self.fileformats),
+            _ => None,
+        }
+    }
+
+    // 检查字符串选项的值，返回错误信息
+    fn check_string(name: &str, value: &str) -> Result<(), String> {
+        if let "listchars" | "lcs" = name {
+            ListChars::parse(value)?;
+        }
+        if let ("signcolumn" | "scl", false) = (name, matches!(value, "auto" | "yes" | "no")) {
+            return Err(format!("E474: Invalid argument: {}={}", name, value));
+        }
+        if let ("showbreak" | "sbr", true) = (name, value.chars().any(|c| c.is_control() || display::char_width(c) != 1)) {
+            return Err(String::from("E595: 'showbreak' contains unprintable or wide character"));
+        }
+        if let ("foldmethod" | "fdm", false) = (name, matches!(value, "manual" | "indent" | "marker" | "syntax" | "expr")) {
+            return Err(format!("E474: Invalid argument: {}={}", name, value));
+        }
+        if let "foldmarker" | "fmr" = name {
+            match value.split_once(',') {
+                Some((open, close)) if !open.is_empty() && !close.is_empty() && !close.contains(',') => (),
+                _ => return Err(format!("E536: Comma required: {}={}", name, value)),
+            }
+        }
+        if let ("foldexpr" | "fde", Err(_)) = (name, Regex::new(value)) {
+            return Err(format!("E474: Invalid argument: {}={}", name, value));
+        }
+        if let ("undodir" | "udir" | "backupdir" | "bdir" | "shell" | "sh", true) = (name, value.is_empty()) {
+            return Err(format!("E474: Invalid argument: {}={}", name, value));
+        }
+        if let ("fileencoding" | "fenc", None) = (name, encoding::canonical(value)) {
+            return Err(format!("E474: Invalid argument: {}={}", name, value));
+        }
+        if let ("fileencodings" | "fencs", false) = (name, encoding::check_list(value)) {
+            return Err(format!("E474: Invalid argument: {}={}", name, value));
+        }
+        if let ("fileformat" | "ff", false) = (name, matches!(value, "unix" | "dos" | "mac")) {
+            return Err(format!("E474: Invalid argument: {}={}", name, value));
+        }
+        if let ("fileformats" | "ffs", false) = (name, value.split(',').all(|item| matches!(item, "unix" | "dos" | "mac"))) {
+            return Err(format!("E474: Invalid argument: {}={}", name, value));
+        }
+        if let "backspace" | "bs" = name {
+            let valid = matches!(value, "0" | "1" | "2" | "3")
+                || value
+                    .split(',')
+                    .all(|item| matches!(item, "" | "indent" | "eol" | "start" | "nostop"));
+            if !valid {
+                return Err(format!("E474: Invalid argument: {}={}", name, value));
+            }
+        }
+        Ok(())
+    }
+
+    // 处理 :set 的单个参数，返回需要显示的内容（如 :set tm?）
+    pub fn set(&mut self, arg: &str) -> Result<Option<String>, String> {
+        if let Some((name, value)) = arg.split_once('=') {
+            if let Some(number) = self.number_option(name) {
+                let value: usize = value
+                    .parse()
+                    .map_err(|_| format!("E521: Number required after =: {}", arg))?;
+                if value == 0 && matches!(name, "tabstop" | "ts" | "numberwidth" | "nuw") {
+                    return Err(format!("E487: Argument must be positive: {}", arg));
+                }
+                if (value > 20 && matches!(name, "numberwidth" | "nuw")) || (value > 12 && matches!(name, "foldcolumn" | "fdc")) {
+                    return Err(format!("E474: Invalid argument: {}", arg));
+                }
+                *number = value;
+                return Ok(None);
+            }
+            if self.string_option(name).is_some() {
+                Self::check_string(name, value)?;
+                if let Some(string) = self.string_option(name) {
+                    *string = value.to_string();
+                }
+                if let "filetype" | "ft" = name {
+                    self.syntax = value.to_string();
+                }
+                if let Some(fileencoding) = encoding::canonical(&self.fileencoding) {
+                    self.fileencoding = fileencoding.to_string();
+                }
+                return Ok(None);
+            }
+            return Err(format!("E518: Unknown option: {}", name));
+        }
+
+        if let Some(name) = arg.strip_suffix('?') {
+            if let Some(flag) = self.bool_option(name) {
+                let prefix = if *flag { "" } else { "no" };
+                return Ok(Some(format!("  {}{}", prefix, name)));
+            }
+            if let Some(number) = self.number_option(name) {
+                return Ok(Some(format!("  {}={}", name, number)));
+            }
+            if let Some(string) = self.string_option(name) {
+                return Ok(Some(format!("  {}={}", name, string)));
+            }
+            return Err(format!("E518: Unknown option: {}", name));
+        }
+
+        if let Some(flag) = self.bool_option(arg) {
+            *flag = true;
+            return Ok(None);
+        }
+        if let Some(name) = arg.strip_prefix("no") {
+            if let Some(flag) = self.bool_option(name) {
+                *flag = false;
+                return Ok(None);
+            }
+        }
+        if let Some(name) = arg.strip_prefix("inv").or_else(|| arg.strip_suffix('!')) {
+            if let Some(flag) = self.bool_option(name) {
+                *flag = !*flag;
+                return Ok(None);
+            }
+        }
+        if let Some(number) = self.number_option(arg) {
+            return Ok(Some(format!("  {}={}", arg, number)));
+        }
+        if let Some(string) = self.string_option(arg) {
+            return Ok(Some(format!("  {}={}", arg, string)));
+        }
+        Err(format!("E518: Unknown option: {}", arg))
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+// 寄存器内容，linewise 表示按整行保存的文本
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Registers {
+    registers: HashMap<char, Register>,
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Self {
+            registers: HashMap::new(),
+        }
+    }
+
+    // a-z、A-Z、0-9 和 " - : . 等特殊寄存器
+    pub fn is_valid(name: char) -> bool {
+        name.is_ascii_alphanumeric() || matches!(name, '"' | '-' | ':' | '.' | '/' | '_')
+    }
+
+    pub fn get(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name.to_ascii_lowercase())
+    }
+
+    // 大写寄存器名表示追加到对应的小写寄存器
+    pub fn set(&mut self, name: char, register: Register) {
+        if name.is_ascii_uppercase() {
+            let entry = self.registers.entry(name.to_ascii_lowercase()).or_default();
+            if register.linewise && !entry.linewise && !entry.text.is_empty() {
+                entry.text.push('\n');
+            }
+            entry.text.push_str(&register.text);
+            entry.linewise |= register.linewise;
+        } else {
+            self.registers.insert(name, register);
+        }
+    }
+
+    // 复制的文本保存到指定寄存器（或 "0）和无名寄存器
+    pub fn yank(&mut self, name: Option<char>, register: Register) {
+        match name {
+            Some('_') => return,
+            Some(name) if name != '"' => self.set(name, register.clone()),
+            _ => self.set('0', register.clone()),
+        }
+        self.registers.insert('"', register);
+    }
+
+    // 删除的文本：整行或多行的删除进入 "1 并依次后移，小删除进入 "-
+    pub fn delete(&mut self, name: Option<char>, register: Register) {
+        match name {
+            Some('_') => return,
+            Some(name) if name != '"' => self.set(name, register.clone()),
+            _ if register.linewise || register.text.contains('\n') => {
+                for i in (1..9).rev() {
+                    let from = char::from_digit(i, 10).unwrap_or('1');
+                    let to = char::from_digit(i + 1, 10).unwrap_or('9');
+                    if let Some(previous) = self.registers.remove(&from) {
+                        self.registers.insert(to, previous);
+                    }
+                }
+                self.set('1', register.clone());
+            }
+            _ => self.set('-', register.clone()),
+        }
+        self.registers.insert('"', register);
+    }
+}
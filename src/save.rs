@@ -0,0 +1,154 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+// 写入文件时的备份选项，force 是 :w! 的 !
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveOptions<'a> {
+    pub backup: bool,
+    pub writebackup: bool,
+    pub backupdir: &'a str,
+    pub force: bool,
+}
+
+fn error(message: String) -> io::Error {
+    io::Error::other(message)
+}
+
+// backupdir 中一项对应的备份文件：. 是文件所在的目录，以 // 结尾时用完整路径作为文件名以免重名
+pub fn backup_path(dir: &str, file: &Path) -> Option<PathBuf> {
+    let name = file.file_name()?.to_string_lossy().into_owned();
+    if dir == "." {
+        return Some(file.with_file_name(format!("{}~", name)));
+    }
+    let expanded = match dir.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+        None => PathBuf::from(dir),
+    };
+    if dir.ends_with("//") {
+        let full = fs::canonicalize(file).ok()?;
+        return Some(expanded.join(format!("{}~", full.to_string_lossy().replace('/', "%"))));
+    }
+    Some(expanded.join(format!("{}~", name)))
+}
+
+// 按 backupdir 的顺序使用第一个可以创建备份的目录，返回备份文件
+fn make_backup(backupdir: &str, target: &Path, link: bool) -> Option<PathBuf> {
+    for dir in backupdir.split(',').filter(|dir| !dir.is_empty()) {
+        let Some(backup) = backup_path(dir, target) else {
+            continue;
+        };
+        if dir != "." && backup.parent().is_some_and(|parent| fs::create_dir_all(parent).is_err()) {
+            continue;
+        }
+        let _ = fs::remove_file(&backup);
+        // 改名写入时原来的文件不会被修改，可以用硬链接作为备份
+        if (link && fs::hard_link(target, &backup).is_ok()) || fs::copy(target, &backup).is_ok() {
+            return Some(backup);
+        }
+    }
+    None
+}
+
+// 和要写入的文件在同一个目录中的临时文件，改名时不会跨文件系统
+fn create_temp(target: &Path, mode: u32) -> io::Result<(PathBuf, File)> {
+    let name = target.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut last = io::Error::from(io::ErrorKind::AlreadyExists);
+    for i in 0..100 {
+        let temp = target.with_file_name(format!(".{}.{}.{}.tmp", name, std::process::id(), i));
+        match OpenOptions::new().write(true).create_new(true).mode(mode).open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => last = error,
+            Err(error) => return Err(error),
+        }
+    }
+    Err(last)
+}
+
+// 安全地写入文件：先写到同一目录中的临时文件并 fsync，再改名覆盖原来的文件，写入失败时原来的文件不变。
+// 符号链接写入链接指向的文件；保留权限和所有者，不能保留所有者或文件有多个硬链接时
+// 把临时文件的内容复制到原来的文件中。contents 写入文件的内容
+pub fn save(path: &Path, options: &SaveOptions, contents: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let metadata = match fs::metadata(&target) {
+        Ok(metadata) if metadata.is_dir() => return Err(error(format!("E502: \"{}\" is a directory", path.display()))),
+        Ok(metadata) => Some(metadata),
+        Err(_) => None,
+    };
+    if let (Some(metadata), false) = (&metadata, options.force) {
+        let writable = OpenOptions::new().write(true).open(&target).is_ok();
+        if metadata.permissions().readonly() || !writable {
+            return Err(error(format!("E505: \"{}\" is read-only (add ! to override)", path.display())));
+        }
+    }
+    let dir = target.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if !dir.is_dir() {
+        return Err(error(format!(
+            "E212: Can't open file for writing: directory \"{}\" does not exist",
+            dir.display()
+        )));
+    }
+
+    let mode = metadata.as_ref().map_or(0o666, |metadata| metadata.mode() & 0o7777);
+    let (temp, file) = create_temp(&target, mode).map_err(|cause| {
+        error(format!(
+            "E212: Can't open file for writing: directory \"{}\" is not writable ({})",
+            dir.display(),
+            cause
+        ))
+    })?;
+    let written = (|| -> io::Result<File> {
+        let mut writer = BufWriter::new(file);
+        contents(&mut writer)?;
+        let file = writer.into_inner().map_err(|error| error.into_error())?;
+        file.sync_all()?;
+        Ok(file)
+    })();
+    let file = match written {
+        Ok(file) => file,
+        Err(cause) => {
+            let _ = fs::remove_file(&temp);
+            return Err(error(format!("E514: Write error (file system full?): {}", cause)));
+        }
+    };
+
+    // 所有者不同（root 编辑其他用户的文件）时尝试改成原来的所有者。创建时的权限经过了 umask，
+    // chown 还会清除 setuid、setgid，所以之后再设置原来的权限
+    let in_place = match &metadata {
+        Some(metadata) => {
+            let owned = std::os::unix::fs::fchown(&file, Some(metadata.uid()), Some(metadata.gid())).is_ok();
+            let restored = file.set_permissions(fs::Permissions::from_mode(mode)).is_ok();
+            metadata.nlink() > 1 || !owned || !restored
+        }
+        None => false,
+    };
+    drop(file);
+    let backup = match &metadata {
+        Some(_) if options.backup || options.writebackup => match make_backup(options.backupdir, &target, !in_place) {
+            Some(backup) => Some(backup),
+            None if options.force => None,
+            None => {
+                let _ = fs::remove_file(&temp);
+                return Err(error(String::from("E510: Can't make backup file (add ! to override)")));
+            }
+        },
+        _ => None,
+    };
+    let replaced = if in_place {
+        // 复制到原来的文件中以保留 inode、所有者和硬链接
+        fs::copy(&temp, &target).and_then(|_| File::open(&target)?.sync_all())
+    } else {
+        // 目录也要 fsync，改名才会写到磁盘上
+        fs::rename(&temp, &target).and_then(|_| File::open(dir)?.sync_all())
+    };
+    let _ = fs::remove_file(&temp);
+    if let Err(cause) = replaced {
+        let kept = backup.as_ref().map_or(String::new(), |backup| format!(", original kept in \"{}\"", backup.display()));
+        return Err(error(format!("E212: Can't open file for writing: {}{}", cause, kept)));
+    }
+    if let (Some(backup), false) = (backup, options.backup) {
+        let _ = fs::remove_file(backup);
+    }
+    Ok(())
+}
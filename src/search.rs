@@ -0,0 +1,145 @@
+use std::io;
+
+use regex::Regex;
+
+use crate::buffer::Buffer;
+use crate::editor::{Editor, Mode};
+use crate::mark::Position;
+use crate::operator::MotionKind;
+use crate::register::Register;
+
+// 在一行中查找：向前找 col 之后开始的第一个匹配，向后找 col 之前开始的最后一个匹配
+fn find_in_line(regex: &Regex, line: &str, col: Option<usize>, forward: bool) -> Option<usize> {
+    let mut starts = regex.find_iter(line).map(|found| found.start());
+    match (col, forward) {
+        (None, true) => starts.next(),
+        (None, false) => starts.last(),
+        (Some(col), true) => starts.find(|&start| start > col),
+        (Some(col), false) => starts.take_while(|&start| start < col).last(),
+    }
+}
+
+// 从 from 开始查找，到达文件的一端时从另一端继续，返回匹配的位置和是否绕回
+pub fn find(buffer: &Buffer, regex: &Regex, from: Position, forward: bool) -> Option<(Position, bool)> {
+    let count = buffer.line_count();
+    if count == 0 {
+        return None;
+    }
+    let line = |row: usize| buffer.get_line(row).map_or("", String::as_str);
+    if let Some(col) = find_in_line(regex, line(from.row), Some(from.col), forward) {
+        return Some((Position::new(from.row, col), false));
+    }
+    for step in 1..=count {
+        let (row, wrapped) = if forward {
+            ((from.row + step) % count, from.row + step >= count)
+        } else {
+            ((from.row + count - step) % count, step > from.row)
+        };
+        // 最后回到开始的行时查找光标另一侧的匹配
+        if let Some(col) = find_in_line(regex, line(row), None, forward) {
+            return Some((Position::new(row, col), wrapped));
+        }
+    }
+    None
+}
+
+// 一行中所有非空匹配的字节范围 [start, end)
+pub fn line_matches(regex: &Regex, line: &str) -> Vec<(usize, usize)> {
+    regex
+        .find_iter(line)
+        .filter(|found| !found.is_empty())
+        .map(|found| (found.start(), found.end()))
+        .collect()
+}
+
+impl Editor {
+    // hlsearch 时要高亮的上一次搜索模式，没有搜索过或模式无效时为 None
+    pub fn search_highlight(&self) -> Option<Regex> {
+        if !self.options.hlsearch {
+            return None;
+        }
+        let pattern = &self.registers.get('/')?.text;
+        let mut cache = self.search_regex.borrow_mut();
+        if cache.as_ref().is_none_or(|(cached, _)| cached != pattern) {
+            *cache = Some((pattern.clone(), Regex::new(pattern).ok()));
+        }
+        cache.as_ref().and_then(|(_, regex)| regex.clone())
+    }
+
+    // / 和 ? 在命令行中输入搜索的模式
+    pub fn start_search(&mut self, command_type: char) -> io::Result<()> {
+        self.mode = Mode::Command;
+        self.command_type = command_type;
+        self.command_line.clear();
+        self.set_cursor_style()
+    }
+
+    // 在命令行输入模式之后按回车：空的模式使用上一次搜索的模式
+    pub fn search_command(&mut self, pattern: &str, forward: bool) -> io::Result<()> {
+        if !pattern.is_empty() {
+            self.registers.set('/', Register {
+                text: pattern.to_string(),
+                linewise: false,
+            });
+        }
+        self.search_forward = forward;
+        let start = self.cursor.position();
+        let count = self.count.take();
+        let count = self.motion_count(count).unwrap_or(1);
+        let moved = self.search_next(false, count)?;
+        self.finish_motion(start, MotionKind::Exclusive, moved)
+    }
+
+    // n 和 N：按上一次搜索的方向或相反的方向查找第 count 个匹配
+    pub fn search_next(&mut self, reverse: bool, count: usize) -> io::Result<bool> {
+        let pattern = match self.registers.get('/') {
+            Some(register) => register.text.clone(),
+            None => {
+                self.error("E35: No previous regular expression");
+                return Ok(false);
+            }
+        };
+        let regex = match Regex::new(&pattern) {
+            Ok(regex) => regex,
+            Err(_) => {
+                self.error(format!("E383: Invalid search string: {}", pattern));
+                return Ok(false);
+            }
+        };
+        let forward = self.search_forward != reverse;
+        let mut position = self.cursor.position();
+        let mut wrapped = false;
+        for _ in 0..count {
+            match self.find_match(&regex, position, forward)? {
+                Some((found, wrap)) => {
+                    position = found;
+                    wrapped |= wrap;
+                }
+                // 大文件中的匹配在没有读入的部分、而修改还没有写入时已经报告了错误
+                None if self.aborted => return Ok(false),
+                None => {
+                    self.error(format!("E486: Pattern not found: {}", pattern));
+                    return Ok(false);
+                }
+            }
+        }
+        if wrapped {
+            self.messages.push(String::from(if forward {
+                "search hit BOTTOM, continuing at TOP"
+            } else {
+                "search hit TOP, continuing at BOTTOM"
+            }));
+        }
+        self.push_jump();
+        self.cursor.move_to(&self.buffer, position)?;
+        Ok(true)
+    }
+
+    // 在 buffer 中查找，大文件还要查找没有读入的部分
+    fn find_match(&mut self, regex: &Regex, from: Position, forward: bool) -> io::Result<Option<(Position, bool)>> {
+        if self.buffer.large.is_some() {
+            return self.find_in_large_file(regex, from, forward);
+        }
+        Ok(find(&self.buffer, regex, from, forward))
+    }
+}
@@ -0,0 +1,284 @@
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+use termion::event::Key;
+
+use crate::command::LineRange;
+use crate::editor::Editor;
+use crate::file::{self, FileOptions};
+use crate::mark::Position;
+
+// 用 shell 执行的命令，收集标准输出和标准错误
+pub fn shell_command(shell: &str, command: &str) -> Command {
+    let mut process = Command::new(shell);
+    process.arg("-c").arg(command).stdout(Stdio::piped()).stderr(Stdio::piped());
+    process
+}
+
+// 执行命令并等待结束，input 在另一个线程中写入命令的标准输入，以免输出很多时互相等待
+pub fn run(command: &mut Command, input: impl FnOnce(&mut dyn Write) -> io::Result<()> + Send) -> io::Result<Output> {
+    let mut child = command.stdin(Stdio::piped()).spawn()?;
+    let stdin = child.stdin.take();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            if let Some(stdin) = stdin {
+                let mut writer = BufWriter::new(stdin);
+                // 命令没有读完输入就结束时写入会失败，不算错误
+                let _ = input(&mut writer).and_then(|_| writer.flush());
+            }
+        });
+        child.wait_with_output()
+    })
+}
+
+// 命令中的 % 换成当前文件名，\% 是 % 本身
+pub fn expand(command: &str, file: Option<&Path>) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'%') => {
+                chars.next();
+                expanded.push('%');
+            }
+            '%' => match file {
+                Some(file) => expanded.push_str(&file.to_string_lossy()),
+                None => return Err(String::from("E499: Empty file name for '%' or '#', only works with \":p:h\"")),
+            },
+            _ => expanded.push(c),
+        }
+    }
+    Ok(expanded)
+}
+
+// 命令的输出按行拆分，去掉 CR
+pub fn output_lines(output: &[u8]) -> Vec<String> {
+    file::split_lines_as(&String::from_utf8_lossy(output), "dos")
+}
+
+// 命令失败时显示的退出状态
+pub fn exit_message(output: &Output) -> Option<String> {
+    if output.status.success() {
+        return None;
+    }
+    Some(match output.status.code() {
+        Some(code) => format!("shell returned {}", code),
+        None => String::from("shell killed by signal"),
+    })
+}
+
+// :SudoWrite 等待输入密码，密码不显示
+#[derive(Debug, Clone, PartialEq)]
+pub struct SudoPrompt {
+    pub path: PathBuf,
+    pub password: String,
+}
+
+impl SudoPrompt {
+    pub fn message() -> String {
+        format!("[sudo] password for {}: ", std::env::var("USER").unwrap_or_default())
+    }
+}
+
+impl Editor {
+    pub fn expand_command(&mut self, command: &str) -> Option<String> {
+        match expand(command.trim(), self.buffer.path.as_deref()) {
+            Ok(command) if command.is_empty() => {
+                self.error("E471: Argument required");
+                None
+            }
+            Ok(command) => Some(command),
+            Err(error) => {
+                self.error(error);
+                None
+            }
+        }
+    }
+
+    // :[range]w !cmd：把这些行作为命令的输入，显示命令的输出
+    pub fn write_to_command(&mut self, range: Option<LineRange>, options: &FileOptions, command: &str) -> io::Result<()> {
+        let Some(command) = self.expand_command(command) else {
+            return Ok(());
+        };
+        let format = self.write_format(options);
+        let contents = match self.encode_buffer(range, &format) {
+            Ok(contents) => contents,
+            Err(error) => {
+                self.error(error.to_string());
+                return Ok(());
+            }
+        };
+        let large = self.buffer.large.as_ref().filter(|_| range.is_none());
+        let output = run(&mut shell_command(&self.options.shell, &command), |writer| match large {
+            Some(large) => large.write_around(writer, &contents),
+            None => writer.write_all(&contents),
+        });
+        match output {
+            Ok(output) => {
+                self.messages.extend(output_lines(&output.stdout));
+                self.messages.extend(output_lines(&output.stderr));
+                self.messages.extend(exit_message(&output));
+            }
+            Err(_) => self.error(format!("Cannot execute shell {}", self.options.shell)),
+        }
+        Ok(())
+    }
+
+    // :[line]r[ead] [++opt] [file] 和 :r !cmd：把文件的内容或命令的输出插入到这一行下面，:0r 插入到第一行上面
+    pub fn read_command(&mut self, range: Option<LineRange>, bang: bool, args: &str, before_first: bool) -> io::Result<()> {
+        let row = match range {
+            _ if before_first => 0,
+            Some((_, end)) => end + 1,
+            None => self.cursor.row + 1,
+        };
+        let row = row.min(self.buffer.line_count());
+        let command = if bang { Some(args) } else { args.strip_prefix('!') };
+        let lines = match command {
+            Some(command) => {
+                let Some(command) = self.expand_command(command) else {
+                    return Ok(());
+                };
+                // 和 vim 的 shellredir 一样同时读入标准错误
+                let redirected = format!("({}) 2>&1", command);
+                match run(&mut shell_command(&self.options.shell, &redirected), |_| Ok(())) {
+                    Ok(output) => {
+                        self.messages.extend(exit_message(&output));
+                        output_lines(&output.stdout)
+                    }
+                    Err(_) => {
+                        self.error(format!("Cannot execute shell {}", self.options.shell));
+                        return Ok(());
+                    }
+                }
+            }
+            None => match self.read_lines_from(args) {
+                Some(lines) => lines,
+                None => return Ok(()),
+            },
+        };
+        if lines.is_empty() {
+            return Ok(());
+        }
+        for (i, line) in lines.into_iter().enumerate() {
+            self.buffer.insert_line(row + i, line);
+        }
+        // 光标移到第一个读入的行
+        self.cursor.move_to(&self.buffer, Position::new(row, 0))?;
+        self.cursor.move_to_first_non_blank(&self.buffer)
+    }
+
+    // :r 读入的文件按 ++ 参数或者检测到的编码和换行符转换为行
+    fn read_lines_from(&mut self, args: &str) -> Option<Vec<String>> {
+        let (options, name) = match file::parse_file_args(args) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                self.error(error);
+                return None;
+            }
+        };
+        let path = match (name.trim(), &self.buffer.path) {
+            ("", Some(path)) => path.clone(),
+            ("", None) => {
+                self.error("E32: No file name");
+                return None;
+            }
+            (name, _) => PathBuf::from(name),
+        };
+        let binary = options.binary.unwrap_or(self.options.binary);
+        let Ok((decoded, bytes)) = file::read_file(&path, &self.options.fileencodings, options.encoding, binary) else {
+            self.error(format!("E484: Can't open file {}", path.display()));
+            return None;
+        };
+        let fileformat = match options.fileformat {
+            _ if binary => "unix",
+            Some(fileformat) => fileformat,
+            None => file::detect_format(&decoded.text, &self.options.fileformats).0,
+        };
+        let lines = file::split_lines_as(&decoded.text, fileformat);
+        self.messages.push(format!("\"{}\" {}L, {}B", path.display(), lines.len(), bytes));
+        Some(lines)
+    }
+
+    // :SudoWrite [file]：通过 sudo tee 写入没有权限的文件，sudo 需要密码时在命令行中输入
+    pub fn sudo_write_command(&mut self, args: &str) -> io::Result<()> {
+        let path = match (args.trim(), &self.buffer.path) {
+            ("", Some(path)) => path.clone(),
+            ("", None) => {
+                self.error("E32: No file name");
+                return Ok(());
+            }
+            (name, _) => PathBuf::from(name),
+        };
+        let cached = Command::new("sudo")
+            .args(["-n", "true"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if cached {
+            return self.sudo_write(&path, None);
+        }
+        self.messages.push(SudoPrompt::message());
+        self.sudo_prompt = Some(SudoPrompt { path, password: String::new() });
+        Ok(())
+    }
+
+    // 输入密码，回车时写入，Esc 取消
+    pub fn answer_sudo_prompt(&mut self, key: Key) -> io::Result<()> {
+        let Some(mut prompt) = self.sudo_prompt.take() else {
+            return Ok(());
+        };
+        match key {
+            Key::Char('\n') => return self.sudo_write(&prompt.path, Some(&prompt.password)),
+            Key::Esc | Key::Ctrl('c') => return Ok(()),
+            Key::Backspace => {
+                prompt.password.pop();
+            }
+            Key::Char(c) => prompt.password.push(c),
+            _ => (),
+        }
+        self.messages.push(SudoPrompt::message());
+        self.sudo_prompt = Some(prompt);
+        Ok(())
+    }
+
+    fn sudo_write(&mut self, path: &Path, password: Option<&str>) -> io::Result<()> {
+        let format = self.write_format(&FileOptions::default());
+        let contents = match self.encode_buffer(None, &format) {
+            Ok(contents) => contents,
+            Err(error) => {
+                self.error(error.to_string());
+                return Ok(());
+            }
+        };
+        let large = self.buffer.large.as_ref();
+        let mut command = Command::new("sudo");
+        command.args(["-S", "-p", "", "tee", "--"]).arg(path).stdout(Stdio::null()).stderr(Stdio::piped());
+        let output = run(&mut command, |writer| {
+            if let Some(password) = password {
+                writeln!(writer, "{}", password)?;
+            }
+            match large {
+                Some(large) => large.write_around(writer, &contents),
+                None => writer.write_all(&contents),
+            }
+        });
+        let bytes = large.map_or(contents.len(), |large| large.written_len(contents.len()));
+        match output {
+            Ok(output) if output.status.success() => {
+                self.messages.push(format!("\"{}\" {}L, {}B written", path.display(), self.buffer.line_count(), bytes));
+                self.finish_write(path)?;
+            }
+            Ok(output) => {
+                let reason = output_lines(&output.stderr).into_iter().rfind(|line| !line.trim().is_empty());
+                let reason = reason.map_or(String::new(), |reason| format!(": {}", reason));
+                self.error(format!("E212: Can't open file for writing: {}{}", path.display(), reason));
+            }
+            Err(error) => self.error(format!("E212: Can't open file for writing: sudo: {}", error)),
+        }
+        Ok(())
+    }
+}
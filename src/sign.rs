@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use crate::display;
+use crate::mark::{LineEdit, Position};
+
+// :sign define 定义的标记
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignDefinition {
+    pub text: String,
+    pub texthl: Option<String>,
+}
+
+// :sign place 放在某一行上的标记
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedSign {
+    pub id: usize,
+    pub row: usize,
+    pub name: String,
+}
+
+// 标记列中显示的诊断、版本控制等标记
+#[derive(Debug, Default)]
+pub struct Signs {
+    definitions: BTreeMap<String, SignDefinition>,
+    placed: Vec<PlacedSign>,
+}
+
+// 解析 key=value 形式的参数
+fn arguments(args: &[&str]) -> Result<BTreeMap<String, String>, String> {
+    args.iter()
+        .map(|arg| {
+            arg.split_once('=')
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .ok_or_else(|| format!("E474: Invalid argument: {}", arg))
+        })
+        .collect()
+}
+
+impl Signs {
+    pub fn new() -> Self {
+        Self {
+            definitions: BTreeMap::new(),
+            placed: Vec::new(),
+        }
+    }
+
+    pub fn placed(&self) -> &[PlacedSign] {
+        &self.placed
+    }
+
+    pub fn definition(&self, name: &str) -> Option<&SignDefinition> {
+        self.definitions.get(name)
+    }
+
+    // 一行上显示的标记，有多个时显示最后放置的
+    pub fn at(&self, row: usize) -> Option<&SignDefinition> {
+        self.placed
+            .iter()
+            .rev()
+            .find(|sign| sign.row == row)
+            .and_then(|sign| self.definitions.get(&sign.name))
+    }
+
+    // 标记跟随文本移动，所在行被删除时移到删除位置的下一行
+    pub fn adjust(&mut self, edit: LineEdit) {
+        for sign in &mut self.placed {
+            let mut position = Position::new(sign.row, 0);
+            position.adjust(edit);
+            sign.row = position.row;
+        }
+    }
+
+    // 处理 :sign 命令，返回要显示的行
+    pub fn command(&mut self, args: &str, line_count: usize) -> Result<Vec<String>, String> {
+        let words: Vec<&str> = args.split_whitespace().collect();
+        match words.as_slice() {
+            ["define", name, rest @ ..] => {
+                let arguments = arguments(rest)?;
+                let mut definition = self.definitions.get(*name).cloned().unwrap_or(SignDefinition {
+                    text: String::new(),
+                    texthl: None,
+                });
+                for (key, value) in arguments {
+                    match key.as_str() {
+                        "text" => {
+                            let width: usize = value.chars().map(display::char_width).sum();
+                            if !(1..=2).contains(&width) || value.chars().any(char::is_control) {
+                                return Err(format!("E239: Invalid sign text: {}", value));
+                            }
+                            definition.text = value;
+                        }
+                        "texthl" => definition.texthl = Some(value),
+                        "linehl" | "numhl" | "icon" | "culhl" => (),
+                        _ => return Err(format!("E474: Invalid argument: {}", key)),
+                    }
+                }
+                self.definitions.insert(name.to_string(), definition);
+                Ok(Vec::new())
+            }
+            ["undefine", name] => match self.definitions.remove(*name) {
+                Some(_) => Ok(Vec::new()),
+                None => Err(format!("E155: Unknown sign: {}", name)),
+            },
+            ["list"] => Ok(self.definitions.iter().map(|(name, definition)| show_definition(name, definition)).collect()),
+            ["list", name] => match self.definitions.get(*name) {
+                Some(definition) => Ok(vec![show_definition(name, definition)]),
+                None => Err(format!("E155: Unknown sign: {}", name)),
+            },
+            ["place"] => {
+                let mut lines = vec![String::from("--- Signs ---")];
+                lines.extend(self.placed.iter().map(|sign| {
+                    format!("    line={}  id={}  name={}", sign.row + 1, sign.id, sign.name)
+                }));
+                Ok(lines)
+            }
+            ["place", id, rest @ ..] => {
+                let id: usize = id.parse().map_err(|_| format!("E474: Invalid argument: {}", id))?;
+                let arguments = arguments(rest)?;
+                let name = arguments.get("name").ok_or_else(|| String::from("E156: Missing sign name"))?;
+                if !self.definitions.contains_key(name) {
+                    return Err(format!("E155: Unknown sign: {}", name));
+                }
+                let row = match arguments.get("line").map(|line| line.parse::<usize>()) {
+                    Some(Ok(line)) if line >= 1 => (line - 1).min(line_count.saturating_sub(1)),
+                    Some(_) => return Err(format!("E474: Invalid argument: {}", args)),
+                    // 已经放置的标记可以只改变名字
+                    None => match self.placed.iter().find(|sign| sign.id == id) {
+                        Some(sign) => sign.row,
+                        None => return Err(format!("E885: Not possible to change sign {}", name)),
+                    },
+                };
+                self.placed.retain(|sign| sign.id != id);
+                self.placed.push(PlacedSign { id, row, name: name.clone() });
+                Ok(Vec::new())
+            }
+            ["unplace", "*"] => {
+                self.placed.clear();
+                Ok(Vec::new())
+            }
+            ["unplace", id, ..] => {
+                let id: usize = id.parse().map_err(|_| format!("E474: Invalid argument: {}", id))?;
+                let before = self.placed.len();
+                self.placed.retain(|sign| sign.id != id);
+                if self.placed.len() == before {
+                    return Err(format!("E158: Sign {} not placed", id));
+                }
+                Ok(Vec::new())
+            }
+            ["define" | "undefine" | "unplace"] => Err(String::from("E156: Missing sign name")),
+            _ => Err(format!("E160: Unknown sign command: {}", args.trim())),
+        }
+    }
+}
+
+fn show_definition(name: &str, definition: &SignDefinition) -> String {
+    let mut line = format!("sign {} text={}", name, definition.text);
+    if let Some(texthl) = &definition.texthl {
+        line.push_str(&format!(" texthl={}", texthl));
+    }
+    line
+}
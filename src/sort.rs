@@ -0,0 +1,268 @@
+use std::cmp::Ordering;
+use std::io;
+
+use regex::Regex;
+
+use crate::command::LineRange;
+use crate::editor::Editor;
+use crate::mark::Position;
+use crate::register::Register;
+
+// :sort 按数字排序时使用的数字：n 十进制、x 十六进制、f 浮点数
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SortNumber {
+    Decimal,
+    Hex,
+    Float,
+}
+
+// :sort 的参数，pattern 为空表示使用上一次搜索的模式
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortOptions {
+    pub reverse: bool,
+    pub ignore_case: bool,
+    pub number: Option<SortNumber>,
+    pub unique: bool,
+    // r：按匹配的部分排序，否则跳过匹配的部分，按后面的文本排序
+    pub use_match: bool,
+    pub pattern: Option<String>,
+}
+
+// 排序使用的键，没有数字的行排在有数字的行前面
+#[derive(Debug, Clone, PartialEq)]
+enum SortKey {
+    Text(String),
+    Integer(Option<i128>),
+    Float(Option<f64>),
+}
+
+impl SortKey {
+    fn compare(&self, other: &SortKey) -> Ordering {
+        match (self, other) {
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+            (SortKey::Integer(a), SortKey::Integer(b)) => a.cmp(b),
+            (SortKey::Float(a), SortKey::Float(b)) => match (a, b) {
+                (Some(a), Some(b)) => a.total_cmp(b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            },
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+impl SortOptions {
+    // 解析 :sort 后面的参数，如 "n u /^\w+ /"，" 之后是注释
+    pub fn parse(args: &str) -> Result<Self, String> {
+        let mut options = SortOptions::default();
+        let invalid = || format!("E474: Invalid argument: {}", args.trim());
+        let mut chars = args.chars();
+        while let Some(c) = chars.next() {
+            let number = match c {
+                c if c.is_whitespace() => continue,
+                '"' => break,
+                'i' => {
+                    options.ignore_case = true;
+                    continue;
+                }
+                'u' => {
+                    options.unique = true;
+                    continue;
+                }
+                'r' => {
+                    options.use_match = true;
+                    continue;
+                }
+                'n' => SortNumber::Decimal,
+                'x' => SortNumber::Hex,
+                'f' => SortNumber::Float,
+                // 其他标点字符是模式的分隔符，模式中的 \/ 是分隔符本身
+                c if c.is_ascii_punctuation() && c != '\\' => {
+                    let mut pattern = String::new();
+                    let mut closed = false;
+                    while let Some(next) = chars.next() {
+                        match next {
+                            '\\' => match chars.next() {
+                                Some(escaped) if escaped == c => pattern.push(c),
+                                Some(escaped) => {
+                                    pattern.push('\\');
+                                    pattern.push(escaped);
+                                }
+                                None => pattern.push('\\'),
+                            },
+                            next if next == c => {
+                                closed = true;
+                                break;
+                            }
+                            next => pattern.push(next),
+                        }
+                    }
+                    if !closed {
+                        return Err(invalid());
+                    }
+                    options.pattern = Some(pattern);
+                    continue;
+                }
+                _ => return Err(invalid()),
+            };
+            // n、x、f 只能使用一个
+            if options.number.is_some_and(|current| current != number) {
+                return Err(invalid());
+            }
+            options.number = Some(number);
+        }
+        Ok(options)
+    }
+}
+
+// 行中参与排序的部分：有模式时是匹配的部分或匹配之后的部分，不匹配的行用空文本
+fn sort_text<'a>(line: &'a str, regex: Option<&Regex>, use_match: bool) -> &'a str {
+    let Some(regex) = regex else {
+        return line;
+    };
+    match regex.find(line) {
+        Some(found) if use_match => found.as_str(),
+        Some(found) => &line[found.end()..],
+        None => "",
+    }
+}
+
+fn sort_key(text: &str, options: &SortOptions) -> SortKey {
+    // 数字前面的 - 表示负数
+    let signed = |text: &str, start: usize, value: i128| {
+        if text[..start].ends_with('-') {
+            -value
+        } else {
+            value
+        }
+    };
+    match options.number {
+        None if options.ignore_case => SortKey::Text(text.to_lowercase()),
+        None => SortKey::Text(text.to_string()),
+        Some(SortNumber::Decimal) => SortKey::Integer(text.find(|c: char| c.is_ascii_digit()).map(|start| {
+            let digits = &text[start..];
+            let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+            signed(text, start, digits[..end].parse().unwrap_or(i128::MAX))
+        })),
+        Some(SortNumber::Hex) => SortKey::Integer(text.find(|c: char| c.is_ascii_hexdigit()).map(|start| {
+            let mut digits = &text[start..];
+            // 跳过 0x 前缀
+            if let Some(rest) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+                if rest.starts_with(|c: char| c.is_ascii_hexdigit()) {
+                    digits = rest;
+                }
+            }
+            let end = digits.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(digits.len());
+            signed(text, start, i128::from_str_radix(&digits[..end], 16).unwrap_or(i128::MAX))
+        })),
+        Some(SortNumber::Float) => SortKey::Float(first_float(text)),
+    }
+}
+
+// 文本中的第一个浮点数，如 1.5、-.5、2e10
+fn first_float(text: &str) -> Option<f64> {
+    let bytes = text.as_bytes();
+    let digit = |i: usize| bytes.get(i).is_some_and(u8::is_ascii_digit);
+    let start = (0..bytes.len()).find(|&i| digit(i) || (bytes[i] == b'.' && digit(i + 1)))?;
+    let digits_from = |mut i: usize| {
+        while digit(i) {
+            i += 1;
+        }
+        i
+    };
+    let mut end = digits_from(start);
+    if bytes.get(end) == Some(&b'.') {
+        end = digits_from(end + 1);
+    }
+    // 指数部分后面必须有数字
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(end + 1), Some(b'+' | b'-')));
+        if digit(end + 1 + sign) {
+            end = digits_from(end + 1 + sign);
+        }
+    }
+    let value: f64 = text[start..end].parse().ok()?;
+    Some(if text[..start].ends_with('-') { -value } else { value })
+}
+
+// 稳定地排序，相等的行保持原来的顺序；u 只保留相同的行中的第一行，i 时忽略大小写
+pub fn sort_lines(lines: &[String], options: &SortOptions, regex: Option<&Regex>) -> Vec<String> {
+    let mut keyed: Vec<(SortKey, &String)> = lines
+        .iter()
+        .map(|line| (sort_key(sort_text(line, regex, options.use_match), options), line))
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| {
+        let order = a.compare(b);
+        if options.reverse {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+    let mut sorted: Vec<String> = keyed.into_iter().map(|(_, line)| line.clone()).collect();
+    if options.unique {
+        sorted.dedup_by(|line, prev| {
+            if options.ignore_case {
+                line.to_lowercase() == prev.to_lowercase()
+            } else {
+                line == prev
+            }
+        });
+    }
+    sorted
+}
+
+impl Editor {
+    // :[range]sort[!] [i][u][r][n|x|f] [/pattern/]，没有范围时排序整个 buffer，作为一次修改撤销
+    pub fn sort_command(&mut self, range: Option<LineRange>, bang: bool, args: &str) -> io::Result<()> {
+        let mut options = match SortOptions::parse(args) {
+            Ok(options) => options,
+            Err(error) => {
+                self.error(error);
+                return Ok(());
+            }
+        };
+        options.reverse = bang;
+        // 空的模式使用上一次搜索的模式，否则成为上一次搜索的模式
+        let pattern = match options.pattern.as_deref() {
+            Some("") => match self.registers.get('/') {
+                Some(register) => Some(register.text.clone()),
+                None => {
+                    self.error("E35: No previous regular expression");
+                    return Ok(());
+                }
+            },
+            Some(pattern) => {
+                self.registers.set('/', Register {
+                    text: pattern.to_string(),
+                    linewise: false,
+                });
+                Some(pattern.to_string())
+            }
+            None => None,
+        };
+        let regex = match pattern.as_deref().map(Regex::new).transpose() {
+            Ok(regex) => regex,
+            Err(_) => {
+                self.error(format!("E383: Invalid search string: {}", pattern.unwrap_or_default()));
+                return Ok(());
+            }
+        };
+
+        if self.buffer.line_count() == 0 {
+            return Ok(());
+        }
+        let (first, last) = range.unwrap_or((0, self.buffer.line_count() - 1));
+        let lines = &self.buffer.lines()[first..=last];
+        let sorted = sort_lines(lines, &options, regex.as_ref());
+        if sorted[..] != *lines {
+            let count = lines.len();
+            self.buffer.replace_lines(first, count, &sorted);
+            let removed = count - sorted.len();
+            if removed > 2 {
+                self.messages.push(format!("{} fewer lines", removed));
+            }
+        }
+        self.cursor.move_to(&self.buffer, Position::new(first, 0))?;
+        self.cursor.move_to_first_non_blank(&self.buffer)
+    }
+}
@@ -0,0 +1,348 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use termion::event::Key;
+
+use crate::buffer::Buffer;
+use crate::editor::Editor;
+use crate::file::{self, FileOptions};
+
+// 交换文件的第一行，用来识别交换文件
+const MAGIC: &str = "rustvim swap file";
+
+// 交换文件保存的内容：写入的进程、编辑的文件和 buffer 的内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapInfo {
+    pub pid: u32,
+    pub file: PathBuf,
+    pub modified: bool,
+    // 恢复时需要的文件设置：binary 文件的内容是转义过的
+    pub escaped: bool,
+    pub binary: bool,
+    pub fileencoding: String,
+    pub fileformat: String,
+    pub endofline: bool,
+    pub lines: Vec<String>,
+}
+
+// 第 n 个交换文件名：dir/.name.swp，已经存在时依次使用 .swo、.swn ……
+pub fn swap_path(file: &Path, n: usize) -> PathBuf {
+    let name = file.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let suffix = (b'p' - n.min(15) as u8) as char;
+    file.with_file_name(format!(".{}.sw{}", name, suffix))
+}
+
+// 已经存在的第一个交换文件
+pub fn existing(file: &Path) -> Option<PathBuf> {
+    (0..16).map(|n| swap_path(file, n)).find(|path| path.exists())
+}
+
+// 还没有使用的交换文件名
+pub fn free_path(file: &Path) -> PathBuf {
+    (0..16)
+        .map(|n| swap_path(file, n))
+        .find(|path| !path.exists())
+        .unwrap_or_else(|| swap_path(file, 0))
+}
+
+// 头部的每行是 "名字 值"，空行之后是 buffer 的内容
+pub fn write(path: &Path, info: &SwapInfo) -> io::Result<()> {
+    let header = format!(
+        "{}\npid {}\nfile {}\nmodified {}\nescaped {}\nbinary {}\nfileencoding {}\nfileformat {}\nendofline {}\n\n",
+        MAGIC,
+        info.pid,
+        info.file.display(),
+        info.modified as u8,
+        info.escaped as u8,
+        info.binary as u8,
+        info.fileencoding,
+        info.fileformat,
+        info.endofline as u8
+    );
+    // 交换文件中有文件的内容，权限和原来的文件相同，新文件只有自己可以读写
+    let mode = fs::metadata(&info.file).map_or(0o600, |metadata| metadata.mode() & 0o666);
+    let mut swap = OpenOptions::new().write(true).create(true).truncate(true).mode(mode).open(path)?;
+    swap.write_all((header + &file::join_lines(&info.lines)).as_bytes())
+}
+
+pub fn read(path: &Path) -> io::Result<SwapInfo> {
+    let text = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("E307: {} does not look like a swap file", path.display()),
+        )
+    };
+    let (header, content) = text.split_once("\n\n").ok_or_else(invalid)?;
+    let mut header = header.lines();
+    if header.next() != Some(MAGIC) {
+        return Err(invalid());
+    }
+    let mut info = SwapInfo {
+        pid: 0,
+        file: PathBuf::new(),
+        modified: false,
+        escaped: false,
+        binary: false,
+        fileencoding: String::from("utf-8"),
+        fileformat: String::from("unix"),
+        endofline: true,
+        lines: file::split_lines(content),
+    };
+    for line in header {
+        match line.split_once(' ') {
+            Some(("pid", pid)) => info.pid = pid.parse().map_err(|_| invalid())?,
+            Some(("file", name)) => info.file = PathBuf::from(name),
+            Some(("modified", flag)) => info.modified = flag == "1",
+            Some(("escaped", flag)) => info.escaped = flag == "1",
+            Some(("binary", flag)) => info.binary = flag == "1",
+            Some(("fileencoding", name)) => info.fileencoding = name.to_string(),
+            Some(("fileformat", name)) => info.fileformat = name.to_string(),
+            Some(("endofline", flag)) => info.endofline = flag == "1",
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(info)
+}
+
+// 写交换文件的进程是否还在运行
+pub fn process_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+// 打开文件时发现交换文件的提示
+pub fn attention(file: &Path, swap: &Path, info: Option<&SwapInfo>) -> Vec<String> {
+    let mut lines = vec![
+        String::from("E325: ATTENTION"),
+        format!("Found a swap file by the name \"{}\"", swap.display()),
+    ];
+    if let Some(info) = info {
+        let running = if process_running(info.pid) { " (STILL RUNNING)" } else { "" };
+        lines.push(format!("          owned by: process ID {}{}", info.pid, running));
+        lines.push(format!("         file name: {}", info.file.display()));
+        lines.push(format!("          modified: {}", if info.modified { "YES" } else { "no" }));
+    }
+    lines.extend([
+        format!("While opening file \"{}\"", file.display()),
+        String::from("(1) Another program may be editing the same file."),
+        String::from("(2) An edit session for this file crashed."),
+        format!("    If this is the case, use \"rustvim -r {}\" to recover the changes.", file.display()),
+        format!("Swap file \"{}\" already exists!", swap.display()),
+        String::from("(E)dit anyway, (R)ecover, (D)elete it, (Q)uit:"),
+    ]);
+    lines
+}
+
+// rustvim -r 不带文件名时列出目录中的交换文件
+pub fn list(dir: &Path) -> Vec<String> {
+    let mut swaps: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect())
+        .unwrap_or_default();
+    swaps.retain(|path| {
+        let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let extension = path.extension().map_or_else(String::new, |ext| ext.to_string_lossy().into_owned());
+        name.starts_with('.') && extension.len() == 3 && extension.starts_with("sw")
+    });
+    swaps.sort();
+
+    let mut lines = vec![String::from("Swap files found:"), String::from("   In current directory:")];
+    if swaps.is_empty() {
+        lines.push(String::from("      -- none --"));
+    }
+    for (i, path) in swaps.iter().enumerate() {
+        let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        lines.push(format!("{}.    {}", i + 1, name));
+        match read(path) {
+            Ok(info) => {
+                let running = if process_running(info.pid) { " (STILL RUNNING)" } else { "" };
+                lines.push(format!("          file name: {}", info.file.display()));
+                lines.push(format!("         process ID: {}{}", info.pid, running));
+            }
+            Err(_) => lines.push(String::from("         [cannot be read]")),
+        }
+    }
+    lines
+}
+
+// 发现交换文件后等待用户选择
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapPrompt {
+    pub file: PathBuf,
+    pub swap: PathBuf,
+    pub message: Vec<String>,
+    // 选择编辑或删除之后读入文件使用的 ++ 参数
+    pub options: FileOptions,
+}
+
+impl Editor {
+    // 要打开的文件已有交换文件时显示提示并返回 true
+    pub fn check_swap(&mut self, path: &Path, options: &FileOptions) -> bool {
+        let swap = match existing(path) {
+            Some(swap) => swap,
+            None => return false,
+        };
+        let info = read(&swap).ok();
+        let message = attention(path, &swap, info.as_ref());
+        self.messages.extend(message.clone());
+        self.swap_prompt = Some(SwapPrompt {
+            file: path.to_path_buf(),
+            swap,
+            message,
+            options: options.clone(),
+        });
+        true
+    }
+
+    // 处理 ATTENTION 提示的回答，其他按键重新显示提示
+    pub fn answer_swap_prompt(&mut self, key: Key) -> io::Result<()> {
+        let prompt = match self.swap_prompt.take() {
+            Some(prompt) => prompt,
+            None => return Ok(()),
+        };
+        match key {
+            Key::Char('e' | 'E') => {
+                self.load_file(&prompt.file, &prompt.options)?;
+                self.create_swap();
+            }
+            Key::Char('r' | 'R') => self.recover_from(&prompt.file, &prompt.swap)?,
+            Key::Char('d' | 'D') => {
+                let _ = fs::remove_file(&prompt.swap);
+                self.load_file(&prompt.file, &prompt.options)?;
+                self.create_swap();
+            }
+            // 启动时打开的文件选择退出时退出编辑器，否则放弃打开
+            Key::Char('q' | 'Q') | Key::Esc => {
+                if self.buffer.path.is_none() {
+                    self.quit = true;
+                }
+            }
+            _ => {
+                self.messages.extend(prompt.message.clone());
+                self.swap_prompt = Some(prompt);
+            }
+        }
+        Ok(())
+    }
+
+    // :recover 和 rustvim -r：从交换文件恢复文件的内容
+    pub fn recover(&mut self, path: &Path) -> io::Result<()> {
+        match existing(path) {
+            Some(swap) => self.recover_from(path, &swap),
+            None => {
+                self.error(format!("E305: No swap file found for {}", path.display()));
+                Ok(())
+            }
+        }
+    }
+
+    // 恢复后的 buffer 是修改过的，继续使用原来的交换文件
+    fn recover_from(&mut self, path: &Path, swap: &Path) -> io::Result<()> {
+        let info = match read(swap) {
+            Ok(info) => info,
+            Err(error) => {
+                self.error(error.to_string());
+                return Ok(());
+            }
+        };
+        // 按写交换文件时的设置恢复，写回时使用原来的编码和格式
+        self.options.binary = info.binary;
+        self.options.fileencoding = info.fileencoding;
+        self.options.fileformat = info.fileformat;
+        self.options.endofline = info.endofline;
+        let mut buffer = Buffer::from_lines(info.lines);
+        buffer.escaped = info.escaped;
+        self.set_buffer(buffer, path)?;
+        self.buffer.modified = true;
+        self.messages.push(format!("Using swap file \"{}\"", swap.display()));
+        self.messages.push(String::from("Recovery completed. You should check if everything is OK."));
+        self.messages.push(String::from(
+            "(You might want to write out this file under another name and run diff with the original file to check for changes)",
+        ));
+        if self.options.swapfile {
+            self.swap = Some(swap.to_path_buf());
+            self.write_swap();
+        }
+        Ok(())
+    }
+
+    // 开始编辑有文件名的 buffer 时创建交换文件
+    pub fn create_swap(&mut self) {
+        // updatecount 为 0 时不使用交换文件
+        if !self.options.swapfile || self.options.updatecount == 0 || self.swap.is_some() || self.buffer.large.is_some() {
+            return;
+        }
+        if let Some(path) = &self.buffer.path {
+            self.swap = Some(free_path(path));
+            self.write_swap();
+        }
+    }
+
+    // 退出或打开其他文件时删除交换文件
+    pub fn close_swap(&mut self) {
+        if let Some(swap) = self.swap.take() {
+            let _ = fs::remove_file(swap);
+        }
+    }
+
+    fn write_swap(&mut self) {
+        let (swap, path) = match (&self.swap, &self.buffer.path) {
+            (Some(swap), Some(path)) => (swap, path),
+            _ => return,
+        };
+        let info = SwapInfo {
+            pid: std::process::id(),
+            file: fs::canonicalize(path).unwrap_or_else(|_| path.clone()),
+            modified: self.buffer.modified,
+            escaped: self.buffer.escaped,
+            binary: self.options.binary,
+            fileencoding: self.options.fileencoding.clone(),
+            fileformat: self.options.fileformat.clone(),
+            endofline: self.options.endofline,
+            lines: self.buffer.lines().to_vec(),
+        };
+        if write(swap, &info).is_err() {
+            let message = format!("E297: Write error in swap file {}", swap.display());
+            self.error(message);
+        }
+        self.swap_tick = self.buffer.changedtick;
+        self.keys_since_swap = 0;
+    }
+
+    // buffer 修改过时更新交换文件
+    pub fn sync_swap(&mut self) {
+        if self.swap.is_some() && (self.swap_tick != self.buffer.changedtick || !self.buffer.modified) {
+            self.write_swap();
+        }
+    }
+
+    // 每个按键之后计数，达到 updatecount 时写交换文件
+    pub fn count_swap_key(&mut self) {
+        self.last_input = Instant::now();
+        self.keys_since_swap += 1;
+        if self.options.updatecount > 0 && self.keys_since_swap >= self.options.updatecount {
+            self.sync_swap();
+        }
+    }
+
+    // 超过 updatetime 没有按键时写交换文件
+    pub fn check_swap_idle(&mut self) {
+        let idle = self.last_input.elapsed().as_millis() >= self.options.updatetime as u128;
+        if idle && self.swap_tick != self.buffer.changedtick {
+            self.sync_swap();
+        }
+    }
+
+    // :set swapfile、noswapfile 之后创建或删除交换文件
+    pub fn update_swap_option(&mut self) {
+        if self.options.swapfile && self.options.updatecount > 0 {
+            if self.swap_prompt.is_none() {
+                self.create_swap();
+            }
+        } else {
+            self.close_swap();
+        }
+    }
+}
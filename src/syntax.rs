@@ -0,0 +1,400 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::mark::LineEdit;
+
+// 内置的语法文件，用户目录下的同名文件优先
+const BUILTIN_SYNTAX: [(&str, &str); 5] = [
+    ("rust", include_str!("../runtime/syntax/rust.vim")),
+    ("python", include_str!("../runtime/syntax/python.vim")),
+    ("c", include_str!("../runtime/syntax/c.vim")),
+    ("json", include_str!("../runtime/syntax/json.vim")),
+    ("markdown", include_str!("../runtime/syntax/markdown.vim")),
+];
+
+// 一行中属于某个高亮组的字节范围 [start, end)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub group: String,
+}
+
+#[derive(Debug, Clone)]
+enum Rule {
+    Keyword { group: String, regex: Regex },
+    Match { group: String, regex: Regex },
+    Region { group: String, start: Regex, skip: Option<Regex>, end: Regex, oneline: bool, fold: bool },
+}
+
+impl Rule {
+    fn group(&self) -> &str {
+        match self {
+            Rule::Keyword { group, .. } | Rule::Match { group, .. } | Rule::Region { group, .. } => group,
+        }
+    }
+}
+
+// 从 :syntax 命令或语法文件定义的规则，模式使用 Rust 正则表达式语法
+#[derive(Debug, Clone, Default)]
+pub struct Syntax {
+    rules: Vec<Rule>,
+}
+
+// 从参数开头取出 /pattern/ 形式的模式，分隔符可以是任意非字母字符
+fn take_pattern(text: &str) -> Result<(&str, &str), String> {
+    let mut chars = text.chars();
+    let delimiter = chars
+        .next()
+        .filter(|c| !c.is_alphanumeric() && !c.is_whitespace())
+        .ok_or_else(|| format!("E401: Pattern delimiter not found: {}", text))?;
+    let body = &text[delimiter.len_utf8()..];
+    // 分隔符前有反斜杠时不算结束
+    let mut escaped = false;
+    for (i, c) in body.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == delimiter => return Ok((&body[..i], &body[i + c.len_utf8()..])),
+            _ => (),
+        }
+    }
+    Err(format!("E402: Garbage after pattern: {}", text))
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|_| format!("E402: Invalid pattern: {}", pattern))
+}
+
+impl Syntax {
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // 解析语法文件，每行是一条去掉开头 "syntax" 的命令，" 开头的行是注释
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut syntax = Self::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('"') {
+                continue;
+            }
+            let args = line
+                .strip_prefix("syntax ")
+                .or_else(|| line.strip_prefix("syn "))
+                .ok_or_else(|| format!("E492: Not an editor command: {}", line))?;
+            syntax.define(args)?;
+        }
+        Ok(syntax)
+    }
+
+    // 按名字加载语法文件：先找 ~/.config/rustvim/syntax/{name}.vim，再找内置的
+    pub fn load(name: &str) -> Result<Option<Self>, String> {
+        if let Some(path) = user_syntax_file(name) {
+            if let Ok(text) = fs::read_to_string(path) {
+                return Self::parse(&text).map(Some);
+            }
+        }
+        match BUILTIN_SYNTAX.iter().find(|(builtin, _)| *builtin == name) {
+            Some((_, text)) => Self::parse(text).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // 处理一条 :syntax keyword/match/region 命令的参数
+    pub fn define(&mut self, args: &str) -> Result<(), String> {
+        let (kind, rest) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+        let (group, rest) = rest.trim().split_once(' ').unwrap_or((rest.trim(), ""));
+        if group.is_empty() {
+            return Err(format!("E398: Missing group name: {}", args));
+        }
+        let group = group.to_string();
+        let rest = rest.trim();
+
+        let rule = match kind {
+            "keyword" | "keyw" => {
+                let words: Vec<String> = rest.split_whitespace().map(regex::escape).collect();
+                if words.is_empty() {
+                    return Err(format!("E399: Missing keyword: {}", args));
+                }
+                let regex = compile(&format!(r"\b(?:{})\b", words.join("|")))?;
+                Rule::Keyword { group, regex }
+            }
+            "match" | "mat" => {
+                let (pattern, _) = take_pattern(rest)?;
+                Rule::Match { group, regex: compile(pattern)? }
+            }
+            "region" | "reg" => self.parse_region(group, rest)?,
+            _ => return Err(format!("E410: Invalid :syntax subcommand: {}", kind)),
+        };
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    fn parse_region(&self, group: String, mut rest: &str) -> Result<Rule, String> {
+        let (mut start, mut skip, mut end, mut oneline, mut fold) = (None, None, None, false, false);
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("oneline") {
+                oneline = true;
+                rest = after.trim_start();
+                continue;
+            }
+            // foldmethod=syntax 时跨越多行的 region 是一个折叠
+            if let Some(after) = rest.strip_prefix("fold") {
+                fold = true;
+                rest = after.trim_start();
+                continue;
+            }
+            let (key, after) = rest
+                .split_once('=')
+                .ok_or_else(|| format!("E475: Invalid argument: {}", rest))?;
+            let (pattern, after) = take_pattern(after)?;
+            let regex = compile(pattern)?;
+            match key {
+                "start" => start = Some(regex),
+                "skip" => skip = Some(regex),
+                "end" => end = Some(regex),
+                _ => return Err(format!("E475: Invalid argument: {}", key)),
+            }
+            rest = after.trim_start();
+        }
+        match (start, end) {
+            (Some(start), Some(end)) => Ok(Rule::Region { group, start, skip, end, oneline, fold }),
+            _ => Err(format!("E399: Not enough arguments: syntax region {}", group)),
+        }
+    }
+
+    // 在 region 中从 pos 开始找结束位置，跳过 skip 匹配的内容
+    fn region_end(&self, index: usize, line: &str, mut pos: usize) -> Option<usize> {
+        let (skip, end) = match &self.rules[index] {
+            Rule::Region { skip, end, .. } => (skip, end),
+            _ => return None,
+        };
+        loop {
+            let found = end.find_at(line, pos)?;
+            match skip.as_ref().and_then(|skip| skip.find_at(line, pos)) {
+                Some(skipped) if skipped.start() < found.start() && skipped.end() > pos => pos = skipped.end(),
+                _ => return Some(found.end()),
+            }
+        }
+    }
+
+    // 从 pos 开始最早匹配的规则，同一位置关键字优先，其次是后定义的规则
+    fn next_match(&self, line: &str, pos: usize) -> Option<(usize, usize, usize)> {
+        let mut best: Option<(usize, usize, usize)> = None;
+        for (index, rule) in self.rules.iter().enumerate() {
+            let found = match rule {
+                Rule::Keyword { regex, .. } | Rule::Match { regex, .. } => regex.find_at(line, pos),
+                Rule::Region { start, .. } => start.find_at(line, pos),
+            };
+            let found = match found {
+                Some(found) if found.end() > found.start() => found,
+                _ => continue,
+            };
+            let better = match best {
+                None => true,
+                Some((start, _, best_index)) => {
+                    found.start() < start
+                        || (found.start() == start && !matches!(self.rules[best_index], Rule::Keyword { .. }))
+                }
+            };
+            if better {
+                best = Some((found.start(), found.end(), index));
+            }
+        }
+        best
+    }
+
+    // 高亮一行，state 是行开始时所在的 region，返回高亮范围和行结束时的 region
+    pub fn highlight_line(&self, line: &str, state: Option<usize>) -> (Vec<Span>, Option<usize>) {
+        let mut spans = Vec::new();
+        let mut pos = 0;
+        let span = |start, end, index: usize| Span {
+            start,
+            end,
+            group: self.rules[index].group().to_string(),
+        };
+
+        if let Some(index) = state {
+            match self.region_end(index, line, 0) {
+                Some(end) => {
+                    spans.push(span(0, end, index));
+                    pos = end;
+                }
+                None => {
+                    spans.push(span(0, line.len(), index));
+                    return (spans, Some(index));
+                }
+            }
+        }
+
+        while pos < line.len() {
+            let (start, end, index) = match self.next_match(line, pos) {
+                Some(found) => found,
+                None => break,
+            };
+            if let Rule::Region { oneline, .. } = self.rules[index] {
+                match self.region_end(index, line, end) {
+                    Some(region_end) => {
+                        spans.push(span(start, region_end, index));
+                        pos = region_end.max(start + 1);
+                    }
+                    // 单行的 region 没有结束时不算匹配
+                    None if oneline => pos = start + 1,
+                    None => {
+                        spans.push(span(start, line.len(), index));
+                        return (spans, Some(index));
+                    }
+                }
+                continue;
+            }
+            spans.push(span(start, end, index));
+            pos = end;
+        }
+        (spans, None)
+    }
+
+    // 带 fold 参数的 region
+    fn folds(&self, index: usize) -> bool {
+        matches!(self.rules.get(index), Some(Rule::Region { fold: true, .. }))
+    }
+}
+
+fn user_syntax_file(name: &str) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".config/rustvim/syntax").join(format!("{}.vim", name)))
+}
+
+// 每行的高亮缓存：行开始时的状态和高亮结果，修改后只重新计算受影响的行
+#[derive(Debug, Default)]
+struct Cache {
+    states: Vec<Option<usize>>,
+    spans: Vec<Option<Vec<Span>>>,
+    // states[0..=valid] 是可信的
+    valid: usize,
+}
+
+#[derive(Debug)]
+pub struct Highlighter {
+    // 加载的语法名，对应 syntax 选项
+    pub name: String,
+    pub syntax: Syntax,
+    cache: RefCell<Cache>,
+}
+
+impl Highlighter {
+    pub fn new(name: &str, syntax: Syntax) -> Self {
+        Self {
+            name: name.to_string(),
+            syntax,
+            cache: RefCell::new(Cache::default()),
+        }
+    }
+
+    // 规则改变后整个缓存失效
+    pub fn reset(&mut self) {
+        self.cache = RefCell::new(Cache::default());
+    }
+
+    // row 行的内容改变了
+    pub fn invalidate(&self, row: usize) {
+        let mut cache = self.cache.borrow_mut();
+        if let Some(spans) = cache.spans.get_mut(row) {
+            *spans = None;
+        }
+        cache.valid = cache.valid.min(row);
+    }
+
+    // 插入和删除行时移动缓存，后面没有修改的行可以继续使用
+    pub fn adjust(&self, edit: LineEdit) {
+        let mut cache = self.cache.borrow_mut();
+        let (at, inserted, removed) = match edit {
+            LineEdit::Insert { at, count } => (at, count, 0),
+            LineEdit::Remove { at, count } => (at, 0, count),
+            LineEdit::Split { row, .. } => (row + 1, 1, 0),
+            LineEdit::Join { row, .. } => (row + 1, 0, 1),
+        };
+        if at < cache.spans.len() {
+            let end = (at + removed).min(cache.spans.len());
+            cache.spans.splice(at..end, std::iter::repeat_n(None, inserted));
+            // states[at] 是前一行的结束状态，不受影响
+            cache.states.splice(at + 1..end + 1, std::iter::repeat_n(None, inserted));
+            // 原来在 at 处的行现在接在新的前一行后面，开始状态需要重新计算
+            if let Some(spans) = cache.spans.get_mut(at + inserted) {
+                *spans = None;
+            }
+        }
+        cache.valid = cache.valid.min(at);
+    }
+
+    // 第 row 行的高亮，需要时先计算前面的行
+    pub fn line_spans(&self, lines: &[String], row: usize) -> Vec<Span> {
+        let mut cache = self.cache.borrow_mut();
+        let cache = &mut *cache;
+        if cache.states.len() < lines.len() + 1 {
+            cache.states.resize(lines.len() + 1, None);
+            cache.spans.resize(lines.len(), None);
+        }
+        cache.states.truncate(lines.len() + 1);
+        cache.spans.truncate(lines.len());
+        if row >= lines.len() {
+            return Vec::new();
+        }
+
+        // 前一行的结束状态没有变、这一行也没有修改时，缓存的结果仍然有效
+        while cache.valid <= row {
+            let i = cache.valid;
+            if cache.spans[i].is_none() {
+                let (spans, end) = self.syntax.highlight_line(&lines[i], cache.states[i]);
+                cache.spans[i] = Some(spans);
+                if cache.states[i + 1] != end {
+                    cache.states[i + 1] = end;
+                    if let Some(next) = cache.spans.get_mut(i + 1) {
+                        *next = None;
+                    }
+                }
+            }
+            cache.valid = i + 1;
+        }
+        cache.spans[row].clone().unwrap_or_default()
+    }
+
+    // foldmethod=syntax：带 fold 参数、跨越多行的 region 的范围
+    pub fn fold_ranges(&self, lines: &[String]) -> Vec<(usize, usize)> {
+        if lines.is_empty() {
+            return Vec::new();
+        }
+        self.line_spans(lines, lines.len() - 1);
+        let cache = self.cache.borrow();
+        let mut ranges = Vec::new();
+        let mut open: Option<(usize, usize)> = None;
+        for row in 0..lines.len() {
+            let (state, end) = (cache.states[row], cache.states[row + 1]);
+            if let Some((start, index)) = open {
+                if end != Some(index) {
+                    ranges.push((start, row));
+                    open = None;
+                }
+            }
+            match end {
+                Some(index) if state != end && self.syntax.folds(index) => open = Some((row, index)),
+                _ => (),
+            }
+        }
+        if let Some((start, _)) = open {
+            ranges.push((start, lines.len() - 1));
+        }
+        ranges
+    }
+
+    // 调试和测试用：已经计算过高亮的行数
+    pub fn cached_lines(&self) -> usize {
+        self.cache.borrow().spans.iter().filter(|spans| spans.is_some()).count()
+    }
+}
@@ -0,0 +1,374 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{InputEdit, Language, Node, Parser, Point, Query, QueryCursor, Tree};
+
+use crate::mark::Position;
+use crate::syntax::Span;
+
+// 内置 tree-sitter 语法的语言和高亮查询
+fn language(name: &str) -> Option<(Language, &'static str)> {
+    match name {
+        "rust" => Some((tree_sitter_rust::LANGUAGE.into(), tree_sitter_rust::HIGHLIGHTS_QUERY)),
+        "python" => Some((tree_sitter_python::LANGUAGE.into(), tree_sitter_python::HIGHLIGHTS_QUERY)),
+        "json" => Some((tree_sitter_json::LANGUAGE.into(), tree_sitter_json::HIGHLIGHTS_QUERY)),
+        "markdown" => Some((tree_sitter_md::LANGUAGE.into(), tree_sitter_md::HIGHLIGHT_QUERY_BLOCK)),
+        _ => None,
+    }
+}
+
+// 查询中的捕获名对应的高亮组，如 @function.macro 对应 Macro
+fn capture_group(capture: &str) -> Option<&'static str> {
+    let group = match capture {
+        "function.macro" => "Macro",
+        "punctuation.special" | "variable.builtin" | "string.escape" | "escape" => "Special",
+        "string.special.key" | "property" | "text.reference" => "Identifier",
+        "text.title" => "Title",
+        "text.literal" => "String",
+        "text.uri" => "Underlined",
+        _ => match capture.split('.').next()? {
+            "comment" => "Comment",
+            "string" => "String",
+            "number" => "Number",
+            "boolean" => "Boolean",
+            "constant" => "Constant",
+            "keyword" => "Keyword",
+            "function" => "Function",
+            "type" | "constructor" => "Type",
+            "attribute" => "PreProc",
+            "label" => "Label",
+            "operator" => "Operator",
+            _ => return None,
+        },
+    };
+    Some(group)
+}
+
+// 文本对象对应的节点类型：f 函数、c 类、a 参数所在的列表
+fn object_kinds(object: char) -> &'static [&'static str] {
+    match object {
+        'f' => &[
+            "function_item",
+            "function_signature_item",
+            "closure_expression",
+            "function_definition",
+            "lambda",
+        ],
+        'c' => &[
+            "struct_item",
+            "enum_item",
+            "union_item",
+            "trait_item",
+            "impl_item",
+            "class_definition",
+        ],
+        'a' => &[
+            "arguments",
+            "parameters",
+            "type_arguments",
+            "type_parameters",
+            "closure_parameters",
+            "argument_list",
+            "lambda_parameters",
+        ],
+        _ => &[],
+    }
+}
+
+fn position(point: Point) -> Position {
+    Position::new(point.row, point.column)
+}
+
+fn line_starts(source: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+struct ParseState {
+    parser: Parser,
+    tree: Option<Tree>,
+    // 按 buffer 的修改更新的文本，None 时在下一次解析时用 buffer 的行重新组成
+    source: Option<String>,
+    // 每行开始的字节位置，最后一项是文本的末尾
+    line_starts: Vec<usize>,
+    dirty: bool,
+}
+
+// buffer 的语法树，修改后在下一次使用时增量地重新解析
+pub struct SyntaxTree {
+    pub language: String,
+    query: Query,
+    state: RefCell<ParseState>,
+}
+
+impl fmt::Debug for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyntaxTree").field("language", &self.language).finish()
+    }
+}
+
+impl SyntaxTree {
+    // 没有内置该语言的解析器时返回 None
+    pub fn new(name: &str) -> Option<Self> {
+        let (language, highlights) = language(name)?;
+        let mut parser = Parser::new();
+        parser.set_language(&language).ok()?;
+        let query = Query::new(&language, highlights).ok()?;
+        Some(Self {
+            language: name.to_string(),
+            query,
+            state: RefCell::new(ParseState {
+                parser,
+                tree: None,
+                source: None,
+                line_starts: vec![0],
+                dirty: true,
+            }),
+        })
+    }
+
+    // buffer 中 start 到 old_end 之间的文本换成了 text，位置是修改前的行和字节列。
+    // 修改马上告诉已有的语法树，下一次使用时增量地重新解析
+    pub fn edit(&self, start: Position, old_end: Position, text: &str) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        state.dirty = true;
+        let Some(source) = state.source.as_mut() else {
+            return;
+        };
+        let byte = |position: Position| state.line_starts.get(position.row).map(|start| start + position.col);
+        let (start_byte, old_end_byte) = match (byte(start), byte(old_end)) {
+            (Some(start_byte), Some(old_end_byte))
+                if start_byte <= old_end_byte
+                    && source.is_char_boundary(start_byte)
+                    && source.is_char_boundary(old_end_byte) =>
+            {
+                (start_byte, old_end_byte)
+            }
+            // 和记下的文本对不上时，下一次使用时完整地解析
+            _ => {
+                state.source = None;
+                state.tree = None;
+                return;
+            }
+        };
+        let new_end_byte = start_byte + text.len();
+        let new_end_position = match text.rfind('\n') {
+            Some(i) => Point::new(start.row + text.matches('\n').count(), text.len() - i - 1),
+            None => Point::new(start.row, start.col + text.len()),
+        };
+        if let Some(tree) = state.tree.as_mut() {
+            tree.edit(&InputEdit {
+                start_byte,
+                old_end_byte,
+                new_end_byte,
+                start_position: Point::new(start.row, start.col),
+                old_end_position: Point::new(old_end.row, old_end.col),
+                new_end_position,
+            });
+        }
+        source.replace_range(start_byte..old_end_byte, text);
+
+        // 修改范围中的行换成新文本中的行，后面的行按长度的变化移动
+        let tail: Vec<usize> = state.line_starts[old_end.row + 1..]
+            .iter()
+            .map(|&line_start| line_start - old_end_byte + new_end_byte)
+            .collect();
+        state.line_starts.truncate(start.row + 1);
+        state.line_starts.extend(text.match_indices('\n').map(|(i, _)| start_byte + i + 1));
+        state.line_starts.extend(tail);
+    }
+
+    // 需要时重新解析，第一次解析或文本对不上时才用 buffer 的行组成文本
+    fn update(&self, lines: &[String]) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+        if !state.dirty && state.tree.is_some() {
+            return;
+        }
+        let source = state.source.get_or_insert_with(|| {
+            let mut source = lines.join("\n");
+            if !lines.is_empty() {
+                source.push('\n');
+            }
+            state.line_starts = line_starts(&source);
+            source
+        });
+        state.tree = state.parser.parse(source.as_str(), state.tree.as_ref());
+        state.dirty = false;
+    }
+
+    // 第 row 行的高亮，嵌套的节点排在后面以便覆盖外层
+    pub fn line_spans(&self, lines: &[String], row: usize) -> Vec<Span> {
+        self.update(lines);
+        let state = self.state.borrow();
+        let (tree, source, line_start) = match (&state.tree, &state.source, state.line_starts.get(row)) {
+            (Some(tree), Some(source), Some(&start)) if row < lines.len() => (tree, source, start),
+            _ => return Vec::new(),
+        };
+        let line_end = line_start + lines[row].len();
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(line_start..line_end);
+        let names = self.query.capture_names();
+        let mut found: Vec<(usize, usize, usize, &str)> = Vec::new();
+        let mut captures = cursor.captures(&self.query, tree.root_node(), source.as_bytes());
+        while let Some((query_match, index)) = captures.next() {
+            let capture = query_match.captures[*index];
+            let group = match capture_group(names[capture.index as usize]) {
+                Some(group) => group,
+                None => continue,
+            };
+            let start = capture.node.start_byte().max(line_start) - line_start;
+            let end = capture.node.end_byte().min(line_end).saturating_sub(line_start);
+            if start < end {
+                found.push((start, end, query_match.pattern_index, group));
+            }
+        }
+
+        // 同一个节点被多个模式捕获时，前面的模式优先
+        found.sort_by_key(|&(start, end, pattern, _)| (start, std::cmp::Reverse(end), pattern));
+        found.dedup_by_key(|&mut (start, end, _, _)| (start, end));
+        found
+            .into_iter()
+            .map(|(start, end, _, group)| Span { start, end, group: group.to_string() })
+            .collect()
+    }
+
+    // :InspectTree 显示光标所在的节点和它的祖先
+    pub fn inspect(&self, lines: &[String], at: Position) -> Vec<String> {
+        self.update(lines);
+        let state = self.state.borrow();
+        let tree = match &state.tree {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+        let point = Point::new(at.row, at.col);
+        let mut node = tree.root_node().descendant_for_point_range(point, point);
+        let mut chain = Vec::new();
+        while let Some(current) = node {
+            chain.push(current);
+            node = current.parent();
+        }
+        chain
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(depth, node)| {
+                let (start, end) = (node.start_position(), node.end_position());
+                let field = node
+                    .parent()
+                    .and_then(|parent| {
+                        let mut cursor = parent.walk();
+                        let index = parent.children(&mut cursor).position(|child| child == *node)?;
+                        parent.field_name_for_child(index as u32)
+                    })
+                    .map_or(String::new(), |field| format!("{}: ", field));
+                format!(
+                    "{}{}({} [{}, {}] - [{}, {}])",
+                    "  ".repeat(depth),
+                    field,
+                    node.kind(),
+                    start.row,
+                    start.column,
+                    end.row,
+                    end.column
+                )
+            })
+            .collect()
+    }
+
+    // foldmethod=syntax：跨越多行的命名节点，开始行相同时只取最外层的节点
+    pub fn fold_ranges(&self, lines: &[String]) -> Vec<(usize, usize)> {
+        self.update(lines);
+        let state = self.state.borrow();
+        let tree = match &state.tree {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let mut cursor = tree.walk();
+        let mut visited = false;
+        loop {
+            if !visited {
+                let node = cursor.node();
+                let (start, end) = (node.start_position(), node.end_position());
+                // 以换行结束的节点结束于下一行的开头
+                let end_row = if end.column == 0 { end.row.saturating_sub(1) } else { end.row };
+                let top = ranges.iter().any(|&(row, _)| row == start.row);
+                if node.is_named() && node.parent().is_some() && end_row > start.row && !top {
+                    ranges.push((start.row, end_row));
+                }
+                if cursor.goto_first_child() {
+                    continue;
+                }
+            }
+            if cursor.goto_next_sibling() {
+                visited = false;
+            } else if cursor.goto_parent() {
+                visited = true;
+            } else {
+                break;
+            }
+        }
+        ranges
+    }
+
+    // 文本对象 if/af、ic/ac、ia/aa 的范围 [start, end)
+    pub fn text_object(&self, lines: &[String], at: Position, object: char, around: bool) -> Option<(Position, Position)> {
+        self.update(lines);
+        let state = self.state.borrow();
+        let tree = state.tree.as_ref()?;
+        let point = Point::new(at.row, at.col);
+        let kinds = object_kinds(object);
+        let mut node = tree.root_node().descendant_for_point_range(point, point);
+        while let Some(current) = node {
+            if kinds.contains(&current.kind()) {
+                break;
+            }
+            node = current.parent();
+        }
+        let node = node?;
+
+        if object == 'a' {
+            return argument_range(node, point, around);
+        }
+        if around {
+            return Some((position(node.start_position()), position(node.end_position())));
+        }
+        let body = node.child_by_field_name("body")?;
+        let count = body.child_count();
+        // 大括号包围的块只取括号里面的部分
+        match (body.child(0), count.checked_sub(1).and_then(|last| body.child(last))) {
+            (Some(open), Some(close)) if open.kind() == "{" && close.kind() == "}" && count >= 2 => {
+                Some((position(open.end_position()), position(close.start_position())))
+            }
+            _ => Some((position(body.start_position()), position(body.end_position()))),
+        }
+    }
+}
+
+// 参数列表中光标所在的参数，around 时包括分隔的逗号和空白
+fn argument_range(list: Node, point: Point, around: bool) -> Option<(Position, Position)> {
+    let mut cursor = list.walk();
+    let arguments: Vec<Node> = list
+        .named_children(&mut cursor)
+        .filter(|node| !node.kind().contains("comment"))
+        .collect();
+    let index = arguments
+        .iter()
+        .position(|node| node.end_position() > point)
+        .unwrap_or(arguments.len().checked_sub(1)?);
+    let argument = arguments[index];
+    let (mut start, mut end) = (argument.start_position(), argument.end_position());
+    if around {
+        match (arguments.get(index + 1), index.checked_sub(1).map(|prev| arguments[prev])) {
+            (Some(next), _) => end = next.start_position(),
+            (None, Some(prev)) => start = prev.end_position(),
+            (None, None) => (),
+        }
+    }
+    Some((position(start), position(end)))
+}
@@ -0,0 +1,380 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::editor::Editor;
+use crate::file;
+use crate::mark::Position;
+
+// 撤销文件的第一行，后面是版本号
+const MAGIC: &str = "rustvim undo file 1";
+
+// 一次修改：从 row 开始的 old 几行被替换成 new
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub row: usize,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl Change {
+    // 撤销时应用的反向修改
+    pub fn inverse(&self) -> Change {
+        Change {
+            row: self.row,
+            old: self.new.clone(),
+            new: self.old.clone(),
+        }
+    }
+
+    // 修改后光标的位置：第一个不同的字符
+    pub fn cursor(&self) -> Position {
+        let col = match (self.old.first(), self.new.first()) {
+            (Some(old), Some(new)) => old
+                .char_indices()
+                .zip(new.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(old.len().min(new.len()), |((i, _), _)| i),
+            _ => 0,
+        };
+        Position::new(self.row, col)
+    }
+}
+
+// 比较两个版本的文本，去掉相同的开头和结尾的行
+pub fn diff(old: &[String], new: &[String]) -> Option<Change> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    if prefix == old.len() && prefix == new.len() {
+        return None;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    Some(Change {
+        row: prefix,
+        old: old[prefix..old.len() - suffix].to_vec(),
+        new: new[prefix..new.len() - suffix].to_vec(),
+    })
+}
+
+// 文件内容的 FNV-1a 哈希，用来判断撤销文件是否属于这个文件的内容
+pub fn hash(lines: &[String]) -> u64 {
+    file::join_lines(lines)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+// 撤销树的一个节点，序号从 1 开始，0 是最初的文本
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoEntry {
+    pub seq: usize,
+    pub parent: usize,
+    pub change: Change,
+    // 修改之后的文本是不是 :Hex 的十六进制显示
+    pub hex: bool,
+}
+
+// 撤销树：撤销回到父节点，重做进入最近离开或创建的子节点
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoTree {
+    entries: Vec<UndoEntry>,
+    // 当前文本对应的节点
+    pub current: usize,
+    // 写入文件时的节点，回到这里时 buffer 没有修改
+    pub save_seq: usize,
+    // 每个节点重做时进入的子节点，下标是节点的序号
+    redo_child: Vec<Option<usize>>,
+    // 上一次提交时的文本和 changedtick
+    text: Vec<String>,
+    tick: usize,
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl UndoTree {
+    pub fn new(text: Vec<String>) -> Self {
+        Self {
+            entries: Vec::new(),
+            current: 0,
+            save_seq: 0,
+            redo_child: vec![None],
+            text,
+            tick: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[UndoEntry] {
+        &self.entries
+    }
+
+    // 一个命令结束时把修改作为一个新节点，文本没有变化时不创建节点，hex 是修改之后的显示方式
+    pub fn commit(&mut self, lines: &[String], tick: usize, hex: bool) {
+        if self.committed(tick) {
+            return;
+        }
+        self.tick = tick;
+        if let Some(change) = diff(&self.text, lines) {
+            let seq = self.entries.len() + 1;
+            self.entries.push(UndoEntry {
+                seq,
+                parent: self.current,
+                change,
+                hex,
+            });
+            self.redo_child.push(None);
+            self.redo_child[self.current] = Some(seq);
+            self.current = seq;
+            self.text = lines.to_vec();
+        }
+    }
+
+    // changedtick 为 tick 的文本已经提交过
+    pub fn committed(&self, tick: usize) -> bool {
+        tick == self.tick
+    }
+
+    // 撤销：返回要应用的修改，已经是最初的文本时返回 None
+    pub fn undo(&mut self) -> Option<Change> {
+        let entry = self.entries.get(self.current.checked_sub(1)?)?;
+        let (seq, parent, change) = (entry.seq, entry.parent, entry.change.inverse());
+        self.redo_child[parent] = Some(seq);
+        self.current = parent;
+        Some(change)
+    }
+
+    pub fn redo(&mut self) -> Option<Change> {
+        let child = self.redo_child[self.current]?;
+        self.current = child;
+        Some(self.entries[child - 1].change.clone())
+    }
+
+    // 当前文本是不是十六进制显示，最初的文本总是普通的文本
+    pub fn hex(&self) -> bool {
+        self.current.checked_sub(1).is_some_and(|index| self.entries[index].hex)
+    }
+
+    // 撤销或重做修改 buffer 之后记下新的文本
+    pub fn synced(&mut self, lines: &[String], tick: usize) {
+        self.text = lines.to_vec();
+        self.tick = tick;
+    }
+
+    // 大文件在末尾读入了更多的行，之前的修改的位置不变
+    pub fn loaded(&mut self, lines: &[String]) {
+        self.text.extend_from_slice(lines);
+    }
+
+    // 序列化为文本：头部之后每个节点是 "entry 序号 父节点 行 旧行数 新行数 十六进制" 和这些行
+    pub fn serialize(&self, hash: u64) -> String {
+        let mut text = format!("{}\nhash {:016x}\ncurrent {}\nsave {}\n", MAGIC, hash, self.current, self.save_seq);
+        for entry in &self.entries {
+            let change = &entry.change;
+            text.push_str(&format!(
+                "entry {} {} {} {} {} {}\n",
+                entry.seq,
+                entry.parent,
+                change.row,
+                change.old.len(),
+                change.new.len(),
+                usize::from(entry.hex)
+            ));
+            text.push_str(&file::join_lines(&change.old));
+            text.push_str(&file::join_lines(&change.new));
+        }
+        text
+    }
+
+    // 解析撤销文件，返回撤销树和写入时文件内容的哈希
+    pub fn deserialize(text: &str, lines: &[String]) -> Result<(Self, u64), String> {
+        let invalid = || String::from("E823: Not an undo file");
+        let mut rows = text.split('\n');
+        if rows.next() != Some(MAGIC) {
+            return Err(invalid());
+        }
+        let mut field = |name: &str| -> Result<String, String> {
+            rows.next()
+                .and_then(|row| row.strip_prefix(name))
+                .and_then(|value| value.strip_prefix(' '))
+                .map(str::to_string)
+                .ok_or_else(invalid)
+        };
+        let hash = u64::from_str_radix(&field("hash")?, 16).map_err(|_| invalid())?;
+        let current: usize = field("current")?.parse().map_err(|_| invalid())?;
+        let save_seq: usize = field("save")?.parse().map_err(|_| invalid())?;
+
+        let mut tree = Self::new(lines.to_vec());
+        loop {
+            let header = match rows.next() {
+                Some("") | None => break,
+                Some(header) => header,
+            };
+            let numbers: Vec<usize> = header
+                .strip_prefix("entry ")
+                .ok_or_else(invalid)?
+                .split(' ')
+                .map(|number| number.parse().map_err(|_| invalid()))
+                .collect::<Result<_, _>>()?;
+            // 旧的撤销文件没有十六进制的标记
+            let (seq, parent, row, old, new, hex) = match *numbers.as_slice() {
+                [seq, parent, row, old, new] => (seq, parent, row, old, new, false),
+                [seq, parent, row, old, new, hex @ (0 | 1)] => (seq, parent, row, old, new, hex == 1),
+                _ => return Err(invalid()),
+            };
+            // 节点按顺序保存，父节点在前
+            if seq != tree.entries.len() + 1 || parent >= seq {
+                return Err(invalid());
+            }
+            let mut take = |count: usize| -> Result<Vec<String>, String> {
+                (0..count).map(|_| rows.next().map(str::to_string).ok_or_else(invalid)).collect()
+            };
+            let change = Change {
+                row,
+                old: take(old)?,
+                new: take(new)?,
+            };
+            tree.entries.push(UndoEntry {
+                seq,
+                parent,
+                change,
+                hex,
+            });
+            tree.redo_child.push(None);
+            tree.redo_child[parent] = Some(seq);
+        }
+        if current > tree.entries.len() || save_seq > tree.entries.len() {
+            return Err(invalid());
+        }
+        tree.current = current;
+        tree.save_seq = save_seq;
+        Ok((tree, hash))
+    }
+}
+
+// 文件的撤销文件：undodir 为 . 时是文件旁边的 .name.un~，否则是 undodir 中用 % 代替 / 的完整路径
+pub fn undo_path(undodir: &str, file: &Path) -> Option<PathBuf> {
+    if undodir == "." {
+        let name = file.file_name()?.to_string_lossy().into_owned();
+        return Some(file.with_file_name(format!(".{}.un~", name)));
+    }
+    let dir = match undodir.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+        None => PathBuf::from(undodir),
+    };
+    let full = fs::canonicalize(file).ok()?;
+    Some(dir.join(full.to_string_lossy().replace('/', "%")))
+}
+
+// 撤销文件中有文件的内容：undodir 只有自己可以访问，撤销文件的权限和原来的文件相同
+fn write_private(path: &Path, file: Option<&Path>, text: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    let mode = file.and_then(|file| fs::metadata(file).ok()).map_or(0o600, |metadata| metadata.mode() & 0o666);
+    let mut undo = OpenOptions::new().write(true).create(true).truncate(true).mode(mode).open(path)?;
+    undo.set_permissions(fs::Permissions::from_mode(mode))?;
+    undo.write_all(text.as_bytes())
+}
+
+impl Editor {
+    // 普通模式的命令结束时提交撤销节点
+    pub fn commit_undo(&mut self) {
+        let tick = self.buffer.changedtick;
+        // 大多数按键没有修改文本，不用复制所有的行
+        if self.buffer.undo.committed(tick) {
+            return;
+        }
+        let lines = self.buffer.lines().to_vec();
+        self.buffer.undo.commit(&lines, tick, self.hex);
+    }
+
+    // u、Ctrl-r、:undo、:redo，count 表示重复次数
+    pub fn undo(&mut self, redo: bool, count: usize) -> io::Result<()> {
+        self.commit_undo();
+        let mut applied = 0;
+        let mut cursor = None;
+        for _ in 0..count {
+            let change = if redo { self.buffer.undo.redo() } else { self.buffer.undo.undo() };
+            let change = match change {
+                Some(change) => change,
+                None => break,
+            };
+            self.buffer.replace_lines(change.row, change.old.len(), &change.new);
+            cursor = Some(change.cursor());
+            applied += 1;
+        }
+        let tick = self.buffer.changedtick;
+        let lines = self.buffer.lines().to_vec();
+        self.buffer.undo.synced(&lines, tick);
+        // 撤销或重做 :Hex 时回到那时的显示方式
+        self.hex = self.buffer.undo.hex();
+
+        if applied == 0 {
+            self.error(if redo { "Already at newest change" } else { "Already at oldest change" });
+            return Ok(());
+        }
+        self.buffer.modified = self.buffer.undo.current != self.buffer.undo.save_seq;
+        if let Some(cursor) = cursor {
+            self.cursor.move_to(&self.buffer, cursor)?;
+        }
+        let changes = if applied == 1 { String::from("1 change") } else { format!("{} changes", applied) };
+        let seq = self.buffer.undo.current;
+        self.messages.push(match (redo, seq) {
+            (false, 0) => format!("{}; before #1", changes),
+            (false, _) => format!("{}; before #{}", changes, seq + 1),
+            (true, _) => format!("{}; after #{}", changes, seq),
+        });
+        Ok(())
+    }
+
+    // 写入文件后保存撤销树，undofile 关闭时不保存
+    pub fn write_undo_file(&mut self) {
+        if !self.options.undofile || self.buffer.large.is_some() {
+            return;
+        }
+        let path = match self.buffer.path.as_deref().and_then(|file| undo_path(&self.options.undodir, file)) {
+            Some(path) => path,
+            None => return,
+        };
+        let text = self.buffer.undo.serialize(hash(self.buffer.lines()));
+        if write_private(&path, self.buffer.path.as_deref(), &text).is_err() {
+            self.error(format!("E828: Cannot open undo file for writing: {}", path.display()));
+        }
+    }
+
+    // 打开文件时读入撤销树，文件在编辑器外被修改过时丢弃
+    pub fn read_undo_file(&mut self) {
+        if !self.options.undofile || self.buffer.large.is_some() {
+            return;
+        }
+        let path = match self.buffer.path.as_deref().and_then(|file| undo_path(&self.options.undodir, file)) {
+            Some(path) => path,
+            None => return,
+        };
+        let text = match fs::read(&path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(_) => return,
+        };
+        match UndoTree::deserialize(&text, self.buffer.lines()) {
+            Ok((_, saved)) if saved != hash(self.buffer.lines()) => {
+                self.messages.push(String::from("File contents changed, cannot use undo info"));
+            }
+            Ok((mut tree, _)) => {
+                // 读入的文本就是写入时的文本
+                tree.current = tree.save_seq;
+                tree.synced(self.buffer.lines(), self.buffer.changedtick);
+                self.buffer.undo = tree;
+            }
+            Err(error) => self.error(error),
+        }
+    }
+}
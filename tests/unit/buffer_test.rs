@@ -1,4 +1,5 @@
 use rustvim::buffer::Buffer;
+use rustvim::mark::Position;
 
 #[test]
 fn test_new_buffer() {
@@ -108,9 +109,9 @@ fn test_line_screen_rows() {
     buffer.insert_line(1, String::from("exactly15chars!"));
     assert_eq!(buffer.line_screen_rows(1, term_width).unwrap(), 1);
 
-    // 测试需要折行的长行
+    // 测试需要折行的长行，折行也要留出行号列的宽度
     buffer.insert_line(2, String::from("this is a very long line that needs to be wrapped"));
-    assert_eq!(buffer.line_screen_rows(2, term_width).unwrap(), 3);
+    assert_eq!(buffer.line_screen_rows(2, term_width).unwrap(), 4);
 
     // 测试不存在的行
     assert_eq!(buffer.line_screen_rows(5, term_width).unwrap(), 1);
@@ -141,3 +142,22 @@ fn test_get_line_part() {
     // 测试不存在的行
     assert_eq!(buffer.get_line_part(1, 0, term_width), "");
 }
+
+#[test]
+fn test_insert_and_delete_text() {
+    let mut buffer = Buffer::new();
+    buffer.insert_line(0, String::from("abcd"));
+
+    let end = buffer.insert_text(Position::new(0, 2), "x\nyz\n");
+    assert_eq!(end, Position::new(2, 0), "应该返回插入文本之后的位置");
+    assert_eq!(buffer.get_line(0), Some(&String::from("abx")));
+    assert_eq!(buffer.get_line(1), Some(&String::from("yz")));
+    assert_eq!(buffer.get_line(2), Some(&String::from("cd")));
+
+    let start = Position::new(0, 1);
+    let end = Position::new(2, 1);
+    assert_eq!(buffer.text_range(start, end), "bx\nyz\nc");
+    assert_eq!(buffer.delete_text(start, end), "bx\nyz\nc");
+    assert_eq!(buffer.line_count(), 1, "跨行删除应该合并行");
+    assert_eq!(buffer.get_line(0), Some(&String::from("ad")));
+}
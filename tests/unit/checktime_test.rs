@@ -0,0 +1,98 @@
+use std::fs;
+
+use rustvim::editor::Editor;
+use termion::event::Key;
+
+use super::common::TempDir;
+
+#[test]
+fn test_checktime_prompt() {
+    let dir = TempDir::new("checktime");
+    let file = dir.join("text.txt");
+    fs::write(&file, "one\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile").unwrap();
+    editor.open(&file).unwrap();
+    editor.execute_command("checktime").unwrap();
+    assert!(editor.changed_prompt.is_none(), "文件没有变化");
+
+    fs::write(&file, "changed\n").unwrap();
+    editor.execute_command("checktime").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("W11: Warning: File")));
+    editor.handle_key(Key::Char('x')).unwrap();
+    assert!(editor.changed_prompt.is_some(), "其他按键重新显示提示");
+    editor.handle_key(Key::Char('o')).unwrap();
+    assert_eq!(editor.buffer.lines(), ["one"], "保留 buffer 的内容");
+    editor.execute_command("checktime").unwrap();
+    assert!(editor.changed_prompt.is_none(), "回答之后不再询问");
+
+    editor.handle_key(Key::Char('x')).unwrap();
+    fs::write(&file, "changed again\n").unwrap();
+    editor.execute_command("checktime").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("W12")));
+    editor.handle_key(Key::Char('l')).unwrap();
+    assert_eq!(editor.buffer.lines(), ["changed again"]);
+    assert!(!editor.buffer.modified);
+
+    fs::remove_file(&file).unwrap();
+    editor.execute_command("checktime").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("E211")));
+}
+
+#[test]
+fn test_reload_keeps_file_options() {
+    let dir = TempDir::new("checktime-options");
+    let file = dir.join("text.txt");
+    fs::write(&file, "caf\u{e9}\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile autoread").unwrap();
+    editor.execute_command(&format!("e ++enc=latin1 ++ff=dos {}", file.display())).unwrap();
+    fs::write(&file, "na\u{ef}ve\r\n").unwrap();
+    editor.execute_command("checktime").unwrap();
+    assert_eq!(editor.buffer.lines(), ["na\u{c3}\u{af}ve"], "按原来的编码重新读入");
+    assert_eq!((editor.options.fileencoding.as_str(), editor.options.fileformat.as_str()), ("latin1", "dos"));
+
+    editor.execute_command("set binary").unwrap();
+    fs::write(&file, b"a\xff\x00b").unwrap();
+    editor.execute_command("checktime").unwrap();
+    // 设置 binary 时 buffer 变成修改过的，选择重新读入
+    editor.handle_key(Key::Char('l')).unwrap();
+    assert!(editor.buffer.escaped, "binary 文件重新读入后仍然是 binary");
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read(&file).unwrap(), b"a\xff\x00b");
+}
+
+#[test]
+fn test_autoread() {
+    let dir = TempDir::new("autoread");
+    let file = dir.join("text.txt");
+    fs::write(&file, "one\ntwo\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile autoread").unwrap();
+    editor.open(&file).unwrap();
+    editor.handle_key(Key::Char('j')).unwrap();
+    fs::write(&file, "one\nTWO\nthree\n").unwrap();
+    editor.execute_command("checktime").unwrap();
+    assert!(editor.changed_prompt.is_none(), "没有修改时直接重新读入");
+    assert_eq!(editor.buffer.lines(), ["one", "TWO", "three"]);
+    assert_eq!(editor.cursor.row, 1, "光标留在原来的行");
+
+    // 自己写入文件不算外部修改
+    editor.handle_key(Key::Char('x')).unwrap();
+    editor.execute_command("w").unwrap();
+    assert!(!editor.check_file_changes().unwrap());
+
+    // inotify 报告文件的变化，在等待按键时检查
+    fs::write(&file, "four\n").unwrap();
+    assert!(editor.check_file_changes().unwrap());
+    assert_eq!(editor.buffer.lines(), ["four"]);
+
+    // buffer 修改过时仍然询问
+    editor.handle_key(Key::Char('x')).unwrap();
+    fs::write(&file, "five\n").unwrap();
+    editor.execute_command("checktime").unwrap();
+    assert!(editor.changed_prompt.is_some());
+}
@@ -0,0 +1,33 @@
+use super::common::editor_with_count;
+
+#[test]
+fn test_parse_range() {
+    let mut editor = editor_with_count(10);
+    editor.cursor.row = 4;
+
+    assert_eq!(editor.parse_range("set").unwrap(), (None, "set"));
+    assert_eq!(editor.parse_range("%normal").unwrap(), (Some((0, 9)), "normal"));
+    assert_eq!(editor.parse_range("3,$d").unwrap(), (Some((2, 9)), "d"));
+    assert_eq!(editor.parse_range(".,.+2d").unwrap(), (Some((4, 6)), "d"));
+    assert_eq!(editor.parse_range("-1").unwrap(), (Some((3, 3)), ""));
+    assert_eq!(editor.parse_range("7,2").unwrap(), (Some((1, 6)), ""), "反向范围应该被交换");
+    assert!(editor.parse_range("20").is_err(), "超出行数的范围应该报错");
+}
+
+#[test]
+fn test_goto_line() {
+    let mut editor = editor_with_count(5);
+    editor.execute_command("3").unwrap();
+    assert_eq!(editor.cursor.row, 2);
+
+    editor.execute_command("$").unwrap();
+    assert_eq!(editor.cursor.row, 4);
+}
+
+#[test]
+fn test_unknown_command() {
+    let mut editor = editor_with_count(1);
+    editor.execute_command("foo").unwrap();
+    assert_eq!(editor.messages, vec![String::from("E492: Not an editor command: foo")]);
+    assert!(editor.aborted, "命令出错应该中止宏");
+}
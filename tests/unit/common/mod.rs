@@ -0,0 +1,74 @@
+// 各个测试文件共用的辅助函数
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use rustvim::editor::Editor;
+use termion::event::Key;
+
+// 按顺序插入这些行的编辑器，插入的行先提交，之后的修改可以单独撤销
+pub fn editor_with_lines(lines: &[&str]) -> Editor {
+    let mut editor = Editor::new().unwrap();
+    for (i, line) in lines.iter().enumerate() {
+        editor.buffer.insert_line(i, line.to_string());
+    }
+    editor.commit_undo();
+    editor
+}
+
+// 有 count 行 "line 1"、"line 2" …… 的编辑器
+pub fn editor_with_count(count: usize) -> Editor {
+    let mut editor = Editor::new().unwrap();
+    for i in 0..count {
+        editor.buffer.insert_line(i, format!("line {}", i + 1));
+    }
+    editor
+}
+
+// 依次输入按键，\u{1b} 输入 Esc
+pub fn type_keys(editor: &mut Editor, keys: &str) {
+    for c in keys.chars() {
+        let key = if c == '\u{1b}' { Key::Esc } else { Key::Char(c) };
+        editor.handle_key(key).unwrap();
+    }
+}
+
+pub fn lines(editor: &Editor) -> Vec<String> {
+    (0..editor.buffer.line_count())
+        .filter_map(|i| editor.buffer.get_line(i).cloned())
+        .collect()
+}
+
+// 测试使用的临时目录，测试结束或失败时删除
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("rustvim-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -1,6 +1,8 @@
 use rustvim::buffer::Buffer;
 use rustvim::cursor::Cursor;
 
+use super::common::{editor_with_lines, type_keys};
+
 #[test]
 fn test_new_cursor() {
     let cursor = Cursor::new(4);
@@ -132,3 +134,58 @@ fn test_cursor_column_adjustment() {
     cursor.move_down(&buffer).unwrap();
     assert_eq!(cursor.col, 5, "光标列位置应该调整到短行的末尾");
 }
+
+#[test]
+fn test_screen_row_motions() {
+    let (term_width, _) = termion::terminal_size().unwrap();
+    let mut editor = editor_with_lines(&["", "short"]);
+    let width = editor.buffer.text_width(term_width);
+    // 按实际的宽度构造一行：两个满的屏幕行和第三个屏幕行中的 10 个字符
+    editor.buffer.set_line(0, "x".repeat(2 * width + 10));
+
+    type_keys(&mut editor, "3lgj");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (0, width + 3), "gj 移到同一行的下一个屏幕行");
+    assert_eq!(editor.cursor.screen_row, 5);
+    type_keys(&mut editor, "g$");
+    assert_eq!(editor.cursor.col, 2 * width - 1);
+    type_keys(&mut editor, "g0");
+    assert_eq!(editor.cursor.col, width);
+    type_keys(&mut editor, "gk");
+    assert_eq!(editor.cursor.col, 0);
+    type_keys(&mut editor, "3gj");
+    assert_eq!(editor.cursor.row, 1, "最后一个屏幕行之后是下一行");
+    type_keys(&mut editor, "gk");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (0, 2 * width));
+}
+
+#[test]
+fn test_horizontal_scroll() {
+    let (term_width, _) = termion::terminal_size().unwrap();
+    let mut editor = editor_with_lines(&["", "short"]);
+    let width = editor.buffer.text_width(term_width);
+    let len = 3 * width;
+    editor.buffer.set_line(0, "x".repeat(len));
+    editor.execute_command("set nowrap").unwrap();
+    assert_eq!(editor.buffer.line_screen_rows(0, term_width).unwrap(), 1);
+
+    type_keys(&mut editor, "10zl");
+    assert_eq!(editor.cursor.leftcol, 10);
+    assert_eq!(editor.cursor.col, 10, "光标跟着移到屏幕内");
+    assert_eq!(editor.cursor.screen_col, 5);
+    type_keys(&mut editor, "zh");
+    assert_eq!(editor.cursor.leftcol, 9);
+
+    type_keys(&mut editor, "$");
+    let vcol = editor.cursor.col;
+    assert_eq!(editor.cursor.leftcol, vcol - width / 2, "sidescroll=0 时光标放到屏幕中间");
+    type_keys(&mut editor, "zs");
+    assert_eq!(editor.cursor.leftcol, len - 1, "最多滚动到最后一个字符");
+    type_keys(&mut editor, "ze");
+    assert_eq!(editor.cursor.leftcol, vcol + 1 - width);
+
+    editor.execute_command("set sidescroll=1").unwrap();
+    type_keys(&mut editor, "0zl");
+    assert_eq!(editor.cursor.col, 1);
+    type_keys(&mut editor, "h");
+    assert_eq!(editor.cursor.leftcol, 0, "sidescroll=1 时每次只滚动一列");
+}
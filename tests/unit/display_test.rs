@@ -0,0 +1,124 @@
+use rustvim::buffer::Buffer;
+use rustvim::cursor::Cursor;
+use rustvim::display::{self, Display, ListChars};
+use rustvim::editor::Editor;
+
+fn shown(line: &str, display: &Display) -> String {
+    display::cells(line, display).iter().filter_map(|cell| cell.ch).collect()
+}
+
+#[test]
+fn test_tab_expansion() {
+    let display = Display::new();
+    assert_eq!(shown("a\tb", &display), format!("a{}b", " ".repeat(7)));
+    assert_eq!(display::virtual_col("a\tb", 2, &display), 8);
+    assert_eq!(display::col_at_virtual("a\tb", 5, &display), 1, "制表符中间的列属于制表符");
+    assert_eq!(shown("\u{1}x", &display), "^Ax", "控制字符应该显示为 ^X");
+    assert_eq!(display::virtual_col("中文x", 6, &display), 4, "中文字符占两列");
+}
+
+#[test]
+fn test_listchars() {
+    let mut display = Display {
+        tabstop: 4,
+        list: true,
+        listchars: ListChars::new(),
+        ..Display::new()
+    };
+    assert_eq!(shown("\tx ", &display), "^Ix $", "没有 tab 项时制表符显示为 ^I");
+
+    display.listchars = ListChars::parse("tab:>-,trail:~,nbsp:+").unwrap();
+    assert_eq!(shown("\ta\u{a0}b  ", &display), ">---a+b~~");
+
+    display.listchars = ListChars::parse("tab:<->,space:.").unwrap();
+    assert_eq!(shown("\ta b", &display), "<-->a.b");
+    assert!(ListChars::parse("tab:>").is_err());
+    assert!(ListChars::parse("foo:x").is_err());
+}
+
+#[test]
+fn test_screen_rows_with_tabs() {
+    let mut buffer = Buffer::new();
+    buffer.insert_line(0, String::from("\t\tab"));
+    // 两个制表符展开为 16 列，加上行号共 23 列
+    assert_eq!(buffer.line_screen_rows(0, 20).unwrap(), 2);
+    assert_eq!(buffer.get_line_part(0, 1, 20), " ab");
+}
+
+#[test]
+fn test_vertical_move_keeps_virtual_column() {
+    let mut buffer = Buffer::new();
+    buffer.insert_line(0, String::from("abcdefghij"));
+    buffer.insert_line(1, String::from("\tx"));
+    buffer.insert_line(2, String::from("abcdefghij"));
+    let mut cursor = Cursor::new(4);
+
+    for _ in 0..9 {
+        cursor.move_right(&buffer).unwrap();
+    }
+    cursor.move_down(&buffer).unwrap();
+    assert_eq!(cursor.col, 2, "屏幕第 9 列在制表符之后");
+    cursor.move_down(&buffer).unwrap();
+    assert_eq!(cursor.col, 9, "上下移动应该保持原来的屏幕列");
+}
+
+#[test]
+fn test_multibyte_cursor_move() {
+    let mut buffer = Buffer::new();
+    buffer.insert_line(0, String::from("中文"));
+    let mut cursor = Cursor::new(4);
+
+    cursor.move_right(&buffer).unwrap();
+    assert_eq!(cursor.col, 3, "应该按字符移动");
+    cursor.move_left(&buffer).unwrap();
+    assert_eq!(cursor.col, 0);
+}
+
+#[test]
+fn test_retab() {
+    assert_eq!(display::retab("\tfoo\tbar", 8, 4, true, false), "        foo     bar");
+    assert_eq!(display::retab("        foo", 8, 4, false, false), "        foo", "没有 ! 时只处理含制表符的空白");
+    assert_eq!(display::retab("        foo a", 8, 4, false, true), "\t\tfoo a");
+
+    let mut editor = Editor::new().unwrap();
+    editor.buffer.insert_line(0, String::from("\tx"));
+    editor.buffer.insert_line(1, String::from("\t\ty"));
+    editor.execute_command("set et").unwrap();
+    editor.execute_command("2retab 4").unwrap();
+    assert_eq!(editor.buffer.get_line(0), Some(&String::from("\tx")), "范围之外的行不变");
+    assert_eq!(editor.buffer.get_line(1), Some(&(" ".repeat(16) + "y")));
+    assert_eq!(editor.options.tabstop, 4);
+    assert_eq!(editor.buffer.display.tabstop, 4);
+
+    editor.execute_command("retab 0").unwrap();
+    assert!(editor.aborted);
+}
+
+#[test]
+fn test_linebreak_and_showbreak() {
+    let mut buffer = Buffer::new();
+    buffer.insert_line(0, String::from("  alpha beta gamma delta"));
+    // 宽度 20 减去 5 列行号，每行 15 列
+    assert_eq!(buffer.get_line_part(0, 0, 20), "  alpha beta ga");
+
+    buffer.display.linebreak = true;
+    assert_eq!(buffer.get_line_part(0, 0, 20), "  alpha beta ", "linebreak 在空格之后折行");
+    assert_eq!(buffer.get_line_part(0, 1, 20), "gamma delta");
+
+    buffer.display.showbreak = String::from(">");
+    buffer.display.breakindent = true;
+    let rows = buffer.screen_rows(0, 20);
+    assert_eq!(rows[1].prefix, 3, "后续折行缩进两列再加上 showbreak");
+    assert_eq!(rows[0].prefix, 0);
+
+    buffer.display.wrap = false;
+    assert_eq!(buffer.line_screen_rows(0, 20).unwrap(), 1, "nowrap 时不折行");
+}
+
+#[test]
+fn test_wide_char_not_split() {
+    let mut buffer = Buffer::new();
+    buffer.insert_line(0, format!("{}中文", "a".repeat(14)));
+    assert_eq!(buffer.get_line_part(0, 0, 20), "a".repeat(14), "宽字符放不下时移到下一行");
+    assert_eq!(buffer.get_line_part(0, 1, 20), "中文");
+}
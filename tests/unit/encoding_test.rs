@@ -0,0 +1,110 @@
+use std::fs;
+
+use rustvim::editor::Editor;
+use rustvim::encoding;
+use rustvim::file::{self, FileOptions};
+
+use super::common::TempDir;
+
+const FENCS: &str = "ucs-bom,utf-8,gb18030,sjis,latin1";
+
+#[test]
+fn test_detect_encodings() {
+    let detected = |bytes: &[u8]| {
+        let decoded = encoding::detect(bytes, FENCS);
+        (decoded.text, decoded.encoding, decoded.bom)
+    };
+    assert_eq!(detected("héllo\n".as_bytes()), (String::from("héllo\n"), "utf-8", false));
+    assert_eq!(detected(b"\xef\xbb\xbfabc"), (String::from("abc"), "utf-8", true));
+    assert_eq!(detected(b"\xff\xfea\x00b\x00"), (String::from("ab"), "utf-16le", true));
+    assert_eq!(detected(b"\xfe\xff\x00a\x00b"), (String::from("ab"), "utf-16", true));
+    assert_eq!(detected(b"caf\xe9\n"), (String::from("café\n"), "latin1", false), "其他编码都有错误时使用 latin1");
+
+    // 中文和日文的文件在两种编码下都没有错误
+    assert_eq!(detected(b"\xc4\xe3\xba\xc3"), (String::from("你好"), "gb18030", false));
+    assert_eq!(detected(b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd"), (String::from("こんにちは"), "sjis", false));
+    assert_eq!(detected(b"\x93\xfa\x96\x7b"), (String::from("日本"), "sjis", false));
+    assert_eq!(encoding::detect(b"\x82\xb1\x82\xf1", "utf-8,gb18030").encoding, "gb18030", "只尝试列表中的编码");
+
+    assert_eq!(encoding::encode("日本", "sjis", false).unwrap(), b"\x93\xfa\x96\x7b");
+    assert_eq!(encoding::encode("ab", "utf-16le", true).unwrap(), b"\xff\xfea\x00b\x00");
+    assert_eq!(encoding::encode("ab", "utf-16", false).unwrap(), b"\x00a\x00b");
+    assert_eq!(encoding::encode("你", "latin1", false), None);
+    assert_eq!(encoding::canonical("Shift_JIS"), Some("sjis"));
+
+    assert_eq!(file::parse_file_args("++enc=gbk a.txt").unwrap(), (FileOptions { encoding: Some("cp936"), ..FileOptions::default() }, "a.txt"));
+    assert!(file::parse_file_args("++enc=klingon a.txt").unwrap_err().starts_with("E474"));
+    assert!(file::parse_file_args("++foo a.txt").unwrap_err().starts_with("E474"));
+}
+
+#[test]
+fn test_read_and_write_encodings() {
+    let dir = TempDir::new("encoding");
+    let file = dir.join("chinese.txt");
+    fs::write(&file, b"\xc4\xe3\xba\xc3\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile").unwrap();
+    editor.open(&file).unwrap();
+    assert_eq!(editor.buffer.lines(), ["你好"], "内部使用 UTF-8");
+    assert_eq!(editor.options.fileencoding, "gb18030");
+    assert!(editor.messages[0].contains("[converted] 1L, 5B"));
+
+    editor.buffer.insert_line(1, String::from("世界"));
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read(&file).unwrap(), b"\xc4\xe3\xba\xc3\n\xca\xc0\xbd\xe7\n", "按原来的编码写入");
+
+    editor.execute_command("set fenc=latin1").unwrap();
+    assert!(editor.buffer.modified, "修改编码后需要重新写入");
+    editor.execute_command("w").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("E513")));
+    editor.execute_command("set fenc=utf8 bomb").unwrap();
+    assert_eq!(editor.options.fileencoding, "utf-8");
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read(&file).unwrap(), "\u{feff}你好\n世界\n".as_bytes());
+
+    // 用指定的编码重新读入
+    editor.execute_command("e ++enc=latin1").unwrap();
+    assert_eq!(editor.options.fileencoding, "latin1");
+    assert_eq!(editor.buffer.lines()[0], "ï»¿ä½\u{a0}å¥½", "BOM 不是 latin1 的 BOM");
+    editor.execute_command("e ++enc=utf-8").unwrap();
+    assert_eq!(editor.buffer.lines(), ["你好", "世界"]);
+    assert!(editor.options.bomb);
+    editor.execute_command("set fenc=klingon").unwrap();
+    assert!(editor.aborted);
+
+    let utf16 = dir.join("utf16.txt");
+    fs::write(&utf16, b"\xff\xfeh\x00i\x00\n\x00").unwrap();
+    editor.execute_command(&format!("e {}", utf16.display())).unwrap();
+    assert_eq!(editor.buffer.lines(), ["hi"]);
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read(&utf16).unwrap(), b"\xff\xfeh\x00i\x00\n\x00", "保留 BOM 和字节序");
+}
+
+#[test]
+fn test_illegal_bytes_not_written() {
+    let dir = TempDir::new("illegal");
+    let file = dir.join("cafe.txt");
+    fs::write(&file, b"caf\xe9\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile").unwrap();
+    editor.open(&file).unwrap();
+    editor.messages.clear();
+    editor.execute_command("e ++enc=utf-8").unwrap();
+    assert!(editor.messages[0].contains("[ILLEGAL BYTE]"));
+
+    editor.execute_command("w").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("E513")), "不合法的字节会丢失，不应该写入");
+    assert_eq!(fs::read(&file).unwrap(), b"caf\xe9\n");
+
+    editor.execute_command("w ++enc=utf-8").unwrap();
+    assert_eq!(fs::read(&file).unwrap(), "caf\u{fffd}\n".as_bytes(), "++enc 指定编码时写入");
+    fs::write(&file, b"caf\xe9\n").unwrap();
+    editor.execute_command("e! ++enc=utf-8").unwrap();
+    editor.execute_command("w!").unwrap();
+    assert_eq!(fs::read(&file).unwrap(), "caf\u{fffd}\n".as_bytes(), ":w! 写入替换字符");
+    editor.aborted = false;
+    editor.execute_command("w").unwrap();
+    assert!(!editor.aborted, "写入之后文件中不再有不合法的字节");
+}
@@ -0,0 +1,140 @@
+use std::fs;
+
+use rustvim::editor::Editor;
+use rustvim::display;
+use rustvim::file;
+
+use super::common::TempDir;
+
+#[test]
+fn test_detect_line_endings() {
+    assert_eq!(file::detect_format("a\r\nb\r\n", "unix,dos,mac"), ("dos", false));
+    assert_eq!(file::detect_format("a\nb\n", "unix,dos,mac"), ("unix", false));
+    assert_eq!(file::detect_format("a\rb\r", "unix,dos,mac"), ("mac", false));
+    assert_eq!(file::detect_format("a\r\nb\r\nc\n", "unix,dos,mac"), ("dos", true), "混合时使用最多的一种");
+    assert_eq!(file::detect_format("a\r\nb\n", "unix,dos,mac"), ("unix", true), "数量相同时使用靠前的一种");
+    assert_eq!(file::detect_format("a\r\nb\r\n", "unix"), ("unix", false));
+    assert_eq!(file::detect_format("abc", "dos,unix"), ("dos", false));
+
+    assert_eq!(file::split_lines_as("a\r\nb\nc\r\n", "dos"), ["a", "b", "c"]);
+    assert_eq!(file::split_lines_as("a\r\nb\r\n", "unix"), ["a\r", "b\r"]);
+    assert_eq!(file::split_lines_as("a\rb\nc\r", "mac"), ["a", "b", "c"]);
+    assert_eq!(file::join_lines_as(&[String::from("a"), String::from("b")], "mac"), "a\rb\r");
+}
+
+#[test]
+fn test_preserve_and_convert_line_endings() {
+    let dir = TempDir::new("fileformat");
+    let path = dir.join("windows.txt");
+    fs::write(&path, "one\r\ntwo\r\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile").unwrap();
+    editor.open(&path).unwrap();
+    assert_eq!(editor.buffer.lines(), ["one", "two"]);
+    assert_eq!(editor.options.fileformat, "dos");
+    assert!(editor.messages[0].contains("[dos] 2L, 10B"));
+    editor.buffer.insert_line(2, String::from("three"));
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one\r\ntwo\r\nthree\r\n", "按原来的换行符写入");
+
+    editor.execute_command("set ff=unix").unwrap();
+    assert!(editor.buffer.modified);
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\nthree\n");
+    editor.execute_command("w ++ff=mac").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one\rtwo\rthree\r");
+    assert_eq!(editor.options.fileformat, "unix", "++ff 只影响这次写入");
+
+    fs::write(&path, "a\r\nb\r\nc\n").unwrap();
+    editor.execute_command("e").unwrap();
+    assert!(editor.messages.iter().any(|line| line.contains("[dos] [mixed] 3L")));
+    assert_eq!(editor.buffer.lines(), ["a", "b", "c"]);
+    editor.execute_command("e ++ff=unix").unwrap();
+    assert_eq!(editor.buffer.lines(), ["a\r", "b\r", "c"]);
+    editor.execute_command("set ff=windows").unwrap();
+    assert!(editor.aborted);
+}
+
+#[test]
+fn test_binary_mode() {
+    let dir = TempDir::new("binary");
+    let path = dir.join("data.bin");
+    let original: &[u8] = b"ELF\x00\x01\xff\xfe\nabc\xc3\n\xf4\x8f\xbc\x80\r\nend";
+    fs::write(&path, original).unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile binary").unwrap();
+    editor.open(&path).unwrap();
+    assert_eq!(editor.buffer.line_count(), 4);
+    assert!(!editor.options.endofline);
+    assert!(editor.messages[0].contains("[noeol] 4L"));
+    let display = editor.buffer.display.clone();
+    let shown: String = display::cells(&editor.buffer.lines()[0], &display).iter().filter_map(|cell| cell.ch).collect();
+    assert_eq!(shown, "ELF^@^A<ff><fe>", "NUL 显示为 ^@，不是 UTF-8 的字节显示为 <xx>");
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), original, "写入原来的字节，不加最后的换行");
+
+    // 修改之后其他字节不变
+    editor.buffer.set_line(3, String::from("END"));
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"ELF\x00\x01\xff\xfe\nabc\xc3\n\xf4\x8f\xbc\x80\r\nEND");
+
+    // 不是 binary 模式时按文本读入并加上最后的换行
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile").unwrap();
+    editor.open(&path).unwrap();
+    assert!(!editor.options.binary && editor.options.fileencoding == "latin1");
+    editor.execute_command("e ++bin").unwrap();
+    assert!(editor.options.binary);
+    assert_eq!(editor.buffer.lines()[1], "abc\u{10ffc3}");
+    editor.execute_command("set eol").unwrap();
+    editor.execute_command("w").unwrap();
+    assert!(fs::read(&path).unwrap().ends_with(b"END\n"));
+}
+
+#[test]
+fn test_private_use_characters() {
+    let dir = TempDir::new("private-use");
+    let path = dir.join("text.txt");
+    // U+10FFC3 是 binary 模式中表示字节 0xc3 的字符，文本文件中是普通的字符
+    fs::write(&path, "a\u{10ffc3}b\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile").unwrap();
+    editor.open(&path).unwrap();
+    assert_eq!(editor.buffer.lines(), ["a\u{10ffc3}b"]);
+    editor.buffer.insert_line(1, String::from("c"));
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\u{10ffc3}b\nc\n", "不是 binary 模式时按原样写入");
+    editor.execute_command("w ++bin").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\u{10ffc3}b\nc\n", "++bin 写入也不还原为字节");
+}
+
+#[test]
+fn test_filetype_from_path() {
+    assert_eq!(file::detect_filetype(std::path::Path::new("src/main.rs")), "rust");
+    assert_eq!(file::detect_filetype(std::path::Path::new("setup.PY")), "python");
+    assert_eq!(file::detect_filetype(std::path::Path::new("Makefile")), "make");
+    assert_eq!(file::detect_filetype(std::path::Path::new("notes.txt")), "");
+
+    let dir = TempDir::new("filetype");
+    let rust = dir.join("main.rs");
+    let text = dir.join("notes.txt");
+    fs::write(&rust, "fn main() {}\n").unwrap();
+    fs::write(&text, "fn main() {}\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile").unwrap();
+    editor.open(&rust).unwrap();
+    assert_eq!(editor.options.filetype, "rust");
+    assert!(editor.buffer.highlighter.is_some() && editor.buffer.tree.is_some(), "打开文件时加载语法和语法树");
+    assert!(editor.indent_rule().is_some());
+
+    // :e 另一个文件时不沿用上一个文件的类型
+    editor.execute_command(&format!("e {}", text.display())).unwrap();
+    assert_eq!(editor.options.filetype, "");
+    assert!(editor.buffer.highlighter.is_none() && editor.buffer.tree.is_none());
+    editor.execute_command(&format!("e {}", rust.display())).unwrap();
+    assert!(editor.buffer.highlighter.is_some() && editor.buffer.tree.is_some(), "每个 buffer 重新建立语法状态");
+}
@@ -0,0 +1,71 @@
+use rustvim::editor::Editor;
+use rustvim::filter;
+
+use super::common::{editor_with_lines, type_keys};
+
+// 用 sh 执行过滤命令，不受测试环境的 $SHELL 影响
+fn sh_editor(lines: &[&str]) -> Editor {
+    let mut editor = editor_with_lines(lines);
+    type_keys(&mut editor, ":set shell=sh\n");
+    editor
+}
+
+#[test]
+fn test_filter_lines() {
+    let mut editor = sh_editor(&["c", "a", "b", "x"]);
+    type_keys(&mut editor, ":1,3!sort\n");
+    assert_eq!(editor.buffer.lines()[..4], ["a", "b", "c", "x"]);
+    assert_eq!(editor.messages, ["3 lines filtered"]);
+    type_keys(&mut editor, "u");
+    assert_eq!(editor.buffer.lines()[..4], ["c", "a", "b", "x"], "过滤作为一次修改撤销");
+
+    // !{motion} 在命令行中填入范围
+    type_keys(&mut editor, "j!j");
+    assert_eq!(editor.command_line, ".,.+1!");
+    type_keys(&mut editor, "tr a-z A-Z\n");
+    assert_eq!(editor.buffer.lines()[..4], ["c", "A", "B", "x"]);
+    assert_eq!(editor.cursor.row, 1);
+    type_keys(&mut editor, "G!!");
+    assert_eq!(editor.command_line, ".!");
+    type_keys(&mut editor, "echo y; echo z\n");
+    assert_eq!(editor.buffer.lines()[3..], ["y", "z"], "输出的行数可以不同");
+    type_keys(&mut editor, "ggVj!");
+    assert_eq!(editor.command_line, "'<,'>!");
+    type_keys(&mut editor, "sort -r\n");
+    assert_eq!(editor.buffer.lines()[..2], ["c", "A"]);
+
+    // 命令失败时不修改 buffer，显示标准错误和退出状态
+    type_keys(&mut editor, ":%!echo oops >&2; exit 3\n");
+    assert_eq!(editor.buffer.lines()[..2], ["c", "A"]);
+    assert_eq!(editor.messages, ["oops", "shell returned 3"]);
+    type_keys(&mut editor, ":!echo hi\n");
+    assert_eq!(editor.messages, ["hi"], "没有范围时只显示输出");
+    type_keys(&mut editor, ":%!true\n");
+    assert_eq!(editor.buffer.lines(), [""], "输出为空时删除这些行");
+}
+
+#[test]
+fn test_format_lines() {
+    let mut editor = sh_editor(&["one", "two", "three"]);
+    type_keys(&mut editor, ":set formatprg=tr\\ a-z\\ A-Z\n");
+    assert_eq!(editor.options.formatprg, "tr a-z A-Z");
+    type_keys(&mut editor, "gqj");
+    assert_eq!(editor.buffer.lines()[..3], ["ONE", "TWO", "three"]);
+    assert_eq!(editor.cursor.row, 1, "光标移到格式化后的最后一行");
+    type_keys(&mut editor, "jgqq");
+    assert_eq!(editor.buffer.lines()[2], "THREE");
+    type_keys(&mut editor, "uu");
+    assert_eq!(editor.buffer.lines()[..3], ["one", "two", "three"]);
+
+    // 没有 formatprg 时按 textwidth 排列
+    let mut editor = sh_editor(&["  aaa bbb ccc", "ddd", "", "eee   fff"]);
+    type_keys(&mut editor, ":set formatprg= textwidth=10\n");
+    type_keys(&mut editor, "gqG");
+    assert_eq!(editor.buffer.lines()[..4], ["  aaa bbb", "  ccc ddd", "", "eee fff"]);
+    assert_eq!(editor.cursor.row, 3);
+    type_keys(&mut editor, "ggVjgq");
+    assert_eq!(editor.buffer.lines()[..2], ["  aaa bbb", "  ccc ddd"]);
+
+    let lines = vec![String::from("a verylongword b")];
+    assert_eq!(filter::format_text(&lines, 5, 8), ["a", "verylongword", "b"]);
+}
@@ -0,0 +1,145 @@
+use rustvim::fold::{self, Level};
+use rustvim::mark::Position;
+
+use super::common::{editor_with_lines, type_keys};
+
+fn lines(text: &[&str]) -> Vec<String> {
+    text.iter().map(|line| line.to_string()).collect()
+}
+
+const CODE: [&str; 8] = [
+    "fn main() {",
+    "    let x = 1;",
+    "    if x > 0 {",
+    "        print();",
+    "    }",
+    "}",
+    "",
+    "fn other() {}",
+];
+
+#[test]
+fn test_levels_to_ranges() {
+    let levels = [Level::Depth(0), Level::Depth(1), Level::Depth(2), Level::Depth(2), Level::Depth(1), Level::Depth(0)];
+    assert_eq!(fold::from_levels(&levels), vec![(2, 3), (1, 4)]);
+    let levels = [Level::Start(1), Level::Depth(1), Level::Start(1), Level::Depth(1), Level::End(1), Level::Depth(0)];
+    assert_eq!(fold::from_levels(&levels), vec![(0, 1), (2, 4)], ">1 结束同级的折叠并开始新的折叠");
+
+    let levels = fold::indent_levels(&lines(&CODE), 8, 4);
+    assert_eq!(fold::from_levels(&levels), vec![(1, 4)], "只有一行的折叠不计算在内");
+
+    let text = lines(&["a {{{", "b", "c {{{", "d }}}", "e }}}", "f"]);
+    assert_eq!(fold::from_levels(&fold::marker_levels(&text, "{{{,}}}")), vec![(2, 3), (0, 4)]);
+}
+
+#[test]
+fn test_manual_folds() {
+    let mut editor = editor_with_lines(&CODE);
+    type_keys(&mut editor, "jzfj");
+    assert_eq!(editor.buffer.closed_fold(2), Some((1, 2)), "zfj 创建关闭的折叠");
+    assert_eq!(editor.buffer.line_screen_rows(1, 80).unwrap(), 1);
+    assert_eq!(editor.buffer.line_screen_rows(2, 80).unwrap(), 0, "折叠中的其他行不显示");
+    assert_eq!(editor.buffer.folds().text(1, 2, "    let x = 1;"), "+--  2 lines: let x = 1;");
+
+    type_keys(&mut editor, "j");
+    assert_eq!(editor.cursor.row, 3, "j 跳过关闭的折叠");
+    type_keys(&mut editor, "k");
+    assert_eq!(editor.cursor.row, 1, "k 停在折叠的开始行");
+
+    type_keys(&mut editor, "zo");
+    assert_eq!(editor.buffer.closed_fold(1), None);
+    type_keys(&mut editor, "jza");
+    assert_eq!(editor.buffer.closed_fold(2), Some((1, 2)));
+    assert_eq!(editor.cursor.row, 1, "关闭折叠时光标移到开始行");
+
+    editor.buffer.insert_line(0, String::from("// header"));
+    assert_eq!(editor.buffer.closed_fold(2), Some((2, 3)), "折叠跟随文本移动");
+
+    type_keys(&mut editor, "ggzc");
+    assert!(editor.messages.iter().any(|message| message.starts_with("E490")));
+
+    // dd 删除整个关闭的折叠
+    editor.cursor.move_to(&editor.buffer, Position::new(2, 0)).unwrap();
+    type_keys(&mut editor, "dd");
+    assert_eq!(editor.buffer.line_count(), 7);
+    assert_eq!(editor.buffer.get_line(2).unwrap(), "        print();");
+    assert_eq!(editor.buffer.folds().folds(), vec![], "被删除的折叠也被删除");
+}
+
+#[test]
+fn test_visual_and_nested_folds() {
+    let mut editor = editor_with_lines(&CODE);
+    type_keys(&mut editor, "jjjzfjggVGzf");
+    assert_eq!(editor.buffer.closed_fold(3), Some((0, 7)));
+    assert!(matches!(editor.mode, rustvim::editor::Mode::Normal));
+
+    type_keys(&mut editor, "zo");
+    assert_eq!(editor.buffer.closed_fold(3), Some((3, 4)), "zo 只打开外层的折叠");
+    type_keys(&mut editor, "zR");
+    assert_eq!(editor.buffer.closed_fold(3), None);
+    assert_eq!(editor.options.foldlevel, 2);
+    type_keys(&mut editor, "zM");
+    assert_eq!(editor.buffer.closed_fold(5), Some((0, 7)));
+    assert_eq!(editor.options.foldlevel, 0);
+
+    editor.execute_command("set fdm=indent").unwrap();
+    type_keys(&mut editor, "zf");
+    type_keys(&mut editor, "j");
+    assert!(editor.messages.iter().any(|message| message.starts_with("E350")));
+}
+
+#[test]
+fn test_computed_folds() {
+    let mut editor = editor_with_lines(&CODE);
+    editor.execute_command("set sw=4 fdm=indent").unwrap();
+    assert_eq!(editor.buffer.closed_fold(2), Some((1, 4)), "foldlevel=0 时所有折叠都关闭");
+
+    editor.execute_command("set fdl=1").unwrap();
+    assert_eq!(editor.buffer.closed_fold(2), None);
+    assert_eq!(editor.buffer.closed_fold(3), None, "只有一行的折叠不能关闭");
+
+    // zj、zk 在折叠之间移动
+    let mut editor = editor_with_lines(&["x", "a {{{", "b", "c }}}", "y", "d {{{1", "e", "}}}", "z"]);
+    editor.execute_command("set fdm=marker").unwrap();
+    assert_eq!(editor.buffer.closed_fold(2), Some((1, 3)));
+    assert_eq!(editor.buffer.folds().text(5, 7, "d {{{1"), "+--  3 lines: d", "去掉折叠标记");
+    type_keys(&mut editor, "zRzj");
+    assert_eq!(editor.cursor.row, 1);
+    type_keys(&mut editor, "zj");
+    assert_eq!(editor.cursor.row, 5);
+    type_keys(&mut editor, "Gzk");
+    assert_eq!(editor.cursor.row, 7);
+    type_keys(&mut editor, "dzk");
+    assert_eq!(editor.buffer.line_count(), 4, "zk 可以作为操作符的移动，按行作用");
+
+    // 修改文本后重新计算，打开的折叠保持打开
+    let mut editor = editor_with_lines(&["# a", "1", "# b", "2"]);
+    editor.execute_command("set fde=^# fdm=expr fdl=1").unwrap();
+    assert_eq!(editor.buffer.folds().folds().len(), 2);
+    editor.buffer.insert_line(4, String::from("# c"));
+    editor.buffer.insert_line(5, String::from("3"));
+    let folds = editor.buffer.folds().folds();
+    assert_eq!(folds.len(), 3);
+    assert!(folds.iter().all(|fold| !fold.closed));
+
+    editor.execute_command("set fdm=fancy").unwrap();
+    assert!(editor.aborted);
+    editor.execute_command("set fmr=abc").unwrap();
+    assert!(editor.aborted);
+}
+
+#[test]
+fn test_syntax_folds() {
+    let mut editor = editor_with_lines(&CODE);
+    editor.execute_command("set ft=rust fdm=syntax fdl=1").unwrap();
+    let ranges: Vec<(usize, usize)> = editor.buffer.folds().folds().iter().map(|fold| (fold.start, fold.end)).collect();
+    assert_eq!(ranges, vec![(0, 5), (2, 4)], "多行的语法节点，开始行相同时取最外层");
+    assert_eq!(editor.buffer.folds().column(3, 3), "|+ ", "foldlevel=1 时第二层的折叠关闭");
+    assert_eq!(editor.buffer.folds().column(2, 1), "|");
+
+    // 没有语法树时使用带 fold 参数的 region
+    let mut editor = editor_with_lines(&["int x;", "/* a", " * b */", "int y;"]);
+    editor.execute_command("set ft=c fdm=syntax").unwrap();
+    assert_eq!(editor.buffer.closed_fold(2), Some((1, 2)));
+    assert_eq!(editor.buffer.folds().column(1, 2), "+ ");
+}
@@ -0,0 +1,71 @@
+use rustvim::gutter::Gutter;
+
+use super::common::editor_with_count;
+
+#[test]
+fn test_number_width() {
+    let mut gutter = Gutter::new();
+    assert_eq!(gutter.number_width(999), 4);
+    assert_eq!(gutter.number_width(1000), 5, "超过 999 行时行号列变宽");
+    assert_eq!(gutter.number_text(9, 0, 1000), "  10 ");
+
+    gutter.relativenumber = true;
+    assert_eq!(gutter.number_text(9, 12, 20), "  3 ");
+    assert_eq!(gutter.number_text(12, 12, 20), "13  ", "混合行号的光标行显示左对齐的行号");
+    gutter.number = false;
+    assert_eq!(gutter.number_text(12, 12, 20), "  0 ");
+
+    gutter.relativenumber = false;
+    assert_eq!(gutter.width(20, true), 2, "没有行号时只有标记列");
+    gutter.signcolumn = String::from("no");
+    gutter.foldcolumn = 3;
+    assert_eq!(gutter.width(20, true), 3);
+}
+
+#[test]
+fn test_cursor_follows_gutter() {
+    let mut editor = editor_with_count(1000);
+    editor.cursor.move_to(&editor.buffer, rustvim::mark::Position::new(0, 2)).unwrap();
+    assert_eq!(editor.cursor.screen_col, 8, "5 列行号之后的第 3 列");
+
+    editor.execute_command("set nonumber").unwrap();
+    assert_eq!(editor.buffer.gutter_width(), 0);
+    assert_eq!(editor.cursor.screen_col, 3);
+
+    editor.execute_command("set fdc=2 scl=yes").unwrap();
+    assert_eq!(editor.cursor.screen_col, 7);
+    editor.execute_command("set fdc=13").unwrap();
+    assert!(editor.aborted);
+    editor.execute_command("set scl=maybe").unwrap();
+    assert!(editor.aborted);
+    editor.execute_command("set nuw=0").unwrap();
+    assert!(editor.aborted);
+}
+
+#[test]
+fn test_signs() {
+    let mut editor = editor_with_count(5);
+    editor.execute_command("sign define err text=>> texthl=Error").unwrap();
+    editor.execute_command("sign place 1 line=3 name=err").unwrap();
+    assert_eq!(editor.buffer.gutter_width(), 6, "signcolumn=auto 时有标记才显示标记列");
+    assert_eq!(editor.buffer.signs.at(2).unwrap().text, ">>");
+
+    // 标记跟随文本移动
+    editor.buffer.insert_line(0, String::from("new"));
+    assert!(editor.buffer.signs.at(3).is_some());
+    editor.buffer.remove_line(3);
+    assert!(editor.buffer.signs.at(3).is_some(), "删除所在行后标记移到下一行");
+
+    editor.execute_command("sign place").unwrap();
+    assert_eq!(editor.messages, ["--- Signs ---", "    line=4  id=1  name=err"]);
+
+    editor.execute_command("sign place 2 line=1 name=nosuch").unwrap();
+    assert!(editor.aborted);
+    editor.execute_command("sign define bad text=abc").unwrap();
+    assert!(editor.aborted, "标记文本最多两列");
+
+    editor.execute_command("sign unplace 1").unwrap();
+    assert_eq!(editor.buffer.gutter_width(), 4);
+    editor.execute_command("sign unplace 1").unwrap();
+    assert!(editor.aborted);
+}
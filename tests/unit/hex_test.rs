@@ -0,0 +1,102 @@
+use std::fs;
+
+use rustvim::editor::Editor;
+use rustvim::hex;
+use termion::event::Key;
+
+use super::common::{type_keys, TempDir};
+
+#[test]
+fn test_dump_format() {
+    let lines = hex::dump(b"Hello, world!\n\x00\x01\xffmore");
+    assert_eq!(lines[0], "00000000: 4865 6c6c 6f2c 2077 6f72 6c64 210a 0001  Hello, world!...");
+    assert_eq!(lines[1], "00000010: ff6d 6f72 65                             .more");
+    assert_eq!(hex::parse(&lines, 0).unwrap(), b"Hello, world!\n\x00\x01\xffmore");
+
+    assert_eq!(hex::byte_at(hex::hex_col(3)), Some((3, false, 0)));
+    assert_eq!(hex::byte_at(hex::hex_col(3) + 1), Some((3, false, 1)));
+    assert_eq!(hex::byte_at(hex::hex_col(3) + 2), Some((3, false, 1)), "分组之间的空格属于前一个字节");
+    assert_eq!(hex::byte_at(hex::ascii_col(15)), Some((15, true, 0)));
+    assert_eq!(hex::byte_at(5), None);
+}
+
+#[test]
+fn test_hex_editing() {
+    let dir = TempDir::new("hex");
+    let path = dir.join("data.bin");
+    fs::write(&path, b"ABCDEFGHIJKLMNOPQR\xff").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile binary").unwrap();
+    editor.open(&path).unwrap();
+    editor.execute_command("Hex").unwrap();
+    assert!(editor.hex && !editor.buffer.modified);
+    assert_eq!(editor.buffer.line_count(), 2);
+    assert_eq!(editor.cursor.col, hex::hex_col(0));
+
+    // 在十六进制列中修改，ASCII 列同时更新
+    type_keys(&mut editor, "lr6");
+    assert_eq!(editor.buffer.lines()[0], "00000000: 4162 4344 4546 4748 494a 4b4c 4d4e 4f50  AbCDEFGHIJKLMNOP");
+    type_keys(&mut editor, "18l");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (1, hex::hex_col(2)), "按字节移到下一行，不超过最后一个字节");
+    type_keys(&mut editor, "$");
+    assert_eq!(editor.cursor.col, hex::ascii_col(2), "移动后光标放到字节上");
+    type_keys(&mut editor, "hr!");
+    assert_eq!(editor.buffer.lines()[1], "00000010: 5121 ff                                  Q!.");
+    type_keys(&mut editor, "3rx");
+    assert!(editor.aborted, "不能替换不存在的字节");
+    type_keys(&mut editor, "k0");
+    assert_eq!(editor.cursor.col, hex::hex_col(0));
+    type_keys(&mut editor, "r0");
+    assert!(editor.buffer.lines()[0].contains("0162"));
+
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"\x01bCDEFGHIJKLMNOPQ!\xff", "写入修改后的字节");
+
+    editor.execute_command("Hex").unwrap();
+    assert!(!editor.hex);
+    assert_eq!(editor.buffer.lines(), ["\u{1}bCDEFGHIJKLMNOPQ!\u{10ffff}"]);
+    type_keys(&mut editor, "lrB");
+    assert_eq!(editor.buffer.lines()[0], "\u{1}BCDEFGHIJKLMNOPQ!\u{10ffff}", "普通的 r 替换字符");
+}
+
+#[test]
+fn test_hex_undo_and_invalid_dump() {
+    let dir = TempDir::new("hex-undo");
+    let path = dir.join("hello.txt");
+    fs::write(&path, "hello world\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile").unwrap();
+    editor.open(&path).unwrap();
+    type_keys(&mut editor, ":Hex\n");
+    assert!(editor.hex);
+    // 撤销 :Hex 时回到普通的文本，写入的还是原来的内容
+    type_keys(&mut editor, "u");
+    assert!(!editor.hex);
+    assert_eq!(editor.buffer.lines(), ["hello world"]);
+    type_keys(&mut editor, ":w\n");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "hello world\n");
+    editor.handle_key(Key::Ctrl('r')).unwrap();
+    assert!(editor.hex, "重做 :Hex 时回到十六进制显示");
+    assert!(editor.buffer.lines()[0].starts_with("00000000: 6865 6c6c"));
+
+    // 破坏了格式的 dump 不能写入，也不能转换回文本
+    type_keys(&mut editor, "0x:w\n");
+    assert!(editor.messages.iter().any(|line| line.starts_with("Invalid hex dump in line 1")));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "hello world\n");
+    type_keys(&mut editor, ":Hex\n");
+    assert!(editor.hex);
+    type_keys(&mut editor, "u:Hex\n");
+    assert!(!editor.hex);
+    assert_eq!(editor.buffer.lines(), ["hello world"]);
+
+    let lines = hex::dump(b"0123456789abcdefXYZ");
+    let parse = |lines: &[String]| hex::parse(lines, 0);
+    assert_eq!(parse(&lines[1..]).unwrap_err(), format!("Invalid hex dump in line 1: {}", lines[1]), "偏移不对");
+    assert!(parse(&[lines[0].replacen("3031", "30  ", 1), lines[1].clone()]).is_err(), "字节不连续");
+    assert!(parse(&[lines[0].replacen("3031 ", "3031x", 1), lines[1].clone()]).is_err(), "分组之间不是空格");
+    assert!(parse(&[lines[1].replacen("10", "00", 1), lines[0].replacen("00000000", "00000010", 1)]).is_err(), "只有最后一行可以不满");
+    assert_eq!(hex::parse(&lines[1..], 1).unwrap(), b"XYZ", "范围从第 2 行开始");
+    assert_eq!(parse(&[String::new()]).unwrap(), b"");
+}
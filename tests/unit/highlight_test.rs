@@ -0,0 +1,69 @@
+use rustvim::editor::Editor;
+use rustvim::highlight::{Color, ColorDepth, Highlights};
+
+#[test]
+fn test_highlight_command() {
+    let mut highlights = Highlights::new();
+    highlights.command("Comment ctermfg=DarkGreen guifg=#80a0ff gui=bold,italic").unwrap();
+    let comment = highlights.get("Comment").unwrap();
+    assert_eq!(comment.ctermfg, Some(Color::Ansi(2)));
+    assert_eq!(comment.guifg, Some(Color::Rgb(0x80, 0xa0, 0xff)));
+    assert!(comment.gui.bold && comment.gui.italic);
+    assert_eq!(
+        highlights.command("Comment").unwrap(),
+        ["Comment        xxx ctermfg=2 gui=bold,italic guifg=#80a0ff"]
+    );
+    assert_eq!(highlights.command("String").unwrap(), ["String         xxx links to Constant"]);
+
+    assert!(highlights.command("Comment ctermfg=purple").is_err(), "未知的颜色名");
+    assert!(highlights.command("Comment guifg=#12").is_err());
+    assert!(highlights.command("Comment ctermfg").is_err(), "缺少等号");
+    assert!(highlights.command("NoSuchGroup").is_err());
+
+    highlights.command("clear").unwrap();
+    assert_eq!(highlights.get("Comment").unwrap().ctermfg, Some(Color::Ansi(4)), "clear 恢复默认设置");
+}
+
+#[test]
+fn test_color_downgrade() {
+    let mut highlights = Highlights::new();
+    highlights.command("Foo ctermfg=196 guifg=#ff0000 guibg=#000080 cterm=bold").unwrap();
+
+    highlights.depth = ColorDepth::TrueColor;
+    assert_eq!(highlights.start("Foo"), "\x1b[1;38;2;255;0;0;48;2;0;0;128m", "真彩色优先使用 gui 颜色");
+    highlights.depth = ColorDepth::Color256;
+    assert_eq!(highlights.start("Foo"), "\x1b[1;38;5;196;48;5;18m", "没有 ctermbg 时把 guibg 降级为 256 色");
+    highlights.depth = ColorDepth::Color16;
+    assert_eq!(highlights.start("Foo"), "\x1b[1;91;44m", "16 色终端使用最接近的颜色");
+
+    highlights.command("link Bar Foo").unwrap();
+    assert_eq!(highlights.start("Bar"), highlights.start("Foo"));
+    highlights.command("Bar ctermfg=1").unwrap();
+    assert_eq!(highlights.start("Bar"), "\x1b[31m", "设置颜色后不再使用链接");
+    assert_eq!(highlights.start("Normal"), "", "没有设置的组不输出转义序列");
+    assert_eq!(highlights.end(), "\x1b[m");
+
+    // 结束其他组时回到 Normal 的颜色
+    highlights.command("Normal ctermfg=7 ctermbg=0").unwrap();
+    assert_eq!(highlights.end(), "\x1b[m\x1b[37;40m");
+}
+
+#[test]
+fn test_colorscheme() {
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("colorscheme desert").unwrap();
+    assert!(!editor.aborted);
+    assert_eq!(editor.highlights.colors_name, "desert");
+    assert_eq!(editor.highlights.get("CursorLine").unwrap().ctermbg, Some(Color::Ansi(236)));
+
+    editor.execute_command("colo").unwrap();
+    assert_eq!(editor.messages, ["desert"]);
+    editor.execute_command("colo nosuchscheme").unwrap();
+    assert!(editor.aborted);
+    assert_eq!(editor.highlights.colors_name, "desert");
+
+    editor.execute_command("colorscheme default").unwrap();
+    assert_eq!(editor.highlights.get("CursorLine").unwrap().ctermbg, None);
+    editor.execute_command("hi LineNr ctermfg=Yellow").unwrap();
+    assert_eq!(editor.highlights.get("LineNr").unwrap().ctermfg, Some(Color::Ansi(11)));
+}
@@ -0,0 +1,122 @@
+use rustvim::indent::{self, BraceIndent, IndentRule, PythonIndent, YamlIndent};
+
+use super::common::{editor_with_lines, lines, type_keys};
+
+#[test]
+fn test_indent_helpers() {
+    assert_eq!(indent::display_width("\tab", 4), 6);
+    assert_eq!(indent::indent_width("  \t x", 8), 9);
+    assert_eq!(indent::indent_string(10, 8, false), "\t  ");
+    assert_eq!(indent::indent_string(3, 8, true), "   ");
+    assert_eq!(indent::shift_width(5, 4, true, false), 9);
+    assert_eq!(indent::shift_width(5, 4, true, true), 8, "shiftround 应该对齐到 shiftwidth 的倍数");
+    assert_eq!(indent::shift_width(5, 4, false, true), 4);
+    assert_eq!(indent::shift_width(2, 4, false, false), 0);
+}
+
+#[test]
+fn test_filetype_rules() {
+    let brace = BraceIndent;
+    assert_eq!(brace.indent(Some("fn main() {"), "", 8, 4), 4);
+    assert_eq!(brace.indent(Some("    foo();"), "}", 8, 4), 0);
+
+    let python = PythonIndent;
+    assert_eq!(python.indent(Some("    if x:"), "", 8, 4), 8);
+    assert_eq!(python.indent(Some("        return 1"), "", 8, 4), 4);
+    assert_eq!(python.indent(Some("        a = 1"), "        else:", 8, 4), 4);
+    assert_eq!(python.indent(Some("        a = 1"), "else:", 8, 4), 0, "已经减少缩进的 else 保持不变");
+    assert_eq!(python.indent(Some("        a = 1"), "b = 2", 8, 4), 0, "块结束后的行保持原来的缩进");
+
+    let yaml = YamlIndent;
+    assert_eq!(yaml.indent(Some("key:"), "", 8, 2), 2);
+    assert_eq!(yaml.indent(Some("  - name: a"), "", 8, 2), 4, "列表项的内容应该和 - 后面的文本对齐");
+}
+
+#[test]
+fn test_autoindent() {
+    let mut editor = editor_with_lines(&["    foo"]);
+    editor.execute_command("set ai").unwrap();
+
+    type_keys(&mut editor, "$i\nbar\n\nbaz\u{1b}");
+    assert_eq!(lines(&editor), vec!["    foo", "    bar", "", "    baz"], "只有自动缩进的行应该被清空");
+
+    type_keys(&mut editor, "Oqux\u{1b}");
+    assert_eq!(lines(&editor)[3], "    qux", "O 应该复制下一行的缩进");
+}
+
+#[test]
+fn test_no_autoindent() {
+    let mut editor = editor_with_lines(&["    foo"]);
+
+    type_keys(&mut editor, "obar\u{1b}");
+    assert_eq!(lines(&editor), vec!["    foo", "bar"]);
+}
+
+#[test]
+fn test_smartindent() {
+    let mut editor = editor_with_lines(&["if (x) {"]);
+    editor.execute_command("set si sw=4 et").unwrap();
+
+    type_keys(&mut editor, "$i\nfoo();\n}\u{1b}");
+    assert_eq!(lines(&editor), vec!["if (x) {", "    foo();", "}"], "输入 }} 时应该减少缩进");
+}
+
+#[test]
+fn test_filetype_indent() {
+    let mut editor = editor_with_lines(&["def f(x):"]);
+    editor.execute_command("set ft=python sw=4 et").unwrap();
+
+    type_keys(&mut editor, "oif x:\nreturn 1\nelse:\npass\u{1b}");
+    assert_eq!(
+        lines(&editor),
+        vec!["def f(x):", "    if x:", "        return 1", "    else:", "        pass"]
+    );
+}
+
+#[test]
+fn test_equal_operator() {
+    let mut editor = editor_with_lines(&["fn main() {", "let a = 1;", "      if a {", "b();", "}", "   }"]);
+    editor.execute_command("set sw=4 et").unwrap();
+
+    type_keys(&mut editor, "=G");
+    assert_eq!(
+        lines(&editor),
+        vec!["fn main() {", "    let a = 1;", "    if a {", "        b();", "    }", "}"]
+    );
+    assert_eq!(editor.messages.last().map(|m| m.as_str()), Some("6 lines indented "));
+
+    type_keys(&mut editor, "gg2j>>");
+    assert_eq!(lines(&editor)[2], "        if a {");
+    type_keys(&mut editor, "==");
+    assert_eq!(lines(&editor)[2], "    if a {", "== 应该重新缩进当前行");
+}
+
+#[test]
+fn test_equal_keeps_valid_python() {
+    let text = ["if a:", "\tb = 1", "c = 2", "def f():", "\tif b:", "\t\treturn 1", "\treturn 2", "x = [", "    1,", "]"];
+    let mut editor = editor_with_lines(&text);
+    editor.execute_command("set ft=python sw=8 noet").unwrap();
+
+    type_keys(&mut editor, "=G");
+    assert_eq!(lines(&editor), text, "缩进正确的文件重新缩进后不变");
+}
+
+#[test]
+fn test_shift_operators() {
+    let mut editor = editor_with_lines(&["a", "", "b", "c"]);
+    editor.execute_command("set sw=2 et").unwrap();
+
+    type_keys(&mut editor, "3>>");
+    assert_eq!(lines(&editor), vec!["  a", "", "  b", "c"], "空行不应该被移动");
+
+    type_keys(&mut editor, ">j.");
+    assert_eq!(lines(&editor), vec!["      a", "", "  b", "c"], ". 应该重复 >j");
+
+    type_keys(&mut editor, "<<");
+    assert_eq!(lines(&editor)[0], "    a");
+    assert_eq!(editor.cursor.col, 4, "光标应该在第一个非空字符上");
+
+    editor.execute_command("set noet sw=4 ts=8").unwrap();
+    type_keys(&mut editor, "Vj>");
+    assert_eq!(lines(&editor)[0], "\ta", "noexpandtab 时应该使用制表符");
+}
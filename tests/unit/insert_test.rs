@@ -0,0 +1,179 @@
+use rustvim::editor::{Editor, Mode};
+use termion::event::Key;
+
+use super::common::{editor_with_lines, lines, type_keys};
+
+fn press(editor: &mut Editor, keys: &[Key]) {
+    for key in keys {
+        editor.handle_key(*key).unwrap();
+    }
+}
+
+#[test]
+fn test_backspace_joins_lines() {
+    let mut editor = editor_with_lines(&["ab", "cd"]);
+
+    type_keys(&mut editor, "ji");
+    press(&mut editor, &[Key::Backspace]);
+    assert_eq!(lines(&editor), vec!["abcd"], "backspace 包含 eol 时应该合并行");
+    assert_eq!(editor.cursor.col, 2);
+
+    press(&mut editor, &[Key::Ctrl('h')]);
+    assert_eq!(lines(&editor), vec!["acd"], "Ctrl-h 和退格相同");
+}
+
+#[test]
+fn test_backspace_multibyte() {
+    let mut editor = editor_with_lines(&["中x"]);
+
+    type_keys(&mut editor, "licafé");
+    press(&mut editor, &[Key::Backspace]);
+    assert_eq!(lines(&editor)[0], "中cafx", "退格删除整个多字节字符");
+    press(&mut editor, &[Key::Backspace, Key::Backspace, Key::Backspace, Key::Backspace]);
+    assert_eq!(lines(&editor)[0], "x");
+    assert_eq!(editor.cursor.col, 0);
+}
+
+#[test]
+fn test_backspace_option() {
+    let mut editor = editor_with_lines(&["ab", "cd"]);
+    editor.execute_command("set backspace=").unwrap();
+
+    type_keys(&mut editor, "jlix");
+    press(&mut editor, &[Key::Backspace, Key::Backspace]);
+    assert_eq!(lines(&editor), vec!["ab", "cd"], "没有 start 时不能删除插入之前的文本");
+
+    editor.execute_command("set bs=start").unwrap();
+    press(&mut editor, &[Key::Backspace, Key::Backspace]);
+    assert_eq!(lines(&editor), vec!["ab", "d"], "没有 eol 时不能合并行");
+
+    editor.execute_command("set bs=eol,start").unwrap();
+    press(&mut editor, &[Key::Backspace]);
+    assert_eq!(lines(&editor), vec!["abd"]);
+}
+
+#[test]
+fn test_delete_key() {
+    let mut editor = editor_with_lines(&["ab", "cd"]);
+
+    type_keys(&mut editor, "li");
+    press(&mut editor, &[Key::Delete, Key::Delete]);
+    assert_eq!(lines(&editor), vec!["acd"], "行尾的 Delete 应该合并下一行");
+}
+
+#[test]
+fn test_ctrl_w_and_ctrl_u() {
+    let mut editor = editor_with_lines(&["    foo"]);
+
+    type_keys(&mut editor, "$i bar baz");
+    press(&mut editor, &[Key::Ctrl('w')]);
+    assert_eq!(lines(&editor), vec!["    foo bar "]);
+
+    press(&mut editor, &[Key::Ctrl('w'), Key::Ctrl('w')]);
+    assert_eq!(lines(&editor), vec!["    foo"], "Ctrl-w 应该先停在插入开始处");
+
+    press(&mut editor, &[Key::Ctrl('w')]);
+    assert_eq!(lines(&editor), vec!["    "]);
+
+    type_keys(&mut editor, "x y");
+    press(&mut editor, &[Key::Ctrl('u')]);
+    assert_eq!(lines(&editor), vec!["    "], "Ctrl-u 应该保留缩进");
+    press(&mut editor, &[Key::Ctrl('u')]);
+    assert_eq!(lines(&editor), vec![""]);
+}
+
+#[test]
+fn test_tab_and_expandtab() {
+    let mut editor = editor_with_lines(&["ab"]);
+    editor.execute_command("set ts=4").unwrap();
+
+    type_keys(&mut editor, "i\t\u{1b}");
+    assert_eq!(lines(&editor), vec!["\tab"]);
+
+    editor.execute_command("set et").unwrap();
+    type_keys(&mut editor, "$i\tx\u{1b}");
+    assert_eq!(lines(&editor), vec!["\tab  x"], "expandtab 时应该用空格补齐到下一个制表位");
+}
+
+#[test]
+fn test_home_end() {
+    let mut editor = editor_with_lines(&["abc"]);
+
+    type_keys(&mut editor, "li");
+    press(&mut editor, &[Key::End, Key::Char('1'), Key::Home, Key::Char('0')]);
+    assert_eq!(lines(&editor), vec!["0abc1"]);
+}
+
+#[test]
+fn test_ctrl_t_ctrl_d() {
+    let mut editor = editor_with_lines(&["foo"]);
+    editor.execute_command("set sw=4 et").unwrap();
+
+    type_keys(&mut editor, "$i");
+    press(&mut editor, &[Key::Ctrl('t'), Key::Ctrl('t')]);
+    assert_eq!(lines(&editor), vec!["        foo"]);
+    assert_eq!(editor.cursor.col, 11, "光标应该跟随文本移动");
+
+    press(&mut editor, &[Key::Ctrl('d'), Key::Char('!')]);
+    assert_eq!(lines(&editor), vec!["    foo!"]);
+
+    editor.execute_command("set noet ts=8").unwrap();
+    press(&mut editor, &[Key::Ctrl('t'), Key::Ctrl('t')]);
+    assert_eq!(lines(&editor), vec!["\t    foo!"], "noexpandtab 时应该使用制表符");
+}
+
+#[test]
+fn test_ctrl_r_register() {
+    let mut editor = editor_with_lines(&["foo"]);
+
+    type_keys(&mut editor, "\"ayyyw$i ");
+    press(&mut editor, &[Key::Ctrl('r'), Key::Char('"')]);
+    assert_eq!(lines(&editor), vec!["foo foo"]);
+
+    press(&mut editor, &[Key::Char('-'), Key::Ctrl('r'), Key::Char('a'), Key::Esc]);
+    assert_eq!(lines(&editor), vec!["foo foo-foo", ""], "按行的寄存器应该带换行");
+}
+
+#[test]
+fn test_ctrl_v_literal() {
+    let mut editor = editor_with_lines(&[""]);
+    editor.execute_command("set et").unwrap();
+
+    type_keys(&mut editor, "i");
+    press(&mut editor, &[Key::Ctrl('v'), Key::Char('\t')]);
+    press(&mut editor, &[Key::Ctrl('v'), Key::Char('0'), Key::Char('6'), Key::Char('5')]);
+    press(&mut editor, &[Key::Ctrl('v'), Key::Char('u'), Key::Char('2'), Key::Char('0'), Key::Char('a'), Key::Char('c')]);
+    press(&mut editor, &[Key::Ctrl('v'), Key::Char('x'), Key::Char('4'), Key::Char('z')]);
+    press(&mut editor, &[Key::Ctrl('v'), Key::Ctrl('a')]);
+    assert_eq!(lines(&editor), vec!["\tA€\u{4}z\u{1}"]);
+}
+
+#[test]
+fn test_ctrl_o() {
+    let mut editor = editor_with_lines(&["abc", "def"]);
+
+    type_keys(&mut editor, "ix");
+    press(&mut editor, &[Key::Ctrl('o')]);
+    assert!(matches!(editor.mode, Mode::Normal));
+    type_keys(&mut editor, "j");
+    assert!(matches!(editor.mode, Mode::Insert), "执行一个命令后应该回到插入模式");
+    type_keys(&mut editor, "y");
+    assert_eq!(lines(&editor), vec!["xabc", "dyef"]);
+
+    press(&mut editor, &[Key::Ctrl('o')]);
+    type_keys(&mut editor, "2dd");
+    assert!(matches!(editor.mode, Mode::Insert), "带计数的命令结束后才回到插入模式");
+    assert_eq!(lines(&editor), vec!["xabc"]);
+}
+
+#[test]
+fn test_repeat_after_ctrl_o() {
+    let mut editor = editor_with_lines(&["abc def"]);
+
+    type_keys(&mut editor, "i");
+    press(&mut editor, &[Key::Ctrl('o')]);
+    type_keys(&mut editor, "wX\u{1b}");
+    assert_eq!(lines(&editor), vec!["abc Xdef"]);
+    type_keys(&mut editor, "0.");
+    assert_eq!(lines(&editor), vec!["Xabc Xdef"], ". 只重复回到插入模式后输入的文本");
+}
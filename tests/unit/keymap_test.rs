@@ -0,0 +1,237 @@
+use std::fs;
+
+use rustvim::editor::{Editor, Mode};
+use rustvim::keymap::{self, Keymaps, MapMatch, MapMode, Mapping};
+use termion::event::Key;
+
+use super::common::TempDir;
+
+fn mapping(lhs: &str, rhs: &str, noremap: bool) -> Mapping {
+    Mapping {
+        lhs: keymap::parse_keys(lhs, "\\"),
+        rhs: keymap::parse_keys(rhs, "\\"),
+        noremap,
+        silent: false,
+    }
+}
+
+#[test]
+fn test_parse_keys() {
+    assert_eq!(keymap::parse_keys("jk", ","), vec![Key::Char('j'), Key::Char('k')]);
+    assert_eq!(keymap::parse_keys("<Esc>", ","), vec![Key::Esc]);
+    assert_eq!(keymap::parse_keys(":w<CR>", ","), vec![Key::Char(':'), Key::Char('w'), Key::Char('\n')]);
+    assert_eq!(keymap::parse_keys("<C-W>", ","), vec![Key::Ctrl('w')]);
+    assert_eq!(keymap::parse_keys("<leader>x", ","), vec![Key::Char(','), Key::Char('x')]);
+    assert_eq!(keymap::parse_keys("<lt>a>", ","), vec![Key::Char('<'), Key::Char('a'), Key::Char('>')]);
+    assert_eq!(keymap::parse_keys("<F5>", ","), vec![Key::F(5)]);
+    assert_eq!(keymap::parse_keys("<unknown>", ",").len(), 9, "未知的记法应该按普通字符处理");
+}
+
+#[test]
+fn test_format_keys() {
+    let keys = keymap::parse_keys("<C-w>j<Space><CR>", "\\");
+    assert_eq!(keymap::format_keys(&keys), "<C-W>j<Space><CR>");
+}
+
+#[test]
+fn test_parse_map_args() {
+    let args = keymap::parse_map_args("<silent> <buffer> <leader>w :w<CR>");
+    assert!(args.silent);
+    assert!(args.buffer);
+    assert_eq!(args.lhs, "<leader>w");
+    assert_eq!(args.rhs, ":w<CR>");
+}
+
+#[test]
+fn test_trie_lookup() {
+    let mut maps = Keymaps::new();
+    maps.add(MapMode::Normal, mapping("g", "0", true), false);
+    maps.add(MapMode::Normal, mapping("gx", "$", true), false);
+    maps.add(MapMode::Normal, mapping("ab", "l", true), false);
+
+    let keys = |s: &str| keymap::parse_keys(s, "\\");
+    assert!(matches!(maps.lookup(MapMode::Normal, &keys("g")), MapMatch::Ambiguous(_)));
+    assert!(matches!(maps.lookup(MapMode::Normal, &keys("gx")), MapMatch::Full(_)));
+    assert_eq!(maps.lookup(MapMode::Normal, &keys("a")), MapMatch::Prefix);
+    assert_eq!(maps.lookup(MapMode::Normal, &keys("z")), MapMatch::None);
+    assert_eq!(maps.lookup(MapMode::Insert, &keys("ab")), MapMatch::None, "映射只在对应模式下生效");
+
+    assert!(maps.remove(MapMode::Normal, &keys("gx"), false));
+    assert!(matches!(maps.lookup(MapMode::Normal, &keys("g")), MapMatch::Full(_)));
+    assert!(!maps.remove(MapMode::Normal, &keys("gx"), false), "重复删除应该失败");
+
+    maps.clear(MapMode::Normal, false);
+    assert_eq!(maps.lookup(MapMode::Normal, &keys("g")), MapMatch::None);
+}
+
+#[test]
+fn test_buffer_mapping_priority() {
+    let mut maps = Keymaps::new();
+    maps.add(MapMode::Normal, mapping("x", "a", true), false);
+    maps.add(MapMode::Normal, mapping("x", "b", true), true);
+
+    match maps.lookup(MapMode::Normal, &[Key::Char('x')]) {
+        MapMatch::Full(m) => assert_eq!(m.rhs, vec![Key::Char('b')], "buffer 局部映射应该优先"),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(maps.list(MapMode::Normal).len(), 2);
+}
+
+#[test]
+fn test_insert_mapping() {
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("inoremap jk <Esc>").unwrap();
+
+    editor.handle_key(Key::Char('i')).unwrap();
+    editor.handle_key(Key::Char('a')).unwrap();
+    editor.handle_key(Key::Char('j')).unwrap();
+    assert_eq!(editor.buffer.get_line(0), Some(&String::from("a")), "j 是映射前缀，应该等待");
+    editor.handle_key(Key::Char('k')).unwrap();
+
+    assert!(matches!(editor.mode, Mode::Normal), "jk 应该返回普通模式");
+    assert_eq!(editor.buffer.get_line(0), Some(&String::from("a")));
+}
+
+#[test]
+fn test_ambiguous_prefix_flushed_on_timeout() {
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("inoremap jk <Esc>").unwrap();
+    editor.handle_key(Key::Char('i')).unwrap();
+    editor.handle_key(Key::Char('j')).unwrap();
+    assert_eq!(editor.pending_keys, vec![Key::Char('j')]);
+
+    editor.process_pending(true).unwrap();
+    assert!(editor.pending_keys.is_empty());
+    assert_eq!(editor.buffer.get_line(0), Some(&String::from("j")), "超时后应该按原样插入");
+
+    // 不匹配的后续按键会让前缀按原样执行
+    editor.handle_key(Key::Char('j')).unwrap();
+    editor.handle_key(Key::Char('x')).unwrap();
+    assert_eq!(editor.buffer.get_line(0), Some(&String::from("jjx")));
+}
+
+#[test]
+fn test_recursive_and_noremap() {
+    let mut editor = Editor::new().unwrap();
+    editor.buffer.insert_line(0, String::from("hello"));
+    editor.execute_command("nmap L l").unwrap();
+    editor.execute_command("nmap Q L").unwrap();
+    editor.execute_command("nnoremap W L").unwrap();
+
+    editor.handle_key(Key::Char('Q')).unwrap();
+    assert_eq!(editor.cursor.col, 1, "递归映射应该继续展开");
+
+    editor.handle_key(Key::Char('W')).unwrap();
+    assert_eq!(editor.cursor.col, 1, "noremap 的 rhs 不应该再被映射");
+
+    editor.execute_command("nmap j jL").unwrap();
+    editor.handle_key(Key::Char('j')).unwrap();
+    assert_eq!(editor.cursor.col, 2, "rhs 开头的 lhs 不应该再被映射");
+    assert!(editor.messages.is_empty(), "不应该出现递归映射错误");
+}
+
+#[test]
+fn test_leader_and_unmap() {
+    let mut editor = Editor::new().unwrap();
+    editor.buffer.insert_line(0, String::from("hello"));
+    editor.execute_command("let mapleader = \",\"").unwrap();
+    editor.execute_command("nnoremap <leader>e $").unwrap();
+
+    editor.handle_key(Key::Char(',')).unwrap();
+    editor.handle_key(Key::Char('e')).unwrap();
+    assert_eq!(editor.cursor.col, 5);
+
+    editor.execute_command("nunmap <leader>e").unwrap();
+    assert!(editor.messages.is_empty());
+    editor.execute_command("nunmap <leader>e").unwrap();
+    assert_eq!(editor.messages, vec![String::from("E31: No such mapping")]);
+}
+
+#[test]
+fn test_map_listing_and_mapclear() {
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("nnoremap <silent> <buffer> gh 0").unwrap();
+    editor.execute_command("map gl $").unwrap();
+
+    editor.execute_command("nmap").unwrap();
+    assert_eq!(editor.messages.len(), 2);
+    assert!(editor.messages[0].contains("gh") && editor.messages[0].contains("*@"));
+
+    editor.messages.clear();
+    editor.execute_command("mapclear").unwrap();
+    editor.execute_command("nmap").unwrap();
+    assert_eq!(editor.messages.len(), 1, "mapclear 不应该清除 buffer 局部映射");
+
+    editor.messages.clear();
+    editor.execute_command("nmapclear <buffer>").unwrap();
+    editor.execute_command("nmap").unwrap();
+    assert_eq!(editor.messages, vec![String::from("No mapping found")]);
+}
+
+#[test]
+fn test_silent_mapping_echo() {
+    let mut editor = Editor::new().unwrap();
+    editor.buffer.insert_line(0, String::from("hello"));
+    editor.execute_command("nnoremap gs :set ts=4<CR>").unwrap();
+    editor.execute_command("nnoremap <silent> gS :set ts=2<CR>").unwrap();
+
+    editor.handle_key(Key::Char('g')).unwrap();
+    editor.handle_key(Key::Char('s')).unwrap();
+    assert_eq!(editor.options.tabstop, 4);
+    assert_eq!(editor.messages, vec![String::from(":set ts=4")], "普通映射执行的命令应该留在命令行上");
+
+    editor.handle_key(Key::Char('g')).unwrap();
+    editor.handle_key(Key::Char('S')).unwrap();
+    assert_eq!(editor.options.tabstop, 2);
+    assert!(editor.messages.is_empty(), "<silent> 映射不应该显示命令");
+
+    // 直接输入的命令不受影响
+    for c in ":set ts=8\n".chars() {
+        editor.handle_key(Key::Char(c)).unwrap();
+    }
+    assert!(editor.messages.is_empty());
+}
+
+#[test]
+fn test_buffer_mappings_follow_file() {
+    let dir = TempDir::new("buffermap");
+    let first = dir.join("first.txt");
+    let second = dir.join("second.txt");
+    fs::write(&first, "one\n").unwrap();
+    fs::write(&second, "two\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.open(&first).unwrap();
+    editor.execute_command("nnoremap <buffer> gh 0").unwrap();
+    editor.execute_command("nnoremap gl $").unwrap();
+    editor.execute_command("e").unwrap();
+    editor.messages.clear();
+    editor.execute_command("nmap").unwrap();
+    assert_eq!(editor.messages.len(), 2, "重新读入同一个文件时应该保留 buffer 局部映射");
+
+    editor.execute_command(&format!("e {}", second.display())).unwrap();
+    editor.messages.clear();
+    editor.execute_command("nmap").unwrap();
+    assert_eq!(editor.messages.len(), 1, "换成其他文件时应该去掉 buffer 局部映射");
+    assert!(editor.messages[0].contains("gl"));
+
+    editor.execute_command(&format!("e {}", first.display())).unwrap();
+    editor.messages.clear();
+    editor.execute_command("nmap").unwrap();
+    assert_eq!(editor.messages.len(), 2, "回到原来的文件时恢复它的 buffer 局部映射");
+}
+
+#[test]
+fn test_noremap_abbreviations() {
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("nn a b").unwrap();
+    editor.execute_command("cno c d").unwrap();
+    editor.execute_command("cn e f").unwrap();
+    editor.execute_command("on g h").unwrap();
+    editor.messages.clear();
+    editor.execute_command("map!").unwrap();
+    editor.execute_command("omap").unwrap();
+    editor.execute_command("nmap").unwrap();
+    let listed: Vec<&str> = editor.messages.iter().map(|line| &line[..4]).collect();
+    assert_eq!(listed, ["c  c", "No m", "n  a"], ":cn 和 :on 不是 cnoremap 和 onoremap");
+}
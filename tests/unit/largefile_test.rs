@@ -0,0 +1,67 @@
+use std::fs;
+
+use rustvim::editor::Editor;
+use rustvim::largefile::WINDOW_LINES;
+
+use super::common::{type_keys, TempDir};
+
+// 光标所在的行在文件中的行号
+fn file_line(editor: &Editor) -> usize {
+    editor.buffer.line_offset() + editor.cursor.row
+}
+
+#[test]
+fn test_large_file_window() {
+    let dir = TempDir::new("largefile");
+    let file = dir.join("big.log");
+    let text: String = (0..60000).map(|i| format!("line {:05} ........\n", i)).collect();
+    fs::write(&file, &text).unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile largefile=1").unwrap();
+    editor.open(&file).unwrap();
+    assert!(editor.messages[0].contains("[large]"));
+    assert_eq!(editor.buffer.line_count(), WINDOW_LINES, "只读入开头的一部分行");
+    assert_eq!(editor.buffer.get_line(0).unwrap(), "line 00000 ........");
+
+    type_keys(&mut editor, "G");
+    assert_eq!(file_line(&editor), 59999);
+    assert_eq!(editor.buffer.get_line(editor.cursor.row).unwrap(), "line 59999 ........");
+    type_keys(&mut editor, "30000G");
+    assert_eq!(file_line(&editor), 29999);
+    type_keys(&mut editor, "gg");
+    assert_eq!(file_line(&editor), 0);
+
+    // 移到读入部分的末尾时继续读入
+    type_keys(&mut editor, "900j");
+    assert!(editor.buffer.line_count() > WINDOW_LINES);
+    assert_eq!(file_line(&editor), 900);
+
+    type_keys(&mut editor, "/line 45678\n");
+    assert_eq!(file_line(&editor), 45678, "在没有读入的部分中查找");
+    type_keys(&mut editor, "?line 00012\n");
+    assert_eq!(file_line(&editor), 12);
+    type_keys(&mut editor, "/line 00005\n");
+    assert_eq!(file_line(&editor), 5);
+    assert_eq!(editor.messages, ["search hit BOTTOM, continuing at TOP"]);
+
+    // 写入时没有读入的部分原样保留
+    type_keys(&mut editor, "50000Gx");
+    editor.execute_command("w").unwrap();
+    let written = fs::read_to_string(&file).unwrap();
+    assert_eq!(written.len(), text.len() - 1);
+    assert!(written.contains("\nine 49999 ........\nline 50000"));
+    assert!(written.starts_with("line 00000") && written.ends_with("line 59999 ........\n"));
+    assert!(!editor.buffer.modified);
+    type_keys(&mut editor, "x");
+    type_keys(&mut editor, "gg");
+    assert!(editor.messages[0].starts_with("E37"), "修改没有写入时不能换到其他部分");
+    type_keys(&mut editor, "u");
+    type_keys(&mut editor, "gg");
+    assert_eq!(file_line(&editor), 0);
+    assert!(editor.buffer.highlighter.is_none());
+
+    editor.execute_command("set largefile=0").unwrap();
+    editor.execute_command("e").unwrap();
+    assert_eq!(editor.buffer.line_count(), 60000);
+}
@@ -0,0 +1,87 @@
+use rustvim::editor::Mode;
+
+use super::common::{editor_with_lines, type_keys};
+
+#[test]
+fn test_record_and_play() {
+    let mut editor = editor_with_lines(&["a", "b", "c", "d"]);
+
+    type_keys(&mut editor, "qa0i-\u{1b}jq");
+    assert_eq!(editor.recording, None, "q 应该结束录制");
+    assert_eq!(editor.registers.get('a').unwrap().text, "0i-<Esc>j");
+    assert_eq!(editor.buffer.get_line(0), Some(&String::from("-a")));
+
+    type_keys(&mut editor, "@a");
+    assert_eq!(editor.buffer.get_line(1), Some(&String::from("-b")));
+
+    type_keys(&mut editor, "@@");
+    assert_eq!(editor.buffer.get_line(2), Some(&String::from("-c")));
+    assert_eq!(editor.cursor.row, 3);
+}
+
+#[test]
+fn test_count_stops_on_failed_motion() {
+    let mut editor = editor_with_lines(&["a", "b", "c"]);
+    type_keys(&mut editor, "qa0i-\u{1b}jq");
+
+    type_keys(&mut editor, "5@a");
+    assert_eq!(editor.buffer.get_line(1), Some(&String::from("-b")));
+    assert_eq!(editor.buffer.get_line(2), Some(&String::from("-c")), "j 在最后一行失败后应该停止");
+    assert!(editor.typeahead.is_empty());
+}
+
+#[test]
+fn test_recursive_macro() {
+    let mut editor = editor_with_lines(&["x", "x", "x", "x"]);
+    type_keys(&mut editor, "qb0i+\u{1b}j@bq");
+    assert_eq!(editor.registers.get('b').unwrap().text, "0i+<Esc>j@b");
+
+    type_keys(&mut editor, "@b");
+    for row in 0..4 {
+        assert_eq!(editor.buffer.get_line(row), Some(&String::from("+x")), "递归宏应该处理到最后一行");
+    }
+}
+
+#[test]
+fn test_append_to_register() {
+    let mut editor = editor_with_lines(&["abc"]);
+    type_keys(&mut editor, "qclq");
+    type_keys(&mut editor, "qClq");
+    assert_eq!(editor.registers.get('c').unwrap().text, "ll");
+
+    editor.cursor.col = 0;
+    type_keys(&mut editor, "@c");
+    assert_eq!(editor.cursor.col, 2);
+}
+
+#[test]
+fn test_repeat_last_command_line() {
+    let mut editor = editor_with_lines(&["1", "2", "3", "4"]);
+    type_keys(&mut editor, ":+1\n");
+    assert_eq!(editor.cursor.row, 1);
+
+    type_keys(&mut editor, "@:");
+    assert_eq!(editor.cursor.row, 2);
+}
+
+#[test]
+fn test_normal_over_range() {
+    let mut editor = editor_with_lines(&["a", "b", "c"]);
+    editor.execute_command("2,3normal i#").unwrap();
+    assert_eq!(editor.buffer.get_line(0), Some(&String::from("a")));
+    assert_eq!(editor.buffer.get_line(1), Some(&String::from("#b")));
+    assert_eq!(editor.buffer.get_line(2), Some(&String::from("#c")));
+    assert!(matches!(editor.mode, Mode::Normal), ":normal 结束后应该回到普通模式");
+
+    type_keys(&mut editor, "qd$i;\u{1b}q");
+    editor.execute_command("%norm @d").unwrap();
+    assert_eq!(editor.buffer.get_line(0), Some(&String::from("a;")));
+    assert_eq!(editor.buffer.get_line(2), Some(&String::from("#c;;")));
+}
+
+#[test]
+fn test_invalid_register() {
+    let mut editor = editor_with_lines(&["a"]);
+    type_keys(&mut editor, "@@");
+    assert_eq!(editor.messages, vec![String::from("E748: No previously used register")]);
+}
@@ -0,0 +1,207 @@
+use std::fs;
+
+use rustvim::buffer::Buffer;
+use rustvim::editor::{Editor, Mode};
+use rustvim::mark::{ChangeList, JumpList, Position};
+use termion::event::Key;
+
+use super::common::{editor_with_lines, type_keys, TempDir};
+
+#[test]
+fn test_marks_follow_inserted_and_removed_lines() {
+    let mut buffer = Buffer::new();
+    for (i, line) in ["one", "two", "three"].iter().enumerate() {
+        buffer.insert_line(i, line.to_string());
+    }
+    buffer.marks.set('a', Position::new(1, 2));
+    buffer.marks.set('B', Position::new(2, 0));
+
+    buffer.insert_line(0, String::from("zero"));
+    assert_eq!(buffer.marks.get('a'), Some(Position::new(2, 2)), "插入行后标记应该下移");
+    assert_eq!(buffer.marks.get('B'), Some(Position::new(3, 0)));
+
+    buffer.remove_line(0);
+    assert_eq!(buffer.marks.get('a'), Some(Position::new(1, 2)), "删除行后标记应该上移");
+
+    buffer.remove_line(1);
+    assert_eq!(buffer.marks.get('a'), None, "所在行被删除的标记应该被删除");
+    assert_eq!(buffer.marks.get('B'), Some(Position::new(1, 0)));
+}
+
+#[test]
+fn test_marks_follow_split_and_join() {
+    let mut buffer = Buffer::new();
+    buffer.insert_line(0, String::from("hello world"));
+    buffer.marks.set('a', Position::new(0, 8));
+    buffer.marks.set('b', Position::new(0, 2));
+
+    buffer.split_line(0, 6);
+    assert_eq!(buffer.get_line(0), Some(&String::from("hello ")));
+    assert_eq!(buffer.get_line(1), Some(&String::from("world")));
+    assert_eq!(buffer.marks.get('a'), Some(Position::new(1, 2)), "拆分点之后的标记应该移到新行");
+    assert_eq!(buffer.marks.get('b'), Some(Position::new(0, 2)));
+
+    assert_eq!(buffer.join_lines(0), Some(6));
+    assert_eq!(buffer.get_line(0), Some(&String::from("hello world")));
+    assert_eq!(buffer.marks.get('a'), Some(Position::new(0, 8)), "合并后标记应该回到原位置");
+    assert_eq!(buffer.join_lines(0), None, "最后一行不能再合并");
+}
+
+#[test]
+fn test_jumplist_navigation() {
+    let mut jumps = JumpList::new();
+    jumps.push(Position::new(0, 0));
+    jumps.push(Position::new(5, 0));
+
+    assert_eq!(jumps.back(Position::new(9, 0)), Some(Position::new(5, 0)));
+    assert_eq!(jumps.back(Position::new(5, 0)), Some(Position::new(0, 0)));
+    assert_eq!(jumps.back(Position::new(0, 0)), None);
+    assert_eq!(jumps.forward(), Some(Position::new(5, 0)));
+    assert_eq!(jumps.forward(), Some(Position::new(9, 0)), "Ctrl-i 应该能回到开始 Ctrl-o 的位置");
+    assert_eq!(jumps.forward(), None);
+
+    jumps.push(Position::new(5, 3));
+    assert_eq!(jumps.entries().len(), 3, "同一行的旧条目应该被替换");
+}
+
+#[test]
+fn test_changelist_navigation() {
+    let mut changes = ChangeList::new();
+    changes.record(Position::new(1, 0));
+    changes.record(Position::new(1, 4));
+    changes.record(Position::new(7, 2));
+    assert_eq!(changes.entries().len(), 2, "同一行的连续修改只占一条");
+
+    assert_eq!(changes.older(), Some(Position::new(7, 2)));
+    assert_eq!(changes.older(), Some(Position::new(1, 4)));
+    assert_eq!(changes.older(), None);
+    assert_eq!(changes.newer(), Some(Position::new(7, 2)));
+    assert_eq!(changes.newer(), None);
+}
+
+#[test]
+fn test_set_and_jump_to_mark() {
+    let mut editor = editor_with_lines(&["first", "  second", "third"]);
+    type_keys(&mut editor, "jllllma");
+    type_keys(&mut editor, "G");
+    assert_eq!(editor.cursor.row, 2);
+
+    type_keys(&mut editor, "'a");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (1, 2), "'a 应该跳到第一个非空字符");
+
+    type_keys(&mut editor, "gg`a");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (1, 4), "`a 应该跳到准确位置");
+
+    type_keys(&mut editor, "''");
+    assert_eq!(editor.cursor.row, 0, "'' 应该回到跳转前的位置");
+
+    type_keys(&mut editor, "'z");
+    assert_eq!(editor.messages, vec![String::from("E20: Mark not set")]);
+}
+
+#[test]
+fn test_ctrl_o_and_ctrl_i() {
+    let mut editor = editor_with_lines(&["a", "b", "c", "d"]);
+    type_keys(&mut editor, "Gggj");
+    assert_eq!(editor.cursor.row, 1);
+
+    editor.handle_key(Key::Ctrl('o')).unwrap();
+    assert_eq!(editor.cursor.row, 3, "Ctrl-o 应该回到 gg 之前的位置");
+    editor.handle_key(Key::Ctrl('o')).unwrap();
+    assert_eq!(editor.cursor.row, 0);
+
+    editor.handle_key(Key::Char('\t')).unwrap();
+    assert_eq!(editor.cursor.row, 3);
+    editor.handle_key(Key::Char('\t')).unwrap();
+    assert_eq!(editor.cursor.row, 1, "Ctrl-i 应该能回到开始 Ctrl-o 的位置");
+}
+
+#[test]
+fn test_change_marks_and_changelist() {
+    let mut editor = editor_with_lines(&["a", "b", "c"]);
+    type_keys(&mut editor, "ix");
+    editor.handle_key(Key::Esc).unwrap();
+    type_keys(&mut editor, "Giyz");
+    editor.handle_key(Key::Esc).unwrap();
+
+    assert_eq!(editor.buffer.marks.get('.'), Some(Position::new(2, 1)));
+    assert_eq!(editor.buffer.marks.get('^'), Some(Position::new(2, 2)));
+    assert_eq!(editor.buffer.marks.get('['), Some(Position::new(2, 0)), "'[ 应该是本次插入的开始");
+
+    type_keys(&mut editor, "gg");
+    type_keys(&mut editor, "g;");
+    assert_eq!(editor.cursor.row, 2);
+    type_keys(&mut editor, "g;");
+    assert_eq!(editor.cursor.row, 0);
+    type_keys(&mut editor, "g,");
+    assert_eq!(editor.cursor.row, 2);
+}
+
+#[test]
+fn test_visual_marks_and_range() {
+    let mut editor = editor_with_lines(&["a", "b", "c", "d"]);
+    type_keys(&mut editor, "jVj");
+    assert!(matches!(editor.mode, Mode::VisualLine));
+    editor.handle_key(Key::Esc).unwrap();
+    assert!(matches!(editor.mode, Mode::Normal));
+    assert_eq!(editor.buffer.marks.get('<'), Some(Position::new(1, 0)));
+    assert_eq!(editor.buffer.marks.get('>'), Some(Position::new(2, 1)));
+
+    type_keys(&mut editor, "ggvj:");
+    assert_eq!(editor.command_line, "'<,'>");
+    type_keys(&mut editor, "normal i-\n");
+    assert_eq!(editor.buffer.get_line(0), Some(&String::from("-a")));
+    assert_eq!(editor.buffer.get_line(1), Some(&String::from("-b")));
+    assert_eq!(editor.buffer.get_line(2), Some(&String::from("c")));
+}
+
+#[test]
+fn test_list_marks_and_jumps() {
+    let mut editor = editor_with_lines(&["first", "second"]);
+    type_keys(&mut editor, "majmB");
+    editor.execute_command("marks aB").unwrap();
+    assert_eq!(editor.messages.len(), 3);
+    assert!(editor.messages[1].starts_with("   B"));
+    assert!(editor.messages[2].contains("first"));
+
+    editor.messages.clear();
+    type_keys(&mut editor, "G");
+    editor.execute_command("jumps").unwrap();
+    assert_eq!(editor.messages.last(), Some(&String::from(">")));
+}
+
+#[test]
+fn test_file_marks_across_files() {
+    let dir = TempDir::new("filemarks");
+    let first = dir.join("first.txt");
+    let second = dir.join("second.txt");
+    fs::write(&first, "one\ntwo\nthree\n").unwrap();
+    fs::write(&second, "alpha\nbeta\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.open(&first).unwrap();
+    type_keys(&mut editor, "jlmA");
+    editor.execute_command(&format!("e {}", second.display())).unwrap();
+    type_keys(&mut editor, "jmB");
+    assert_eq!(editor.buffer.path.as_deref(), Some(second.as_path()));
+
+    // :marks 列出其他文件中的标记和文件名
+    editor.messages.clear();
+    editor.execute_command("marks AB").unwrap();
+    assert_eq!(editor.messages.len(), 3, "应该列出两个文件标记");
+    assert!(editor.messages[1].contains("first.txt"), "A 应该显示所在的文件");
+    assert!(editor.messages[2].contains("beta"), "B 在当前文件中，应该显示文本");
+
+    // 'A 打开标记所在的文件
+    type_keys(&mut editor, "`A");
+    assert_eq!(editor.buffer.path.as_deref(), Some(first.as_path()), "应该打开 A 所在的文件");
+    assert_eq!(editor.cursor.position(), Position::new(1, 1));
+
+    // 修改过的 buffer 不能离开
+    type_keys(&mut editor, "x'B");
+    assert_eq!(editor.buffer.path.as_deref(), Some(first.as_path()), "修改过时不应该切换文件");
+    assert!(editor.messages.iter().any(|message| message.starts_with("E37")));
+    type_keys(&mut editor, "u'B");
+    assert_eq!(editor.buffer.path.as_deref(), Some(second.as_path()), "撤销后应该切换到 B 所在的文件");
+    assert_eq!(editor.cursor.position(), Position::new(1, 0));
+}
@@ -1,3 +1,31 @@
 mod buffer_test;
+mod checktime_test;
+mod command_test;
+mod common;
 mod cursor_test;
+mod display_test;
 mod editor_test;
+mod encoding_test;
+mod file_test;
+mod filter_test;
+mod fold_test;
+mod gutter_test;
+mod hex_test;
+mod highlight_test;
+mod indent_test;
+mod insert_test;
+mod keymap_test;
+mod largefile_test;
+mod macros_test;
+mod mark_test;
+mod operator_test;
+mod options_test;
+mod register_test;
+mod save_test;
+mod search_test;
+mod shell_test;
+mod sort_test;
+mod swap_test;
+mod syntax_test;
+mod treesitter_test;
+mod undo_test;
@@ -0,0 +1,159 @@
+use rustvim::editor::Mode;
+
+use super::common::{editor_with_lines, lines, type_keys};
+
+#[test]
+fn test_count_motion() {
+    let mut editor = editor_with_lines(&["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11"]);
+
+    type_keys(&mut editor, "10j");
+    assert_eq!(editor.cursor.row, 10, "10j 应该向下移动 10 行");
+
+    type_keys(&mut editor, "3G");
+    assert_eq!(editor.cursor.row, 2, "3G 应该跳到第 3 行");
+
+    type_keys(&mut editor, "G");
+    assert_eq!(editor.cursor.row, 11, "G 应该跳到最后一行");
+}
+
+#[test]
+fn test_zero_is_motion_without_count() {
+    let mut editor = editor_with_lines(&["abcdef"]);
+
+    type_keys(&mut editor, "3l0");
+    assert_eq!(editor.cursor.col, 0, "没有计数时 0 是移动到行首");
+    assert_eq!(editor.count, None);
+
+    type_keys(&mut editor, "10l");
+    assert_eq!(editor.cursor.col, 6, "10l 中的 0 是计数的一部分，移动到行尾为止");
+}
+
+#[test]
+fn test_count_delete_chars() {
+    let mut editor = editor_with_lines(&["abcdef"]);
+
+    type_keys(&mut editor, "3x");
+    assert_eq!(lines(&editor), vec!["def"]);
+    assert_eq!(editor.registers.get('"').unwrap().text, "abc");
+
+    // $ 之后 x 删除最后一个字符，录制的宏不会因此中止
+    type_keys(&mut editor, "$x");
+    assert_eq!(lines(&editor), vec!["de"]);
+    assert!(!editor.aborted);
+    type_keys(&mut editor, "qa$xq@a");
+    assert_eq!(lines(&editor), vec![""]);
+}
+
+#[test]
+fn test_operator_count_multiplies() {
+    let mut editor = editor_with_lines(&["a b c d e f g h"]);
+
+    type_keys(&mut editor, "d2w");
+    assert_eq!(lines(&editor), vec!["c d e f g h"]);
+
+    type_keys(&mut editor, "2d2w");
+    assert_eq!(lines(&editor), vec!["g h"], "2d2w 应该删除 4 个单词");
+}
+
+#[test]
+fn test_delete_word_stops_at_line_end() {
+    let mut editor = editor_with_lines(&["foo bar", "baz"]);
+
+    type_keys(&mut editor, "wdw");
+    assert_eq!(lines(&editor), vec!["foo ", "baz"], "dw 不应该删除到下一行");
+}
+
+#[test]
+fn test_change_word() {
+    let mut editor = editor_with_lines(&["foo bar"]);
+
+    type_keys(&mut editor, "cwxy\u{1b}");
+    assert_eq!(lines(&editor), vec!["xy bar"], "cw 不应该删除单词后的空格");
+    assert!(matches!(editor.mode, Mode::Normal));
+}
+
+#[test]
+fn test_count_delete_lines() {
+    let mut editor = editor_with_lines(&["1", "2", "3", "4", "5"]);
+
+    type_keys(&mut editor, "dd");
+    assert_eq!(lines(&editor), vec!["2", "3", "4", "5"]);
+
+    type_keys(&mut editor, "3dd");
+    assert_eq!(lines(&editor), vec!["5"]);
+    assert!(editor.registers.get('"').unwrap().linewise);
+
+    type_keys(&mut editor, "dd");
+    assert_eq!(lines(&editor), vec![""], "删除所有行后应该保留一个空行");
+}
+
+#[test]
+fn test_yank_and_put_count() {
+    let mut editor = editor_with_lines(&["a", "b"]);
+
+    type_keys(&mut editor, "yy3p");
+    assert_eq!(lines(&editor), vec!["a", "a", "a", "a", "b"]);
+    assert_eq!(editor.cursor.row, 1);
+
+    type_keys(&mut editor, "G\"byygg\"0P");
+    assert_eq!(editor.registers.get('b').unwrap().text, "b");
+    assert_eq!(lines(&editor)[0], "a", "指定寄存器的复制不应该修改 \"0");
+
+    type_keys(&mut editor, "p");
+    assert_eq!(lines(&editor)[1], "b", "无名寄存器应该指向最近的复制");
+}
+
+#[test]
+fn test_count_insert() {
+    let mut editor = editor_with_lines(&["ab"]);
+
+    type_keys(&mut editor, "3ix\u{1b}");
+    assert_eq!(lines(&editor), vec!["xxxab"]);
+
+    type_keys(&mut editor, "2oy\u{1b}");
+    assert_eq!(lines(&editor), vec!["xxxab", "y", "y"]);
+}
+
+#[test]
+fn test_repeat_change_with_count() {
+    let mut editor = editor_with_lines(&["abcdefgh"]);
+
+    type_keys(&mut editor, "2x");
+    assert_eq!(lines(&editor), vec!["cdefgh"]);
+
+    type_keys(&mut editor, ".");
+    assert_eq!(lines(&editor), vec!["efgh"], ". 应该使用原来的计数");
+
+    type_keys(&mut editor, "3.");
+    assert_eq!(lines(&editor), vec!["h"], "3. 应该替换原来的计数");
+
+    // 新的计数同时替换操作符和移动命令的计数
+    let mut editor = editor_with_lines(&["a b c d e f g h i j k l m n"]);
+    type_keys(&mut editor, "d3w");
+    assert_eq!(lines(&editor), vec!["d e f g h i j k l m n"]);
+    type_keys(&mut editor, "2.");
+    assert_eq!(lines(&editor), vec!["f g h i j k l m n"], "d3w 之后 2. 应该是 d2w");
+    type_keys(&mut editor, ".");
+    assert_eq!(lines(&editor), vec!["h i j k l m n"], "之后的 . 使用新的计数");
+    type_keys(&mut editor, "2d2w.");
+    assert_eq!(lines(&editor), vec![""], "2d2w 之后 . 保留两个计数");
+
+    // 插入的文本中的数字不是计数
+    let mut editor = editor_with_lines(&["x"]);
+    type_keys(&mut editor, "2i12\u{1b}");
+    assert_eq!(lines(&editor), vec!["1212x"]);
+    type_keys(&mut editor, "3.");
+    assert_eq!(lines(&editor), vec!["1212121212x"], "3. 插入三次");
+}
+
+#[test]
+fn test_visual_delete() {
+    let mut editor = editor_with_lines(&["abcdef", "1", "2", "3"]);
+
+    type_keys(&mut editor, "lvld");
+    assert_eq!(lines(&editor)[0], "adef");
+    assert!(matches!(editor.mode, Mode::Normal));
+
+    type_keys(&mut editor, "jVjd");
+    assert_eq!(lines(&editor), vec!["adef", "3"]);
+}
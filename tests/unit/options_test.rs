@@ -0,0 +1,50 @@
+use rustvim::options::Options;
+
+#[test]
+fn test_set_bool_option() {
+    let mut options = Options::new();
+    assert!(options.timeout);
+
+    options.set("notimeout").unwrap();
+    assert!(!options.timeout);
+
+    options.set("timeout!").unwrap();
+    assert!(options.timeout);
+
+    options.set("invto").unwrap();
+    assert!(!options.timeout);
+    assert_eq!(options.set("timeout?"), Ok(Some(String::from("  notimeout"))));
+}
+
+#[test]
+fn test_set_number_option() {
+    let mut options = Options::new();
+    options.set("timeoutlen=500").unwrap();
+    assert_eq!(options.timeoutlen, 500);
+
+    options.set("tm=200").unwrap();
+    assert_eq!(options.timeoutlen, 200);
+    assert_eq!(options.set("tm?"), Ok(Some(String::from("  tm=200"))));
+
+    assert!(options.set("tm=abc").is_err(), "非数字的值应该报错");
+}
+
+#[test]
+fn test_unknown_option() {
+    let mut options = Options::new();
+    assert_eq!(options.set("foo"), Err(String::from("E518: Unknown option: foo")));
+}
+
+#[test]
+fn test_backspace_option() {
+    let mut options = Options::new();
+    assert!(options.backspace_allows("eol"));
+
+    assert_eq!(options.set("bs=0"), Ok(None));
+    assert!(!options.backspace_allows("start"));
+    assert_eq!(options.set("backspace=2"), Ok(None));
+    assert!(options.backspace_allows("start"));
+    assert_eq!(options.set("bs?"), Ok(Some(String::from("  bs=2"))));
+    assert!(options.set("bs=foo").is_err(), "未知的值应该报错");
+    assert!(options.set("ts=0").is_err(), "tabstop 必须是正数");
+}
@@ -0,0 +1,32 @@
+use rustvim::register::{Register, Registers};
+
+#[test]
+fn test_set_and_get() {
+    let mut registers = Registers::new();
+    assert_eq!(registers.get('a'), None);
+
+    registers.set('a', Register { text: String::from("abc"), linewise: false });
+    assert_eq!(registers.get('a').unwrap().text, "abc");
+    assert_eq!(registers.get('A').unwrap().text, "abc", "大写寄存器名读取对应的小写寄存器");
+}
+
+#[test]
+fn test_uppercase_appends() {
+    let mut registers = Registers::new();
+    registers.set('a', Register { text: String::from("abc"), linewise: false });
+    registers.set('A', Register { text: String::from("def"), linewise: false });
+    assert_eq!(registers.get('a').unwrap().text, "abcdef");
+
+    registers.set('A', Register { text: String::from("line"), linewise: true });
+    assert_eq!(registers.get('a').unwrap().text, "abcdef\nline");
+    assert!(registers.get('a').unwrap().linewise);
+}
+
+#[test]
+fn test_valid_names() {
+    assert!(Registers::is_valid('a'));
+    assert!(Registers::is_valid('Z'));
+    assert!(Registers::is_valid('0'));
+    assert!(Registers::is_valid(':'));
+    assert!(!Registers::is_valid('^'));
+}
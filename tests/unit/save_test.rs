@@ -0,0 +1,96 @@
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::PathBuf;
+
+use rustvim::editor::Editor;
+use termion::event::Key;
+
+use super::common::TempDir;
+
+fn mode(path: &PathBuf) -> u32 {
+    fs::metadata(path).unwrap().mode() & 0o7777
+}
+
+#[test]
+fn test_atomic_write() {
+    let dir = TempDir::new("save");
+    let file = dir.join("text.txt");
+    fs::write(&file, "one\n").unwrap();
+    // 组可写的权限会被 umask 去掉，写入后要恢复
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o664)).unwrap();
+    let inode = fs::metadata(&file).unwrap().ino();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile").unwrap();
+    editor.open(&file).unwrap();
+    editor.handle_key(Key::Char('x')).unwrap();
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "ne\n");
+    assert_eq!(mode(&file), 0o664, "保留权限");
+    assert_ne!(fs::metadata(&file).unwrap().ino(), inode, "写到临时文件再改名");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1, "不留下临时文件和备份");
+
+    // 符号链接写入指向的文件
+    let link = dir.join("link.txt");
+    std::os::unix::fs::symlink(&file, &link).unwrap();
+    editor.open(&link).unwrap();
+    editor.handle_key(Key::Char('x')).unwrap();
+    editor.execute_command("w").unwrap();
+    assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "e\n");
+
+    // 有多个硬链接时在原来的文件中写入
+    let hard = dir.join("hard.txt");
+    fs::hard_link(&file, &hard).unwrap();
+    editor.open(&file).unwrap();
+    editor.handle_key(Key::Char('x')).unwrap();
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read_to_string(&hard).unwrap(), "\n");
+
+    // 只读的文件需要 :w!
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o444)).unwrap();
+    editor.buffer.insert_line(0, String::from("new"));
+    editor.execute_command("w").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("E505")));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "\n");
+    fs::remove_file(&hard).unwrap();
+    editor.execute_command("w!").unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "new\n\n");
+    assert_eq!(mode(&file), 0o444);
+
+    editor.execute_command(&format!("w {}", dir.join("missing/x.txt").display())).unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("E212") && line.contains("does not exist")));
+}
+
+#[test]
+fn test_backup() {
+    let dir = TempDir::new("backup");
+    let file = dir.join("text.txt");
+    fs::write(&file, "one\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command(&format!("set noswapfile backup backupdir={}", dir.join("bak").display())).unwrap();
+    editor.open(&file).unwrap();
+    editor.handle_key(Key::Char('x')).unwrap();
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read_to_string(dir.join("bak/text.txt~")).unwrap(), "one\n", "备份写入之前的内容");
+
+    editor.execute_command("set backupdir=.").unwrap();
+    editor.handle_key(Key::Char('x')).unwrap();
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read_to_string(dir.join("text.txt~")).unwrap(), "ne\n");
+
+    // 不能创建备份时不写入，除非使用 :w!
+    fs::write(dir.join("notdir"), "").unwrap();
+    editor.execute_command(&format!("set nobackup backupdir={}", dir.join("notdir").display())).unwrap();
+    editor.handle_key(Key::Char('x')).unwrap();
+    editor.execute_command("w").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("E510")));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "e\n");
+    editor.execute_command("w!").unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "\n");
+    editor.execute_command("set nowritebackup").unwrap();
+    editor.buffer.insert_line(0, String::from("x"));
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "x\n\n");
+}
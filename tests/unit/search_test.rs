@@ -0,0 +1,69 @@
+use rustvim::search;
+use termion::event::Key;
+
+use super::common::{editor_with_lines, type_keys};
+
+#[test]
+fn test_search_forward_and_backward() {
+    let mut editor = editor_with_lines(&["foo bar", "baz foo", "foo"]);
+    type_keys(&mut editor, "/foo\n");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (1, 4));
+    type_keys(&mut editor, "n");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (2, 0));
+    type_keys(&mut editor, "n");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (0, 0));
+    assert_eq!(editor.messages, ["search hit BOTTOM, continuing at TOP"]);
+    type_keys(&mut editor, "N");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (2, 0), "N 反向查找");
+
+    type_keys(&mut editor, "?ba\n");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (1, 0));
+    type_keys(&mut editor, "n");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (0, 4), "n 保持 ? 的方向");
+    type_keys(&mut editor, "gg2/o\n");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (0, 2));
+
+    // 空的模式使用上一次的模式
+    type_keys(&mut editor, "/\n");
+    assert_eq!((editor.cursor.row, editor.cursor.col), (1, 5));
+    assert_eq!(editor.registers.get('/').unwrap().text, "o");
+    type_keys(&mut editor, "/qux\n");
+    assert_eq!(editor.messages, ["E486: Pattern not found: qux"]);
+    assert_eq!((editor.cursor.row, editor.cursor.col), (1, 5));
+
+    let mut editor = editor_with_lines(&["abc"]);
+    type_keys(&mut editor, "n");
+    assert_eq!(editor.messages, ["E35: No previous regular expression"]);
+}
+
+#[test]
+fn test_search_as_motion() {
+    let mut editor = editor_with_lines(&["one two three"]);
+    type_keys(&mut editor, "d/thr\n");
+    assert_eq!(editor.buffer.lines(), ["three"]);
+    type_keys(&mut editor, "u");
+
+    // 取消搜索时同时取消操作符
+    type_keys(&mut editor, "d/two");
+    editor.handle_key(Key::Esc).unwrap();
+    type_keys(&mut editor, "w");
+    assert_eq!(editor.buffer.lines(), ["one two three"]);
+    assert_eq!(editor.cursor.col, 4);
+    type_keys(&mut editor, "0dn");
+    assert_eq!(editor.buffer.lines(), ["three"], "取消的搜索不改变上一次的模式");
+}
+
+#[test]
+fn test_hlsearch_matches() {
+    let mut editor = editor_with_lines(&["foo bar foo", "x*"]);
+    type_keys(&mut editor, "/foo\n");
+    assert!(editor.search_highlight().is_none(), "没有设置 hlsearch 时不高亮");
+
+    editor.execute_command("set hls").unwrap();
+    let regex = editor.search_highlight().unwrap();
+    assert_eq!(search::line_matches(&regex, "foo bar foo"), [(0, 3), (8, 11)]);
+
+    type_keys(&mut editor, "/x*\n");
+    let regex = editor.search_highlight().unwrap();
+    assert_eq!(search::line_matches(&regex, "ax"), [(1, 2)], "空的匹配不高亮");
+}
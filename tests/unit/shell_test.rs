@@ -0,0 +1,125 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use rustvim::editor::Editor;
+use rustvim::shell;
+use termion::event::Key;
+
+use super::common::TempDir;
+
+fn open(file: &Path) -> Editor {
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile shell=sh").unwrap();
+    editor.open(file).unwrap();
+    editor.messages.clear();
+    editor
+}
+
+#[test]
+fn test_write_to_command_and_append() {
+    let dir = TempDir::new("shell-write");
+    let file = dir.join("text.txt");
+    fs::write(&file, "one\ntwo\nthree\n").unwrap();
+    let mut editor = open(&file);
+
+    editor.execute_command("w !cat").unwrap();
+    assert_eq!(editor.messages, ["one", "two", "three"]);
+    editor.messages.clear();
+    editor.execute_command("2,3w !wc -l").unwrap();
+    assert_eq!(editor.messages.last().unwrap().trim(), "2");
+    editor.execute_command("w !exit 3").unwrap();
+    assert_eq!(editor.messages.last().unwrap(), "shell returned 3");
+
+    let log = dir.join("log.txt");
+    editor.execute_command(&format!("w >> {}", log.display())).unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("E212")), "没有 ! 时不创建文件");
+    editor.execute_command(&format!("w! >> {}", log.display())).unwrap();
+    editor.execute_command(&format!("1w >> {}", log.display())).unwrap();
+    assert_eq!(fs::read_to_string(&log).unwrap(), "one\ntwo\nthree\none\n");
+    assert!(editor.messages.iter().any(|line| line.ends_with("1L, 4B appended")));
+
+    // 写入部分行到当前文件需要 !
+    editor.execute_command("1,2w").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("E140")));
+    let part = dir.join("part.txt");
+    editor.execute_command(&format!("2,$w {}", part.display())).unwrap();
+    assert_eq!(fs::read_to_string(&part).unwrap(), "two\nthree\n");
+    assert_eq!(editor.buffer.path.as_deref(), Some(file.as_path()));
+}
+
+#[test]
+fn test_read_file_and_command() {
+    let dir = TempDir::new("shell-read");
+    let file = dir.join("text.txt");
+    let other = dir.join("other.txt");
+    fs::write(&file, "one\ntwo\n").unwrap();
+    fs::write(&other, "a\r\nb\r\n").unwrap();
+    let mut editor = open(&file);
+
+    editor.execute_command(&format!("r {}", other.display())).unwrap();
+    assert_eq!(editor.buffer.lines(), ["one", "a", "b", "two"], "插入到光标行下面，按文件的换行符拆分");
+    assert_eq!(editor.cursor.row, 1);
+    editor.execute_command(&format!("0r {}", other.display())).unwrap();
+    assert_eq!(editor.buffer.lines()[..2], ["a", "b"]);
+    editor.execute_command("$r !printf 'x\\ny'").unwrap();
+    assert_eq!(editor.buffer.lines()[6..], ["x", "y"]);
+    editor.execute_command("r !echo %").unwrap();
+    assert_eq!(editor.buffer.get_line(editor.cursor.row).unwrap(), &file.display().to_string());
+    editor.messages.clear();
+    editor.execute_command("r !echo err >&2; exit 2").unwrap();
+    assert_eq!(editor.buffer.get_line(editor.cursor.row).unwrap(), "err", "同时读入标准错误");
+    assert_eq!(editor.messages, ["shell returned 2"]);
+    editor.execute_command("r nosuchfile").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("E484")));
+
+    assert_eq!(shell::expand("a \\% %", None).unwrap_err().split(':').next(), Some("E499"));
+    assert_eq!(shell::expand("a \\%", None).unwrap(), "a %");
+}
+
+#[test]
+fn test_sudo_write() {
+    let dir = TempDir::new("sudo");
+    // 用假的 sudo 检查密码，再执行后面的命令
+    let bin = dir.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let sudo = bin.join("sudo");
+    fs::write(
+        &sudo,
+        "#!/bin/sh\n[ \"$1\" = -n ] && exit 1\nread -r password\n[ \"$password\" = secret ] || { echo 'Sorry, try again.' >&2; exit 1; }\nshift 3\nexec \"$@\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&sudo, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = std::env::var("PATH").unwrap_or_default();
+    std::env::set_var("PATH", format!("{}:{}", bin.display(), path));
+
+    let file = dir.join("root.conf");
+    fs::write(&file, "old\n").unwrap();
+    let mut editor = open(&file);
+    editor.handle_key(Key::Char('x')).unwrap();
+    editor.execute_command("SudoWrite").unwrap();
+    assert!(editor.messages.iter().any(|line| line.starts_with("[sudo] password for")));
+    for c in "wrong\n".chars() {
+        editor.handle_key(Key::Char(c)).unwrap();
+    }
+    assert!(editor.messages.iter().any(|line| line.starts_with("E212") && line.ends_with("Sorry, try again.")));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "old\n");
+
+    editor.execute_command("SudoWrite").unwrap();
+    for c in "secrex".chars() {
+        editor.handle_key(Key::Char(c)).unwrap();
+    }
+    editor.handle_key(Key::Backspace).unwrap();
+    editor.handle_key(Key::Backspace).unwrap();
+    for c in "et\n".chars() {
+        editor.handle_key(Key::Char(c)).unwrap();
+    }
+    assert_eq!(fs::read_to_string(&file).unwrap(), "ld\n");
+    assert!(!editor.buffer.modified);
+    assert!(editor.messages.iter().all(|line| !line.contains("secret")), "不显示密码");
+
+    editor.execute_command("SudoWrite").unwrap();
+    editor.handle_key(Key::Esc).unwrap();
+    assert!(editor.sudo_prompt.is_none());
+    std::env::set_var("PATH", path);
+}
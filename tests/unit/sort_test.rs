@@ -0,0 +1,54 @@
+use rustvim::sort::{self, SortNumber, SortOptions};
+
+use super::common::{editor_with_lines, type_keys};
+
+fn sorted(lines: &[&str], args: &str) -> Vec<String> {
+    let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let options = SortOptions::parse(args).unwrap();
+    let regex = options.pattern.as_deref().map(|pattern| regex::Regex::new(pattern).unwrap());
+    sort::sort_lines(&lines, &options, regex.as_ref())
+}
+
+#[test]
+fn test_sort_command() {
+    let mut editor = editor_with_lines(&["pear", "Apple", "fig", "apple", "x"]);
+    type_keys(&mut editor, ":1,4sort\n");
+    assert_eq!(editor.buffer.lines()[..5], ["Apple", "apple", "fig", "pear", "x"]);
+    type_keys(&mut editor, "u");
+    assert_eq!(editor.buffer.lines()[..5], ["pear", "Apple", "fig", "apple", "x"], "排序作为一次修改撤销");
+    type_keys(&mut editor, "G:1,4sort! i\n");
+    assert_eq!(editor.buffer.lines()[..5], ["pear", "fig", "Apple", "apple", "x"]);
+    assert_eq!(editor.cursor.row, 0, "光标移到范围的第一行");
+    type_keys(&mut editor, ":1,4sort iu\n");
+    assert_eq!(editor.buffer.lines()[..4], ["Apple", "fig", "pear", "x"], "iu 忽略大小写去掉重复的行");
+
+    // 没有范围时排序整个 buffer，/pat/ 跳过匹配的前缀并成为搜索模式
+    let mut editor = editor_with_lines(&["b 2", "a 10", "c 1"]);
+    editor.buffer.remove_line(3);
+    type_keys(&mut editor, ":sort /\\w /\n");
+    assert_eq!(editor.buffer.lines(), ["c 1", "a 10", "b 2"]);
+    assert_eq!(editor.registers.get('/').unwrap().text, "\\w ");
+    type_keys(&mut editor, ":sort n\n");
+    assert_eq!(editor.buffer.lines(), ["c 1", "b 2", "a 10"]);
+    type_keys(&mut editor, ":sort // r\n");
+    assert_eq!(editor.buffer.lines(), ["a 10", "b 2", "c 1"], "空的模式使用上一次搜索的模式");
+
+    type_keys(&mut editor, ":sort nx\n");
+    assert!(editor.messages[0].starts_with("E474"), "n、x、f 只能使用一个");
+    type_keys(&mut editor, ":sort /(/\n");
+    assert!(editor.messages[0].starts_with("E383"));
+    assert_eq!(editor.buffer.lines(), ["a 10", "b 2", "c 1"]);
+}
+
+#[test]
+fn test_sort_lines() {
+    assert_eq!(sorted(&["x10", "-3", "none", "2"], "n"), ["none", "-3", "2", "x10"], "没有数字的行排在前面");
+    assert_eq!(sorted(&["0x1F", "0xa", "ff"], "x"), ["0xa", "0x1F", "ff"]);
+    assert_eq!(sorted(&["1.5e1", "-.5", "2.25"], "f"), ["-.5", "2.25", "1.5e1"]);
+    assert_eq!(sorted(&["id=3 b", "id=1 c", "no id"], "r /\\d+/ n"), ["no id", "id=1 c", "id=3 b"]);
+    assert_eq!(sorted(&["a", "a", "b", "a"], "u"), ["a", "b"]);
+    assert_eq!(sorted(&["a/2", "b/1"], r"#.*\/#"), ["b/1", "a/2"], "其他分隔符");
+    assert_eq!(SortOptions::parse("n \" comment").unwrap().number, Some(SortNumber::Decimal));
+    assert!(SortOptions::parse("/abc").is_err());
+    assert!(SortOptions::parse("z").is_err());
+}
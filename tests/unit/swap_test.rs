@@ -0,0 +1,170 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use rustvim::editor::Editor;
+use rustvim::swap::{self, SwapInfo};
+use termion::event::Key;
+
+use super::common::{type_keys, TempDir};
+
+#[test]
+fn test_swap_file_format() {
+    let dir = TempDir::new("swap-format");
+    let file = dir.join("notes.txt");
+    assert_eq!(swap::swap_path(&file, 0), dir.join(".notes.txt.swp"));
+    assert_eq!(swap::swap_path(&file, 1), dir.join(".notes.txt.swo"));
+
+    let info = SwapInfo {
+        pid: 42,
+        file: file.clone(),
+        modified: true,
+        escaped: false,
+        binary: false,
+        fileencoding: String::from("latin1"),
+        fileformat: String::from("dos"),
+        endofline: false,
+        lines: vec![String::from("first"), String::new(), String::from("third")],
+    };
+    let path = swap::free_path(&file);
+    swap::write(&path, &info).unwrap();
+    assert_eq!(swap::read(&path).unwrap(), info, "空行不影响头部的解析");
+    assert_eq!(swap::existing(&file), Some(path.clone()));
+    assert_eq!(swap::free_path(&file), dir.join(".notes.txt.swo"));
+
+    fs::write(dir.join(".other.swp"), "not a swap file").unwrap();
+    fs::write(dir.join(".日本語"), "").unwrap();
+    assert!(swap::read(&dir.join(".other.swp")).unwrap_err().to_string().starts_with("E307"));
+    let listed = swap::list(&dir);
+    assert!(listed.iter().any(|line| line.ends_with(".notes.txt.swp")));
+    assert!(listed.iter().any(|line| line.contains("[cannot be read]")));
+    assert!(!listed.iter().any(|line| line.contains("日本語")), "不是交换文件的隐藏文件不列出");
+
+    // 交换文件的权限和原来的文件相同
+    fs::write(&file, "secret\n").unwrap();
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+    let private = dir.join(".notes.txt.swn");
+    swap::write(&private, &info).unwrap();
+    assert_eq!(fs::metadata(&private).unwrap().permissions().mode() & 0o777, 0o640);
+}
+
+#[test]
+fn test_recover_after_crash() {
+    let dir = TempDir::new("swap-recover");
+    let file = dir.join("code.rs");
+    fs::write(&file, "fn main() {}\n").unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set uc=3").unwrap();
+    editor.open(&file).unwrap();
+    let swap_file = dir.join(".code.rs.swp");
+    assert_eq!(editor.swap, Some(swap_file.clone()), "打开文件时创建交换文件");
+
+    type_keys(&mut editor, "Ohi");
+    assert_eq!(swap::read(&swap_file).unwrap().lines, vec!["hi", "fn main() {}"], "3 个按键后写交换文件");
+    editor.handle_key(Key::Esc).unwrap();
+    editor.sync_swap();
+    // 进程崩溃：没有删除交换文件
+    drop(editor);
+
+    let mut editor = Editor::new().unwrap();
+    editor.open(&file).unwrap();
+    assert!(editor.swap_prompt.is_some());
+    assert!(editor.messages.iter().any(|line| line == "E325: ATTENTION"));
+    type_keys(&mut editor, "x");
+    assert!(editor.swap_prompt.is_some(), "其他按键重新显示提示");
+    type_keys(&mut editor, "r");
+    assert_eq!(editor.buffer.lines(), ["hi", "fn main() {}"]);
+    assert!(editor.buffer.modified);
+    assert_eq!(editor.swap, Some(swap_file.clone()), "继续使用原来的交换文件");
+
+    editor.execute_command("q").unwrap();
+    assert!(!editor.quit && editor.aborted, "E37: 修改过的 buffer 不能退出");
+    editor.execute_command("w").unwrap();
+    assert!(!editor.buffer.modified);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "hi\nfn main() {}\n");
+    editor.close_swap();
+    assert!(!swap_file.exists(), "退出时删除交换文件");
+}
+
+// 崩溃前编辑文件，返回交换文件的路径
+fn crash_while_editing(setup: &str, file: &Path, keys: &str) -> PathBuf {
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command(setup).unwrap();
+    editor.open(file).unwrap();
+    type_keys(&mut editor, keys);
+    editor.sync_swap();
+    editor.swap.clone().unwrap()
+}
+
+#[test]
+fn test_recover_binary_file() {
+    let dir = TempDir::new("swap-binary");
+    let file = dir.join("data.bin");
+    fs::write(&file, b"a\xff\x00b").unwrap();
+    // rustvim -b：以 binary 模式编辑
+    let swap_file = crash_while_editing("set binary", &file, "ix\u{1b}");
+
+    let mut editor = Editor::new().unwrap();
+    editor.recover(&file).unwrap();
+    assert_eq!(editor.swap, Some(swap_file));
+    assert!(editor.options.binary && editor.buffer.escaped, "恢复 binary 文件的设置");
+    assert!(!editor.options.endofline);
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read(&file).unwrap(), b"xa\xff\x00b", "转义的字节按原样写回");
+    editor.close_swap();
+}
+
+#[test]
+fn test_recover_converted_file() {
+    let dir = TempDir::new("swap-converted");
+    let file = dir.join("latin.txt");
+    fs::write(&file, b"caf\xe9\r\n").unwrap();
+    crash_while_editing("set uc=200", &file, "i!\u{1b}");
+
+    let mut editor = Editor::new().unwrap();
+    editor.recover(&file).unwrap();
+    assert_eq!(editor.buffer.lines(), ["!caf\u{e9}"]);
+    assert_eq!((editor.options.fileencoding.as_str(), editor.options.fileformat.as_str()), ("latin1", "dos"));
+    editor.execute_command("w").unwrap();
+    assert_eq!(fs::read(&file).unwrap(), b"!caf\xe9\r\n", "按原来的编码和格式写回");
+    editor.close_swap();
+}
+
+#[test]
+fn test_swap_prompt_choices() {
+    let dir = TempDir::new("swap-choices");
+    let file = dir.join("a.txt");
+    fs::write(&file, "one\n").unwrap();
+
+    let mut first = Editor::new().unwrap();
+    first.open(&file).unwrap();
+
+    // 编辑另一个进程正在编辑的文件时使用下一个交换文件名
+    let mut second = Editor::new().unwrap();
+    second.open(&file).unwrap();
+    type_keys(&mut second, "e");
+    assert_eq!(second.buffer.lines(), ["one"]);
+    assert_eq!(second.swap, Some(dir.join(".a.txt.swo")));
+    second.close_swap();
+
+    let mut third = Editor::new().unwrap();
+    third.open(&file).unwrap();
+    type_keys(&mut third, "d");
+    assert_eq!(third.swap, Some(dir.join(".a.txt.swp")), "删除旧的交换文件后重新创建");
+    first.swap = None;
+
+    third.execute_command("set noswapfile").unwrap();
+    assert!(!dir.join(".a.txt.swp").exists());
+    third.execute_command("e! missing.txt").unwrap();
+    assert!(third.messages.iter().any(|line| line.ends_with("[New]")));
+
+    let mut fourth = Editor::new().unwrap();
+    fourth.recover(&dir.join("none.txt")).unwrap();
+    assert!(fourth.messages[0].starts_with("E305"));
+    let mut fifth = Editor::new().unwrap();
+    fs::write(dir.join(".a.txt.swp"), "garbage").unwrap();
+    fifth.open(&file).unwrap();
+    type_keys(&mut fifth, "q");
+    assert!(fifth.quit, "启动时选择退出时退出编辑器");
+}
@@ -0,0 +1,105 @@
+use rustvim::buffer::Buffer;
+use rustvim::editor::Editor;
+use rustvim::syntax::{Highlighter, Syntax};
+
+fn groups(spans: &[rustvim::syntax::Span], line: &str) -> Vec<(String, String)> {
+    spans
+        .iter()
+        .map(|span| (line[span.start..span.end].to_string(), span.group.clone()))
+        .collect()
+}
+
+fn buffer_with_lines(lines: &[&str]) -> Buffer {
+    let mut buffer = Buffer::new();
+    for (i, line) in lines.iter().enumerate() {
+        buffer.insert_line(i, line.to_string());
+    }
+    buffer
+}
+
+#[test]
+fn test_keyword_match_region() {
+    let syntax = Syntax::parse(
+        "\" 注释行\n\
+         syntax keyword Keyword let fn\n\
+         syntax match Number /\\b[0-9]+\\b/\n\
+         syntax region String start=/\"/ skip=/\\\\./ end=/\"/\n",
+    )
+    .unwrap();
+    let line = r#"let x = "a\"b" + 42; letter"#;
+    let (spans, state) = syntax.highlight_line(line, None);
+    assert_eq!(
+        groups(&spans, line),
+        vec![
+            ("let".to_string(), "Keyword".to_string()),
+            (r#""a\"b""#.to_string(), "String".to_string()),
+            ("42".to_string(), "Number".to_string()),
+        ],
+        "关键字只匹配整个单词，skip 跳过转义的引号"
+    );
+    assert_eq!(state, None);
+
+    assert!(Syntax::parse("syntax match Foo bar").is_err(), "缺少模式分隔符");
+    assert!(Syntax::parse("syntax region Foo start=/a/").is_err(), "region 需要 end");
+    assert!(Syntax::parse("highlight Foo").is_err());
+}
+
+#[test]
+fn test_region_across_lines() {
+    let syntax = Syntax::load("rust").unwrap().unwrap();
+    let (spans, state) = syntax.highlight_line("let a = 1; /* start", None);
+    assert!(state.is_some(), "没有结束的注释延续到下一行");
+    assert_eq!(spans.last().unwrap().group, "Comment");
+
+    let (spans, end) = syntax.highlight_line("fn still */ fn", state);
+    assert_eq!(end, None);
+    assert_eq!((spans[0].start, spans[0].end), (0, 11));
+    assert_eq!((spans[1].start, spans[1].group.as_str()), (12, "Keyword"));
+}
+
+#[test]
+fn test_incremental_cache() {
+    let mut buffer = buffer_with_lines(&["fn a() {}", "/* x", "y", "*/ fn b() {}"]);
+    buffer.highlighter = Some(Highlighter::new("rust", Syntax::load("rust").unwrap().unwrap()));
+    assert_eq!(buffer.line_spans(2)[0].group, "Comment");
+    assert_eq!(buffer.highlighter.as_ref().unwrap().cached_lines(), 3, "只计算到需要的行");
+
+    // 修改最后一行不影响前面的缓存
+    assert_eq!(buffer.line_spans(3)[1].group, "Keyword");
+    buffer.insert_char(3, 0, ' ');
+    assert_eq!(buffer.highlighter.as_ref().unwrap().cached_lines(), 3);
+
+    // 删掉注释开头后，后面的行都不再是注释
+    buffer.set_line(1, "x".to_string());
+    assert!(buffer.line_spans(2).is_empty());
+    assert_eq!(buffer.line_spans(3)[0].group, "Keyword");
+
+    // 插入注释开头的行，后面的行重新变成注释
+    buffer.insert_line(1, "/*".to_string());
+    assert_eq!(buffer.line_spans(3)[0].group, "Comment");
+    buffer.remove_line(1);
+    assert!(buffer.line_spans(2).is_empty());
+}
+
+#[test]
+fn test_syntax_commands() {
+    let mut editor = Editor::new().unwrap();
+    editor.buffer.insert_line(0, "def f(): return None".to_string());
+    editor.execute_command("set ft=python").unwrap();
+    assert_eq!(editor.options.syntax, "python", "设置 filetype 时同时设置 syntax");
+    assert_eq!(editor.buffer.line_spans(0)[0].group, "Keyword", "python 使用语法树高亮");
+
+    editor.execute_command("syntax off").unwrap();
+    assert!(editor.buffer.line_spans(0).is_empty());
+    editor.execute_command("syntax on").unwrap();
+    assert!(!editor.buffer.line_spans(0).is_empty());
+
+    editor.execute_command("syntax clear").unwrap();
+    assert!(editor.buffer.line_spans(0).is_empty());
+    editor.execute_command("syn keyword Todo None").unwrap();
+    let spans = editor.buffer.line_spans(0);
+    assert_eq!((spans[0].start, spans[0].group.as_str()), (16, "Todo"));
+
+    editor.execute_command("syntax match Foo /(/").unwrap();
+    assert!(editor.aborted, "无效的正则表达式应该报错");
+}
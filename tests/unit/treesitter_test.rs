@@ -0,0 +1,131 @@
+use rustvim::editor::Editor;
+use rustvim::mark::Position;
+use rustvim::treesitter::SyntaxTree;
+
+use super::common::{editor_with_lines, lines, type_keys};
+
+// 设置了 filetype 的编辑器，按文件类型建立语法树
+fn editor_with_filetype(filetype: &str, lines: &[&str]) -> Editor {
+    let mut editor = editor_with_lines(lines);
+    editor.execute_command(&format!("set ft={}", filetype)).unwrap();
+    editor
+}
+
+fn group_at(editor: &Editor, row: usize, col: usize) -> Option<String> {
+    editor
+        .buffer
+        .line_spans(row)
+        .into_iter()
+        .rev()
+        .find(|span| col >= span.start && col < span.end)
+        .map(|span| span.group)
+}
+
+#[test]
+fn test_tree_highlighting_follows_edits() {
+    let mut editor = editor_with_filetype("rust", &["fn main() {", "    let s = \"x\";", "}"]);
+    assert!(editor.buffer.tree.is_some());
+    assert_eq!(group_at(&editor, 0, 0).as_deref(), Some("Keyword"));
+    assert_eq!(group_at(&editor, 0, 3).as_deref(), Some("Function"));
+    assert_eq!(group_at(&editor, 1, 12).as_deref(), Some("String"));
+
+    // 在第一行插入注释开头，后面的行都变成注释
+    editor.buffer.insert_text(Position::new(0, 0), "/* ");
+    assert_eq!(group_at(&editor, 1, 4).as_deref(), Some("Comment"));
+    editor.buffer.delete_text(Position::new(0, 0), Position::new(0, 3));
+    assert_eq!(group_at(&editor, 1, 4).as_deref(), Some("Keyword"));
+
+    let mut editor = editor_with_filetype("json", &["{\"a\": [1, true]}"]);
+    assert_eq!(group_at(&editor, 0, 7).as_deref(), Some("Number"));
+    editor.execute_command("set ft=text").unwrap();
+    assert!(editor.buffer.tree.is_none(), "没有解析器的文件类型不使用语法树");
+}
+
+// 增量更新的语法树和重新解析整个 buffer 得到的高亮应该相同
+fn assert_same_as_full_parse(editor: &Editor, step: &str) {
+    let tree = SyntaxTree::new("rust").unwrap();
+    let lines = lines(editor);
+    for row in 0..lines.len() {
+        assert_eq!(editor.buffer.line_spans(row), tree.line_spans(&lines, row), "{} 之后第 {} 行的高亮不同", step, row);
+    }
+}
+
+#[test]
+fn test_tree_edits_match_full_parse() {
+    let mut editor = editor_with_filetype("rust", &["fn main() {", "    let s = \"é\";", "}"]);
+    assert_same_as_full_parse(&editor, "解析");
+
+    let end = editor.buffer.insert_text(Position::new(1, 4), "let a = 1;\n    /* x\n    */ ");
+    assert_eq!(end, Position::new(3, 7));
+    assert_same_as_full_parse(&editor, "插入多行文本");
+    editor.buffer.delete_text(Position::new(1, 14), Position::new(3, 7));
+    assert_same_as_full_parse(&editor, "删除多行文本");
+    editor.buffer.insert_char(0, 14, ';');
+    assert_same_as_full_parse(&editor, "在行尾之后插入字符");
+    editor.buffer.remove_char(2, 13);
+    assert_same_as_full_parse(&editor, "删除多字节字符");
+    editor.buffer.join_lines(0);
+    editor.buffer.split_line(0, 11);
+    assert_same_as_full_parse(&editor, "合并和拆分行");
+    editor.buffer.replace_lines(1, 2, &[String::from("    // c"), String::from("    1"), String::from("}")]);
+    editor.buffer.remove_line(0);
+    editor.buffer.insert_line(6, String::from("fn g() {}"));
+    assert_same_as_full_parse(&editor, "替换、删除和添加行");
+
+    type_keys(&mut editor, "ggddGofn h(x: u8) {}\u{1b}kJu");
+    assert_same_as_full_parse(&editor, "普通模式的编辑");
+}
+
+#[test]
+fn test_function_and_class_objects() {
+    let source = ["struct P {", "    x: i32,", "}", "fn f(a: i32) {", "    let b = a;", "    b", "}", "end"];
+    let mut editor = editor_with_filetype("rust", &source);
+    editor.cursor.move_to(&editor.buffer, Position::new(4, 6)).unwrap();
+    type_keys(&mut editor, "dif");
+    assert_eq!(lines(&editor), ["struct P {", "    x: i32,", "}", "fn f(a: i32) {", "}", "end"], "if 删除函数体中的整行");
+
+    editor.cursor.move_to(&editor.buffer, Position::new(3, 0)).unwrap();
+    type_keys(&mut editor, "daf");
+    assert_eq!(lines(&editor), ["struct P {", "    x: i32,", "}", "end"]);
+
+    editor.cursor.move_to(&editor.buffer, Position::new(1, 4)).unwrap();
+    type_keys(&mut editor, "vacd");
+    assert_eq!(lines(&editor), ["end"], "可视模式选中整个结构体");
+
+    let mut editor = editor_with_filetype("python", &["class A:", "    def f(self):", "        return 1"]);
+    editor.cursor.move_to(&editor.buffer, Position::new(2, 8)).unwrap();
+    type_keys(&mut editor, "yaf");
+    assert_eq!(editor.registers.get('"').unwrap().text, "    def f(self):\n        return 1");
+}
+
+#[test]
+fn test_argument_objects() {
+    let mut editor = editor_with_filetype("rust", &["f(one, two(x), three);"]);
+    editor.cursor.move_to(&editor.buffer, Position::new(0, 8)).unwrap();
+    type_keys(&mut editor, "daa");
+    assert_eq!(lines(&editor), ["f(one, three);"]);
+    editor.cursor.move_to(&editor.buffer, Position::new(0, 9)).unwrap();
+    type_keys(&mut editor, "daa");
+    assert_eq!(lines(&editor), ["f(one);"], "最后一个参数删除前面的逗号");
+    type_keys(&mut editor, "cianew\u{1b}");
+    assert_eq!(lines(&editor), ["f(new);"]);
+
+    let mut editor = editor_with_filetype("markdown", &["# title"]);
+    type_keys(&mut editor, "daf");
+    assert!(editor.aborted, "没有函数节点时取消");
+}
+
+#[test]
+fn test_inspect_tree() {
+    let mut editor = editor_with_filetype("json", &["[1, 2]"]);
+    editor.cursor.move_to(&editor.buffer, Position::new(0, 4)).unwrap();
+    editor.execute_command("InspectTree").unwrap();
+    assert_eq!(
+        editor.messages,
+        ["(document [0, 0] - [1, 0])", "  (array [0, 0] - [0, 6])", "    (number [0, 4] - [0, 5])"]
+    );
+
+    let mut editor = editor_with_filetype("", &["x"]);
+    editor.execute_command("InspectTree").unwrap();
+    assert!(editor.aborted);
+}
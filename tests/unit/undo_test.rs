@@ -0,0 +1,123 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use rustvim::editor::Editor;
+use rustvim::undo::{self, Change, UndoTree};
+use termion::event::Key;
+
+use super::common::{type_keys, TempDir};
+
+fn lines(text: &[&str]) -> Vec<String> {
+    text.iter().map(|line| line.to_string()).collect()
+}
+
+#[test]
+fn test_diff_and_serialize() {
+    let old = lines(&["a", "b", "c", "d"]);
+    let new = lines(&["a", "x", "y", "d"]);
+    let change = undo::diff(&old, &new).unwrap();
+    assert_eq!(change, Change { row: 1, old: lines(&["b", "c"]), new: lines(&["x", "y"]) });
+    assert_eq!(undo::diff(&old, &old), None);
+    let change = undo::diff(&lines(&["a", "a"]), &lines(&["a", "a", "a"])).unwrap();
+    assert_eq!((change.old.len(), change.new.len()), (0, 1), "重复的行不会被计算两次");
+
+    let mut tree = UndoTree::new(old.clone());
+    tree.commit(&new, 1, false);
+    tree.commit(&lines(&["a", "x", "", "y", "d"]), 2, false);
+    tree.undo();
+    tree.save_seq = 1;
+    let text = tree.serialize(undo::hash(&new));
+    let (loaded, hash) = UndoTree::deserialize(&text, &new).unwrap();
+    assert_eq!(hash, undo::hash(&new));
+    assert_eq!(loaded.entries(), tree.entries(), "空行也能正确保存");
+    assert_eq!((loaded.current, loaded.save_seq), (1, 1));
+    assert_eq!(UndoTree::deserialize("garbage", &new).unwrap_err(), "E823: Not an undo file");
+}
+
+#[test]
+fn test_undo_and_redo() {
+    let mut editor = Editor::new().unwrap();
+    editor.buffer.insert_line(0, String::from("one two"));
+    editor.commit_undo();
+    type_keys(&mut editor, "wcwthree");
+    editor.handle_key(Key::Esc).unwrap();
+    type_keys(&mut editor, "ofour");
+    editor.handle_key(Key::Esc).unwrap();
+    assert_eq!(editor.buffer.lines(), ["one three", "four"]);
+
+    type_keys(&mut editor, "u");
+    assert_eq!(editor.buffer.lines(), ["one three"], "整个插入作为一次修改");
+    type_keys(&mut editor, "u");
+    assert_eq!(editor.buffer.lines(), ["one two"]);
+    assert_eq!((editor.cursor.row, editor.cursor.col), (0, 5), "光标移到第一个不同的字符");
+    assert_eq!(editor.messages, ["1 change; before #2"]);
+    editor.handle_key(Key::Ctrl('r')).unwrap();
+    assert_eq!(editor.buffer.lines(), ["one three"]);
+
+    // 撤销之后的修改创建新的分支，重做进入新的分支
+    type_keys(&mut editor, "ux");
+    assert_eq!(editor.buffer.lines(), ["one to"]);
+    type_keys(&mut editor, "u");
+    editor.handle_key(Key::Ctrl('r')).unwrap();
+    assert_eq!(editor.buffer.lines(), ["one to"]);
+    assert_eq!(editor.buffer.undo.entries().len(), 4);
+
+    type_keys(&mut editor, "3u");
+    assert!(editor.messages[0].starts_with("2 changes"));
+    type_keys(&mut editor, "u");
+    assert_eq!(editor.messages, ["Already at oldest change"]);
+    editor.execute_command("redo").unwrap();
+    assert_eq!(editor.buffer.lines(), ["one two"]);
+}
+
+#[test]
+fn test_undofile() {
+    let dir = TempDir::new("undofile");
+    let undodir = dir.join("undo");
+    let file = dir.join("text.txt");
+    fs::write(&file, "hello\n").unwrap();
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command(&format!("set noswapfile undofile undodir={}", undodir.display())).unwrap();
+    editor.open(&file).unwrap();
+    type_keys(&mut editor, "$aworld");
+    editor.handle_key(Key::Esc).unwrap();
+    editor.execute_command("w").unwrap();
+    let undo_file = undo::undo_path(&editor.options.undodir, &file).unwrap();
+    assert!(undo_file.starts_with(&undodir) && undo_file.exists(), "写入文件时创建撤销文件");
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&undodir), 0o700, "只有自己可以访问 undodir");
+    assert_eq!(mode(&undo_file), 0o640, "撤销文件的权限和原来的文件相同");
+    type_keys(&mut editor, "u");
+    assert!(editor.buffer.modified);
+    editor.handle_key(Key::Ctrl('r')).unwrap();
+    assert!(!editor.buffer.modified, "回到写入时的状态时没有修改");
+
+    // 重新启动后仍然可以撤销
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command(&format!("set noswapfile undofile undodir={}", undodir.display())).unwrap();
+    editor.open(&file).unwrap();
+    type_keys(&mut editor, "u");
+    assert_eq!(editor.buffer.lines(), ["hello"]);
+    editor.handle_key(Key::Ctrl('r')).unwrap();
+    assert_eq!(editor.buffer.lines(), ["helloworld"]);
+
+    // 文件在编辑器外被修改过时丢弃撤销信息
+    fs::write(&file, "changed\n").unwrap();
+    editor.execute_command("e!").unwrap();
+    assert!(editor.messages.iter().any(|line| line == "File contents changed, cannot use undo info"));
+    type_keys(&mut editor, "u");
+    assert_eq!(editor.buffer.lines(), ["changed"]);
+
+    let mut editor = Editor::new().unwrap();
+    editor.execute_command("set noswapfile undodir=.").unwrap();
+    editor.open(&file).unwrap();
+    type_keys(&mut editor, "x");
+    editor.execute_command("w").unwrap();
+    assert!(!dir.join(".text.txt.un~").exists(), "没有设置 undofile 时不保存");
+    editor.execute_command("set undofile").unwrap();
+    editor.execute_command("w").unwrap();
+    assert!(dir.join(".text.txt.un~").exists());
+}